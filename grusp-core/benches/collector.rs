@@ -0,0 +1,33 @@
+#[macro_use]
+extern crate criterion;
+extern crate grusp_core;
+
+use criterion::Criterion;
+use grusp_core::corpus;
+use grusp_core::grusp::{FileCollector, Traversal};
+use std::env;
+use std::fs;
+
+fn collector(c: &mut Criterion) {
+    let dir = env::temp_dir().join("grusp-bench-deep-tree");
+    let _ = fs::remove_dir_all(&dir);
+    corpus::deep_tree(&dir, 6, 3, 2).unwrap();
+    let queries = vec![dir.to_string_lossy().into_owned()];
+
+    let mut group = c.benchmark_group("collector");
+    group.bench_function("deep_tree/dfs", |b| {
+        b.iter(|| FileCollector::new(&queries).collect())
+    });
+    group.bench_function("deep_tree/bfs", |b| {
+        b.iter(|| FileCollector::new(&queries).traversal(Traversal::BreadthFirst).collect())
+    });
+    group.bench_function("deep_tree/max_depth", |b| {
+        b.iter(|| FileCollector::new(&queries).max_depth(Some(3)).collect())
+    });
+    group.finish();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, collector);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+#[macro_use]
+extern crate criterion;
+extern crate grusp_core;
+extern crate regex;
+
+use criterion::{Criterion, Throughput};
+use grusp_core::corpus;
+use grusp_core::grusp::{Matcher, SearchOptions};
+use regex::Regex;
+use std::io::Cursor;
+
+fn matcher(c: &mut Criterion) {
+    let literal = Regex::new(&regex::escape(corpus::NEEDLE)).unwrap();
+    let pattern = Regex::new(r"fn\s+\w+\(").unwrap();
+    let corpora = vec![
+        ("long_lines", corpus::long_lines(1_000, 2_000)),
+        ("binary_ish", corpus::binary_ish(10_000)),
+    ];
+
+    let mut group = c.benchmark_group("matcher");
+    for (name, data) in &corpora {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(format!("{}/literal", name), |b| {
+            b.iter(|| Matcher::new(&literal).collect(&mut Cursor::new(data)).unwrap())
+        });
+        group.bench_function(format!("{}/regex", name), |b| {
+            b.iter(|| Matcher::new(&pattern).collect(&mut Cursor::new(data)).unwrap())
+        });
+        group.bench_function(format!("{}/first_capture", name), |b| {
+            b.iter(|| Matcher::new(&pattern).max_captures_per_line(Some(1)).collect(&mut Cursor::new(data)).unwrap())
+        });
+        group.bench_function(format!("{}/spans_only", name), |b| {
+            let options = SearchOptions::new().spans_only(true);
+            b.iter(|| Matcher::new(&pattern).options(options.clone()).collect(&mut Cursor::new(data)).unwrap())
+        });
+        group.bench_function(format!("{}/count", name), |b| {
+            b.iter(|| Matcher::new(&pattern).keep_lines(false).collect(&mut Cursor::new(data)).unwrap())
+        });
+    }
+
+    let files = corpus::small_files(1_000);
+    group.throughput(Throughput::Bytes(files.iter().map(|f| f.len() as u64).sum()));
+    group.bench_function("small_files/regex", |b| {
+        b.iter(|| {
+            for file in &files {
+                Matcher::new(&pattern).collect(&mut Cursor::new(file)).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, matcher);
+criterion_main!(benches);
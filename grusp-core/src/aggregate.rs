@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use regex::{Captures, Regex};
+use matcher::Matches;
+
+/// The statistic an `Aggregator` computes over the numbers it finds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// The total of the numbers.
+    Sum,
+    /// The mean of the numbers.
+    Avg,
+    /// The smallest number.
+    Min,
+    /// The largest number.
+    Max,
+    /// The number of matches, whether or not they captured a number.
+    Count,
+}
+
+/// A capture group of the regex, by its index or its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Group {
+    /// The group at the index, counting from one.
+    Index(usize),
+    /// The group with the name, such as `(?P<host>\w+)`.
+    Name(String),
+}
+
+impl Group {
+    /// Reads a group as an index when it's a number, otherwise as a name.
+    pub fn parse(group: &str) -> Self {
+        match group.parse() {
+            Ok(index) => Group::Index(index),
+            Err(_) => Group::Name(group.to_string()),
+        }
+    }
+
+    /// Returns true if the regex has the group.
+    pub fn exists_in(&self, regex: &Regex) -> bool {
+        match *self {
+            Group::Index(index) => index > 0 && index < regex.captures_len(),
+            Group::Name(ref name) => regex.capture_names().any(|n| n == Some(name)),
+        }
+    }
+
+    fn index(&self, regex: &Regex) -> Option<usize> {
+        match *self {
+            Group::Index(index) => Some(index),
+            Group::Name(ref name) => regex.capture_names().position(|n| n == Some(name)),
+        }
+    }
+
+    pub(crate) fn get<'t>(&self, captures: &Captures<'t>) -> Option<&'t str> {
+        match *self {
+            Group::Index(index) => captures.get(index),
+            Group::Name(ref name) => captures.name(name),
+        }
+        .map(|m| m.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    matches: u64,
+    numbers: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Summary {
+    fn add(&mut self, number: Option<f64>) {
+        self.matches += 1;
+        if let Some(number) = number {
+            self.numbers += 1;
+            self.sum += number;
+            self.min = Some(self.min.map_or(number, |min| min.min(number)));
+            self.max = Some(self.max.map_or(number, |max| max.max(number)));
+        }
+    }
+
+    fn value(&self, aggregation: Aggregation) -> Option<f64> {
+        match aggregation {
+            Aggregation::Sum | Aggregation::Avg if self.numbers == 0 => None,
+            Aggregation::Sum => Some(self.sum),
+            Aggregation::Avg => Some(self.sum / self.numbers as f64),
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Count => Some(self.matches as f64),
+        }
+    }
+}
+
+/// Reduces the matches of a search to a statistic, like an awk one-liner would. The
+/// first capture group of each match is parsed as a number, or the whole match when the
+/// regex has no other groups. Matches can be grouped by another capture, giving one
+/// statistic per distinct value of it. The matches can be added from several threads.
+///
+/// Numbers may have thousands separators, and matches that aren't numbers only count
+/// towards `Aggregation::Count`.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{Aggregation, AggregateGroup, Aggregator, Matcher};
+/// use regex::Regex;
+///
+/// let log = "GET took 20ms\nPOST took 15ms\nGET took 10ms\n";
+///
+/// let regex = Regex::new(r"took (\d+)ms").unwrap();
+/// let matches = Matcher::new(&regex).collect(&mut log.as_bytes()).unwrap();
+/// let aggregator = Aggregator::new(Aggregation::Avg, &regex);
+/// aggregator.add(&matches);
+/// assert_eq!(aggregator.to_string(), "15");
+///
+/// let regex = Regex::new(r"(GET|POST) took (\d+)ms").unwrap();
+/// let matches = Matcher::new(&regex).collect(&mut log.as_bytes()).unwrap();
+/// let aggregator = Aggregator::new(Aggregation::Max, &regex).group_by(Some(AggregateGroup::Index(1)));
+/// aggregator.add(&matches);
+/// assert_eq!(aggregator.to_string(), "GET   20\nPOST  15");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Aggregator {
+    aggregation: Aggregation,
+    regex: Regex,
+    group_by: Option<Group>,
+    groups: Mutex<BTreeMap<String, Summary>>,
+}
+
+impl Aggregator {
+    /// Creates an aggregator for the matches of the regex.
+    pub fn new(aggregation: Aggregation, regex: &Regex) -> Self {
+        Aggregator { aggregation, regex: regex.clone(), group_by: None, groups: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Consumes the aggregator and computes the statistic for each value of the group
+    /// instead of once over all of the matches. Matches without the group are skipped.
+    pub fn group_by(self, group_by: Option<Group>) -> Self {
+        Self { group_by, ..self }
+    }
+
+    /// Adds every match of the lines to the statistics.
+    pub fn add(&self, matches: &Matches) {
+        let group_index = self.group_by.as_ref().and_then(|group| group.index(&self.regex));
+        let value_index = (1..self.regex.captures_len()).find(|&index| Some(index) != group_index).unwrap_or(0);
+        let mut groups = self.groups.lock().expect("Aggregate lock poisoned");
+        for line in &matches.lines {
+            for captures in self.regex.captures_iter(&line.value) {
+                let key = match self.group_by {
+                    Some(ref group) => match group.get(&captures) {
+                        Some(key) => key.to_string(),
+                        None => continue,
+                    },
+                    None => String::new(),
+                };
+                let number = captures.get(value_index).and_then(|value| parse_number(value.as_str()));
+                groups.entry(key).or_default().add(number);
+            }
+        }
+    }
+
+    /// Returns true if no matches have been added.
+    pub fn is_empty(&self) -> bool {
+        self.groups.lock().expect("Aggregate lock poisoned").is_empty()
+    }
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().replace(',', "").parse().ok()
+}
+
+/// Whole numbers are shown without a fraction, however they were written.
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+impl fmt::Display for Aggregator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let groups = self.groups.lock().expect("Aggregate lock poisoned");
+        if self.group_by.is_none() {
+            let value = groups.get("").and_then(|summary| summary.value(self.aggregation));
+            return write!(f, "{}", value.map(format_number).unwrap_or_default());
+        }
+        let rows: Vec<(&String, String)> = groups
+            .iter()
+            .filter_map(|(key, summary)| summary.value(self.aggregation).map(|value| (key, format_number(value))))
+            .collect();
+        let width = rows.iter().map(|&(key, _)| key.chars().count()).max().unwrap_or(0);
+        let rows: Vec<String> = rows
+            .iter()
+            .map(|&(key, ref value)| format!("{:width$}  {}", key, value, width = width))
+            .collect();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matcher::Matcher;
+
+    fn aggregate(aggregation: Aggregation, pattern: &str, group_by: Option<Group>, text: &str) -> String {
+        let regex = Regex::new(pattern).unwrap();
+        let matches = Matcher::new(&regex).collect(&mut text.as_bytes()).unwrap();
+        let aggregator = Aggregator::new(aggregation, &regex).group_by(group_by);
+        aggregator.add(&matches);
+        aggregator.to_string()
+    }
+
+    #[test]
+    fn it_aggregates_the_first_group() {
+        let text = "took 10ms\ntook 2.5ms took 1,000ms\n";
+        assert_eq!(aggregate(Aggregation::Sum, r"took ([\d.,]+)ms", None, text), "1012.5");
+        assert_eq!(aggregate(Aggregation::Min, r"took ([\d.,]+)ms", None, text), "2.5");
+        assert_eq!(aggregate(Aggregation::Max, r"took ([\d.,]+)ms", None, text), "1000");
+        assert_eq!(aggregate(Aggregation::Count, r"took ([\d.,]+)ms", None, text), "3");
+    }
+
+    #[test]
+    fn it_aggregates_the_whole_match_without_groups() {
+        assert_eq!(aggregate(Aggregation::Avg, r"\d+", None, "1 2\n6\n"), "3");
+    }
+
+    #[test]
+    fn it_only_counts_matches_that_arent_numbers() {
+        let text = "size=4\nsize=big\n";
+        assert_eq!(aggregate(Aggregation::Avg, r"size=(\w+)", None, text), "4");
+        assert_eq!(aggregate(Aggregation::Count, r"size=(\w+)", None, text), "2");
+        assert_eq!(aggregate(Aggregation::Sum, r"size=(\w+)", None, "size=big\n"), "");
+    }
+
+    #[test]
+    fn it_groups_by_a_named_capture() {
+        let text = "host=b db=3\nhost=a db=1\nhost=b db=4\n";
+        let pattern = r"host=(?P<host>\w+) db=(\d+)";
+        assert_eq!(aggregate(Aggregation::Count, pattern, Some(Group::parse("host")), text), "a  1\nb  2");
+        assert_eq!(aggregate(Aggregation::Sum, pattern, Some(Group::Index(1)), text), "a  1\nb  7");
+    }
+
+    #[test]
+    fn it_checks_that_the_group_exists() {
+        let regex = Regex::new(r"(?P<host>\w+) (\d+)").unwrap();
+        assert!(Group::parse("host").exists_in(&regex));
+        assert!(Group::parse("2").exists_in(&regex));
+        assert!(!Group::parse("0").exists_in(&regex));
+        assert!(!Group::parse("3").exists_in(&regex));
+        assert!(!Group::parse("port").exists_in(&regex));
+    }
+}
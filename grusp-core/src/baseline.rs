@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use filter::MatchFilter;
+use matcher::Line;
+
+/// What a baseline does with the lines it's given as a filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Keeps every line, remembering it.
+    Record,
+    /// Keeps only the lines that aren't in the baseline.
+    Check,
+}
+
+/// The matches known when a baseline was recorded, so that a check can fail on new
+/// matches only, such as to stop new TODOs from being added while the existing ones are
+/// worked through.
+///
+/// A match is known by its path and a hash of its line with the surrounding whitespace
+/// trimmed, so matches survive lines being added above them or being reindented. Each
+/// line of a baseline file is the hash in hex followed by the path, sorted so the file
+/// diffs well when it's committed. A line found more times than the baseline has it is
+/// new.
+///
+/// As a filter, a recording baseline keeps and remembers every line, while a loaded one
+/// only keeps the lines it doesn't know, using up each known line as it's found. Clones
+/// share the same matches, so one can be added to the filters and the other saved.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::{Baseline, Line, MatchFilter};
+/// use std::path::Path;
+///
+/// let line = |value: &str| Line { number: None, value: value.to_string(), captures: Vec::new() };
+/// let recorded = Baseline::record();
+/// recorded.keep(&line("// TODO: old"), Path::new("lib.rs"));
+///
+/// let baseline = Baseline::parse(&recorded.to_string()).unwrap();
+/// assert!(!baseline.keep(&line("    // TODO: old"), Path::new("lib.rs")));
+/// assert!(baseline.keep(&line("// TODO: old"), Path::new("lib.rs")));
+/// assert!(baseline.keep(&line("// TODO: new"), Path::new("lib.rs")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    mode: Mode,
+    matches: Arc<Mutex<BTreeMap<(String, u64), usize>>>,
+}
+
+impl Baseline {
+    /// Creates an empty baseline that remembers the lines it filters.
+    pub fn record() -> Self {
+        Baseline { mode: Mode::Record, matches: Arc::new(Mutex::new(BTreeMap::new())) }
+    }
+
+    /// Parses the contents of a baseline file into a baseline that filters out the lines
+    /// it has. The error describes the first line that couldn't be parsed.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut matches = BTreeMap::new();
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(2, ' ');
+            let hash = parts.next().and_then(|hash| u64::from_str_radix(hash, 16).ok());
+            match (hash, parts.next()) {
+                (Some(hash), Some(path)) => *matches.entry((path.to_string(), hash)).or_insert(0) += 1,
+                _ => return Err(format!("Invalid baseline entry on line {}: '{}'", number + 1, line)),
+            }
+        }
+        Ok(Baseline { mode: Mode::Check, matches: Arc::new(Mutex::new(matches)) })
+    }
+
+    /// Reads a baseline file, see `parse`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the baseline to a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Returns the number of matches in the baseline.
+    pub fn len(&self) -> usize {
+        self.matches.lock().expect("Baseline lock poisoned").values().sum()
+    }
+
+    /// Returns true if the baseline has no matches.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Baseline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let matches = self.matches.lock().expect("Baseline lock poisoned");
+        for (&(ref path, hash), &count) in matches.iter() {
+            for _ in 0..count {
+                writeln!(f, "{:016x} {}", hash, path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MatchFilter for Baseline {
+    fn keep(&self, line: &Line, path: &Path) -> bool {
+        let key = (path.to_string_lossy().into_owned(), hash(line.value.trim()));
+        let mut matches = self.matches.lock().expect("Baseline lock poisoned");
+        match self.mode {
+            Mode::Record => {
+                *matches.entry(key).or_insert(0) += 1;
+                true
+            }
+            Mode::Check => match matches.get_mut(&key) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            },
+        }
+    }
+}
+
+/// The 64 bit FNV-1a hash, which unlike the standard library's hasher is guaranteed to
+/// stay the same between releases, so baselines keep working.
+fn hash(line: &str) -> u64 {
+    line.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(value: &str) -> Line {
+        Line { number: None, value: value.to_string(), captures: Vec::new() }
+    }
+
+    #[test]
+    fn it_hashes_with_fnv() {
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn it_keeps_lines_found_more_often_than_recorded() {
+        let recorded = Baseline::record();
+        assert!(recorded.keep(&line("TODO"), Path::new("a")));
+        assert_eq!(recorded.len(), 1);
+        let baseline = Baseline::parse(&recorded.to_string()).unwrap();
+        assert!(!baseline.keep(&line("TODO"), Path::new("a")));
+        assert!(baseline.keep(&line("TODO"), Path::new("a")));
+        assert!(baseline.keep(&line("TODO"), Path::new("b")));
+    }
+
+    #[test]
+    fn it_writes_sorted_entries() {
+        let recorded = Baseline::record();
+        recorded.keep(&line("x"), Path::new("src/b.rs"));
+        recorded.keep(&line("x"), Path::new("src/a b.rs"));
+        recorded.keep(&line("x"), Path::new("src/a b.rs"));
+        let hash = format!("{:016x}", hash("x"));
+        assert_eq!(
+            recorded.to_string(),
+            format!("{h} src/a b.rs\n{h} src/a b.rs\n{h} src/b.rs\n", h = hash)
+        );
+        assert_eq!(Baseline::parse(&recorded.to_string()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn it_reports_invalid_entries() {
+        assert_eq!(
+            Baseline::parse("00ff a.rs\nnothex a.rs").unwrap_err(),
+            "Invalid baseline entry on line 2: 'nothex a.rs'"
+        );
+    }
+}
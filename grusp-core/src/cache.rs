@@ -0,0 +1,167 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use index::{read_u32, read_u64};
+use matcher::{Capture, Line, Matches};
+
+/// An on-disk cache of the matches found in each file, keyed by a hash of the file's
+/// contents and of the options the search ran with, so a file that hasn't changed
+/// between identical searches doesn't have to be matched again.
+///
+/// The options have to describe everything that changes which lines match, such as the
+/// pattern and its flags. Hashes are only stable for a build of grusp, so a cache
+/// written by another version is just missed.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{Matcher, ResultCache};
+/// use std::io::Cursor;
+///
+/// let dir = std::env::temp_dir().join("grusp-result-cache-doc");
+/// let cache = ResultCache::new(&dir, "test --ignore-case");
+/// let contents = b"a test\nno match";
+/// let reg = regex::Regex::new(r"test").unwrap();
+/// let matches = Matcher::new(&reg).collect(&mut Cursor::new(&contents[..])).unwrap();
+/// cache.put(contents, &matches).unwrap();
+/// assert_eq!(cache.get(contents).unwrap().lines()[0].value, "a test");
+/// assert!(cache.get(b"changed").is_none());
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Creates a cache for searches run with the options, stored under the directory.
+    pub fn new(dir: &Path, options: &str) -> Self {
+        Self { dir: dir.join(format!("{:016x}", hash(options.as_bytes()))) }
+    }
+
+    /// Returns the matches cached for a file with the contents, without a path.
+    pub fn get(&self, contents: &[u8]) -> Option<Matches> {
+        let bytes = fs::read(self.entry(contents)).ok()?;
+        decode(&mut Cursor::new(bytes)).ok()
+    }
+
+    /// Caches the matches found in a file with the contents.
+    pub fn put(&self, contents: &[u8], matches: &Matches) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut bytes = Vec::new();
+        encode(matches, &mut bytes)?;
+        fs::write(self.entry(contents), bytes)
+    }
+
+    fn entry(&self, contents: &[u8]) -> PathBuf {
+        self.dir.join(format!("{:016x}", hash(contents)))
+    }
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn encode<W: Write>(matches: &Matches, writer: &mut W) -> Result<()> {
+    writer.write_all(&matches.count.to_le_bytes())?;
+    writer.write_all(&(matches.lines.len() as u32).to_le_bytes())?;
+    for line in &matches.lines {
+        writer.write_all(&[line.number.is_some() as u8])?;
+        writer.write_all(&(line.number.unwrap_or(0) as u64).to_le_bytes())?;
+        writer.write_all(&(line.value.len() as u32).to_le_bytes())?;
+        writer.write_all(line.value.as_bytes())?;
+        writer.write_all(&(line.captures.len() as u32).to_le_bytes())?;
+        for capture in &line.captures {
+            for field in &[capture.start, capture.end, capture.offset, capture.column] {
+                writer.write_all(&(*field as u64).to_le_bytes())?;
+            }
+            let rule = capture.rule.as_deref().unwrap_or("");
+            writer.write_all(&[capture.rule.is_some() as u8])?;
+            writer.write_all(&(rule.len() as u32).to_le_bytes())?;
+            writer.write_all(rule.as_bytes())?;
+        }
+    }
+    writer.write_all(&(matches.replaced_bytes as u64).to_le_bytes())?;
+    writer.write_all(&(matches.long_lines as u64).to_le_bytes())?;
+    Ok(())
+}
+
+fn decode<R: Read>(reader: &mut R) -> Result<Matches> {
+    let count = read_u32(reader)?;
+    let mut lines = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let mut has_number = [0];
+        reader.read_exact(&mut has_number)?;
+        let number = read_u64(reader)? as usize;
+        let mut value = vec![0; read_u32(reader)? as usize];
+        reader.read_exact(&mut value)?;
+        let value = String::from_utf8(value).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut captures = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let start = read_u64(reader)? as usize;
+            let end = read_u64(reader)? as usize;
+            let offset = read_u64(reader)? as usize;
+            let column = read_u64(reader)? as usize;
+            let mut has_rule = [0];
+            reader.read_exact(&mut has_rule)?;
+            let mut rule = vec![0; read_u32(reader)? as usize];
+            reader.read_exact(&mut rule)?;
+            let rule = String::from_utf8(rule).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            let value = value.get(start..end).ok_or_else(|| Error::new(ErrorKind::InvalidData, "Capture out of bounds"))?;
+            let rule = if has_rule[0] == 1 { Some(rule) } else { None };
+            captures.push(Capture { start, end, value: value.to_string(), offset, column, rule });
+        }
+        lines.push(Line { number: if has_number[0] == 1 { Some(number) } else { None }, value, captures });
+    }
+    let replaced_bytes = read_u64(reader)? as usize;
+    let long_lines = read_u64(reader)? as usize;
+    Ok(Matches { path: None, count, lines, replaced_bytes, long_lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_matches() {
+        let matches = Matches {
+            path: None,
+            count: 2,
+            replaced_bytes: 3,
+            long_lines: 0,
+            lines: vec![
+                Line {
+                    number: Some(3),
+                    value: "caf\u{e9} test".to_string(),
+                    captures: vec![Capture { start: 6, end: 10, value: "test".to_string(), offset: 20, column: 6, rule: Some("todo".to_string()) }],
+                },
+                Line { number: None, value: String::new(), captures: Vec::new() },
+            ],
+        };
+        let mut bytes = Vec::new();
+        encode(&matches, &mut bytes).unwrap();
+        let decoded = decode(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", matches));
+    }
+
+    #[test]
+    fn it_keeps_searches_with_different_options_apart() {
+        let dir = ::std::env::temp_dir().join("grusp-result-cache-test");
+        let matches = Matches { path: None, count: 1, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        Cache::new(&dir, "a").put(b"contents", &matches).unwrap();
+        assert!(Cache::new(&dir, "a").get(b"contents").is_some());
+        assert!(Cache::new(&dir, "b").get(b"contents").is_none());
+    }
+
+    #[test]
+    fn it_rejects_corrupt_entries() {
+        assert!(decode(&mut Cursor::new(vec![1, 0, 0])).is_err());
+    }
+}
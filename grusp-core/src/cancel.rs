@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A token that stops a search early when it's cancelled, possibly from another thread.
+/// Clones share the same state, so one can be handed to the search while another is kept
+/// to cancel it.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{CancellationToken, Matcher};
+/// use std::io::Cursor;
+///
+/// let token = CancellationToken::new();
+/// token.clone().cancel();
+/// let reg = regex::Regex::new(r"test").unwrap();
+/// let mut buf_read = Cursor::new("test\ntest");
+/// let matches = Matcher::new(&reg).cancellation(&token).collect(&mut buf_read).unwrap();
+/// assert!(token.is_cancelled());
+/// assert_eq!(matches.count(), 1);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    is_cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that hasn't been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every search using the token or any of its clones.
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true once the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::SeqCst)
+    }
+}
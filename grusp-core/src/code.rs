@@ -0,0 +1,185 @@
+use std::path::Path;
+use filter::MatchFilter;
+use matcher::Line;
+
+/// The part of a source file that a match has to be in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeContext {
+    /// Line and block comments.
+    Comments,
+    /// String and character literals.
+    Strings,
+    /// Everything that isn't a comment or a literal.
+    Code,
+}
+
+/// The comment and string syntax of a family of languages.
+#[derive(Debug, PartialEq, Eq)]
+struct Syntax {
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const C_LIKE: Syntax = Syntax { line_comment: "//", block_comment: Some(("/*", "*/")), quotes: &['"', '\''] };
+// Lifetimes make single quotes ambiguous in Rust, so only double quoted strings are found
+const RUST: Syntax = Syntax { line_comment: "//", block_comment: Some(("/*", "*/")), quotes: &['"'] };
+const SCRIPT: Syntax = Syntax { line_comment: "#", block_comment: None, quotes: &['"', '\''] };
+const SQL_LIKE: Syntax = Syntax { line_comment: "--", block_comment: None, quotes: &['"', '\''] };
+
+impl Syntax {
+    fn for_path(path: &Path) -> Option<&'static Syntax> {
+        let extension = path.extension().and_then(|e| e.to_str())?;
+        match extension {
+            "rs" => Some(&RUST),
+            "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "js" | "jsx" | "ts" | "tsx" | "go" |
+            "kt" | "scala" | "swift" | "php" => Some(&C_LIKE),
+            "py" | "rb" | "sh" | "bash" | "zsh" | "pl" | "yml" | "yaml" | "toml" | "r" => Some(&SCRIPT),
+            "sql" | "lua" | "hs" => Some(&SQL_LIKE),
+            _ => None,
+        }
+    }
+
+    /// Finds the context of every byte in the line. This only looks at the line itself,
+    /// so a block comment or string that started on an earlier line is seen as code.
+    fn contexts(&self, line: &str) -> Vec<CodeContext> {
+        let mut contexts = vec![CodeContext::Code; line.len()];
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let rest = &line[start..];
+            let end = if rest.starts_with(self.line_comment) {
+                Some((line.len(), CodeContext::Comments))
+            } else if let Some((open, close)) = self.block_comment.filter(|&(open, _)| rest.starts_with(open)) {
+                let end = rest[open.len()..].find(close).map_or(line.len(), |i| start + open.len() + i + close.len());
+                Some((end, CodeContext::Comments))
+            } else if self.quotes.contains(&c) {
+                let mut end = line.len();
+                let mut is_escaped = false;
+                for (i, next) in rest.char_indices().skip(1) {
+                    if is_escaped {
+                        is_escaped = false;
+                    } else if next == '\\' {
+                        is_escaped = true;
+                    } else if next == c {
+                        end = start + i + next.len_utf8();
+                        break;
+                    }
+                }
+                Some((end, CodeContext::Strings))
+            } else {
+                None
+            };
+            if let Some((end, context)) = end {
+                for slot in &mut contexts[start..end] {
+                    *slot = context;
+                }
+                while chars.peek().is_some_and(|&(i, _)| i < end) {
+                    chars.next();
+                }
+            }
+        }
+        contexts
+    }
+}
+
+/// A filter that only keeps lines with a match inside comments, inside string literals
+/// or in the rest of the code, using a lightweight tokenizer for a set of known languages
+/// picked by file extension. Lines from files in other languages are never kept.
+///
+/// The tokenizer works a line at a time, so the inside of a block comment or string that
+/// spans several lines is treated as code.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::{CodeContext, CodeContextFilter, MatchFilter};
+/// use grusp_core::grusp::{Capture, Line};
+/// use std::path::Path;
+///
+/// let line = Line {
+///     number: Some(1),
+///     value: "let x = 1; // TODO".to_string(),
+///     captures: vec![Capture { start: 14, end: 18, value: "TODO".to_string(), offset: 14, column: 15, rule: None }],
+/// };
+/// assert!(CodeContextFilter::new(CodeContext::Comments).keep(&line, Path::new("main.rs")));
+/// assert!(!CodeContextFilter::new(CodeContext::Code).keep(&line, Path::new("main.rs")));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct CodeContextFilter {
+    context: CodeContext,
+}
+
+impl CodeContextFilter {
+    /// Creates a filter that keeps lines with a match in the context.
+    pub fn new(context: CodeContext) -> Self {
+        CodeContextFilter { context }
+    }
+}
+
+impl MatchFilter for CodeContextFilter {
+    fn keep(&self, line: &Line, path: &Path) -> bool {
+        let syntax = match Syntax::for_path(path) {
+            Some(syntax) => syntax,
+            None => return false,
+        };
+        let contexts = syntax.contexts(&line.value);
+        line.captures.iter().any(|capture| {
+            contexts.get(capture.start).is_some_and(|&context| context == self.context)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matcher::Capture;
+
+    fn line(value: &str, needle: &str) -> Line {
+        let captures = value
+            .match_indices(needle)
+            .map(|(start, m)| Capture { start, end: start + m.len(), value: m.to_string(), offset: start, column: start + 1, rule: None })
+            .collect();
+        Line { number: Some(1), value: value.to_string(), captures }
+    }
+
+    fn keeps(context: CodeContext, value: &str, needle: &str, path: &str) -> bool {
+        CodeContextFilter::new(context).keep(&line(value, needle), Path::new(path))
+    }
+
+    #[test]
+    fn it_finds_line_comments() {
+        assert!(keeps(CodeContext::Comments, "x = 1 # fix", "fix", "a.py"));
+        assert!(keeps(CodeContext::Comments, "-- fix", "fix", "a.sql"));
+        assert!(!keeps(CodeContext::Comments, "fix() // later", "fix", "a.js"));
+    }
+
+    #[test]
+    fn it_finds_block_comments() {
+        assert!(keeps(CodeContext::Comments, "a /* fix */ b", "fix", "a.c"));
+        assert!(keeps(CodeContext::Code, "a /* x */ fix", "fix", "a.c"));
+    }
+
+    #[test]
+    fn it_finds_strings_with_escaped_quotes() {
+        assert!(keeps(CodeContext::Strings, r#"let s = "a \" fix";"#, "fix", "a.rs"));
+        assert!(keeps(CodeContext::Code, r#"let s = "a \" b"; fix()"#, "fix", "a.rs"));
+        assert!(keeps(CodeContext::Strings, "x = 'fix'", "fix", "a.py"));
+    }
+
+    #[test]
+    fn it_ignores_comment_markers_inside_strings() {
+        assert!(keeps(CodeContext::Strings, r#"url = "http://fix""#, "fix", "a.go"));
+        assert!(keeps(CodeContext::Code, r##"s = "#"; fix"##, "fix", "a.rb"));
+    }
+
+    #[test]
+    fn it_treats_rust_lifetimes_as_code() {
+        assert!(keeps(CodeContext::Code, "fn f<'a>(x: &'a str) -> fix", "fix", "main.rs"));
+    }
+
+    #[test]
+    fn it_never_keeps_lines_in_unknown_languages() {
+        assert!(!keeps(CodeContext::Code, "fix", "fix", "notes.txt"));
+        assert!(!keeps(CodeContext::Code, "fix", "fix", ""));
+    }
+}
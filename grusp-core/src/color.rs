@@ -0,0 +1,68 @@
+use std::env;
+use colored::control::{self, SHOULD_COLORIZE};
+
+/// Whether output is colored, decided once when a program starts rather than by each
+/// display. Displays are colored by default when the choice applied last colors output,
+/// and can still be told otherwise with their `color` builders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Follows the conventions other command line tools share: `NO_COLOR` turns colors
+    /// off, `CLICOLOR_FORCE` turns them on even when piped, and `CLICOLOR=0` turns them
+    /// off. Otherwise output is colored when it's written to a terminal.
+    Auto,
+    /// Always colors output.
+    Always,
+    /// Never colors output.
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether to color output from the environment variables read by `var`
+    /// and whether the output is a terminal. Variables that are set but empty are
+    /// treated as unset.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::ColorChoice;
+    ///
+    /// let env = |vars: &'static [(&'static str, &'static str)]| {
+    ///     move |name: &str| vars.iter().find(|v| v.0 == name).map(|v| v.1.to_string())
+    /// };
+    /// assert!(ColorChoice::Auto.resolve(env(&[]), true));
+    /// assert!(!ColorChoice::Auto.resolve(env(&[]), false));
+    /// assert!(!ColorChoice::Auto.resolve(env(&[("NO_COLOR", "1")]), true));
+    /// assert!(ColorChoice::Auto.resolve(env(&[("CLICOLOR_FORCE", "1")]), false));
+    /// assert!(!ColorChoice::Auto.resolve(env(&[("CLICOLOR", "0")]), true));
+    /// assert!(!ColorChoice::Never.resolve(env(&[("CLICOLOR_FORCE", "1")]), true));
+    /// ```
+    pub fn resolve<F>(self, var: F, is_terminal: bool) -> bool
+        where F: Fn(&str) -> Option<String>
+    {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto if var("NO_COLOR").is_some() => false,
+            ColorChoice::Auto => match var("CLICOLOR_FORCE") {
+                Some(ref force) if force != "0" => true,
+                _ => is_terminal && var("CLICOLOR").is_none_or(|clicolor| clicolor != "0"),
+            },
+        }
+    }
+
+    /// Resolves the choice against the environment of the process and applies it to
+    /// every display and colored string made afterwards. Returns whether output is
+    /// colored.
+    pub fn apply(self, is_terminal: bool) -> bool {
+        let is_colored = self.resolve(|name| env::var(name).ok(), is_terminal);
+        control::set_override(is_colored);
+        is_colored
+    }
+}
+
+/// Returns true if output is colored, as decided by the last choice applied, or by the
+/// environment when none has been.
+pub fn is_colored() -> bool {
+    SHOULD_COLORIZE.should_colorize()
+}
@@ -0,0 +1,129 @@
+//! Synthetic corpora used to benchmark the matcher and the file collector.
+//!
+//! Everything is generated from a fixed seed so runs can be compared with each other.
+//! Every corpus contains the needle `fn grusp_needle(` on roughly one line in fifty.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The literal that's planted throughout the generated text.
+pub const NEEDLE: &str = "fn grusp_needle(";
+
+const WORDS: [&str; 16] = [
+    "let", "match", "struct", "impl", "self", "return", "where", "loop",
+    "value", "buffer", "regex", "path", "line", "count", "option", "result",
+];
+
+/// A small xorshift generator, which is all the randomness the corpora need.
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Rng(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn text(rng: &mut Rng, lines: usize, width: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(lines * (width + 1));
+    for _ in 0..lines {
+        let start = out.len();
+        if rng.below(50) == 0 {
+            out.extend_from_slice(NEEDLE.as_bytes());
+        }
+        while out.len() - start < width {
+            out.extend_from_slice(WORDS[rng.below(WORDS.len())].as_bytes());
+            out.push(b' ');
+        }
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Generates `lines` lines that are each about `width` bytes long.
+pub fn long_lines(lines: usize, width: usize) -> Vec<u8> {
+    text(&mut Rng::new(), lines, width)
+}
+
+/// Generates `count` small files of a few short lines each.
+pub fn small_files(count: usize) -> Vec<Vec<u8>> {
+    let mut rng = Rng::new();
+    (0..count).map(|_| {
+        let lines = 1 + rng.below(20);
+        text(&mut rng, lines, 40)
+    }).collect()
+}
+
+/// Generates text where one line in ten is replaced with random bytes, including
+/// NULs and invalid UTF-8, like a log with embedded binary blobs.
+pub fn binary_ish(lines: usize) -> Vec<u8> {
+    let mut rng = Rng::new();
+    let mut out = Vec::new();
+    for _ in 0..lines {
+        if rng.below(10) == 0 {
+            let len = rng.below(200);
+            out.extend((0..len).map(|_| rng.next() as u8).filter(|&b| b != b'\n'));
+            out.push(b'\n');
+        } else {
+            out.extend(text(&mut rng, 1, 80));
+        }
+    }
+    out
+}
+
+/// Writes a tree of directories `depth` levels deep, where every directory has
+/// `breadth` sub directories and `files` small files.
+pub fn deep_tree(dir: &Path, depth: usize, breadth: usize, files: usize) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (i, contents) in small_files(files).iter().enumerate() {
+        fs::write(dir.join(format!("file_{}.rs", i)), contents)?;
+    }
+    if depth > 0 {
+        for i in 0..breadth {
+            deep_tree(&dir.join(format!("dir_{}", i)), depth - 1, breadth, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes every corpus into `dir` so the same data can be searched with the binary.
+pub fn write(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("long_lines.txt"), long_lines(10_000, 2_000))?;
+    fs::write(dir.join("binary_ish.log"), binary_ish(100_000))?;
+    let small = dir.join("small_files");
+    fs::create_dir_all(&small)?;
+    for (i, contents) in small_files(5_000).iter().enumerate() {
+        fs::write(small.join(format!("file_{}.rs", i)), contents)?;
+    }
+    deep_tree(&dir.join("deep_tree"), 6, 3, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_generates_the_same_corpus_every_time() {
+        assert_eq!(long_lines(100, 80), long_lines(100, 80));
+        assert_eq!(binary_ish(100), binary_ish(100));
+    }
+
+    #[test]
+    fn it_plants_the_needle() {
+        let corpus = String::from_utf8(long_lines(1_000, 80)).unwrap();
+        assert_eq!(corpus.lines().count(), 1_000);
+        assert!(corpus.matches(NEEDLE).count() > 5);
+    }
+}
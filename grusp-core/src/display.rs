@@ -1,12 +1,105 @@
-use matcher::{Matches, Line};
+use matcher::{Matches, Line, Capture};
+use highlight::Rules as HighlightRules;
+use replace::Replacement;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::io;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use colored::*;
+use color;
 
 /// MatchDisplay to format a single Match
 #[derive(Debug)]
 pub struct LineDisplay<'a> {
     match_to_display: &'a Line,
     is_colored: bool,
+    is_trimmed: bool,
+    highlights: HighlightRules,
+    redaction: Option<Redaction>,
+    replacement: Option<Replacement>,
+    number_width: usize,
+    max_columns: Option<usize>,
+}
+
+/// How wide the line numbers of a file's lines are padded, so they're right aligned in a
+/// column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberWidth {
+    /// Pads every line number to at least this many digits.
+    Fixed(usize),
+    /// Pads the line numbers to the digits of the largest one shown for the file.
+    Auto,
+}
+
+/// Replaces the matched text of displayed lines, so results can be shared without the
+/// sensitive values they matched.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::Redaction;
+///
+/// assert_eq!(Redaction::Mask("****".to_string()).apply("hunter2"), "****");
+/// // Hashes are stable, so the same value can still be followed through the results
+/// assert_eq!(Redaction::Hash.apply("hunter2"), Redaction::Hash.apply("hunter2"));
+/// assert_ne!(Redaction::Hash.apply("hunter2"), Redaction::Hash.apply("hunter3"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redaction {
+    /// Replaces each match with the same text.
+    Mask(String),
+    /// Replaces each match with `#` and a short hash of the matched text.
+    Hash,
+}
+
+impl Redaction {
+    /// Returns the text a match is replaced with.
+    pub fn apply(&self, value: &str) -> String {
+        match *self {
+            Redaction::Mask(ref mask) => mask.clone(),
+            Redaction::Hash => {
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                format!("#{:08x}", hasher.finish() as u32)
+            }
+        }
+    }
+
+    /// Replaces every capture of the line, moving the captures onto the replacements.
+    fn line(&self, line: &Line) -> Line {
+        let mut value = String::new();
+        let mut captures = Vec::new();
+        let mut last = 0;
+        for cap in &line.captures {
+            if cap.start < last {
+                continue;
+            }
+            value.push_str(&line.value[last..cap.start]);
+            let replacement = self.apply(&cap.value);
+            let start = value.len();
+            value.push_str(&replacement);
+            captures.push(Capture {
+                start,
+                end: value.len(),
+                value: replacement,
+                offset: cap.offset,
+                column: cap.column,
+                rule: cap.rule.clone(),
+            });
+            last = cap.end;
+        }
+        value.push_str(&line.value[last..]);
+        Line { number: line.number, value, captures }
+    }
+}
+
+/// How a byte of a line is painted, from lowest to highest precedence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paint {
+    Plain,
+    Highlight(Color),
+    Capture,
 }
 
 /// A struct used to wrap the matches that are found and then
@@ -18,43 +111,188 @@ pub struct MatchesDisplay {
     is_colored: bool,
     is_count_only: bool,
     just_file_names: bool,
+    is_trimmed: bool,
+    is_passthru: bool,
+    highlights: HighlightRules,
+    redaction: Option<Redaction>,
+    replacement: Option<Replacement>,
+    number_width: usize,
+    max_columns: Option<usize>,
+    is_vimgrep: bool,
 }
 
 impl<'a> LineDisplay<'a> {
     fn prefix_fmt(&self) -> Option<String> {
         self.match_to_display.number.map(|line_number| {
+            let line_number = format!("{:>width$}", line_number, width = self.number_width);
             if self.is_colored {
-                line_number.to_string().yellow().to_string()
+                line_number.yellow().to_string()
             } else {
-                line_number.to_string()
+                line_number
             }
         })
     }
 
+    /// The names of the patterns that matched the line, in the order they first matched,
+    /// so the matches of a lint-style scan show which rules they broke.
+    fn rules_fmt(&self) -> String {
+        let mut rules: Vec<&str> = Vec::new();
+        for rule in self.match_to_display.captures.iter().filter_map(|cap| cap.rule.as_deref()) {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        if rules.is_empty() {
+            return String::new();
+        }
+        let rules = format!("[{}]", rules.join(", "));
+        if self.is_colored {
+            format!("{} ", rules.cyan())
+        } else {
+            format!("{} ", rules)
+        }
+    }
+
     fn line_fmt(&self) -> String {
-        let line = &*self.match_to_display.value;
+        let replaced = self.replacement.as_ref().map(|replacement| replacement.line(self.match_to_display));
+        let line = replaced.as_ref().unwrap_or(self.match_to_display);
+        let redacted = self.redaction.as_ref().map(|redaction| redaction.line(line));
+        let to_display = redacted.as_ref().unwrap_or(line);
+        let line = &*to_display.value;
+        let offset = if self.is_trimmed { line.len() - line.trim_start().len() } else { 0 };
+        let end = line.trim_end().len().max(offset);
+        let ranges = to_display.highlight_ranges();
+        let (start, window_end) = self.window(line, &ranges, offset, end);
+        // Ellipses mark the parts of a long line that are cut off
+        let (before, after) = (if start > offset { "…" } else { "" }, if window_end < end { "…" } else { "" });
+        let (offset, end) = (start, window_end);
 
-        if self.is_colored {
-            let mut output = String::new();
-            let mut prev_end = 0;
-            for cap in &self.match_to_display.captures {
-                output.push_str(&line[prev_end..cap.start]);
-                output.push_str(&cap.value.black().on_yellow().to_string());
-                prev_end = cap.end;
+        if !self.is_colored {
+            return format!("{}{}{}", before, &line[offset..end], after);
+        }
+        let mut paints: Vec<Paint> = self.highlights
+            .colors(line)
+            .into_iter()
+            .map(|color| color.map_or(Paint::Plain, Paint::Highlight))
+            .collect();
+        for range in ranges {
+            for paint in &mut paints[range] {
+                *paint = Paint::Capture;
             }
-            output.push_str(&line[prev_end..]);
-            output.trim_right().to_string()
-        } else {
-            line.trim_right().to_string()
         }
+        // The ranges and highlights start and end on character boundaries, but a run is
+        // still widened to the end of its last character so it can always be sliced out of
+        // the line. Leading whitespace is dropped when trimming.
+        let mut output = before.to_string();
+        let mut start = offset;
+        while start < end {
+            let paint = paints[start];
+            let mut run = paints[start..end].iter().take_while(|&&p| p == paint).count();
+            while !line.is_char_boundary(start + run) {
+                run += 1;
+            }
+            let text = &line[start..start + run];
+            match paint {
+                Paint::Plain => output.push_str(text),
+                Paint::Highlight(color) => output.push_str(&text.color(color).to_string()),
+                Paint::Capture => output.push_str(&text.black().on_yellow().to_string()),
+            }
+            start += run;
+        }
+        output.push_str(after);
+        output
     }
 
+    /// Returns the bytes of the line between `start` and `end` that are shown, which are
+    /// at most `max_columns` characters centered on the first match, so a match in a long
+    /// line, such as in minified code, is still shown.
+    fn window(&self, line: &str, ranges: &[Range<usize>], start: usize, end: usize) -> (usize, usize) {
+        let max_columns = match self.max_columns {
+            Some(max_columns) => max_columns,
+            None => return (start, end),
+        };
+        let text = &line[start..end];
+        let columns = text.chars().count();
+        if columns <= max_columns {
+            return (start, end);
+        }
+        let column_of = |byte: usize| text.char_indices().take_while(|&(i, _)| start + i < byte).count();
+        let center = match ranges.first() {
+            Some(range) => (column_of(range.start) + column_of(range.end)) / 2,
+            None => 0,
+        };
+        let first = center.saturating_sub(max_columns / 2).min(columns - max_columns);
+        let byte_of = |column: usize| text.char_indices().nth(column).map_or(end, |(i, _)| start + i);
+        (byte_of(first), byte_of(first + max_columns))
+    }
+
+    /// Displays a line the same way the parent displays its lines, padding its number to
+    /// the width of the parent's line numbers.
     pub fn new(match_to_display: &'a Line, parent: &MatchesDisplay) -> LineDisplay<'a> {
         LineDisplay {
-            match_to_display: match_to_display,
+            match_to_display,
             is_colored: parent.is_colored,
+            is_trimmed: parent.is_trimmed,
+            highlights: parent.highlights.clone(),
+            redaction: parent.redaction.clone(),
+            replacement: parent.replacement.clone(),
+            number_width: parent.number_width,
+            max_columns: parent.max_columns,
         }
     }
+
+    /// Displays a line on its own, such as when lines are streamed as they're matched.
+    /// Use the builder functions to configure.
+    pub fn single(match_to_display: &'a Line) -> LineDisplay<'a> {
+        LineDisplay {
+            match_to_display,
+            is_colored: color::is_colored(),
+            is_trimmed: false,
+            highlights: HighlightRules::default(),
+            redaction: None,
+            replacement: None,
+            number_width: 0,
+            max_columns: None,
+        }
+    }
+
+    /// Consumes the display and right aligns the line number, if any, padding it to at
+    /// least `number_width` digits.
+    pub fn number_width(self, number_width: usize) -> Self {
+        Self { number_width, ..self }
+    }
+
+    /// Consumes the display and colors the parts of the line matching the rules.
+    pub fn highlights(self, highlights: HighlightRules) -> Self {
+        Self { highlights, ..self }
+    }
+
+    /// Consumes the display and replaces the matches with the redaction, if any.
+    pub fn redact(self, redaction: Option<Redaction>) -> Self {
+        Self { redaction, ..self }
+    }
+
+    /// Consumes the display and cuts the line down to at most `max_columns` characters
+    /// around its first match, if any.
+    pub fn max_columns(self, max_columns: Option<usize>) -> Self {
+        Self { max_columns, ..self }
+    }
+
+    /// Consumes the display and shows the matches replaced, if there's a replacement.
+    pub fn replace(self, replacement: Option<Replacement>) -> Self {
+        Self { replacement, ..self }
+    }
+
+    /// Consumes the display and enables/disables colored output, instead of following the
+    /// `ColorChoice` applied.
+    pub fn color(self, is_colored: bool) -> Self {
+        Self { is_colored, ..self }
+    }
+
+    /// Consumes the display and enables/disables removing the leading whitespace.
+    pub fn trim(self, is_trimmed: bool) -> Self {
+        Self { is_trimmed, ..self }
+    }
 }
 
 impl MatchesDisplay {
@@ -63,19 +301,111 @@ impl MatchesDisplay {
     /// a display struct. Use the builder functions to configure.
     pub fn new(matches: Matches) -> MatchesDisplay {
         MatchesDisplay {
-            matches: matches,
-            is_colored: true,
+            matches,
+            is_colored: color::is_colored(),
             is_count_only: false,
             just_file_names: false,
+            is_trimmed: false,
+            is_passthru: false,
+            highlights: HighlightRules::default(),
+            redaction: None,
+            replacement: None,
+            number_width: 0,
+            max_columns: None,
+            is_vimgrep: false,
         }
     }
 
+    /// Consumes the display and pads the line numbers of the lines, if any, so they're
+    /// right aligned.
+    pub fn number_width(self, number_width: Option<NumberWidth>) -> Self {
+        let number_width = match number_width {
+            Some(NumberWidth::Fixed(width)) => width,
+            Some(NumberWidth::Auto) => self.matches
+                .lines
+                .iter()
+                .filter_map(|line| line.number)
+                .max()
+                .map_or(0, |number| number.to_string().len()),
+            None => 0,
+        };
+        Self { number_width, ..self }
+    }
+
+    /// Consumes the display and replaces the matches of each line with the redaction, if any.
+    pub fn redact(self, redaction: Option<Redaction>) -> Self {
+        Self { redaction, ..self }
+    }
+
+    /// Consumes the display and cuts each line longer than `max_columns` characters down
+    /// to that many, centered on its first match, with ellipses where it was cut.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Display, Matcher};
+    /// use std::io::Cursor;
+    ///
+    /// let regex = regex::Regex::new("needle").unwrap();
+    /// let line = format!("{}needle{}", "a".repeat(50), "b".repeat(50));
+    /// let matches = Matcher::new(&regex).collect(&mut Cursor::new(line)).unwrap();
+    /// let display = Display::new(matches).color(false).max_columns(Some(10));
+    /// assert_eq!(display.to_string(), "matched 1 time\n1:…aaneedlebb…\n");
+    /// # }
+    /// ```
+    pub fn max_columns(self, max_columns: Option<usize>) -> Self {
+        Self { max_columns, ..self }
+    }
+
+    /// Consumes the display and shows the matches of each line replaced, if there's a
+    /// replacement. Nothing is written to the files the lines came from.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Display, Matcher, Replacement};
+    /// use std::io::Cursor;
+    ///
+    /// let regex = regex::Regex::new(r"old_(\w+)").unwrap();
+    /// let matches = Matcher::new(&regex).collect(&mut Cursor::new("old_api();")).unwrap();
+    /// let display = Display::new(matches).color(false).replace(Some(Replacement::new(&regex, "new_$1")));
+    /// assert_eq!(display.to_string(), "matched 1 time\n1:new_api();\n");
+    /// # }
+    /// ```
+    pub fn replace(self, replacement: Option<Replacement>) -> Self {
+        Self { replacement, ..self }
+    }
+
+    /// Consumes the display and colors the parts of each line matching the rules, on top
+    /// of the highlighted matches. Only applies to colored output.
+    pub fn highlights(self, highlights: HighlightRules) -> Self {
+        Self { highlights, ..self }
+    }
+
+    /// Consumes the display and enables/disables passthru output, where the lines are
+    /// shown under just the path, without the number of times they matched.
+    pub fn passthru(self, is_passthru: bool) -> Self {
+        Self { is_passthru, ..self }
+    }
+
+    /// Consumes the display and enables/disables removing the leading whitespace of lines.
+    pub fn trim(self, is_trimmed: bool) -> Self {
+        Self { is_trimmed, ..self }
+    }
+
     /// Consumes self and enables/disables displaying just the file names
     pub fn just_file_names(self, just_file_names: bool) -> Self {
         Self { just_file_names, ..self }
     }
 
-    /// Consumes the display and enables/disables colored output.
+    /// Consumes the display and enables/disables colored output, instead of following the
+    /// `ColorChoice` applied.
     pub fn color(self, is_colored: bool) -> Self {
         Self { is_colored, ..self }
     }
@@ -84,21 +414,89 @@ impl MatchesDisplay {
     pub fn count_only(self, is_count_only: bool) -> Self {
         Self { is_count_only, ..self }
     }
+
+    /// Consumes the display and enables/disables showing each match on a line of its own
+    /// as `path:line:column:content`, which Vim's default errorformat reads into the
+    /// quickfix list. Columns count bytes from one, as Vim's do. Lines are shown without
+    /// colors, and matches without a path are shown as `<stdin>`.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Display, Matcher};
+    /// use std::path::Path;
+    ///
+    /// let regex = regex::Regex::new("TODO").unwrap();
+    /// let matches = Matcher::new(&regex)
+    ///     .collect_path(&mut "fn main() {}\n// TODO: a, TODO: b\n".as_bytes(), Path::new("src/main.rs"))
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     Display::new(matches).vimgrep(true).to_string(),
+    ///     "src/main.rs:2:4:// TODO: a, TODO: b\nsrc/main.rs:2:13:// TODO: a, TODO: b\n"
+    /// );
+    /// # }
+    /// ```
+    pub fn vimgrep(self, is_vimgrep: bool) -> Self {
+        Self { is_vimgrep, ..self }
+    }
+
+    fn vimgrep_fmt<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        let path = self.matches.path.as_ref().map_or("<stdin>".into(), |path| path.to_string_lossy());
+        for line in &self.matches.lines {
+            let replaced = self.replacement.as_ref().map(|replacement| replacement.line(line));
+            let line = replaced.as_ref().unwrap_or(line);
+            let redacted = self.redaction.as_ref().map(|redaction| redaction.line(line));
+            let line = redacted.as_ref().unwrap_or(line);
+            let value = line.value.trim_end();
+            let value = if self.is_trimmed { value.trim_start() } else { value };
+            for cap in &line.captures {
+                let rule = cap.rule.as_ref().map(|rule| format!("[{}] ", rule)).unwrap_or_default();
+                writeln!(f, "{}:{}:{}:{}{}", path, line.number.unwrap_or(0), cap.start + 1, rule, value)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'a> fmt::Display for LineDisplay<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(prefix) = self.prefix_fmt() {
-            write!(f, "{}:{}", prefix, self.line_fmt())?;
+            write!(f, "{}:{}{}", prefix, self.rules_fmt(), self.line_fmt())?;
         } else {
-            write!(f, "{}", self.line_fmt())?;
+            write!(f, "{}{}", self.rules_fmt(), self.line_fmt())?;
         }
         Ok(())
     }
 }
 
-impl fmt::Display for MatchesDisplay {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl MatchesDisplay {
+    /// Writes the matches to the writer as they're displayed, so they can go straight to
+    /// a file, a socket or a buffer. Colors follow `color`, which can be set by whether
+    /// the writer is a terminal.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Display, Matcher};
+    /// use std::io::Cursor;
+    ///
+    /// let regex = regex::Regex::new("test").unwrap();
+    /// let matches = Matcher::new(&regex).collect(&mut Cursor::new("a test")).unwrap();
+    /// let mut output = Vec::new();
+    /// Display::new(matches).color(false).write_to(&mut output).unwrap();
+    /// assert_eq!(output, b"matched 1 time\n1:a test\n");
+    /// # }
+    /// ```
+    pub fn write_to<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        if self.is_vimgrep {
+            return self.vimgrep_fmt(f);
+        }
         let mut ret = String::new();
 
         if let Some(ref path) = self.matches.path {
@@ -112,18 +510,27 @@ impl fmt::Display for MatchesDisplay {
         if self.just_file_names {
             return write!(f, "{}", ret.trim())
         }
+        if self.is_passthru {
+            if self.matches.path.is_some() {
+                writeln!(f, "{}", ret.trim())?;
+            }
+            for m in &self.matches.lines {
+                writeln!(f, "{}", LineDisplay::new(m, self))?;
+            }
+            return Ok(())
+        }
         if self.is_colored {
             ret.push_str(&format!("matched {} time", self.matches.count.to_string().yellow()));
         } else {
-            ret.push_str(&format!("matched {} time", self.matches.count.to_string()));
+            ret.push_str(&format!("matched {} time", self.matches.count));
         }
-        if self.matches.count > 1 { ret.push_str("s"); }
+        if self.matches.count > 1 { ret.push('s'); }
         write!(f, "{}", ret)?;
 
         if !self.is_count_only {
-            writeln!(f, "")?;
+            writeln!(f)?;
             for m in &self.matches.lines {
-                writeln!(f, "{}", LineDisplay::new(m, &self))?;
+                writeln!(f, "{}", LineDisplay::new(m, self))?;
             }
         }
 
@@ -131,6 +538,14 @@ impl fmt::Display for MatchesDisplay {
     }
 }
 
+impl fmt::Display for MatchesDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut output = Vec::new();
+        self.write_to(&mut output).map_err(|_| fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&output))
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -142,6 +557,8 @@ mod tests {
     fn it_formats_a_match_with_just_counts() {
         let m = Matches {
             count: 12,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: Some(Path::new("./path/to/something").to_owned()),
             lines: vec![
                 Line {
@@ -152,6 +569,9 @@ mod tests {
                             start: 5,
                             end: 9,
                             value: "text".to_string(),
+                            offset: 5,
+                            column: 6,
+                            rule: None,
                         },
                     ],
                 },
@@ -171,6 +591,8 @@ mod tests {
     fn it_formats_a_match_with_just_count_but_single_time() {
         let m = Matches {
             count: 1,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: Some(Path::new("./path/to/something").to_owned()),
             lines: Vec::new(),
         };
@@ -188,6 +610,8 @@ mod tests {
     fn it_formats_a_match_without_color() {
         let m = Matches {
             count: 12,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: Some(Path::new("./path/to/something").to_owned()),
             lines: vec![
                 Line {
@@ -198,6 +622,9 @@ mod tests {
                             start: 5,
                             end: 9,
                             value: "text".to_string(),
+                            offset: 5,
+                            column: 6,
+                            rule: None,
                         },
                     ],
                 },
@@ -219,6 +646,8 @@ mod tests {
     fn it_formats_a_match() {
         let m = Matches {
             count: 12,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: Some(Path::new("./path/to/something").to_owned()),
             lines: vec![
                 Line {
@@ -229,6 +658,9 @@ mod tests {
                             start: 5,
                             end: 9,
                             value: "text".to_string(),
+                            offset: 5,
+                            column: 6,
+                            rule: None,
                         },
                     ],
                 },
@@ -250,6 +682,8 @@ mod tests {
     fn it_formats_a_match_without_a_path() {
         let m = Matches {
             count: 12,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: None,
             lines: vec![
                 Line {
@@ -260,6 +694,9 @@ mod tests {
                             start: 5,
                             end: 9,
                             value: "text".to_string(),
+                            offset: 5,
+                            column: 6,
+                            rule: None,
                         },
                     ],
                 },
@@ -280,6 +717,8 @@ mod tests {
     fn it_prints_just_matching_files() {
         let m = Matches {
             count: 12,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: Some(Path::new("./path/to/something").to_owned()),
             lines: vec![
                 Line {
@@ -290,6 +729,9 @@ mod tests {
                             start: 5,
                             end: 9,
                             value: "text".to_string(),
+                            offset: 5,
+                            column: 6,
+                            rule: None,
                         },
                     ],
                 },
@@ -305,6 +747,8 @@ mod tests {
     fn it_prints_nothing_when_just_file_names_but_no_path() {
         let m = Matches {
             count: 12,
+            replaced_bytes: 0,
+            long_lines: 0,
             path: None,
             lines: vec![
                 Line {
@@ -315,6 +759,9 @@ mod tests {
                             start: 5,
                             end: 9,
                             value: "text".to_string(),
+                            offset: 5,
+                            column: 6,
+                            rule: None,
                         },
                     ],
                 },
@@ -325,4 +772,184 @@ mod tests {
             ""
         );
     }
+
+    #[test]
+    fn it_trims_leading_whitespace_and_shifts_captures() {
+        let line = Line {
+            number: None,
+            value: "        let text = 1;".to_string(),
+            captures: vec![
+                Capture { start: 0, end: 9, value: "        l".to_string(), offset: 0, column: 1, rule: None },
+                Capture { start: 12, end: 16, value: "text".to_string(), offset: 12, column: 13, rule: None },
+            ],
+        };
+        let matches = || Matches { count: 0, path: None, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches()).trim(true);
+        assert_eq!(
+            LineDisplay::new(&line, &display).to_string(),
+            format!("{}et {} = 1;", "l".black().on_yellow(), "text".black().on_yellow())
+        );
+        let display = MatchesDisplay::new(matches()).trim(true).color(false);
+        assert_eq!(LineDisplay::new(&line, &display).to_string(), "let text = 1;");
+    }
+
+    #[test]
+    fn it_highlights_overlapping_captures_once() {
+        let capture = |start: usize, end: usize| Capture { start, end, value: String::new(), offset: start, column: start + 1, rule: None };
+        let line = Line {
+            number: None,
+            value: "foobar baz".to_string(),
+            captures: vec![capture(3, 6), capture(0, 4), capture(8, 40)],
+        };
+        let matches = || Matches { count: 0, path: None, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches());
+        assert_eq!(
+            LineDisplay::new(&line, &display).to_string(),
+            format!("{} b{}", "foobar".black().on_yellow(), "az".black().on_yellow())
+        );
+    }
+
+    #[test]
+    fn it_widens_captures_inside_characters_to_whole_characters() {
+        let capture = |start: usize, end: usize| Capture { start, end, value: String::new(), offset: start, column: start + 1, rule: None };
+        let line = Line {
+            number: None,
+            value: "日本語 🎉 ok".to_string(),
+            captures: vec![capture(4, 5), capture(11, 12), capture(16, 30)],
+        };
+        let matches = || Matches { count: 0, path: None, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches());
+        assert_eq!(
+            LineDisplay::new(&line, &display).to_string(),
+            format!("日{}語 {} o{}", "本".black().on_yellow(), "🎉".black().on_yellow(), "k".black().on_yellow())
+        );
+        let display = MatchesDisplay::new(matches()).max_columns(Some(4)).color(false);
+        assert_eq!(LineDisplay::new(&line, &display).to_string(), "日本語 …");
+    }
+
+    #[test]
+    fn it_centers_long_lines_on_their_first_match() {
+        let line = Line {
+            number: None,
+            value: "var a=1;var ñeedle=2;var b=3;".to_string(),
+            captures: vec![Capture { start: 12, end: 19, value: "ñeedle".to_string(), offset: 12, column: 12, rule: None }],
+        };
+        let matches = || Matches { count: 0, path: None, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches()).max_columns(Some(12));
+        assert_eq!(
+            LineDisplay::new(&line, &display).to_string(),
+            format!("…ar {}=2;…", "ñeedle".black().on_yellow())
+        );
+        // A match near either end keeps the window against that end
+        let start = Line { captures: vec![Capture { start: 0, end: 3, value: "var".to_string(), offset: 0, column: 1, rule: None }], ..line.clone() };
+        let display = MatchesDisplay::new(matches()).max_columns(Some(6)).color(false);
+        assert_eq!(LineDisplay::new(&start, &display).to_string(), "var a=…");
+        let display = MatchesDisplay::new(matches()).max_columns(Some(100)).color(false);
+        assert_eq!(LineDisplay::new(&line, &display).to_string(), line.value);
+    }
+
+    #[test]
+    fn it_formats_passthru_without_counts() {
+        let m = Matches {
+            count: 1,
+            replaced_bytes: 0,
+            long_lines: 0,
+            path: Some(Path::new("./path/to/something").to_owned()),
+            lines: vec![
+                Line { number: Some(1), value: "some".to_string(), captures: Vec::new() },
+                Line { number: Some(2), value: "text".to_string(), captures: Vec::new() },
+            ],
+        };
+        assert_eq!(
+            format!("{}", MatchesDisplay::new(m).passthru(true).color(false)),
+            "./path/to/something\n1:some\n2:text\n"
+        )
+    }
+
+    #[test]
+    fn it_colors_highlights_under_the_captures() {
+        let line = Line {
+            number: None,
+            value: "ERROR: disk full".to_string(),
+            captures: vec![
+                Capture { start: 7, end: 11, value: "disk".to_string(), offset: 7, column: 8, rule: None },
+            ],
+        };
+        let rules = HighlightRules::parse("ERROR=red\n: disk=blue").unwrap();
+        let matches = Matches { count: 1, path: None, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches).highlights(rules);
+        assert_eq!(
+            LineDisplay::new(&line, &display).to_string(),
+            format!(
+                "{}{}{} full",
+                "ERROR".red(),
+                ": ".blue(),
+                "disk".black().on_yellow()
+            )
+        );
+    }
+
+    #[test]
+    fn it_redacts_the_captures() {
+        let line = Line {
+            number: Some(3),
+            value: "user=kevin pass=hunter2".to_string(),
+            captures: vec![
+                Capture { start: 5, end: 10, value: "kevin".to_string(), offset: 5, column: 6, rule: None },
+                Capture { start: 16, end: 23, value: "hunter2".to_string(), offset: 16, column: 17, rule: None },
+            ],
+        };
+        let matches = || Matches { count: 1, path: None, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches()).color(false).redact(Some(Redaction::Mask("****".to_string())));
+        assert_eq!(LineDisplay::new(&line, &display).to_string(), "3:user=**** pass=****");
+        let display = MatchesDisplay::new(matches()).redact(Some(Redaction::Mask("**".to_string())));
+        assert_eq!(
+            LineDisplay::new(&line, &display).to_string(),
+            format!("{}:user={} pass={}", "3".yellow(), "**".black().on_yellow(), "**".black().on_yellow())
+        );
+    }
+
+    #[test]
+    fn it_shows_the_rules_that_matched() {
+        let capture = |start: usize, rule: &str| Capture {
+            start,
+            end: start + 1,
+            value: "x".to_string(),
+            offset: start,
+            column: start + 1,
+            rule: Some(rule.to_string()),
+        };
+        let line = Line { number: Some(2), value: "x x x".to_string(), captures: vec![capture(0, "b"), capture(2, "a"), capture(4, "b")] };
+        assert_eq!(LineDisplay::single(&line).color(false).to_string(), "2:[b, a] x x x");
+        let matches = Matches { count: 1, path: None, lines: vec![line], replaced_bytes: 0, long_lines: 0 };
+        assert_eq!(MatchesDisplay::new(matches).vimgrep(true).to_string().lines().nth(1), Some("<stdin>:2:3:[a] x x x"));
+    }
+
+    #[test]
+    fn it_redacts_with_hashes() {
+        let line = Line {
+            number: None,
+            value: "a=x b=x".to_string(),
+            captures: vec![
+                Capture { start: 2, end: 3, value: "x".to_string(), offset: 2, column: 3, rule: None },
+                Capture { start: 6, end: 7, value: "x".to_string(), offset: 6, column: 7, rule: None },
+            ],
+        };
+        let display = LineDisplay::single(&line).color(false).redact(Some(Redaction::Hash));
+        let hash = Redaction::Hash.apply("x");
+        assert_eq!(hash.len(), 9);
+        assert_eq!(display.to_string(), format!("a={} b={}", hash, hash));
+    }
+
+    #[test]
+    fn it_right_aligns_line_numbers() {
+        let line = |number: usize| Line { number: Some(number), value: "x".to_string(), captures: Vec::new() };
+        let matches = || Matches { count: 2, path: None, lines: vec![line(9), line(10)], replaced_bytes: 0, long_lines: 0 };
+        let display = MatchesDisplay::new(matches()).color(false).number_width(Some(NumberWidth::Auto));
+        assert_eq!(display.to_string(), "matched 2 times\n 9:x\n10:x\n");
+        let display = MatchesDisplay::new(matches()).color(false).number_width(Some(NumberWidth::Fixed(4)));
+        assert_eq!(display.to_string(), "matched 2 times\n   9:x\n  10:x\n");
+        let display = MatchesDisplay::new(matches()).color(false);
+        assert_eq!(display.to_string(), "matched 2 times\n9:x\n10:x\n");
+    }
 }
@@ -0,0 +1,216 @@
+use std::path::Path;
+use regex::Regex;
+use matcher::Line;
+
+/// How the lines that start a function, class or section are found in a language.
+#[derive(Debug)]
+pub struct Language {
+    extensions: &'static [&'static str],
+    heading: Regex,
+    /// Whether what a heading encloses is indented beneath it. Otherwise a heading
+    /// encloses every line up to the next one, as in Markdown.
+    is_indented: bool,
+}
+
+/// The headings of the supported languages, which are the functions, types and blocks
+/// of code and the sections of Markdown. A match is enclosed by the nearest heading
+/// above it that's indented less than it is, or just the nearest one in Markdown.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{EnclosingRules, Matcher};
+/// use regex::Regex;
+/// use std::path::Path;
+///
+/// let regex = Regex::new("TODO").unwrap();
+/// let rules = EnclosingRules::new();
+/// let code = "fn main() {\n    let a = 1;\n    // TODO\n}\n";
+/// let matches = Matcher::new(&regex)
+///     .enclosing(Some(&rules))
+///     .collect_path(&mut code.as_bytes(), Path::new("main.rs"))
+///     .unwrap();
+/// assert_eq!(matches.count(), 1);
+/// assert_eq!(matches.lines()[0].value, "fn main() {");
+/// assert_eq!(matches.lines()[1].number, Some(3));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct EnclosingRules {
+    languages: Vec<Language>,
+}
+
+impl Default for EnclosingRules {
+    fn default() -> Self {
+        let language = |extensions, heading, is_indented| Language {
+            extensions,
+            heading: Regex::new(heading).expect("Headings must compile"),
+            is_indented,
+        };
+        EnclosingRules {
+            languages: vec![
+                language(
+                    &["rs"],
+                    r"^\s*(pub(\([^)]*\))?\s+)?((async|const|unsafe|default)\s+)*(fn|impl|trait|mod|struct|enum|macro_rules!)\b",
+                    true,
+                ),
+                language(&["py"], r"^\s*(async\s+)?(def|class)\s", true),
+                language(&["rb"], r"^\s*(def|class|module)\s", true),
+                language(&["go"], r"^(func|type)\s", true),
+                language(
+                    &["js", "jsx", "ts", "tsx"],
+                    r"^\s*(export\s+)?(default\s+)?(async\s+)?(function\b|class\s)|^\s*(export\s+)?(const|let)\s+\w+\s*=\s*(async\s+)?(\([^)]*\)|\w+)\s*=>",
+                    true,
+                ),
+                language(&["c", "h", "cc", "cpp", "hpp"], r"^[A-Za-z_][\w\s\*&:<>,]*\([^;]*$", true),
+                language(&["sh", "bash", "zsh"], r"^\s*(function\s+\w+|\w+\s*\(\s*\))", true),
+                language(&["md", "markdown"], r"^#{1,6}\s", false),
+            ],
+        }
+    }
+}
+
+impl EnclosingRules {
+    /// Creates the rules of every supported language.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the language of the file, from its extension, if it's supported.
+    pub fn for_path(&self, path: &Path) -> Option<&Language> {
+        let extension = path.extension().and_then(|e| e.to_str())?;
+        self.languages.iter().find(|language| language.extensions.contains(&extension))
+    }
+}
+
+/// The headings above the line being read that could still enclose a match, from the
+/// outermost to the innermost, found while matching a buffer.
+#[derive(Debug)]
+pub(crate) struct Headings<'a> {
+    language: &'a Language,
+    /// The indentation of each heading, and whether it's been shown, so each is only
+    /// shown once for the matches beneath it.
+    open: Vec<(usize, Line, bool)>,
+}
+
+impl<'a> Headings<'a> {
+    pub(crate) fn new(language: &'a Language) -> Self {
+        Headings { language, open: Vec::new() }
+    }
+
+    /// Returns the heading enclosing the line, if it hasn't been shown before.
+    pub(crate) fn enclosing(&mut self, line: &str) -> Option<Line> {
+        let indent = indentation(line);
+        let heading = if self.language.is_indented {
+            self.open.iter_mut().rev().find(|&&mut (open, _, _)| open < indent)
+        } else {
+            self.open.last_mut()
+        };
+        let (_, heading, is_shown) = heading?;
+        if *is_shown {
+            return None;
+        }
+        *is_shown = true;
+        Some(heading.clone())
+    }
+
+    /// Remembers the line if it's a heading, replacing the headings it closes. A heading
+    /// that's already shown, as a match of its own, isn't shown again above the lines
+    /// beneath it.
+    pub(crate) fn read(&mut self, line: &str, number: usize, is_shown: bool) {
+        if !self.language.heading.is_match(line) {
+            return;
+        }
+        let indent = indentation(line);
+        if self.language.is_indented {
+            self.open.retain(|&(open, _, _)| open < indent);
+        } else {
+            self.open.clear();
+        }
+        let heading = Line { number: Some(number), value: line.to_string(), captures: Vec::new() };
+        self.open.push((indent, heading, is_shown));
+    }
+}
+
+/// The width of the leading whitespace of a line, with tabs as four spaces.
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matcher::Matcher;
+
+    fn enclosed(path: &str, text: &str) -> Vec<(Option<usize>, String)> {
+        let regex = Regex::new("TODO").unwrap();
+        let rules = EnclosingRules::new();
+        Matcher::new(&regex)
+            .enclosing(Some(&rules))
+            .collect_path(&mut text.as_bytes(), Path::new(path))
+            .unwrap()
+            .lines
+            .into_iter()
+            .map(|line| (line.number, line.value))
+            .collect()
+    }
+
+    #[test]
+    fn it_shows_the_innermost_heading_once_per_group() {
+        let code = "impl A {\n    fn a() {\n        // TODO 1\n        // TODO 2\n    }\n\n    // TODO 3\n}\n";
+        assert_eq!(
+            enclosed("a.rs", code),
+            vec![
+                (Some(2), "    fn a() {".to_string()),
+                (Some(3), "        // TODO 1".to_string()),
+                (Some(4), "        // TODO 2".to_string()),
+                (Some(1), "impl A {".to_string()),
+                (Some(7), "    // TODO 3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_skips_matches_outside_of_any_heading() {
+        let code = "def a():\n    pass\n# TODO\n";
+        assert_eq!(enclosed("a.py", code), vec![(Some(3), "# TODO".to_string())]);
+        assert_eq!(enclosed("a.txt", "fn a() {\n    TODO\n"), vec![(Some(2), "    TODO".to_string())]);
+    }
+
+    #[test]
+    fn it_encloses_markdown_by_the_nearest_section() {
+        let text = "# Intro\nTODO\n## Usage\ntext\nTODO\n";
+        assert_eq!(
+            enclosed("README.md", text),
+            vec![
+                (Some(1), "# Intro".to_string()),
+                (Some(2), "TODO".to_string()),
+                (Some(3), "## Usage".to_string()),
+                (Some(5), "TODO".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_shows_headings_kept_as_matches_only_once() {
+        let regex = Regex::new("TODO").unwrap();
+        let rules = EnclosingRules::new();
+        let code = "class A:\n    def a(self):\n        # TODO\n        pass\n";
+        let lines: Vec<_> = Matcher::new(&regex)
+            .invert_match(true)
+            .enclosing(Some(&rules))
+            .collect_path(&mut code.as_bytes(), Path::new("a.py"))
+            .unwrap()
+            .lines
+            .into_iter()
+            .map(|line| line.number)
+            .collect();
+        assert_eq!(lines, vec![Some(1), Some(2), Some(4)]);
+    }
+}
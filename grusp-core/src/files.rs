@@ -1,12 +1,155 @@
-use std::path::PathBuf;
-use std::io::Result;
-use glob::glob;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::io::{Error, Result};
+use std::time::SystemTime;
+use std::fs::Metadata;
+use std::collections::{HashMap, HashSet, VecDeque};
+use glob::{MatchOptions, Pattern, PatternError};
+use ignore::Rules;
+use types::TypeMatcher;
+use git;
+use paths;
+
+/// Extensions of files that are binary, skipped before they're opened when the default
+/// excludes are on. They're compared without regard to case.
+const BINARY_EXTENSIONS: &[&str] = &[
+    // Images
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "tif", "tiff", "webp", "psd",
+    // Archives
+    "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "tar", "jar", "war",
+    // Compiled code
+    "o", "a", "so", "dylib", "dll", "exe", "obj", "lib", "class", "pyc", "pyo", "wasm", "rlib",
+    // Media
+    "mp3", "mp4", "mov", "avi", "mkv", "wav", "flac", "ogg",
+    // Fonts
+    "woff", "woff2", "ttf", "otf", "eot",
+    // Documents and disk images
+    "pdf", "iso", "dmg",
+];
 
 /// A struct that allows the user to locate a set of files based on configured options.
 #[derive(Debug)]
 pub struct Collecter<'a> {
     queries: &'a Vec<String>,
     max_depth: Option<usize>,
+    query_max_depths: HashMap<String, usize>,
+    ignore: Rules,
+    is_git_only: bool,
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    owner: Option<u32>,
+    permissions: Option<Permissions>,
+    traversal: Traversal,
+    is_case_insensitive: bool,
+    is_default_excludes: bool,
+    types: Option<TypeMatcher>,
+    /// The globs of the queries starting with `!`, whose paths are left out.
+    negations: Vec<Pattern>,
+}
+
+/// The files and directories a walk has found so far, and the paths it couldn't read.
+#[derive(Debug, Default)]
+struct Found {
+    files: Vec<PathBuf>,
+    unreadable: Vec<(PathBuf, Error)>,
+}
+
+/// The queries of a collector that aren't valid globs and the paths it couldn't read,
+/// returned by `try_collect` along with the files found by the rest of them. It displays
+/// the invalid globs, as the unreadable paths are usually counted with the files that
+/// can't be searched.
+#[derive(Debug)]
+pub struct CollectError {
+    files: Vec<PathBuf>,
+    queries: Vec<(String, PatternError)>,
+    unreadable: Vec<(PathBuf, Error)>,
+}
+
+impl CollectError {
+    /// Returns each query that isn't a valid glob, with why.
+    pub fn queries(&self) -> &[(String, PatternError)] {
+        &self.queries
+    }
+
+    /// Returns each file or directory that couldn't be read, like a directory without
+    /// permission to list it, with why. Nothing under it was collected.
+    pub fn unreadable(&self) -> &[(PathBuf, Error)] {
+        &self.unreadable
+    }
+
+    /// Returns the files found by the valid queries.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Consumes the error and returns the files found by the valid queries, so a search
+    /// can carry on without the invalid ones.
+    pub fn into_files(self) -> Vec<PathBuf> {
+        self.files
+    }
+}
+
+impl fmt::Display for CollectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (query, error)) in self.queries.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "Invalid glob {}: {} at position {}", query, error.msg, error.pos)?;
+        }
+        Ok(())
+    }
+}
+
+/// The order in which directories are walked, which determines the order files are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traversal {
+    /// Descends into each directory as soon as it's found.
+    DepthFirst,
+    /// Returns every file at one depth before descending any further.
+    BreadthFirst,
+}
+
+/// A filter on the permission bits of a file, following the conventions of `find -perm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permissions {
+    /// The permission bits must be exactly the mode, e.g. `644`.
+    Exact(u32),
+    /// All of the bits in the mode must be set, e.g. `-220`.
+    All(u32),
+    /// At least one of the bits in the mode must be set, e.g. `/002`.
+    Any(u32),
+}
+
+impl Permissions {
+    /// Parses an octal mode optionally prefixed with `-` (all bits) or `/` (any bit).
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::Permissions;
+    /// assert_eq!(Permissions::parse("644"), Some(Permissions::Exact(0o644)));
+    /// assert_eq!(Permissions::parse("/002"), Some(Permissions::Any(0o002)));
+    /// assert_eq!(Permissions::parse("rwx"), None);
+    /// ```
+    pub fn parse(mode: &str) -> Option<Self> {
+        let (constructor, mode): (fn(u32) -> Self, &str) = match mode.chars().next() {
+            Some('-') => (Permissions::All, &mode[1..]),
+            Some('/') => (Permissions::Any, &mode[1..]),
+            _ => (Permissions::Exact, mode),
+        };
+        u32::from_str_radix(mode, 8).ok().filter(|m| *m <= 0o7777).map(constructor)
+    }
+
+    /// Whether or not the permission bits of a file mode satisfy the filter.
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match *self {
+            Permissions::Exact(bits) => mode == bits,
+            Permissions::All(bits) => mode & bits == bits,
+            Permissions::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
 }
 
 impl<'a> Collecter<'a> {
@@ -21,7 +164,22 @@ impl<'a> Collecter<'a> {
     /// let collector = grusp::FileCollector::new(&queries);
     /// ```
     pub fn new(queries: &'a Vec<String>) -> Self {
-        Self { queries: &queries, max_depth: None }
+        Self {
+            queries,
+            max_depth: None,
+            query_max_depths: HashMap::new(),
+            ignore: Rules::new(),
+            is_git_only: false,
+            modified_after: None,
+            modified_before: None,
+            owner: None,
+            permissions: None,
+            traversal: Traversal::DepthFirst,
+            is_case_insensitive: cfg!(any(target_os = "macos", windows)),
+            is_default_excludes: false,
+            types: None,
+            negations: Vec::new(),
+        }
     }
 
     /// Builds the collector to search to a specified max depth. The
@@ -51,8 +209,156 @@ impl<'a> Collecter<'a> {
         self
     }
 
+    /// Builds the collector to search one of its queries to a different max depth than
+    /// the rest, overriding `max_depth` for the paths that query expands to.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp;
+    /// let queries = vec!["example_dir/".to_string(), "example_dir/sub_dir".to_string()];
+    /// let files = grusp::FileCollector::new(&queries)
+    ///     .query_max_depth("example_dir/", 0)
+    ///     .collect();
+    /// assert_eq!(files.len(), 4)
+    /// ```
+    pub fn query_max_depth(mut self, query: &str, max_depth: usize) -> Self {
+        self.query_max_depths.insert(query.to_string(), max_depth);
+        self
+    }
+
+    /// Builds the collector to skip any files or directories excluded by the
+    /// gitignore-style rules. Directories that are ignored are not descended into.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp;
+    /// let queries = vec!["example_dir/".to_string()];
+    /// let rules = grusp::IgnoreRules::parse("sub_dir/\n*-2.txt").unwrap();
+    /// let files = grusp::FileCollector::new(&queries).ignore(rules).collect();
+    /// assert_eq!(files.len(), 1)
+    /// ```
+    pub fn ignore(mut self, rules: Rules) -> Self {
+        self.ignore = rules;
+        self
+    }
+
+    /// Builds the collector to only return files that are tracked by git. This
+    /// shells out to `git ls-files` for each path the queries expand to, so files
+    /// outside of a repository are never returned.
+    pub fn git_only(mut self, is_git_only: bool) -> Self {
+        self.is_git_only = is_git_only;
+        self
+    }
+
+    /// Builds the collector to only return files last modified at or after the given time.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp;
+    /// use std::time::{Duration, SystemTime};
+    /// let queries = vec!["example_dir/".to_string()];
+    /// let an_hour_from_now = SystemTime::now() + Duration::from_secs(60 * 60);
+    /// let files = grusp::FileCollector::new(&queries).modified_after(Some(an_hour_from_now)).collect();
+    /// assert!(files.is_empty())
+    /// ```
+    pub fn modified_after(mut self, time: Option<SystemTime>) -> Self {
+        self.modified_after = time;
+        self
+    }
+
+    /// Builds the collector to only return files last modified at or before the given time.
+    pub fn modified_before(mut self, time: Option<SystemTime>) -> Self {
+        self.modified_before = time;
+        self
+    }
+
+    /// Builds the collector to only return files owned by the user id. This filter
+    /// only applies on unix platforms.
+    pub fn owner(mut self, uid: Option<u32>) -> Self {
+        self.owner = uid;
+        self
+    }
+
+    /// Builds the collector to only return files whose permission bits match. This
+    /// filter only applies on unix platforms.
+    pub fn permissions(mut self, permissions: Option<Permissions>) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Builds the collector to walk directories in the given order. Breadth first returns
+    /// the files closest to the queried paths first. Defaults to depth first.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::{self, Traversal};
+    /// use std::path::Path;
+    /// let queries = vec!["example_dir/".to_string()];
+    /// let files = grusp::FileCollector::new(&queries).traversal(Traversal::BreadthFirst).collect();
+    /// assert_eq!(files[0].parent(), Some(Path::new("example_dir")))
+    /// ```
+    pub fn traversal(mut self, traversal: Traversal) -> Self {
+        self.traversal = traversal;
+        self
+    }
+
+    /// Builds the collector to treat paths that only differ by case as the same file
+    /// when removing duplicates. Defaults to true on macOS and Windows, whose
+    /// filesystems are case insensitive by default.
+    pub fn case_insensitive_paths(mut self, is_case_insensitive: bool) -> Self {
+        self.is_case_insensitive = is_case_insensitive;
+        self
+    }
+
+    /// Builds the collector to skip files with the extension of a binary format, such as
+    /// `.png`, `.zip` or `.class`, without opening them. Files named by a query, or
+    /// matched by its glob, are still returned. Defaults to false.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp;
+    /// let queries = vec!["example_dir/".to_string()];
+    /// let files = grusp::FileCollector::new(&queries).default_excludes(true).collect();
+    /// assert_eq!(files.len(), 4)
+    /// ```
+    pub fn default_excludes(mut self, is_default_excludes: bool) -> Self {
+        self.is_default_excludes = is_default_excludes;
+        self
+    }
+
+    /// Builds the collector to only return the files of the types the matcher was made
+    /// from, such as `--type rust`. Like the default excludes, files named by a query or
+    /// matched by its glob are still returned.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::{self, FileTypes};
+    /// let queries = vec!["example_dir/".to_string()];
+    /// let rust = FileTypes::new().matcher(&["rust"]).unwrap();
+    /// let files = grusp::FileCollector::new(&queries).types(Some(rust)).collect();
+    /// assert!(files.is_empty())
+    /// ```
+    pub fn types(mut self, types: Option<TypeMatcher>) -> Self {
+        self.types = types;
+        self
+    }
+
     /// Consumes the collector and returns a set of paths that it finds while
-    /// searching recursively through the glob queries.
+    /// searching recursively through the glob queries. A file found by more than
+    /// one query is only returned once, the first time it's found. Queries naming a
+    /// path that exists are taken literally, and Windows drive letters, UNC paths and
+    /// backslashes are handled before globbing. Braces are expanded, so a query like
+    /// `src/**/*.{rs,toml}` is several globs, and a query starting with `!`, like
+    /// `!**/tests/**`, leaves out the files and directories it matches from those of the
+    /// other queries. With only negated queries the current directory is searched.
+    /// Queries that aren't valid globs are skipped, use `try_collect` to find out about
+    /// them.
     ///
     /// ### Examples
     ///
@@ -60,41 +366,258 @@ impl<'a> Collecter<'a> {
     /// use grusp_core::grusp;
     /// let queries = vec!["example_dir/**/*.txt".to_string()];
     /// let files = grusp::FileCollector::new(&queries).collect();
-    /// assert_eq!(files.len(), 5)
+    /// assert_eq!(files.len(), 5);
+    ///
+    /// let queries = vec!["example_dir/**/*.{txt,md}".to_string(), "!**/sub_dir/**".to_string()];
+    /// let files = grusp::FileCollector::new(&queries).collect();
+    /// assert_eq!(files.len(), 3)
     /// ```
     pub fn collect(self) -> Vec<PathBuf> {
-        let mut files = Vec::new();
+        self.try_collect().unwrap_or_else(CollectError::into_files)
+    }
+
+    /// Consumes the collector and returns the paths it finds like `collect`, or an error
+    /// listing the queries that aren't valid globs and the directories that couldn't be
+    /// read. The error still has the files found by the other queries and elsewhere.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp;
+    /// let queries = vec!["example_dir/**/*.txt".to_string(), "example_dir/[".to_string()];
+    /// let error = grusp::FileCollector::new(&queries).try_collect().unwrap_err();
+    /// assert_eq!(error.queries()[0].0, "example_dir/[");
+    /// assert_eq!(error.to_string(), "Invalid glob example_dir/[: invalid range pattern at position 12");
+    /// assert_eq!(error.files().len(), 5)
+    /// ```
+    pub fn try_collect(mut self) -> ::std::result::Result<Vec<PathBuf>, CollectError> {
+        let mut found = Found::default();
+        let mut roots = Vec::new();
+        let mut invalid = Vec::new();
+        let mut queries = Vec::new();
         for query in self.queries {
-            glob(&query)
-                .expect("Glob pattern failed")
-                .filter_map(|p| p.ok())
-                .for_each(|p| {
-                    self.recurse(p, &mut files, 0).expect("Unknown file error")
-                });
+            match paths::negation(query) {
+                Some(glob) => match paths::negation_patterns(glob) {
+                    Ok(patterns) => self.negations.extend(patterns),
+                    Err(e) => invalid.push((query.clone(), e)),
+                },
+                None => queries.push(query),
+            }
+        }
+        let current_dir = ".".to_string();
+        if queries.is_empty() && !self.negations.is_empty() {
+            queries.push(&current_dir);
         }
+        for query in queries {
+            let max_depth = self.query_max_depths.get(query).cloned().or(self.max_depth);
+            let paths = match paths::expand(query) {
+                Ok(paths) => paths,
+                Err(e) => {
+                    invalid.push((query.clone(), e));
+                    continue;
+                }
+            };
+            for p in paths {
+                if self.is_git_only { roots.push(p.clone()); }
+                self.walk(p, &mut found, max_depth);
+            }
+        }
+        let Found { mut files, unreadable } = found;
+        if self.is_git_only {
+            let tracked = git::tracked_files(&roots);
+            files.retain(|f| {
+                let is_tracked = f.canonicalize().map(|f| tracked.contains(&f)).unwrap_or(false);
+                if !is_tracked {
+                    info!("Skipped {}: it isn't tracked by git", f.display());
+                }
+                is_tracked
+            });
+        }
+        let files = self.dedupe(files);
+        if invalid.is_empty() && unreadable.is_empty() {
+            Ok(files)
+        } else {
+            Err(CollectError { files, queries: invalid, unreadable })
+        }
+    }
+
+    fn dedupe(&self, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut seen = HashSet::new();
         files
+            .into_iter()
+            .filter(|f| {
+                let is_new = seen.insert(self.path_key(f));
+                if !is_new {
+                    debug!("Skipped {}: it was already found by another query", f.display());
+                }
+                is_new
+            })
+            .collect()
     }
 
-    fn recurse(&self, path: PathBuf, files: &mut Vec<PathBuf>, depth: usize) -> Result<()> {
+    /// The key used to tell whether two paths are the same file: the canonical path,
+    /// case folded when the filesystem is case insensitive.
+    fn path_key(&self, path: &Path) -> PathBuf {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        if self.is_case_insensitive {
+            PathBuf::from(canonical.to_string_lossy().to_lowercase())
+        } else {
+            canonical
+        }
+    }
+
+    /// Walks the root into the files found. The paths that can't be read, like a directory
+    /// without permission to list it, are added to `unreadable` and the walk goes on.
+    fn walk(&self, root: PathBuf, found: &mut Found, max_depth: Option<usize>) {
+        match self.traversal {
+            Traversal::DepthFirst => self.recurse(&root, root.clone(), found, 0, max_depth),
+            Traversal::BreadthFirst => {
+                let mut queue = VecDeque::new();
+                queue.push_back((root.clone(), 0));
+                while let Some((path, depth)) = queue.pop_front() {
+                    for child in self.visit_or_record(&root, path, found, depth, max_depth) {
+                        queue.push_back((child, depth + 1));
+                    }
+                }
+            }
+        }
+    }
+
+    fn recurse(&self, root: &Path, path: PathBuf, found: &mut Found, depth: usize, max_depth: Option<usize>) {
+        for child in self.visit_or_record(root, path, found, depth, max_depth) {
+            self.recurse(root, child, found, depth + 1, max_depth)
+        }
+    }
+
+    fn visit_or_record(&self, root: &Path, path: PathBuf, found: &mut Found, depth: usize, max_depth: Option<usize>) -> Vec<PathBuf> {
+        self.visit(root, path.clone(), &mut found.files, depth, max_depth).unwrap_or_else(|e| {
+            info!("Could not read {}: {}", path.display(), e);
+            found.unreadable.push((path, e));
+            Vec::new()
+        })
+    }
+
+    /// Adds the path to the files if it's a file that should be searched. When
+    /// it's a directory that should be descended into, returns its entries. Anchored
+    /// ignore rules are relative to the `root` being walked.
+    fn visit(&self,
+             root: &Path,
+             path: PathBuf,
+             files: &mut Vec<PathBuf>,
+             depth: usize,
+             max_depth: Option<usize>)
+             -> Result<Vec<PathBuf>> {
+        let mut children = Vec::new();
+        if self.is_negated(&path) {
+            info!("Skipped {}: it matches a query starting with !", path.display());
+            return Ok(children)
+        }
         if Self::is_hidden(&path) {
-            return Ok(())
+            info!("Skipped {}: it's hidden", path.display());
+            return Ok(children)
+        }
+        if self.ignore.is_ignored_in(root, &path, path.is_dir()) {
+            info!("Skipped {}: it matches an ignore rule", path.display());
+            return Ok(children)
         }
         if path.is_dir() {
-            if let Some(max_depth) = self.max_depth {
-                if max_depth < depth { return Ok(()); };
+            if let Some(max_depth) = max_depth {
+                if max_depth < depth {
+                    info!("Skipped {}: it's deeper than the max depth of {}", path.display(), max_depth);
+                    return Ok(children);
+                };
             }
 
+            debug!("Walking {}", path.display());
             let entries = path.read_dir()?;
             for entry in entries {
-                self.recurse(entry?.path(), files, depth + 1)?
+                children.push(entry?.path());
             }
+        } else if self.is_excluded_by_default(&path, depth) {
+            info!("Skipped {}: it has the extension of a binary format", path.display());
+        } else if !self.is_of_type(&path, depth) {
+            info!("Skipped {}: it isn't of the wanted file types", path.display());
+        } else if !self.is_wanted(&path) {
+            info!("Skipped {}: its modified time, owner or permissions are filtered out", path.display());
         } else {
             files.push(path.to_owned());
         }
-        Ok(())
+        Ok(children)
+    }
+
+    /// Whether the path matches the glob of a negated query, without its leading `./`.
+    fn is_negated(&self, path: &Path) -> bool {
+        if self.negations.is_empty() {
+            return false;
+        }
+        let path = path.strip_prefix(".").unwrap_or(path);
+        let options = MatchOptions {
+            case_sensitive: !self.is_case_insensitive,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.negations.iter().any(|pattern| pattern.matches_path_with(path, &options))
+    }
+
+    /// Applies the filters that depend on a file's metadata. Files whose metadata
+    /// can't be read are skipped when a filter is set.
+    fn is_wanted(&self, path: &Path) -> bool {
+        if self.modified_after.is_none() && self.modified_before.is_none() &&
+            self.owner.is_none() && self.permissions.is_none() {
+            return true;
+        }
+        let metadata = match path.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let modified = match metadata.modified() {
+                Ok(modified) => modified,
+                Err(_) => return false,
+            };
+            if !(self.modified_after.is_none_or(|after| modified >= after) &&
+                self.modified_before.is_none_or(|before| modified <= before)) {
+                return false;
+            }
+        }
+        self.is_wanted_by_unix_metadata(&metadata)
+    }
+
+    #[cfg(unix)]
+    fn is_wanted_by_unix_metadata(&self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        self.owner.is_none_or(|uid| metadata.uid() == uid) &&
+            self.permissions.is_none_or(|p| p.matches(metadata.mode()))
+    }
+
+    #[cfg(not(unix))]
+    fn is_wanted_by_unix_metadata(&self, _metadata: &Metadata) -> bool {
+        true
     }
 
-    fn is_hidden(path: &PathBuf) -> bool {
+    /// Whether the file was found in a directory and has a binary extension, when the
+    /// default excludes are on. The paths the queries expand to are at a depth of 0.
+    fn is_excluded_by_default(&self, path: &Path, depth: usize) -> bool {
+        self.is_default_excludes && depth > 0 && Self::is_binary(path)
+    }
+
+    /// Whether the file is of one of the types, when they're set. The paths the queries
+    /// expand to are always wanted.
+    fn is_of_type(&self, path: &Path, depth: usize) -> bool {
+        match self.types {
+            Some(ref types) if depth > 0 => types.is_match(path),
+            _ => true,
+        }
+    }
+
+    fn is_binary(path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| BINARY_EXTENSIONS.iter().any(|binary| extension.eq_ignore_ascii_case(binary)))
+    }
+
+    fn is_hidden(path: &Path) -> bool {
         if let Some(file_name) = path.file_name().and_then(|f| f.to_str()) {
             file_name.starts_with(".")
         } else {
@@ -105,7 +628,6 @@ impl<'a> Collecter<'a> {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
     use super::*;
 
     #[test]
@@ -171,6 +693,157 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn it_can_restrict_the_depth_of_a_single_query() {
+        let query = vec!["./example_dir".to_string(), "./example_dir/sub_dir".to_string()];
+        let files = Collecter::new(&query)
+            .max_depth(Some(0))
+            .query_max_depth("./example_dir/sub_dir", 5)
+            .query_max_depth("./missing", 5)
+            .collect();
+        assert_eq!(files.len(), 4);
+
+        let query = vec!["./example_dir".to_string()];
+        let files = Collecter::new(&query).query_max_depth("./example_dir", 0).collect();
+        assert_eq!(files.len(), 2);
+    }
+
+    #[test]
+    fn it_skips_paths_matching_ignore_rules() {
+        let query = vec!["./example_dir".to_string()];
+        let rules = Rules::parse("*-1.txt\n!sub-example-1.txt").unwrap();
+        let files = Collecter::new(&query).ignore(rules).collect();
+
+        assert_eq!(files.len(), 3);
+        assert!(!files.contains(&Path::new("example_dir/example-1.txt").to_owned()));
+        assert!(files.contains(
+            &Path::new("example_dir/sub_dir/sub-example-1.txt").to_owned(),
+        ));
+    }
+
+    #[test]
+    fn it_can_restrict_to_git_tracked_files() {
+        let query = vec!["./example_dir".to_string()];
+        let files = Collecter::new(&query).git_only(true).collect();
+
+        assert_eq!(files.len(), 4);
+        assert!(files.contains(
+            &Path::new("example_dir/sub_dir/sub-example-1.txt").to_owned(),
+        ));
+    }
+
+    #[test]
+    fn it_filters_files_by_modified_time() {
+        use std::fs::{self, File};
+        use std::time::Duration;
+
+        let dir = ::std::env::temp_dir().join("grusp-modified-time-test");
+        fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(60 * 60 * 24 * 10);
+        File::create(dir.join("old.txt")).unwrap().set_modified(old).unwrap();
+        File::create(dir.join("new.txt")).unwrap().set_modified(now).unwrap();
+
+        let query = vec![dir.to_str().unwrap().to_string()];
+        let a_day_ago = now - Duration::from_secs(60 * 60 * 24);
+        let files = Collecter::new(&query).modified_after(Some(a_day_ago)).collect();
+        assert_eq!(files, vec![dir.join("new.txt")]);
+
+        let files = Collecter::new(&query).modified_before(Some(a_day_ago)).collect();
+        assert_eq!(files, vec![dir.join("old.txt")]);
+    }
+
+    #[test]
+    fn it_matches_permission_bits() {
+        assert!(Permissions::Exact(0o644).matches(0o100644));
+        assert!(!Permissions::Exact(0o644).matches(0o100664));
+        assert!(Permissions::All(0o220).matches(0o664));
+        assert!(!Permissions::All(0o220).matches(0o644));
+        assert!(Permissions::Any(0o022).matches(0o664));
+        assert!(!Permissions::Any(0o022).matches(0o644));
+    }
+
+    #[test]
+    fn it_parses_permissions() {
+        assert_eq!(Permissions::parse("-0220"), Some(Permissions::All(0o220)));
+        assert_eq!(Permissions::parse("755"), Some(Permissions::Exact(0o755)));
+        assert_eq!(Permissions::parse("/"), None);
+        assert_eq!(Permissions::parse("999"), None);
+        assert_eq!(Permissions::parse("77777"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_filters_files_by_owner_and_permissions() {
+        use std::fs::{self, File};
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = ::std::env::temp_dir().join("grusp-permissions-test");
+        fs::create_dir_all(&dir).unwrap();
+        let writable = dir.join("writable.txt");
+        let private = dir.join("private.txt");
+        File::create(&writable).unwrap();
+        File::create(&private).unwrap();
+        fs::set_permissions(&writable, fs::Permissions::from_mode(0o666)).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let query = vec![dir.to_str().unwrap().to_string()];
+        let files = Collecter::new(&query).permissions(Some(Permissions::Any(0o002))).collect();
+        assert_eq!(files, vec![writable.clone()]);
+
+        let uid = writable.metadata().unwrap().uid();
+        let files = Collecter::new(&query).owner(Some(uid)).collect();
+        assert_eq!(files.len(), 2);
+        let files = Collecter::new(&query).owner(Some(uid + 1)).collect();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn it_can_traverse_breadth_first() {
+        let query = vec!["./example_dir".to_string()];
+        let files = Collecter::new(&query).traversal(Traversal::BreadthFirst).collect();
+
+        assert_eq!(files.len(), 4);
+        let depths: Vec<usize> = files.iter().map(|f| f.components().count()).collect();
+        assert_eq!(depths, vec![2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn it_finds_the_same_files_either_way() {
+        let query = vec!["./example_dir".to_string()];
+        let mut depth_first = Collecter::new(&query).traversal(Traversal::DepthFirst).collect();
+        let mut breadth_first = Collecter::new(&query).traversal(Traversal::BreadthFirst).collect();
+        depth_first.sort();
+        breadth_first.sort();
+        assert_eq!(depth_first, breadth_first);
+    }
+
+    #[test]
+    fn it_only_returns_files_found_by_several_queries_once() {
+        let query = vec![
+            "./example_dir".to_string(),
+            "example_dir/sub_dir".to_string(),
+            "./example_dir/example-1.txt".to_string(),
+        ];
+        let files = Collecter::new(&query).collect();
+        assert_eq!(files.len(), 4);
+    }
+
+    #[test]
+    fn it_case_folds_paths_when_case_insensitive() {
+        let query = vec![];
+        let insensitive = Collecter::new(&query).case_insensitive_paths(true);
+        assert_eq!(
+            insensitive.path_key(Path::new("Some/Missing/File.txt")),
+            insensitive.path_key(Path::new("some/missing/file.txt"))
+        );
+        let sensitive = Collecter::new(&query).case_insensitive_paths(false);
+        assert_ne!(
+            sensitive.path_key(Path::new("Some/Missing/File.txt")),
+            sensitive.path_key(Path::new("some/missing/file.txt"))
+        );
+    }
+
     #[test]
     fn it_ignores_hidden_files() {
         let query = vec!["./example_dir".to_string()];
@@ -191,8 +864,17 @@ mod tests {
 
     #[test]
     fn can_determine_whether_a_path_is_hidden() {
-        assert!(!Collecter::is_hidden(&Path::new(".").to_path_buf()));
-        assert!(!Collecter::is_hidden(&Path::new("example_dir").to_path_buf()));
-        assert!(Collecter::is_hidden(&Path::new("example_dir/.hiiden").to_path_buf()));
+        assert!(!Collecter::is_hidden(Path::new(".")));
+        assert!(!Collecter::is_hidden(Path::new("example_dir")));
+        assert!(Collecter::is_hidden(Path::new("example_dir/.hiiden")));
+    }
+
+    #[test]
+    fn can_determine_whether_a_path_is_binary() {
+        assert!(Collecter::is_binary(Path::new("logo.png")));
+        assert!(Collecter::is_binary(Path::new("target/Main.CLASS")));
+        assert!(Collecter::is_binary(Path::new("logs.tar.gz")));
+        assert!(!Collecter::is_binary(Path::new("src/main.rs")));
+        assert!(!Collecter::is_binary(Path::new("png")));
     }
 }
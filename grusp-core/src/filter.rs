@@ -0,0 +1,104 @@
+use std::fmt;
+use std::path::Path;
+use matcher::Line;
+
+/// Decides whether a matching line should be kept, after the regex has matched it.
+/// Filters let embedders add semantic rules, such as only keeping matches inside
+/// comments, without changing the matcher.
+///
+/// Any `Fn(&Line, &Path) -> bool` closure is a filter.
+pub trait MatchFilter: Send + Sync {
+    /// Returns true if the line, found in the file at `path`, should be kept. The path is
+    /// empty when the buffer isn't a file, such as stdin.
+    fn keep(&self, line: &Line, path: &Path) -> bool;
+}
+
+impl<F> MatchFilter for F
+    where F: Fn(&Line, &Path) -> bool + Send + Sync
+{
+    fn keep(&self, line: &Line, path: &Path) -> bool {
+        self(line, path)
+    }
+}
+
+/// A chain of filters that a line has to pass all of to be kept. Filters run in the
+/// order they were added and stop at the first one that rejects the line.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{Line, Matcher, MatchFilters};
+/// use std::io::Cursor;
+/// use std::path::Path;
+///
+/// let filters = MatchFilters::new()
+///     .with(|line: &Line, _: &Path| !line.value.starts_with("//"));
+/// let reg = regex::Regex::new(r"test").unwrap();
+/// let mut buf_read = Cursor::new("// test\ntest");
+/// let matches = Matcher::new(&reg).filters(&filters).collect(&mut buf_read).unwrap();
+/// assert_eq!(matches.count(), 1);
+/// assert_eq!(matches.lines()[0].number, Some(2));
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Filters {
+    filters: Vec<Box<dyn MatchFilter>>,
+}
+
+impl Filters {
+    /// Creates an empty chain which keeps every line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consumes the chain and adds a filter to the end of it.
+    pub fn with<F: MatchFilter + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Returns true if there are no filters in the chain.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Returns true if every filter keeps the line.
+    pub fn keep(&self, line: &Line, path: &Path) -> bool {
+        self.filters.iter().all(|filter| filter.keep(line, path))
+    }
+}
+
+impl fmt::Debug for Filters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Filters {{ {} filters }}", self.filters.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(value: &str) -> Line {
+        Line { number: Some(1), value: value.to_string(), captures: Vec::new() }
+    }
+
+    #[test]
+    fn it_keeps_everything_when_empty() {
+        let filters = Filters::new();
+        assert!(filters.is_empty());
+        assert!(filters.keep(&line("anything"), Path::new("")));
+    }
+
+    #[test]
+    fn it_requires_every_filter_to_keep_the_line() {
+        let filters = Filters::new()
+            .with(|line: &Line, _: &Path| line.value.contains('a'))
+            .with(|_: &Line, path: &Path| path.extension().is_some_and(|e| e == "rs"));
+        assert!(filters.keep(&line("a"), Path::new("main.rs")));
+        assert!(!filters.keep(&line("b"), Path::new("main.rs")));
+        assert!(!filters.keep(&line("a"), Path::new("README.md")));
+    }
+}
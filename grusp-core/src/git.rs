@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Error, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+use glob::{MatchOptions, Pattern};
+use paths;
+
+/// A revision of a git repository, such as a commit, branch or tag, whose files can
+/// be searched without checking it out. Paths are relative to the root of the
+/// repository containing the current directory. Its objects are read with the `git`
+/// binary, the same as `tracked_files`, so no git library has to be built in. A single
+/// `git cat-file --batch` is started for all of the files that are read.
+#[derive(Debug)]
+pub struct Revision {
+    rev: String,
+    batch: Mutex<Option<Batch>>,
+}
+
+impl Revision {
+    /// Creates a revision from anything `git` can resolve to a commit, e.g. `HEAD~3`.
+    pub fn new(rev: &str) -> Self {
+        Self { rev: rev.to_string(), batch: Mutex::new(None) }
+    }
+
+    /// Lists the files in the revision that are found by the queries. Queries are paths
+    /// or globs relative to the current directory, just like when searching the working tree,
+    /// including their braces and the queries starting with `!` that leave files out.
+    /// Queries can go up with `..`, but not out of the repository.
+    pub fn files(&self, queries: &[String]) -> Result<Vec<PathBuf>> {
+        let prefix = git(&["rev-parse", "--show-prefix"])?;
+        let prefix = String::from_utf8_lossy(&prefix);
+        let prefix = prefix.trim();
+        let (negated, mut queries): (Vec<&str>, Vec<&str>) =
+            queries.iter().map(String::as_str).partition(|q| q.starts_with('!'));
+        if queries.is_empty() {
+            queries.push(".");
+        }
+        let in_repository = |query: &str| {
+            normalize(&format!("{}{}", prefix, query))
+                .ok_or_else(|| Error::other(format!("{} is outside of the repository", query)))
+        };
+        let queries: Vec<String> = queries
+            .iter()
+            .flat_map(|query| paths::expand_braces(query))
+            .map(|query| in_repository(&query))
+            .collect::<Result<_>>()?;
+        let patterns: Vec<Pattern> = queries.iter().filter_map(|q| Pattern::new(q).ok()).collect();
+        let negations: Vec<Pattern> = negated
+            .iter()
+            .flat_map(|query| paths::expand_braces(&query[1..]))
+            .map(|query| in_repository(&query))
+            .collect::<Result<Vec<_>>>()?
+            .iter()
+            .filter_map(|query| Pattern::new(query).ok())
+            .collect();
+        let tree = git(&["ls-tree", "-r", "-z", "--name-only", "--full-tree", &self.rev])?;
+        let options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+        Ok(split_nul(&tree)
+            .map(PathBuf::from)
+            .filter(|file| {
+                // Like globbing the working tree, a query matching a directory finds
+                // everything underneath it.
+                let is_found = |patterns: &[Pattern], queries: &[String]| file.ancestors().any(|path| {
+                    let path = path.to_str().unwrap_or("");
+                    queries.iter().any(|q| q == path) ||
+                        patterns.iter().any(|p| p.matches_with(path, &options))
+                });
+                is_found(&patterns, &queries) && !is_found(&negations, &[])
+            })
+            .collect())
+    }
+
+    /// Reads the contents of a file, as listed by `files`, from the revision. Files are
+    /// read one at a time, even from several threads.
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let object = format!("{}:{}", self.rev, path.display());
+        // The batch takes a name per line
+        if object.contains('\n') {
+            return git(&["cat-file", "blob", &object]);
+        }
+        let mut batch = self.batch.lock().unwrap();
+        if batch.is_none() {
+            *batch = Some(Batch::start()?);
+        }
+        let result = batch.as_mut().expect("The batch was started").read(&object);
+        // The batch can't be trusted to be at the start of an object after it failed
+        if let Err(BatchError::Broken(_)) = result {
+            *batch = None;
+        }
+        result.map_err(BatchError::into_io)
+    }
+
+    /// The virtual path to display for a file in the revision, e.g. `HEAD~3:src/main.rs`.
+    pub fn display_path(&self, path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}:{}", self.rev, path.display()))
+    }
+}
+
+/// A running `git cat-file --batch`, which reads the name of an object from each line
+/// of its input and writes the object back.
+#[derive(Debug)]
+struct Batch {
+    child: Child,
+    output: BufReader<ChildStdout>,
+}
+
+/// An object that couldn't be read by a batch, which is still usable unless it broke.
+enum BatchError {
+    Missing(Error),
+    Broken(Error),
+}
+
+impl BatchError {
+    fn into_io(self) -> Error {
+        match self {
+            BatchError::Missing(e) | BatchError::Broken(e) => e,
+        }
+    }
+}
+
+impl Batch {
+    fn start() -> Result<Self> {
+        let mut child = Command::new("git")
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let output = BufReader::new(child.stdout.take().expect("The output is piped"));
+        Ok(Self { child, output })
+    }
+
+    fn read(&mut self, object: &str) -> ::std::result::Result<Vec<u8>, BatchError> {
+        let input = self.child.stdin.as_mut().expect("The input is piped");
+        writeln!(input, "{}", object).and_then(|_| input.flush()).map_err(BatchError::Broken)?;
+        let mut header = String::new();
+        self.output.read_line(&mut header).map_err(BatchError::Broken)?;
+        // Either `<sha> <type> <size>` or `<object> missing`
+        let fields: Vec<&str> = header.trim_end().rsplitn(3, ' ').collect();
+        let (size, kind) = match fields[..] {
+            [size, kind, _] => match size.parse::<u64>() {
+                Ok(size) => (size, kind),
+                Err(_) => return Err(BatchError::Broken(Error::other(format!("Unexpected output from git: {}", header.trim_end())))),
+            },
+            _ if header.is_empty() => return Err(BatchError::Broken(Error::other("git cat-file exited"))),
+            _ => return Err(BatchError::Missing(Error::other(format!("{} does not exist", object)))),
+        };
+        // The contents are followed by a newline
+        let mut contents = Vec::with_capacity(size as usize + 1);
+        (&mut self.output).take(size + 1).read_to_end(&mut contents).map_err(BatchError::Broken)?;
+        if contents.len() as u64 != size + 1 {
+            return Err(BatchError::Broken(Error::other("git cat-file exited")));
+        }
+        contents.pop();
+        if kind != "blob" {
+            return Err(BatchError::Missing(Error::other(format!("{} is a {}, not a file", object, kind))));
+        }
+        Ok(contents)
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        // Closing the input ends the batch
+        drop(self.child.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+fn git(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output.stdout)
+}
+
+fn split_nul(output: &[u8]) -> impl Iterator<Item = String> + '_ {
+    output
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+}
+
+/// Removes `.` components and trailing slashes, and resolves `..` components, so that
+/// queries can be compared to the paths git lists. Returns `None` when the query goes
+/// up past the root of the repository.
+fn normalize(query: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in query.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Lists the files tracked by git underneath each of the given paths. The returned
+/// paths are canonicalized so they can be compared against paths found elsewhere.
+/// Paths outside of a git repository do not contribute any files.
+pub fn tracked_files(paths: &[PathBuf]) -> HashSet<PathBuf> {
+    let mut tracked = HashSet::new();
+    for path in paths {
+        if let Ok(files) = ls_files(path) {
+            tracked.extend(files.into_iter().filter_map(|f| f.canonicalize().ok()));
+        }
+    }
+    tracked
+}
+
+fn ls_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let (dir, pathspec) = if path.is_dir() {
+        (path, None)
+    } else {
+        let parent = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        (parent, path.file_name())
+    };
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(["ls-files", "-z", "--"]);
+    if let Some(pathspec) = pathspec {
+        command.arg(pathspec);
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(Error::other(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    Ok(split_nul(&output.stdout).map(|name| dir.join(name)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn it_lists_tracked_files_in_a_directory() {
+        let tracked = tracked_files(&[PathBuf::from("example_dir")]);
+        let expected = Path::new("example_dir/sub_dir/sub-example-1.txt").canonicalize().unwrap();
+        assert!(tracked.contains(&expected));
+    }
+
+    #[test]
+    fn it_lists_a_single_tracked_file() {
+        let tracked = tracked_files(&[PathBuf::from("example_dir/example-1.txt")]);
+        assert_eq!(tracked.len(), 1);
+    }
+
+    #[test]
+    fn it_lists_the_files_in_a_revision() {
+        let files = Revision::new("HEAD").files(&["example_dir/sub_dir".to_string()]).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files.contains(&PathBuf::from("grusp-core/example_dir/sub_dir/sub-example-1.txt")));
+    }
+
+    #[test]
+    fn it_lists_files_in_a_revision_with_a_glob() {
+        let files = Revision::new("HEAD").files(&["./example_dir/*-1.txt".to_string()]).unwrap();
+        assert_eq!(files, vec![PathBuf::from("grusp-core/example_dir/example-1.txt")]);
+    }
+
+    #[test]
+    fn it_reads_files_from_a_revision() {
+        let revision = Revision::new("HEAD");
+        let path = Path::new("grusp-core/example_dir/example-1.txt");
+        let contents = revision.read(path).unwrap();
+        assert_eq!(contents, ::std::fs::read("example_dir/example-1.txt").unwrap());
+        assert_eq!(revision.display_path(path), Path::new("HEAD:grusp-core/example_dir/example-1.txt"));
+    }
+
+    #[test]
+    fn it_reads_several_files_from_one_batch() {
+        let revision = Revision::new("HEAD");
+        assert!(revision.read(Path::new("grusp-core/example_dir/not-a-file.txt")).is_err());
+        assert!(revision.read(Path::new("grusp-core/example_dir")).is_err());
+        for name in &["example-1.txt", "sub_dir/sub-example-1.txt"] {
+            let contents = revision.read(&Path::new("grusp-core/example_dir").join(name)).unwrap();
+            assert_eq!(contents, ::std::fs::read(Path::new("example_dir").join(name)).unwrap());
+        }
+    }
+
+    #[test]
+    fn it_errors_on_unknown_revisions() {
+        assert!(Revision::new("not-a-real-revision").files(&[".".to_string()]).is_err());
+    }
+
+    #[test]
+    fn it_normalizes_queries() {
+        assert_eq!(normalize("./src/"), Some("src".to_string()));
+        assert_eq!(normalize("grusp-core/."), Some("grusp-core".to_string()));
+        assert_eq!(normalize("."), Some(String::new()));
+        assert_eq!(normalize("grusp-core/../src/*.rs"), Some("src/*.rs".to_string()));
+        assert_eq!(normalize("grusp-core/../.."), None);
+    }
+
+    #[test]
+    fn it_lists_files_in_a_revision_above_the_current_directory() {
+        // The tests run from grusp-core, a subdirectory of the repository
+        let files = Revision::new("HEAD").files(&["../src/main.rs".to_string()]).unwrap();
+        assert_eq!(files, vec![PathBuf::from("src/main.rs")]);
+        let files = Revision::new("HEAD").files(&["..".to_string(), "!../src".to_string()]).unwrap();
+        assert!(files.contains(&PathBuf::from("grusp-core/example_dir/example-1.txt")));
+        assert!(!files.contains(&PathBuf::from("src/main.rs")));
+    }
+
+    #[test]
+    fn it_errors_on_queries_outside_of_the_repository() {
+        assert!(Revision::new("HEAD").files(&["../..".to_string()]).is_err());
+        assert!(Revision::new("HEAD").files(&["!../../x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn it_finds_nothing_outside_of_a_repository() {
+        let dir = env::temp_dir().join("grusp-git-test-untracked");
+        ::std::fs::create_dir_all(&dir).unwrap();
+        ::std::fs::write(dir.join("file.txt"), "untracked").unwrap();
+        assert!(tracked_files(&[dir]).is_empty());
+    }
+}
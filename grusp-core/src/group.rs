@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use colored::*;
+use color;
+use regex::Regex;
+use aggregate::Group;
+use display::LineDisplay;
+use matcher::{Capture, Line, Matches};
+
+/// A matching line and the file it was found in.
+type Found = (Option<PathBuf>, Line);
+
+/// Collects the matching lines of a search under the value a capture group had in them,
+/// such as grouping the requests of an access log by their status code. A line is listed
+/// under each distinct value it matched with, and matches without the group are skipped.
+/// The matches can be added from several threads.
+///
+/// When displayed, each value is a heading with the number of lines beneath it, followed
+/// by the lines prefixed with their path. Values and lines are sorted so the output
+/// doesn't depend on the order files were searched in.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{AggregateGroup, GroupedMatches, Matcher};
+/// use regex::Regex;
+///
+/// let regex = Regex::new(r"HTTP/1.1 (?P<status>\d{3})").unwrap();
+/// let log = "GET / HTTP/1.1 200\nGET /a HTTP/1.1 404\nGET /b HTTP/1.1 200\n";
+/// let grouped = GroupedMatches::new(&regex, AggregateGroup::Name("status".to_string())).color(false);
+/// grouped.add(Matcher::new(&regex).collect(&mut log.as_bytes()).unwrap());
+/// assert_eq!(
+///     grouped.to_string(),
+///     "200 matched 2 times\n1:GET / HTTP/1.1 200\n3:GET /b HTTP/1.1 200\n404 matched 1 time\n2:GET /a HTTP/1.1 404\n"
+/// );
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct GroupedMatches {
+    regex: Regex,
+    group: Group,
+    is_colored: bool,
+    is_trimmed: bool,
+    groups: Mutex<BTreeMap<String, Vec<Found>>>,
+    /// About how many bytes the lines take up, kept beside the groups under their lock.
+    size: Mutex<usize>,
+}
+
+impl GroupedMatches {
+    /// Creates an empty grouping of the matches of the regex by one of its groups.
+    pub fn new(regex: &Regex, group: Group) -> Self {
+        GroupedMatches {
+            regex: regex.clone(),
+            group,
+            is_colored: color::is_colored(),
+            is_trimmed: false,
+            groups: Mutex::new(BTreeMap::new()),
+            size: Mutex::new(0),
+        }
+    }
+
+    /// Consumes the grouping and enables/disables colored output, instead of following the
+    /// `ColorChoice` applied.
+    pub fn color(self, is_colored: bool) -> Self {
+        Self { is_colored, ..self }
+    }
+
+    /// Consumes the grouping and enables/disables removing the leading whitespace of lines.
+    pub fn trim(self, is_trimmed: bool) -> Self {
+        Self { is_trimmed, ..self }
+    }
+
+    /// Adds each matching line under the values of the group within it.
+    pub fn add(&self, matches: Matches) {
+        let mut groups = self.groups.lock().expect("Group lock poisoned");
+        let mut size = self.size.lock().expect("Group lock poisoned");
+        for line in matches.lines {
+            let mut values: Vec<String> = self.regex
+                .captures_iter(&line.value)
+                .filter_map(|captures| self.group.get(&captures).map(String::from))
+                .collect();
+            values.sort();
+            values.dedup();
+            for value in values {
+                *size += mem::size_of::<Found>() +
+                    matches.path.as_ref().map_or(0, |path| path.as_os_str().len()) +
+                    line.value.len() +
+                    line.captures.len() * mem::size_of::<Capture>();
+                if !groups.contains_key(&value) {
+                    *size += value.len();
+                }
+                groups.entry(value).or_default().push((matches.path.clone(), line.clone()));
+            }
+        }
+    }
+
+    /// Returns true if no lines have been added.
+    pub fn is_empty(&self) -> bool {
+        self.groups.lock().expect("Group lock poisoned").is_empty()
+    }
+
+    /// Returns about how many bytes of memory the lines added so far take up.
+    pub fn size(&self) -> usize {
+        *self.size.lock().expect("Group lock poisoned")
+    }
+
+    /// Moves the lines added so far into a new grouping with the same options, leaving
+    /// this one empty. This lets a large grouping be displayed in parts as it's added to.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{AggregateGroup, GroupedMatches, Matcher};
+    /// use regex::Regex;
+    ///
+    /// let regex = Regex::new(r"(\d{3})").unwrap();
+    /// let grouped = GroupedMatches::new(&regex, AggregateGroup::Index(1)).color(false);
+    /// grouped.add(Matcher::new(&regex).collect(&mut "GET 200\n".as_bytes()).unwrap());
+    /// assert!(grouped.size() > 0);
+    /// let taken = grouped.take();
+    /// assert_eq!(taken.to_string(), "200 matched 1 time\n1:GET 200\n");
+    /// assert!(grouped.is_empty());
+    /// assert_eq!(grouped.size(), 0);
+    /// # }
+    /// ```
+    pub fn take(&self) -> Self {
+        let mut groups = self.groups.lock().expect("Group lock poisoned");
+        let mut size = self.size.lock().expect("Group lock poisoned");
+        GroupedMatches {
+            regex: self.regex.clone(),
+            group: self.group.clone(),
+            is_colored: self.is_colored,
+            is_trimmed: self.is_trimmed,
+            groups: Mutex::new(mem::take(&mut *groups)),
+            size: Mutex::new(mem::replace(&mut *size, 0)),
+        }
+    }
+}
+
+impl fmt::Display for GroupedMatches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut groups = self.groups.lock().expect("Group lock poisoned");
+        for (value, lines) in groups.iter_mut() {
+            lines.sort_by(|a, b| (&a.0, a.1.number).cmp(&(&b.0, b.1.number)));
+            let times = if lines.len() == 1 { "time" } else { "times" };
+            if self.is_colored {
+                writeln!(f, "{} matched {} {}", value.bright_green(), lines.len().to_string().yellow(), times)?;
+            } else {
+                writeln!(f, "{} matched {} {}", value, lines.len(), times)?;
+            }
+            for (path, line) in lines.iter() {
+                if let Some(path) = path {
+                    write!(f, "{}:", path.display())?;
+                }
+                writeln!(f, "{}", LineDisplay::single(line).color(self.is_colored).trim(self.is_trimmed))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matcher::Matcher;
+    use std::path::Path;
+
+    #[test]
+    fn it_lists_a_line_under_each_distinct_value() {
+        let regex = Regex::new(r"user=(\w+)").unwrap();
+        let grouped = GroupedMatches::new(&regex, Group::Index(1)).color(false);
+        let text = "user=a user=b user=a\nuser=b\nnobody\n";
+        let matches = Matcher::new(&regex).collect_path(&mut text.as_bytes(), Path::new("log")).unwrap();
+        grouped.add(matches);
+        assert_eq!(
+            grouped.to_string(),
+            "a matched 1 time\nlog:1:user=a user=b user=a\nb matched 2 times\nlog:1:user=a user=b user=a\nlog:2:user=b\n"
+        );
+    }
+
+    #[test]
+    fn it_sorts_lines_by_path_and_number() {
+        let regex = Regex::new(r"(x)").unwrap();
+        let grouped = GroupedMatches::new(&regex, Group::Index(1)).color(false);
+        for &path in &["b", "a"] {
+            let matches = Matcher::new(&regex).collect_path(&mut "x\n".as_bytes(), Path::new(path)).unwrap();
+            grouped.add(matches);
+        }
+        assert_eq!(grouped.to_string(), "x matched 2 times\na:1:x\nb:1:x\n");
+    }
+}
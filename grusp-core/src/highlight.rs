@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use colored::Color;
+use regex::Regex;
+
+/// Rules that color parts of every displayed line, on top of the highlighted matches.
+/// This makes it easy to pick out severities when reading logs.
+///
+/// Each line of a rules file is a regex followed by `=` and a color, such as
+/// `ERROR=red`. The colors are black, red, green, yellow, blue, magenta, cyan and white,
+/// optionally prefixed with `bright `. Blank lines and lines starting with `#` are
+/// skipped. When rules overlap the later one wins.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::HighlightRules;
+///
+/// let rules = HighlightRules::parse("# severities\nERROR=red\nWARN(ING)?=bright yellow").unwrap();
+/// assert_eq!(rules.len(), 2);
+/// assert!(HighlightRules::parse("ERROR=reddish").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: Arc<Vec<Rule>>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    color: Color,
+}
+
+impl Rules {
+    /// Parses the rules from the contents of a rules file, one rule per line. The error
+    /// describes the first line that couldn't be parsed.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| format!("Invalid highlight rule on line {}: {}", number + 1, reason);
+            let index = line.rfind('=').ok_or_else(|| invalid(format!("expected REGEX=COLOR but got '{}'", line)))?;
+            let (pattern, color) = (&line[..index], &line[index + 1..]);
+            let regex = Regex::new(pattern).map_err(|e| invalid(e.to_string()))?;
+            let color = color.trim().parse().map_err(|_| invalid(format!("unknown color '{}'", color)))?;
+            rules.push(Rule { regex, color });
+        }
+        Ok(Rules { rules: Arc::new(rules) })
+    }
+
+    /// Returns the number of rules.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns true if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Finds the color of every byte in the line, if any rule matches it.
+    pub(crate) fn colors(&self, line: &str) -> Vec<Option<Color>> {
+        let mut colors = vec![None; line.len()];
+        for rule in self.rules.iter() {
+            for m in rule.regex.find_iter(line) {
+                for color in &mut colors[m.start()..m.end()] {
+                    *color = Some(rule.color);
+                }
+            }
+        }
+        colors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_skips_comments_and_blank_lines() {
+        let rules = Rules::parse("# comment\n\n  \nERROR=red\n").unwrap();
+        assert_eq!(rules.len(), 1);
+    }
+
+    #[test]
+    fn it_splits_on_the_last_equals_sign() {
+        let rules = Rules::parse("a=b=green").unwrap();
+        assert_eq!(rules.colors("a=b"), vec![Some(Color::Green); 3]);
+    }
+
+    #[test]
+    fn it_gives_precedence_to_later_rules() {
+        let rules = Rules::parse("ab=red\nb=blue").unwrap();
+        assert_eq!(rules.colors("abc"), vec![Some(Color::Red), Some(Color::Blue), None]);
+    }
+
+    #[test]
+    fn it_reports_the_line_of_invalid_rules() {
+        assert_eq!(
+            Rules::parse("ERROR=red\nWARN").unwrap_err(),
+            "Invalid highlight rule on line 2: expected REGEX=COLOR but got 'WARN'"
+        );
+        assert!(Rules::parse("ERROR=pink").unwrap_err().contains("unknown color 'pink'"));
+        assert!(Rules::parse("ERR(=red").unwrap_err().starts_with("Invalid highlight rule on line 1"));
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+use std::path::{Component, Path};
+use glob::{MatchOptions, Pattern, PatternError};
+
+/// A set of gitignore-style rules used to exclude paths from a search.
+///
+/// Rules follow the same semantics as a `.gitignore` file:
+///
+/// * Blank lines and lines starting with `#` are skipped.
+/// * A leading `!` negates the rule, re-including anything an earlier rule excluded.
+/// * A trailing `/` only matches directories.
+/// * A rule without a `/` (other than a trailing one) matches the file name at any depth,
+///   otherwise it is anchored to the root of the path being checked, or to the searched
+///   directory with [`is_ignored_in`](#method.is_ignored_in).
+/// * When several rules match a path the last one wins.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::IgnoreRules;
+/// use std::path::Path;
+///
+/// let rules = IgnoreRules::parse("*.log\n!keep.log\nbuild/").unwrap();
+/// assert!(rules.is_ignored(Path::new("logs/debug.log"), false));
+/// assert!(!rules.is_ignored(Path::new("logs/keep.log"), false));
+/// assert!(rules.is_ignored(Path::new("target/build"), true));
+/// assert!(!rules.is_ignored(Path::new("target/build"), false));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Pattern,
+    is_negated: bool,
+    is_dir_only: bool,
+    is_anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Option<Self>, PatternError> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let (is_negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (is_dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let is_anchored = line.contains('/');
+        let pattern = Pattern::new(line.trim_start_matches('/'))?;
+        Ok(Some(Rule { pattern, is_negated, is_dir_only, is_anchored }))
+    }
+
+    fn matches(&self, path: &str, file_name: &str, is_dir: bool) -> bool {
+        if self.is_dir_only && !is_dir {
+            return false;
+        }
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        if self.is_anchored {
+            self.pattern.matches_with(path, &options)
+        } else {
+            self.pattern.matches_with(file_name, &options)
+        }
+    }
+}
+
+impl Rules {
+    /// Creates an empty set of rules which ignores nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a set of rules from the contents of an ignore file, one rule per line.
+    pub fn parse(contents: &str) -> Result<Self, PatternError> {
+        let mut rules = Self::new();
+        for line in contents.lines() {
+            rules.add(line)?;
+        }
+        Ok(rules)
+    }
+
+    /// Adds a single rule. Rules added later take precedence over earlier ones.
+    pub fn add(&mut self, rule: &str) -> Result<(), PatternError> {
+        if let Some(rule) = Rule::parse(rule)? {
+            self.rules.push(rule);
+        }
+        Ok(())
+    }
+
+    /// Returns true if there are no rules in the set.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Determines if the path should be ignored. Anchored rules are matched against the
+    /// path with any leading `./` or `/` removed.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.find(&Self::normalize(path), path, is_dir)
+    }
+
+    /// Determines if a path found by searching `root` should be ignored. Anchored rules
+    /// are matched against the path relative to `root`, like the rules of a `.gitignore`
+    /// in the searched directory, so `build/*` ignores `dir/build/out` under `dir`.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::IgnoreRules;
+    /// use std::path::Path;
+    ///
+    /// let rules = IgnoreRules::parse("build/*").unwrap();
+    /// assert!(rules.is_ignored_in(Path::new("dir"), Path::new("dir/build/out"), false));
+    /// assert!(!rules.is_ignored_in(Path::new("dir"), Path::new("dir/src/build/out"), false));
+    /// ```
+    pub fn is_ignored_in(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.find(&Self::normalize(relative), path, is_dir)
+    }
+
+    fn find(&self, anchored: &str, path: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let file_name = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(anchored, file_name, is_dir))
+            .is_some_and(|rule| !rule.is_negated)
+    }
+
+    fn normalize(path: &Path) -> String {
+        path.components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pattern = self.pattern.as_str();
+        if self.is_negated {
+            write!(f, "!")?;
+        } else if pattern.starts_with('!') || pattern.starts_with('#') {
+            write!(f, "\\")?;
+        }
+        // A leading slash is what anchored a rule without one in the middle
+        if self.is_anchored && !pattern.contains('/') {
+            write!(f, "/")?;
+        }
+        write!(f, "{}", self.pattern)?;
+        if self.is_dir_only {
+            write!(f, "/")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the rules one per line, in order, as they would be written in an ignore file.
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(lines: &[&str]) -> Rules {
+        let mut rules = Rules::new();
+        for line in lines {
+            rules.add(line).unwrap();
+        }
+        rules
+    }
+
+    #[test]
+    fn it_ignores_nothing_when_empty() {
+        let rules = Rules::new();
+        assert!(rules.is_empty());
+        assert!(!rules.is_ignored(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    fn it_skips_comments_and_blank_lines() {
+        let rules = Rules::parse("# a comment\n\n   \n").unwrap();
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn it_matches_file_names_at_any_depth() {
+        let rules = rules(&["*.log"]);
+        assert!(rules.is_ignored(Path::new("debug.log"), false));
+        assert!(rules.is_ignored(Path::new("./logs/nested/debug.log"), false));
+        assert!(!rules.is_ignored(Path::new("logs/debug.txt"), false));
+    }
+
+    #[test]
+    fn it_anchors_rules_containing_a_slash() {
+        let rules = rules(&["/src/generated"]);
+        assert!(rules.is_ignored(Path::new("./src/generated"), true));
+        assert!(!rules.is_ignored(Path::new("lib/src/generated"), true));
+
+        let rules = self::rules(&["docs/*.md"]);
+        assert!(rules.is_ignored(Path::new("docs/index.md"), false));
+        assert!(!rules.is_ignored(Path::new("docs/nested/index.md"), false));
+    }
+
+    #[test]
+    fn it_supports_double_star_segments() {
+        let rules = rules(&["**/fixtures/*.txt"]);
+        assert!(rules.is_ignored(Path::new("fixtures/a.txt"), false));
+        assert!(rules.is_ignored(Path::new("tests/deep/fixtures/a.txt"), false));
+        assert!(!rules.is_ignored(Path::new("tests/fixtures/deep/a.txt"), false));
+    }
+
+    #[test]
+    fn it_only_matches_directories_with_a_trailing_slash() {
+        let rules = rules(&["build/"]);
+        assert!(rules.is_ignored(Path::new("build"), true));
+        assert!(rules.is_ignored(Path::new("nested/build"), true));
+        assert!(!rules.is_ignored(Path::new("build"), false));
+    }
+
+    #[test]
+    fn it_lets_negated_rules_whitelist_paths() {
+        let rules = rules(&["*.txt", "!important.txt"]);
+        assert!(rules.is_ignored(Path::new("notes.txt"), false));
+        assert!(!rules.is_ignored(Path::new("important.txt"), false));
+    }
+
+    #[test]
+    fn it_gives_precedence_to_the_last_matching_rule() {
+        let rules = rules(&["!important.txt", "*.txt"]);
+        assert!(rules.is_ignored(Path::new("important.txt"), false));
+
+        let rules = self::rules(&["*.txt", "!*.txt", "secret.txt"]);
+        assert!(!rules.is_ignored(Path::new("notes.txt"), false));
+        assert!(rules.is_ignored(Path::new("secret.txt"), false));
+    }
+
+    #[test]
+    fn it_can_escape_a_leading_bang() {
+        let rules = rules(&["\\!bang.txt"]);
+        assert!(rules.is_ignored(Path::new("!bang.txt"), false));
+        assert!(!rules.is_ignored(Path::new("bang.txt"), false));
+    }
+
+    #[test]
+    fn it_errors_on_invalid_patterns() {
+        let mut rules = Rules::new();
+        assert!(rules.add("[a-").is_err());
+    }
+
+    #[test]
+    fn it_displays_rules_as_they_would_be_written() {
+        let rules = rules(&["# comment", "*.log", "!keep.log", "/build/", "src/gen", "\\!bang"]);
+        assert_eq!(rules.to_string(), "*.log\n!keep.log\n/build/\nsrc/gen\n\\!bang\n");
+    }
+
+    #[test]
+    fn it_anchors_rules_to_the_searched_directory() {
+        let rules = rules(&["build/*", "/gen", "*.log"]);
+        let root = Path::new("/tmp/project");
+        assert!(rules.is_ignored_in(root, Path::new("/tmp/project/build/out"), false));
+        assert!(rules.is_ignored_in(root, Path::new("/tmp/project/gen"), true));
+        assert!(rules.is_ignored_in(root, Path::new("/tmp/project/src/debug.log"), false));
+        assert!(!rules.is_ignored_in(root, Path::new("/tmp/project/src/build/out"), false));
+        assert!(!rules.is_ignored_in(root, root, true));
+    }
+}
@@ -0,0 +1,610 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Error, ErrorKind, Read, Result};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use regex::Regex;
+use regex_syntax::{Expr, ExprBuilder, Repeater};
+use files::Collecter;
+use write::write_whole;
+
+const MAGIC: &[u8; 8] = b"GRUSPIDX";
+const VERSION: u32 = 2;
+/// How many shards the files of an index are spread over by the hash of their path.
+const SHARDS: usize = 64;
+
+type Trigram = [u8; 3];
+
+/// A trigram index of the files in a directory tree, stored on disk next to them, which
+/// narrows a search down to the files that could possibly match before any are read.
+///
+/// Every run of three bytes within a line of a file is indexed, case folded for ASCII.
+/// A regex is turned into the trigrams its literals require, and only the files that
+/// have all of them are candidates. Patterns without literals of three or more
+/// characters can't be narrowed down, so every file is a candidate.
+///
+/// The files are spread over shards by the hash of their path, each stored in a file of
+/// its own, so updating the index only rewrites the shards of the files that changed.
+/// Files changed since the index was built are always candidates, but files added since
+/// then aren't found until it's updated.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::Index;
+/// use std::path::Path;
+///
+/// let index = Index::build(Path::new("example_dir")).unwrap();
+/// assert_eq!(index.len(), 4);
+/// let regex = regex::Regex::new("a needle nowhere").unwrap();
+/// assert!(index.candidates(&regex, false).is_empty());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Index {
+    root: PathBuf,
+    shards: Vec<Shard>,
+}
+
+/// The files of an index whose paths hash to the same shard, with their trigrams.
+#[derive(Debug, Default)]
+struct Shard {
+    files: Vec<IndexedFile>,
+    trigrams: BTreeMap<Trigram, Vec<u32>>,
+    /// Whether it changed since it was opened, so it has to be saved.
+    is_changed: bool,
+}
+
+/// How many files an update of an index had to read again or drop.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Update {
+    /// Files that weren't in the index.
+    pub added: usize,
+    /// Files modified since they were indexed.
+    pub changed: usize,
+    /// Files in the index that no longer exist.
+    pub removed: usize,
+    /// Files that couldn't be read, with why. They're left out of the index.
+    pub unreadable: Vec<(PathBuf, String)>,
+}
+
+/// A file in the index, relative to its root, and when it was last modified.
+#[derive(Debug, PartialEq)]
+struct IndexedFile {
+    path: PathBuf,
+    modified: u64,
+}
+
+/// The trigrams a file must have to possibly match a pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Query {
+    All,
+    Trigram(Trigram),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+impl Index {
+    /// The name of the directory the index of a directory is stored in, inside the
+    /// directory. It's hidden, so it's never searched itself.
+    pub const FILE_NAME: &'static str = ".grusp-index";
+
+    /// Creates an empty index of the directory, which `update` fills in.
+    pub fn new(root: &Path) -> Self {
+        Self { root: root.to_path_buf(), shards: (0..SHARDS).map(|_| Shard::default()).collect() }
+    }
+
+    /// Indexes every file that searching the directory would find. Files that can't be
+    /// read are left out, `new` and `update` tell which they are.
+    pub fn build(root: &Path) -> Result<Self> {
+        let mut index = Self::new(root);
+        index.update()?;
+        Ok(index)
+    }
+
+    /// Brings the index up to date with its directory, using modification times to find
+    /// what changed. Only files that were added or modified since they were indexed are
+    /// read, files that no longer exist are dropped, and only the shards with any of them
+    /// are saved again. Files that can't be read are dropped and reported in the update.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::Index;
+    /// use std::path::Path;
+    ///
+    /// let mut index = Index::build(Path::new("example_dir")).unwrap();
+    /// let update = index.update().unwrap();
+    /// assert_eq!(update.added + update.changed + update.removed, 0);
+    /// ```
+    pub fn update(&mut self) -> Result<Update> {
+        let queries = vec![self.root.to_string_lossy().into_owned()];
+        let mut current: HashMap<PathBuf, u64> = Collecter::new(&queries)
+            .collect()
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path.strip_prefix(&self.root).map(Path::to_path_buf).unwrap_or(path), modified)
+            })
+            .collect();
+        let mut update = Update::default();
+        let mut changed = HashSet::new();
+        for shard in &mut self.shards {
+            update.removed += shard.retain_unchanged(&mut current, &mut changed);
+        }
+
+        let mut read: Vec<(PathBuf, u64)> = current.into_iter().collect();
+        read.sort();
+        for (path, modified) in read {
+            let is_changed = changed.contains(&path);
+            let contents = match fs::read(self.root.join(&path)) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    // A changed file that can't be read any more has been dropped
+                    if is_changed {
+                        update.removed += 1;
+                    }
+                    update.unreadable.push((path, e.to_string()));
+                    continue;
+                }
+            };
+            if is_changed {
+                update.changed += 1;
+            } else {
+                update.added += 1;
+            }
+            let shard = &mut self.shards[shard_of(&path)];
+            let id = shard.files.len() as u32;
+            for trigram in trigrams(&contents) {
+                shard.trigrams.entry(trigram).or_default().push(id);
+            }
+            shard.files.push(IndexedFile { path, modified });
+            shard.is_changed = true;
+        }
+        Ok(update)
+    }
+
+    /// Reads the index stored in the directory.
+    pub fn open(root: &Path) -> Result<Self> {
+        let dir = root.join(Self::FILE_NAME);
+        if !fs::metadata(&dir)?.is_dir() {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a grusp index, or built by another version"));
+        }
+        let mut index = Self::new(root);
+        for (id, shard) in index.shards.iter_mut().enumerate() {
+            match File::open(dir.join(shard_name(id))) {
+                Ok(file) => *shard = Shard::read(&mut BufReader::new(file))?,
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Writes the shards that changed since the index was opened into the directory, each
+    /// replacing the one that was there whole, so an interrupted save leaves every shard
+    /// readable.
+    pub fn save(&self) -> Result<()> {
+        let dir = self.root.join(Self::FILE_NAME);
+        // An index built by an earlier version is a single file
+        if dir.is_file() {
+            fs::remove_file(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        for (id, shard) in self.shards.iter().enumerate().filter(|(_, shard)| shard.is_changed) {
+            let path = dir.join(shard_name(id));
+            if !shard.files.is_empty() {
+                write_whole(&path, &shard.to_bytes()?)?;
+            } else if let Err(e) = fs::remove_file(&path) {
+                if e.kind() != ErrorKind::NotFound {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of files in the index.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.files.len()).sum()
+    }
+
+    /// Returns true if there are no files in the index.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lists every file in the index that still exists, ordered by path.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.files().map(|file| self.root.join(&file.path)).filter(|path| path.is_file()).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Finds the files that could have a match for the regex: the files with every
+    /// trigram the regex requires, along with any file modified since it was indexed.
+    /// Files that no longer exist are skipped. They're ordered by path.
+    pub fn candidates(&self, regex: &Regex, is_case_insensitive: bool) -> Vec<PathBuf> {
+        let query = ExprBuilder::new()
+            .case_insensitive(is_case_insensitive)
+            .parse(regex.as_str())
+            .map(|expr| Query::from_expr(&expr))
+            .unwrap_or(Query::All);
+        let mut candidates: Vec<PathBuf> = self
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                let ids = shard.evaluate(&query);
+                shard.files.iter().enumerate().filter_map(move |(id, file)| {
+                    let path = self.root.join(&file.path);
+                    let is_stale = modified(&path) != file.modified;
+                    let is_candidate = ids.as_ref().is_none_or(|ids| ids.contains(&(id as u32)));
+                    if path.is_file() && (is_candidate || is_stale) { Some(path) } else { None }
+                })
+            })
+            .collect();
+        candidates.sort();
+        candidates
+    }
+
+    fn files(&self) -> impl Iterator<Item = &IndexedFile> {
+        self.shards.iter().flat_map(|shard| shard.files.iter())
+    }
+}
+
+impl Shard {
+    /// Keeps the files that haven't changed, taking them out of `current` so only the
+    /// rest are read, and renumbers them to close the gaps of the ones dropped. The files
+    /// that changed are added to `changed`, and the number that no longer exist returned.
+    fn retain_unchanged(&mut self, current: &mut HashMap<PathBuf, u64>, changed: &mut HashSet<PathBuf>) -> usize {
+        let mut removed = 0;
+        let mut ids = HashMap::new();
+        let mut files = Vec::new();
+        let len = self.files.len();
+        for (id, file) in self.files.drain(..).enumerate() {
+            match current.get(&file.path) {
+                Some(&modified) if modified == file.modified => {
+                    current.remove(&file.path);
+                    ids.insert(id as u32, files.len() as u32);
+                    files.push(file);
+                }
+                Some(_) => {
+                    changed.insert(file.path);
+                }
+                None => removed += 1,
+            }
+        }
+        if files.len() < len {
+            for postings in self.trigrams.values_mut() {
+                *postings = postings.iter().filter_map(|id| ids.get(id).cloned()).collect();
+            }
+            self.trigrams.retain(|_, postings| !postings.is_empty());
+            self.is_changed = true;
+        }
+        self.files = files;
+        removed
+    }
+
+    fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(reader)? != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a grusp index, or built by another version"));
+        }
+        let mut files = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let mut path = vec![0; read_u32(reader)? as usize];
+            reader.read_exact(&mut path)?;
+            let path = String::from_utf8(path).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            files.push(IndexedFile { path: PathBuf::from(path), modified: read_u64(reader)? });
+        }
+        let mut trigrams = BTreeMap::new();
+        for _ in 0..read_u32(reader)? {
+            let mut trigram = [0; 3];
+            reader.read_exact(&mut trigram)?;
+            let ids = (0..read_u32(reader)?).map(|_| read_u32(reader)).collect::<Result<_>>()?;
+            trigrams.insert(trigram, ids);
+        }
+        Ok(Self { files, trigrams, is_changed: false })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for file in &self.files {
+            let path = file.path.to_str().ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, format!("Can't index the non UTF-8 path {}", file.path.display()))
+            })?;
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&file.modified.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.trigrams.len() as u32).to_le_bytes());
+        for (trigram, ids) in &self.trigrams {
+            bytes.extend_from_slice(trigram);
+            bytes.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+            for id in ids {
+                bytes.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// The ids of the files that satisfy the query, or None when every file does.
+    fn evaluate(&self, query: &Query) -> Option<BTreeSet<u32>> {
+        match *query {
+            Query::All => None,
+            Query::Trigram(ref trigram) => {
+                Some(self.trigrams.get(trigram).map(|ids| ids.iter().cloned().collect()).unwrap_or_default())
+            }
+            Query::And(ref queries) => queries.iter().fold(None, |ids, query| {
+                match (ids, self.evaluate(query)) {
+                    (None, other) | (other, None) => other,
+                    (Some(ids), Some(other)) => Some(ids.intersection(&other).cloned().collect()),
+                }
+            }),
+            Query::Or(ref queries) => {
+                let mut ids = BTreeSet::new();
+                for query in queries {
+                    ids.extend(self.evaluate(query)?);
+                }
+                Some(ids)
+            }
+        }
+    }
+}
+
+/// The shard a file goes in, from the FNV-1a hash of its path, which unlike the hasher
+/// of the standard library is the same across versions of Rust.
+fn shard_of(path: &Path) -> usize {
+    let hash = path.to_string_lossy().bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % SHARDS as u64) as usize
+}
+
+fn shard_name(id: usize) -> String {
+    format!("{:02x}", id)
+}
+
+impl Query {
+    fn from_expr(expr: &Expr) -> Query {
+        match *expr {
+            Expr::Literal { .. } | Expr::LiteralBytes { .. } => Query::from_concat(::std::slice::from_ref(expr)),
+            Expr::Group { ref e, .. } => Query::from_expr(e),
+            Expr::Repeat { ref e, r, .. } => match r {
+                Repeater::OneOrMore => Query::from_expr(e),
+                Repeater::Range { min, .. } if min > 0 => Query::from_expr(e),
+                _ => Query::All,
+            },
+            Expr::Concat(ref es) => Query::from_concat(es),
+            Expr::Alternate(ref es) => {
+                let queries: Vec<Query> = es.iter().map(Query::from_expr).collect();
+                if queries.contains(&Query::All) { Query::All } else { Query::Or(queries) }
+            }
+            _ => Query::All,
+        }
+    }
+
+    /// Joins adjacent literals into runs of bytes, since a trigram can span literals, and
+    /// requires the trigrams of every run along with the queries of everything else.
+    fn from_concat(es: &[Expr]) -> Query {
+        let mut queries = Vec::new();
+        let mut run = Vec::new();
+        for e in es {
+            match *e {
+                Expr::Literal { ref chars, casei } => {
+                    for &c in chars {
+                        // The index only folds ASCII, so a case insensitive non-ASCII
+                        // character could be any of its cases. Trigrams never span lines
+                        if (casei && !c.is_ascii()) || c == '\n' {
+                            queries.push(Query::from_run(&run));
+                            run.clear();
+                        } else {
+                            let mut buf = [0; 4];
+                            run.extend(c.encode_utf8(&mut buf).bytes().map(|b| b.to_ascii_lowercase()));
+                        }
+                    }
+                }
+                Expr::LiteralBytes { ref bytes, .. } => run.extend(bytes.iter().map(|b| b.to_ascii_lowercase())),
+                _ => {
+                    queries.push(Query::from_run(&run));
+                    run.clear();
+                    queries.push(Query::from_expr(e));
+                }
+            }
+        }
+        queries.push(Query::from_run(&run));
+        queries.retain(|query| *query != Query::All);
+        match queries.len() {
+            0 => Query::All,
+            1 => queries.remove(0),
+            _ => Query::And(queries),
+        }
+    }
+
+    fn from_run(run: &[u8]) -> Query {
+        match run.len() {
+            0..=2 => Query::All,
+            _ => Query::And(trigrams(run).into_iter().map(Query::Trigram).collect()),
+        }
+    }
+}
+
+/// The distinct trigrams within the lines of the contents, case folded for ASCII.
+fn trigrams(contents: &[u8]) -> BTreeSet<Trigram> {
+    contents
+        .split(|&b| b == b'\n')
+        .flat_map(|line| line.windows(3))
+        .map(|w| [w[0].to_ascii_lowercase(), w[1].to_ascii_lowercase(), w[2].to_ascii_lowercase()])
+        .collect()
+}
+
+/// When the file was last modified, in nanoseconds since the epoch, or 0 if unknown.
+fn modified(path: &Path) -> u64 {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str) -> Query {
+        Query::from_expr(&ExprBuilder::new().parse(pattern).unwrap())
+    }
+
+    fn trigram(s: &str) -> Query {
+        let b = s.as_bytes();
+        Query::Trigram([b[0], b[1], b[2]])
+    }
+
+    #[test]
+    fn it_requires_the_trigrams_of_literals() {
+        assert_eq!(query("Abcd"), Query::And(vec![trigram("abc"), trigram("bcd")]));
+        assert_eq!(query("ab"), Query::All);
+        assert_eq!(query("ab.*cd"), Query::All);
+    }
+
+    #[test]
+    fn it_joins_literals_around_groups_and_alternations() {
+        assert_eq!(query("abc(x|yz)"), Query::And(vec![trigram("abc")]));
+        assert_eq!(
+            query("(abc|xyz)+"),
+            Query::Or(vec![Query::And(vec![trigram("abc")]), Query::And(vec![trigram("xyz")])])
+        );
+        assert_eq!(query("(abc)?def"), Query::And(vec![trigram("def")]));
+    }
+
+    #[test]
+    fn it_splits_literals_at_newlines() {
+        assert_eq!(query("abc\\ndef"), Query::And(vec![Query::And(vec![trigram("abc")]), Query::And(vec![trigram("def")])]));
+    }
+
+    #[test]
+    fn it_only_indexes_trigrams_within_lines() {
+        let trigrams = trigrams(b"ab\ncde");
+        assert_eq!(trigrams.len(), 1);
+        assert!(trigrams.contains(b"cde"));
+    }
+
+    #[test]
+    fn it_only_reads_files_that_changed_when_updating() {
+        use std::time::{Duration, SystemTime};
+
+        let dir = ::std::env::temp_dir().join("grusp-index-update-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("kept.txt"), "kept needle\n").unwrap();
+        fs::write(dir.join("changed.txt"), "old needle\n").unwrap();
+        fs::write(dir.join("removed.txt"), "removed needle\n").unwrap();
+        let mut index = Index::build(&dir).unwrap();
+
+        fs::write(dir.join("changed.txt"), "new haystack\n").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(60);
+        File::options().write(true).open(dir.join("changed.txt")).unwrap().set_modified(later).unwrap();
+        fs::remove_file(dir.join("removed.txt")).unwrap();
+        fs::write(dir.join("added.txt"), "added needle\n").unwrap();
+        let update = index.update().unwrap();
+        assert_eq!(update, Update { added: 1, changed: 1, removed: 1, unreadable: Vec::new() });
+
+        let regex = Regex::new("needle").unwrap();
+        let mut candidates = index.candidates(&regex, false);
+        candidates.sort();
+        assert_eq!(candidates, vec![dir.join("added.txt"), dir.join("kept.txt")]);
+        assert_eq!(index.candidates(&Regex::new("haystack").unwrap(), false), vec![dir.join("changed.txt")]);
+    }
+
+    #[test]
+    fn it_finds_candidates_and_round_trips_through_disk() {
+        let dir = ::std::env::temp_dir().join("grusp-index-test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "fn needle() {}\n").unwrap();
+        fs::write(dir.join("b.txt"), "nothing here\n").unwrap();
+        Index::build(&dir).unwrap().save().unwrap();
+
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(index.len(), 2);
+        let regex = Regex::new("NEEDLE").unwrap();
+        assert_eq!(index.candidates(&regex, true), vec![dir.join("a.txt")]);
+        let regex = Regex::new("n.").unwrap();
+        assert_eq!(index.candidates(&regex, false).len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_only_saves_the_shards_that_changed() {
+        use std::os::unix::fs::MetadataExt;
+        use std::time::{Duration, SystemTime};
+
+        let dir = ::std::env::temp_dir().join("grusp-index-shards-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let names: Vec<String> = (0..20).map(|i| format!("{}.txt", i)).collect();
+        for name in &names {
+            fs::write(dir.join(name), "needle\n").unwrap();
+        }
+        Index::build(&dir).unwrap().save().unwrap();
+        let shards = dir.join(Index::FILE_NAME);
+        let inodes = || -> HashMap<String, u64> {
+            fs::read_dir(&shards)
+                .unwrap()
+                .map(|entry| entry.unwrap())
+                .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.metadata().unwrap().ino()))
+                .collect()
+        };
+        let before = inodes();
+
+        fs::write(dir.join("0.txt"), "haystack\n").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(60);
+        File::options().write(true).open(dir.join("0.txt")).unwrap().set_modified(later).unwrap();
+        let mut index = Index::open(&dir).unwrap();
+        index.update().unwrap();
+        index.save().unwrap();
+
+        let changed = shard_name(shard_of(Path::new("0.txt")));
+        for (name, inode) in inodes() {
+            assert_eq!(name == changed, before[&name] != inode, "shard {}", name);
+        }
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(index.len(), 20);
+        assert_eq!(index.candidates(&Regex::new("haystack").unwrap(), false), vec![dir.join("0.txt")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_leaves_out_files_it_cannot_read() {
+        let dir = ::std::env::temp_dir().join("grusp-index-unreadable-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "needle\n").unwrap();
+        ::std::os::unix::fs::symlink(dir.join("missing.txt"), dir.join("dangling.txt")).unwrap();
+        let mut index = Index::new(&dir);
+        let update = index.update().unwrap();
+        assert_eq!((update.added, update.changed, update.removed), (1, 0, 0));
+        assert_eq!(update.unreadable.len(), 1);
+        assert_eq!(update.unreadable[0].0, PathBuf::from("dangling.txt"));
+        assert_eq!(index.len(), 1);
+    }
+}
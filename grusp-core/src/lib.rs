@@ -5,15 +5,69 @@
 
 extern crate glob;
 extern crate regex;
+extern crate regex_syntax;
 extern crate colored;
+extern crate unicode_segmentation;
+extern crate unicode_normalization;
+#[macro_use]
+extern crate log;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 mod matcher;
+mod options;
 mod display;
 mod files;
+mod ignore;
+mod git;
+mod paths;
+mod filter;
+mod code;
+mod highlight;
+mod unique;
+mod index;
+mod cancel;
+mod cache;
+mod aggregate;
+mod group;
+mod baseline;
+mod color;
+mod tree;
+mod enclosing;
+mod replace;
+mod write;
+mod mime;
+mod types;
+mod patterns;
+#[doc(hidden)]
+pub mod corpus;
 
 /// The core module for finding matches within files.
 pub mod grusp {
-    pub use matcher::{Matcher, Stats as StatCollector};
-    pub use display::{MatchesDisplay as Display};
-    pub use files::{Collecter as FileCollector};
+    pub use matcher::{Matcher, Matches, MatchRecord, Line, Capture, LongLines, Normalization, Stats as StatCollector};
+    pub use options::SearchOptions;
+    pub use display::{MatchesDisplay as Display, LineDisplay, NumberWidth, Redaction};
+    pub use files::{Collecter as FileCollector, CollectError, Permissions, Traversal};
+    pub use ignore::{Rules as IgnoreRules};
+    pub use git::{Revision as GitRevision};
+    pub use filter::{MatchFilter, Filters as MatchFilters};
+    pub use code::{CodeContext, CodeContextFilter};
+    pub use highlight::{Rules as HighlightRules};
+    pub use unique::{UniqueLinesFilter, UniqueScope};
+    pub use index::{Index, Update as IndexUpdate};
+    pub use cancel::CancellationToken;
+    pub use cache::{Cache as ResultCache};
+    pub use aggregate::{Aggregation, Aggregator, Group as AggregateGroup};
+    pub use group::GroupedMatches;
+    pub use replace::Replacement;
+    pub use write::safe_write;
+    pub use mime::{detect_mime, MimeFilter};
+    pub use types::{FileTypes, TypeMatcher};
+    pub use patterns::{Patterns, Severity};
+    pub use baseline::Baseline;
+    pub use color::{ColorChoice, is_colored};
+    pub use tree::PathTree;
+    pub use enclosing::{EnclosingRules, Language as EnclosingLanguage};
 }
@@ -3,12 +3,33 @@ use std::path::{PathBuf, Path};
 use std::io::prelude::*;
 use regex::Regex;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::VecDeque;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_normalization::UnicodeNormalization;
+use filter::Filters;
+use cancel::CancellationToken;
+use enclosing::{EnclosingRules, Headings};
+use patterns::Patterns;
+use options::SearchOptions;
+
+/// A comment that stops the matches of the line it's on from being reported.
+const IGNORE_LINE: &str = "grusp:ignore-line";
+/// A comment that stops the matches of the file it's in from being reported, when it's
+/// within the first `DIRECTIVE_HEADER_LINES` lines.
+const IGNORE_FILE: &str = "grusp:ignore-file";
+const DIRECTIVE_HEADER_LINES: usize = 5;
+
+/// The matches of a chunk of a file, along with the number of lines in it.
+type Chunk = std::io::Result<(Matches, usize)>;
 
 /// A struct that tallies and maintains an aggregated stats history of matches
 /// even across threads.
 #[derive(Clone, Debug)]
 pub struct Stats {
     counts: Arc<Mutex<Counts>>,
+    max_files: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -16,24 +37,129 @@ struct Counts {
     total: u64,
     lines: u64,
     captures: u64,
+    /// The bytes that weren't valid UTF-8 in every file added.
+    replaced_bytes: u64,
+    /// The lines longer than the maximum line length in every file added.
+    long_lines: u64,
+    /// The files that couldn't be read, with why.
+    unreadable: Vec<(PathBuf, String)>,
+    /// The number of matching lines of each file that matched.
+    files: Vec<(PathBuf, u32)>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            counts: Arc::new(Mutex::new(Counts {
+                total: 0,
+                lines: 0,
+                captures: 0,
+                replaced_bytes: 0,
+                long_lines: 0,
+                unreadable: Vec::new(),
+                files: Vec::new(),
+            })),
+            max_files: None,
+        }
+    }
 }
 
 impl Stats {
     /// Creates a new stat collector struct to tally and keep track of how many
     /// lines, captures, and files match
     pub fn new() -> Self {
-        Self { counts: Arc::new(Mutex::new(Counts { total: 0, lines: 0, captures: 0 })) }
+        Self::default()
+    }
+
+    /// Consumes the stats and sets how many matched files they take. Once that many have
+    /// been added the rest are turned away, so a search can stop after a few examples.
+    /// Files that are added concurrently are taken in the order they're added.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, StatCollector};
+    /// use regex::Regex;
+    /// use std::path::Path;
+    ///
+    /// let regex = Regex::new("old_api").unwrap();
+    /// let stats = StatCollector::new().max_files(Some(1));
+    /// let matcher = Matcher::new(&regex);
+    /// assert!(stats.add(&matcher.collect_path(&mut "new_api".as_bytes(), Path::new("a.rs")).unwrap()));
+    /// assert!(!stats.is_full());
+    /// assert!(stats.add(&matcher.collect_path(&mut "old_api".as_bytes(), Path::new("b.rs")).unwrap()));
+    /// assert!(stats.is_full());
+    /// assert!(!stats.add(&matcher.collect_path(&mut "old_api".as_bytes(), Path::new("c.rs")).unwrap()));
+    /// assert_eq!(stats.total(), 1);
+    /// # }
+    /// ```
+    pub fn max_files(self, max_files: Option<u64>) -> Self {
+        Self { max_files, ..self }
     }
 
-    /// Adds a set of matches for a given file to the stats.
-    pub fn add(&self, m: &Matches) -> () {
+    /// Adds a set of matches for a given file to the stats. Returns false, without adding
+    /// them, when they match and the stats already have as many matched files as they
+    /// take.
+    pub fn add(&self, m: &Matches) -> bool {
+        let mut counts = self.counts.lock().unwrap();
+        counts.replaced_bytes += m.replaced_bytes as u64;
+        counts.long_lines += m.long_lines as u64;
         if m.has_matches() {
-            let mut counts = self.counts.lock().unwrap();
+            if self.max_files.is_some_and(|max| counts.total >= max) {
+                return false;
+            }
             counts.total += 1;
-            counts.lines += m.lines.len() as u64;
+            counts.lines += u64::from(m.count);
             let capture_count: u64 = m.lines.iter().map(|m| m.captures.len() as u64).sum();
             counts.captures += capture_count;
+            if let Some(ref path) = m.path {
+                counts.files.push((path.clone(), m.count));
+            }
         }
+        true
+    }
+
+    /// Returns true if the stats have as many matched files as they take, so the files
+    /// left don't need to be searched.
+    pub fn is_full(&self) -> bool {
+        self.max_files.is_some_and(|max| self.total() >= max)
+    }
+
+    /// Records a file that couldn't be read and why, so the search can carry on without
+    /// it and report it at the end.
+    pub fn add_unreadable(&self, path: &Path, error: &std::io::Error) {
+        self.counts.lock().unwrap().unreadable.push((path.to_path_buf(), error.to_string()));
+    }
+
+    /// Returns up to `n` of the files with the most matching lines, with their number of
+    /// matching lines, from the most to the fewest. Ties are ordered by path.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, StatCollector};
+    /// use regex::Regex;
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let regex = Regex::new("old_api").unwrap();
+    /// let stats = StatCollector::new();
+    /// for &(path, text) in &[("a.rs", "old_api"), ("b.rs", "old_api\nold_api"), ("c.rs", "new_api")] {
+    ///     stats.add(&Matcher::new(&regex).collect_path(&mut text.as_bytes(), Path::new(path)).unwrap());
+    /// }
+    /// assert_eq!(stats.top(5), vec![(PathBuf::from("b.rs"), 2), (PathBuf::from("a.rs"), 1)]);
+    /// # }
+    /// ```
+    pub fn top(&self, n: usize) -> Vec<(PathBuf, u32)> {
+        let mut files = self.counts.lock().unwrap().files.clone();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        files.truncate(n);
+        files
     }
 
     /// Returns the total number of matched files.
@@ -50,34 +176,243 @@ impl Stats {
     pub fn lines(&self) -> u64 {
         self.counts.lock().unwrap().lines
     }
+
+    /// Returns the total number of bytes that weren't valid UTF-8 and were replaced.
+    pub fn replaced_bytes(&self) -> u64 {
+        self.counts.lock().unwrap().replaced_bytes
+    }
+
+    /// Returns the total number of lines that were longer than the maximum line length,
+    /// and were skipped or searched in pieces.
+    pub fn long_lines(&self) -> u64 {
+        self.counts.lock().unwrap().long_lines
+    }
+
+    /// Returns the number of files that couldn't be read.
+    pub fn unreadable(&self) -> u64 {
+        self.counts.lock().unwrap().unreadable.len() as u64
+    }
+
+    /// Returns the files that couldn't be read, with why, ordered by path.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::StatCollector;
+    /// use std::io;
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let stats = StatCollector::new();
+    /// stats.add_unreadable(Path::new("b.log"), &io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+    /// stats.add_unreadable(Path::new("a.log"), &io::Error::new(io::ErrorKind::NotFound, "gone"));
+    /// assert_eq!(stats.unreadable(), 2);
+    /// assert_eq!(
+    ///     stats.unreadable_files(),
+    ///     vec![(PathBuf::from("a.log"), "gone".to_string()), (PathBuf::from("b.log"), "denied".to_string())]
+    /// );
+    /// ```
+    pub fn unreadable_files(&self) -> Vec<(PathBuf, String)> {
+        let mut files = self.counts.lock().unwrap().unreadable.clone();
+        files.sort();
+        files
+    }
 }
 
+/// The matching lines collected from a single buffer.
+///
+/// The fields are read through accessors, or line by line as records with `iter`, so
+/// they can change without breaking code built on them.
+///
+/// With the `serde` feature, matches, their lines and captures can be serialized and
+/// deserialized, such as to store them or send them to another process.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Matches {
-    pub path: Option<PathBuf>,
-    pub count: u32,
-    pub lines: Vec<Line>,
+    /// The file the buffer was read from, if any.
+    pub(crate) path: Option<PathBuf>,
+    /// The number of matching lines.
+    pub(crate) count: u32,
+    /// The matching lines, empty when the matcher doesn't keep lines.
+    pub(crate) lines: Vec<Line>,
+    /// The number of bytes that weren't valid UTF-8 and were replaced while reading.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) replaced_bytes: usize,
+    /// The number of lines longer than the maximum line length.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) long_lines: usize,
 }
 
-#[derive(Debug)]
+/// A matching line and where it was found, as returned by `Matches::iter`.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::Matcher;
+/// use regex::Regex;
+/// use std::path::Path;
+///
+/// let regex = Regex::new("o+").unwrap();
+/// let matches = Matcher::new(&regex)
+///     .collect_path(&mut "foo\nbar\nboo".as_bytes(), Path::new("a.txt"))
+///     .unwrap();
+/// let records: Vec<_> = matches.iter().map(|r| (r.line_number(), r.text(), r.spans().collect::<Vec<_>>())).collect();
+/// assert_eq!(records, vec![(Some(1), "foo", vec![1..3]), (Some(3), "boo", vec![1..3])]);
+/// assert!(matches.iter().all(|r| r.path() == Some(Path::new("a.txt"))));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MatchRecord<'a> {
+    path: Option<&'a Path>,
+    line: &'a Line,
+}
+
+impl<'a> MatchRecord<'a> {
+    /// Returns the file the line was read from, if any.
+    pub fn path(&self) -> Option<&'a Path> {
+        self.path
+    }
+
+    /// Returns the line number, counting from one, when line numbers are tracked.
+    pub fn line_number(&self) -> Option<usize> {
+        self.line.number
+    }
+
+    /// Returns the contents of the line.
+    pub fn text(&self) -> &'a str {
+        &self.line.value
+    }
+
+    /// Returns the byte range within the line of each match. Lines kept for context,
+    /// such as with passthru, and lines matched by inverting the regex have none.
+    pub fn spans(&self) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.line.captures.iter().map(|capture| capture.start..capture.end)
+    }
+}
+
+/// A single matching line.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Line {
+    /// The line number, counting from one, when line numbers are tracked.
     pub number: Option<usize>,
+    /// The contents of the line.
     pub value: String,
+    /// Every match of the regex within the line.
     pub captures: Vec<Capture>,
 }
 
-#[derive(Debug)]
+/// A match of the regex within a line.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Capture {
+    /// The byte offset within the line that the match starts at.
     pub start: usize,
+    /// The byte offset within the line that the match ends at, exclusive.
     pub end: usize,
+    /// The matched text.
     pub value: String,
+    /// The byte offset within the whole buffer that the match starts at, counting the
+    /// bytes as they were read. When a single line is matched this is the same as
+    /// `start`, unless the line was normalized or bytes that weren't valid UTF-8 were
+    /// replaced before the match.
+    pub offset: usize,
+    /// The column the match starts at, counting from one. Columns count grapheme
+    /// clusters, so a character made of several code points is a single column. They're
+    /// counted in the line as it was read, before it was normalized.
+    pub column: usize,
+    /// The name of the pattern that matched, when the regex was made of named patterns.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rule: Option<String>,
 }
 
 impl Matches {
+    /// Creates the matches of a buffer from its matching lines.
+    pub fn from_lines(lines: Vec<Line>) -> Self {
+        Matches { path: None, count: lines.len() as u32, lines, replaced_bytes: 0, long_lines: 0 }
+    }
+
+    /// Creates the matches of a buffer that only counted its matching lines.
+    pub fn from_count(count: u32) -> Self {
+        Matches { path: None, count, lines: Vec::new(), replaced_bytes: 0, long_lines: 0 }
+    }
+
+    /// Returns true if any line matched.
     pub fn has_matches(&self) -> bool {
         self.count > 0
     }
 
+    /// Returns the file the buffer was read from, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the number of matching lines, which is counted even when the lines
+    /// aren't kept.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns the matching lines, which are empty when the matcher doesn't keep lines.
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Returns the number of bytes of the buffer that weren't valid UTF-8. Each run of
+    /// them was read as the replacement character `\u{FFFD}` so the rest of the line,
+    /// and the lines after it, could still be searched.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::Matcher;
+    ///
+    /// let regex = regex::Regex::new("ok").unwrap();
+    /// let matches = Matcher::new(&regex).collect(&mut &b"ok \xff\xfe\nok"[..]).unwrap();
+    /// assert_eq!(matches.count(), 2);
+    /// assert_eq!(matches.lines()[0].value, "ok \u{FFFD}\u{FFFD}");
+    /// assert_eq!(matches.replaced_bytes(), 2);
+    /// # }
+    /// ```
+    pub fn replaced_bytes(&self) -> usize {
+        self.replaced_bytes
+    }
+
+    /// Returns the number of lines longer than the maximum line length of the matcher,
+    /// which were skipped or searched in pieces.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{LongLines, Matcher};
+    ///
+    /// let regex = regex::Regex::new("ok").unwrap();
+    /// let matcher = Matcher::new(&regex).max_line_length(Some((8, LongLines::Skip)));
+    /// let matches = matcher.collect(&mut &b"ok\n[ok, ok, ok]\nok"[..]).unwrap();
+    /// assert_eq!(matches.count(), 2);
+    /// assert_eq!(matches.lines()[1].number, Some(3));
+    /// assert_eq!(matches.long_lines(), 1);
+    /// # }
+    /// ```
+    pub fn long_lines(&self) -> usize {
+        self.long_lines
+    }
+
+    /// Returns a record of each line kept, in the order they were read.
+    pub fn iter(&self) -> impl Iterator<Item = MatchRecord<'_>> {
+        let path = self.path();
+        self.lines.iter().map(move |line| MatchRecord { path, line })
+    }
+
+    /// Sets the path of the file the matches were found in.
     pub fn add_path(mut self, path: &Path) -> Self {
         self.path = Some(path.to_owned());
         self
@@ -88,6 +423,8 @@ impl Matches {
             path: None,
             count: 0,
             lines: Vec::new(),
+            replaced_bytes: 0,
+            long_lines: 0,
         }
     }
 
@@ -97,6 +434,11 @@ impl Matches {
         self.lines.push(m);
     }
 
+    #[inline]
+    fn push(&mut self, m: Line) {
+        self.lines.push(m);
+    }
+
     #[inline]
     fn increment(&mut self) {
         self.count += 1;
@@ -104,6 +446,56 @@ impl Matches {
 }
 
 impl Line {
+    /// Returns the byte ranges of the line to highlight: the spans of its captures in
+    /// order, with the ones that overlap or touch merged into one and empty ones dropped.
+    /// Captures built by hand can overlap, run past the end of the line or start or end
+    /// inside a character, such as when they were found in a differently normalized line,
+    /// so the ranges are kept within the line and widened to whole characters. Each can
+    /// be sliced out of the value safely.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Capture, Line};
+    ///
+    /// let capture = |start, end| Capture { start, end, value: String::new(), offset: start, column: start + 1, rule: None };
+    /// let line = Line { number: None, value: "abcdefgh".to_string(), captures: vec![capture(4, 6), capture(0, 2), capture(1, 3), capture(6, 7), capture(5, 5), capture(7, 20)] };
+    /// assert_eq!(line.highlight_ranges(), vec![0..3, 4..8]);
+    /// let line = Line { value: "日本語".to_string(), captures: vec![capture(4, 5)], ..line };
+    /// assert_eq!(line.highlight_ranges(), vec![3..6]);
+    /// # }
+    /// ```
+    pub fn highlight_ranges(&self) -> Vec<Range<usize>> {
+        let value = &self.value;
+        let floor = |mut i: usize| {
+            i = i.min(value.len());
+            while !value.is_char_boundary(i) { i -= 1; }
+            i
+        };
+        let ceil = |mut i: usize| {
+            i = i.min(value.len());
+            while !value.is_char_boundary(i) { i += 1; }
+            i
+        };
+        let mut spans: Vec<Range<usize>> = self.captures
+            .iter()
+            .map(|capture| floor(capture.start)..ceil(capture.end))
+            .filter(|span| span.start < span.end)
+            .collect();
+        spans.sort_by_key(|span| span.start);
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match ranges.last_mut() {
+                Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                _ => ranges.push(span),
+            }
+        }
+        ranges
+    }
+
     fn new(value: String, captures: Vec<Capture>) -> Self {
         Self {
             number: None,
@@ -115,15 +507,113 @@ impl Line {
     fn line_number(self, number: usize) -> Self {
         Self { number: Some(number), ..self }
     }
+
+    /// Drops the text of the line and of its captures, keeping the spans to read it back.
+    fn without_text(self) -> Self {
+        let captures = self.captures.into_iter().map(|capture| Capture { value: String::new(), ..capture }).collect();
+        Line { value: String::new(), captures, ..self }
+    }
+
+    /// Moves the offsets and columns of the captures, found in a normalized line, to the
+    /// text it was normalized from.
+    fn denormalize(mut self, text: &str, origins: &Origins) -> Self {
+        for capture in &mut self.captures {
+            capture.offset = origins.raw(capture.offset);
+            capture.column = text[..capture.offset].graphemes(true).count() + 1;
+        }
+        self
+    }
+
+    /// Moves the offsets of the captures, which are within the text that was matched,
+    /// to the bytes it was read from, starting at the offset.
+    fn offset_by(mut self, line_offset: usize, origins: &Origins) -> Self {
+        for capture in &mut self.captures {
+            capture.offset = line_offset + origins.raw(capture.offset);
+        }
+        self
+    }
+}
+
+/// A Unicode normalization form. Accented characters can be written either as a single
+/// composed code point or as a base character followed by combining marks, which look
+/// the same but don't match each other unless both are normalized to the same form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Canonical composition, where `é` is the single code point U+00E9.
+    Nfc,
+    /// Canonical decomposition, where `é` is `e` followed by U+0301.
+    Nfd,
+}
+
+impl Normalization {
+    /// Normalizes the text, such as a pattern so that it matches normalized lines.
+    pub fn normalize(self, text: &str) -> String {
+        match self {
+            Normalization::Nfc => text.nfc().collect(),
+            Normalization::Nfd => text.nfd().collect(),
+        }
+    }
+
+    /// Normalizes the text a grapheme cluster at a time, which normalization doesn't
+    /// reach across, recording where the clusters that changed came from.
+    fn normalize_graphemes(self, text: &str) -> (String, Origins) {
+        let mut normalized = String::with_capacity(text.len());
+        let mut origins = Origins::default();
+        for (at, grapheme) in text.grapheme_indices(true) {
+            let start = normalized.len();
+            match self {
+                Normalization::Nfc => normalized.extend(grapheme.nfc()),
+                Normalization::Nfd => normalized.extend(grapheme.nfd()),
+            }
+            if normalized[start..] != *grapheme {
+                origins.push(start..normalized.len(), at..at + grapheme.len());
+            }
+        }
+        (normalized, origins)
+    }
+}
+
+/// What to do with a line longer than the maximum of `Matcher::max_line_length`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LongLines {
+    /// Skips the line without searching it.
+    Skip,
+    /// Searches the line in pieces of the maximum length, which all have the line's
+    /// number. A match spanning two pieces isn't found.
+    Chunk,
+}
+
+/// Reads past the next line terminator without keeping the bytes, and returns how many
+/// were read.
+fn skip_line<T: BufRead>(reader: &mut T, terminator: u8) -> std::io::Result<usize> {
+    let mut skipped = 0;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(skipped);
+        }
+        let (len, is_terminated) = match available.iter().position(|&byte| byte == terminator) {
+            Some(i) => (i + 1, true),
+            None => (available.len(), false),
+        };
+        reader.consume(len);
+        skipped += len;
+        if is_terminated {
+            return Ok(skipped);
+        }
+    }
 }
 
 /// A struct for accumulating and building the matches.
 #[derive(Debug)]
 pub struct Matcher<'a> {
     regex: &'a Regex,
-    with_line_numbers: bool,
-    keep_lines: bool,
-    is_inverted: bool,
+    options: SearchOptions,
+    record_separator: Option<&'a Regex>,
+    filters: Option<&'a Filters>,
+    enclosing: Option<&'a EnclosingRules>,
+    patterns: Option<&'a Patterns>,
+    cancellation: Option<&'a CancellationToken>,
 }
 
 impl<'a> Matcher<'a> {
@@ -131,14 +621,80 @@ impl<'a> Matcher<'a> {
     pub fn new(regex: &'a Regex) -> Self {
         Matcher {
             regex,
-            with_line_numbers: true,
-            keep_lines: true,
-            is_inverted: false,
+            options: SearchOptions::new(),
+            record_separator: None,
+            filters: None,
+            enclosing: None,
+            patterns: None,
+            cancellation: None,
         }
     }
 
-    /// Toggle the tracking of line numbers. If set to false, the returned matches
-    /// will not include the line numbers. Useful when the buffer is not actually a file.
+    /// Sets `SearchOptions::with_line_numbers` of the options of the matcher.
+    pub fn with_line_numbers(mut self, w: bool) -> Self {
+        self.options = self.options.with_line_numbers(w);
+        self
+    }
+
+    /// Sets `SearchOptions::invert_match` of the options of the matcher.
+    pub fn invert_match(mut self, is_inverted: bool) -> Self {
+        self.options = self.options.invert_match(is_inverted);
+        self
+    }
+
+    /// Sets `SearchOptions::line_range` of the options of the matcher.
+    pub fn line_range(mut self, line_range: Option<(usize, usize)>) -> Self {
+        self.options = self.options.line_range(line_range);
+        self
+    }
+
+    /// Sets `SearchOptions::head` of the options of the matcher.
+    pub fn head(mut self, head: Option<usize>) -> Self {
+        self.options = self.options.head(head);
+        self
+    }
+
+    /// Sets `SearchOptions::tail` of the options of the matcher.
+    pub fn tail(mut self, tail: Option<usize>) -> Self {
+        self.options = self.options.tail(tail);
+        self
+    }
+
+    /// Sets `SearchOptions::line_terminator` of the options of the matcher.
+    pub fn line_terminator(mut self, terminator: u8) -> Self {
+        self.options = self.options.line_terminator(terminator);
+        self
+    }
+
+    /// Sets `SearchOptions::crlf` of the options of the matcher.
+    pub fn crlf(mut self, is_crlf: bool) -> Self {
+        self.options = self.options.crlf(is_crlf);
+        self
+    }
+
+    /// Sets `SearchOptions::max_line_length` of the options of the matcher.
+    pub fn max_line_length(mut self, max_line_length: Option<(usize, LongLines)>) -> Self {
+        self.options = self.options.max_line_length(max_line_length);
+        self
+    }
+
+    /// Sets `SearchOptions::passthru` of the options of the matcher.
+    pub fn passthru(mut self, is_passthru: bool) -> Self {
+        self.options = self.options.passthru(is_passthru);
+        self
+    }
+
+    /// Sets `SearchOptions::paragraph` of the options of the matcher.
+    pub fn paragraph(mut self, is_paragraph: bool) -> Self {
+        self.options = self.options.paragraph(is_paragraph);
+        self
+    }
+
+    /// Matches records that each start at a line matching the separator, such as the
+    /// timestamp of a log entry, instead of lines. Like a paragraph, a record is kept as a
+    /// single line holding all of its lines, numbered by its first line. Any lines before
+    /// the first separator make up a record of their own. Takes the place of paragraphs
+    /// when both are set.
     ///
     /// # Examples
     ///
@@ -147,20 +703,194 @@ impl<'a> Matcher<'a> {
     /// # extern crate grusp_core;
     /// # fn main() {
     /// use grusp_core::grusp::Matcher;
-    /// use std::io::Cursor;
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"NullPointer").unwrap();
+    /// let separator = regex::Regex::new(r"^\d{2}:\d{2} ").unwrap();
+    /// let mut buf_read = Cursor::new("10:01 ok\n10:02 Error\n\n  at NullPointer\n10:03 ok\n");
+    /// let matches = Matcher::new(&reg).record_separator(Some(&separator)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// assert_eq!(matches.lines()[0].number, Some(2));
+    /// assert_eq!(matches.lines()[0].value, "10:02 Error\n\n  at NullPointer");
+    /// # }
+    /// ```
+    pub fn record_separator(mut self, separator: Option<&'a Regex>) -> Self {
+        self.record_separator = separator;
+        self
+    }
+
+    /// Sets `SearchOptions::max_captures_per_line` of the options of the matcher.
+    pub fn max_captures_per_line(mut self, max: Option<usize>) -> Self {
+        self.options = self.options.max_captures_per_line(max);
+        self
+    }
+
+    /// Sets `SearchOptions::normalization` of the options of the matcher.
+    pub fn normalization(mut self, normalization: Option<Normalization>) -> Self {
+        self.options = self.options.normalization(normalization);
+        self
+    }
+
+    /// Runs every matching line through a chain of filters, dropping the lines that any of
+    /// them reject before they're counted. Use `collect_path` so the filters know which file
+    /// the line came from.
+    pub fn filters(mut self, filters: &'a Filters) -> Self {
+        self.filters = if filters.is_empty() { None } else { Some(filters) };
+        self
+    }
+
+    /// Shows the heading that encloses each group of matching lines above it, such as the
+    /// function they're in, when the language of the file is one the rules know. The
+    /// headings are kept as lines without captures that aren't counted. Only applies when
+    /// lines are kept, and not to passthru, which shows every line anyway.
+    pub fn enclosing(mut self, rules: Option<&'a EnclosingRules>) -> Self {
+        self.enclosing = rules;
+        self
+    }
+
+    /// Tags each match with the name of the pattern that found it, when the regex was
+    /// made by `Patterns::pattern` from these patterns.
+    pub fn patterns(mut self, patterns: Option<&'a Patterns>) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Stops reading as soon as the token is cancelled, checking it after each line.
+    /// The matches found up to that point are still returned.
+    pub fn cancellation(mut self, token: &'a CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets the options of the search, replacing any set before.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
     ///
     /// let reg = regex::Regex::new(r"test").unwrap();
-    /// let mut buf_read = Cursor::new("test\nnot\ntest");
-    /// let matches = Matcher::new(&reg).with_line_numbers(false).collect(&mut buf_read).unwrap();
-    /// assert_eq!(matches.lines[0].number, None);
+    /// let options = SearchOptions::new().invert_match(true).head(Some(2));
+    /// let matches = Matcher::new(&reg).options(options).collect(&mut "test\nnot\nnot".as_bytes()).unwrap();
+    /// assert_eq!(matches.count(), 1);
     /// # }
     /// ```
-    pub fn with_line_numbers(mut self, w: bool) -> Self {
-        self.with_line_numbers = w;
+    pub fn options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
         self
     }
 
-    /// Can tell the matcher to match on non-matching lines instead of matching lines.
+    /// Whether or not the search has been cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.is_some_and(|token| token.is_cancelled())
+    }
+
+    /// Sets `SearchOptions::keep_lines` of the options of the matcher.
+    pub fn keep_lines(mut self, track_lines: bool) -> Self {
+        self.options = self.options.keep_lines(track_lines);
+        self
+    }
+
+    /// Sets `SearchOptions::first_match_only` of the options of the matcher.
+    pub fn first_match_only(mut self, is_first_match_only: bool) -> Self {
+        self.options = self.options.first_match_only(is_first_match_only);
+        self
+    }
+
+    /// Sets `SearchOptions::directives` of the options of the matcher.
+    pub fn directives(mut self, is_directives: bool) -> Self {
+        self.options = self.options.directives(is_directives);
+        self
+    }
+
+    /// Whether a file with the line among its first lines is ignored by a directive.
+    fn is_ignored_file(&self, number: usize, line: &str) -> bool {
+        self.options.is_directives && number <= DIRECTIVE_HEADER_LINES && line.contains(IGNORE_FILE)
+    }
+
+    /// Matches a single line, without its line terminator, returning it with its captures
+    /// if it should be reported. When inverted a line is only returned if the regex
+    /// doesn't match it. Line ranges and line numbers only apply to `collect`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::Matcher;
+    ///
+    /// let reg = regex::Regex::new(r"test$").unwrap();
+    /// let line = Matcher::new(&reg).match_line("a test").unwrap();
+    /// assert_eq!(line.captures[0].start, 2);
+    /// assert!(Matcher::new(&reg).invert_match(true).match_line("a test").is_none());
+    /// # }
+    /// ```
+    pub fn match_line(&self, line: &str) -> Option<Line> {
+        let to_capture = |m: ::regex::Match, rule: Option<&str>| {
+            Capture {
+                start: m.start(),
+                end: m.end(),
+                value: m.as_str().to_string(),
+                offset: m.start(),
+                column: line[..m.start()].graphemes(true).count() + 1,
+                rule: rule.map(str::to_string),
+            }
+        };
+        let captures: Vec<Capture> = match (self.options.max_captures_per_line, self.patterns) {
+            // OPTIMIZATION
+            // A single find skips setting up the iterator and the capture slots, which are
+            // only needed to tell which pattern matched.
+            (Some(1), None) => self.regex.find(line).map(|m| to_capture(m, None)).into_iter().collect(),
+            (max, patterns) => self.regex
+                .captures_iter(line)
+                .filter_map(|caps| caps.get(0).map(|m| to_capture(m, patterns.and_then(|patterns| patterns.rule(&caps)))))
+                .take(max.unwrap_or(usize::MAX))
+                .collect(),
+        };
+        // When empty, only return if we're inverting the match
+        // When not empty, only return if we're not inverting the match
+        if !(captures.is_empty() || self.options.is_inverted) ||
+            (captures.is_empty() && self.options.is_inverted) {
+            Some(Line::new(line.to_string(), captures))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the line would be returned by `match_line`, without making it or its
+    /// captures.
+    fn is_match(&self, line: &str) -> bool {
+        let is_found = self.options.max_captures_per_line != Some(0) && self.regex.is_match(line);
+        is_found != self.options.is_inverted
+    }
+
+    /// Collects all the matches from a buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::Matcher;
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest");
+    /// let matches = Matcher::new(&reg).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 2);
+    /// # }
+    /// ```
+    pub fn collect<T: BufRead>(&self, reader: &mut T) -> std::io::Result<Matches> {
+        self.collect_from(reader, Path::new(""))
+    }
+
+    /// Collects all the matches from a file that's already been opened, adding the path
+    /// to the matches and passing it to the filters.
     ///
     /// # Examples
     ///
@@ -169,80 +899,435 @@ impl<'a> Matcher<'a> {
     /// # extern crate grusp_core;
     /// # fn main() {
     /// use grusp_core::grusp::Matcher;
+    /// use std::io::{BufReader, Cursor};
+    /// use std::path::Path;
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest");
+    /// let matches = Matcher::new(&reg).collect_path(&mut buf_read, Path::new("test.txt")).unwrap();
+    /// assert_eq!(matches.path(), Some(Path::new("test.txt")));
+    /// # }
+    /// ```
+    pub fn collect_path<T: BufRead>(&self, reader: &mut T, path: &Path) -> std::io::Result<Matches> {
+        Ok(self.collect_from(reader, path)?.add_path(path))
+    }
+
+    /// Reads the text of the lines that were kept without it by `SearchOptions::spans_only`
+    /// back from the buffer they were collected from, along with the text of their
+    /// captures. Lines that already have their text are left as they are, so only the
+    /// lines about to be shown need to be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
     /// use std::io::Cursor;
     ///
+    /// let reg = regex::Regex::new(r"ERROR \w+").unwrap();
+    /// let log = "ok\nan ERROR here\nok\nERROR again\n";
+    /// let matcher = Matcher::new(&reg).options(SearchOptions::new().spans_only(true));
+    /// let mut matches = matcher.collect(&mut Cursor::new(log)).unwrap();
+    /// matcher.read_text(&mut matches, &mut Cursor::new(log)).unwrap();
+    /// assert_eq!(matches.lines()[0].value, "an ERROR here");
+    /// assert_eq!(matches.lines()[1].captures[0].value, "ERROR again");
+    /// # }
+    /// ```
+    pub fn read_text<T: BufRead + Seek>(&self, matches: &mut Matches, reader: &mut T) -> std::io::Result<()> {
+        for line in matches.lines.iter_mut().filter(|line| line.value.is_empty()) {
+            let start = match line.captures.first() {
+                Some(capture) => capture.offset - capture.start,
+                None => continue,
+            };
+            reader.seek(std::io::SeekFrom::Start(start as u64))?;
+            // A piece of a long line is read back the way it was cut
+            let raw = match self.read_line(reader)? {
+                Some(raw) => raw,
+                None => continue,
+            };
+            let text = if raw.is_partial { &raw.text[..] } else { self.trim_terminator(&raw.text) };
+            for capture in &mut line.captures {
+                capture.value = text.get(capture.start..capture.end).unwrap_or_default().to_string();
+            }
+            line.value = text.to_string();
+        }
+        Ok(())
+    }
+
+    /// Whether matching lines are kept without their text, which only works for lines
+    /// that can be read back by the spans of their captures.
+    fn is_spans_only(&self) -> bool {
+        self.options.is_spans_only &&
+            !self.options.is_paragraph &&
+            self.record_separator.is_none() &&
+            self.options.normalization.is_none()
+    }
+
+    /// Collects the matches of a file by splitting it into chunks of about `chunk_size`
+    /// bytes that end on line boundaries, searching them on several threads and merging
+    /// the results in order, so a single huge file doesn't have to be searched on one
+    /// core. The line numbers and byte offsets of the matches are the same as they'd be
+    /// if the file was searched in one go.
+    ///
+    /// Files no larger than a chunk are searched as usual, as are files searched with
+    /// options that depend on what came before a line, such as `head`, `tail`, line
+    /// ranges, paragraphs, records, enclosing headings or stopping at the first match.
+    /// Filters see the lines of the chunks as they're searched rather than in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::Matcher;
+    /// use std::fs;
+    ///
+    /// let path = std::env::temp_dir().join("grusp-chunked-doc.log");
+    /// fs::write(&path, "ok\nERROR a\nok\nok\nERROR b\n").unwrap();
+    /// let regex = regex::Regex::new("ERROR").unwrap();
+    /// let matches = Matcher::new(&regex).collect_chunked(&path, 4).unwrap();
+    /// let numbers: Vec<_> = matches.iter().map(|record| record.line_number()).collect();
+    /// assert_eq!(numbers, vec![Some(2), Some(5)]);
+    /// assert_eq!(matches.lines()[1].captures[0].offset, 17);
+    /// # }
+    /// ```
+    pub fn collect_chunked(&self, path: &Path, chunk_size: u64) -> std::io::Result<Matches> {
+        let len = std::fs::metadata(path)?.len();
+        let is_sequential = self.options.head.is_some() ||
+            self.options.tail.is_some() ||
+            self.options.line_range.is_some() ||
+            self.options.is_paragraph ||
+            self.record_separator.is_some() ||
+            self.enclosing.is_some() ||
+            self.options.is_first_match_only ||
+            self.options.is_directives;
+        if is_sequential || chunk_size == 0 || len <= chunk_size {
+            let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            return self.collect_path(&mut reader, path);
+        }
+        let bounds = self.chunk_bounds(path, len, chunk_size)?;
+        let chunks: Vec<Mutex<Option<Chunk>>> = bounds.windows(2).map(|_| Mutex::new(None)).collect();
+        let next = AtomicUsize::new(0);
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get()).min(chunks.len());
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= chunks.len() {
+                        return;
+                    }
+                    let result = self.collect_chunk(path, bounds[i], bounds[i + 1]);
+                    *chunks[i].lock().unwrap() = Some(result);
+                });
+            }
+        });
+        // Each chunk numbered its lines from one, so they're shifted by the lines before it
+        let mut matches = Matches::new().add_path(path);
+        let mut lines_before = 0;
+        for (chunk, &start) in chunks.into_iter().zip(&bounds) {
+            let (chunk, lines) = chunk.into_inner().unwrap().expect("Every chunk is searched")?;
+            matches.count += chunk.count;
+            matches.replaced_bytes += chunk.replaced_bytes;
+            matches.long_lines += chunk.long_lines;
+            matches.lines.extend(chunk.lines.into_iter().map(|mut line| {
+                line.number = line.number.map(|number| number + lines_before);
+                for capture in &mut line.captures {
+                    capture.offset += start as usize;
+                }
+                line
+            }));
+            lines_before += lines;
+        }
+        Ok(matches)
+    }
+
+    /// Returns the offsets the chunks of the file start at, each just after the first
+    /// line terminator past a multiple of the chunk size, followed by the length.
+    fn chunk_bounds(&self, path: &Path, len: u64, chunk_size: u64) -> std::io::Result<Vec<u64>> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut bounds = vec![0];
+        let mut target = chunk_size;
+        while target < len {
+            reader.seek(std::io::SeekFrom::Start(target - 1))?;
+            let bound = target - 1 + skip_line(&mut reader, self.options.terminator)? as u64;
+            if bound >= len {
+                break;
+            }
+            bounds.push(bound);
+            // A line longer than a chunk takes up the chunks it spans
+            target = (bound / chunk_size + 1) * chunk_size;
+        }
+        bounds.push(len);
+        Ok(bounds)
+    }
+
+    /// Collects the matches between the offsets, which start and end on line boundaries,
+    /// along with the number of lines between them.
+    fn collect_chunk(&self, path: &Path, start: u64, end: u64) -> Chunk {
+        let mut file = std::fs::File::open(path)?;
+        file.seek(std::io::SeekFrom::Start(start))?;
+        let mut contents = Vec::with_capacity((end - start) as usize);
+        file.take(end - start).read_to_end(&mut contents)?;
+        let lines = contents.iter().filter(|&&byte| byte == self.options.terminator).count();
+        Ok((self.collect_from(&mut std::io::Cursor::new(&contents), path)?, lines))
+    }
+
+    fn collect_from<T: BufRead>(&self, reader: &mut T, path: &Path) -> std::io::Result<Matches> {
+        if let Some(tail) = self.options.tail {
+            return self.collect_tail(reader, tail, path);
+        }
+        let mut collector = Collector::new(self, path);
+        while let Some(line) = self.read_line(reader)? {
+            collector.handle(line);
+            if collector.is_done() { break }
+        }
+        collector.finish();
+        Ok(collector.matches)
+    }
+
+    /// Calls `f` with each line as soon as it's read, instead of collecting them, and
+    /// returns the number of matching lines. This lets a never ending stream such as
+    /// `tail -f` be searched. A tail has to be read to the end before any line is known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::Matcher;
+    /// use std::io::{BufReader, Cursor};
+    ///
     /// let reg = regex::Regex::new(r"test").unwrap();
     /// let mut buf_read = Cursor::new("test\nnot\ntest");
-    /// let matches = Matcher::new(&reg).invert_match(true).collect(&mut buf_read).unwrap();
-    /// assert_eq!(matches.lines.len(), 1);
-    /// assert_eq!(matches.lines[0].number, Some(2));
-    /// assert_eq!(matches.lines[0].captures.len(), 0);
+    /// let mut numbers = Vec::new();
+    /// let count = Matcher::new(&reg).for_each_line(&mut buf_read, |line| numbers.push(line.number)).unwrap();
+    /// assert_eq!(count, 2);
+    /// assert_eq!(numbers, vec![Some(1), Some(3)]);
     /// # }
     /// ```
-    pub fn invert_match(mut self, is_inverted: bool) -> Self {
-        self.is_inverted = is_inverted;
-        self
+    pub fn for_each_line<T, F>(&self, reader: &mut T, mut f: F) -> std::io::Result<u32>
+        where T: BufRead, F: FnMut(Line)
+    {
+        if self.options.tail.is_some() {
+            let matches = self.collect(reader)?;
+            matches.lines.into_iter().for_each(f);
+            return Ok(matches.count);
+        }
+        let mut collector = Collector::new(self, Path::new(""));
+        while let Some(line) = self.read_line(reader)? {
+            collector.handle(line);
+            collector.matches.lines.drain(..).for_each(&mut f);
+            if collector.is_done() { break }
+        }
+        collector.finish();
+        collector.matches.lines.drain(..).for_each(&mut f);
+        Ok(collector.matches.count)
+    }
+
+    /// Reads up to and including the next line terminator, or `None` at the end of the
+    /// buffer. Bytes that aren't valid UTF-8 are replaced rather than ending the search
+    /// of the buffer, but a read that fails partway is an error rather than the end.
+    fn read_line<T: BufRead>(&self, reader: &mut T) -> std::io::Result<Option<RawLine>> {
+        let mut line = Vec::new();
+        let (max, long_lines) = match self.options.max_line_length {
+            Some(max_line_length) => max_line_length,
+            None => {
+                return match reader.read_until(self.options.terminator, &mut line)? {
+                    0 => Ok(None),
+                    _ => Ok(Some(RawLine::decode(line))),
+                };
+            }
+        };
+        // Only as much of the buffer as fits is consumed, so the rest of a long line is
+        // still there to be skipped or read as the next piece
+        loop {
+            let available = reader.fill_buf()?;
+            if available.is_empty() {
+                break;
+            }
+            let (len, is_terminated) = match available.iter().position(|&byte| byte == self.options.terminator) {
+                Some(i) => (i + 1, true),
+                None => (available.len(), false),
+            };
+            let room = max - line.len();
+            if len - is_terminated as usize > room {
+                // A character cut in two at the maximum is kept whole
+                let mut end = room;
+                while end < available.len() && end < room + 3 && available[end] & 0xC0 == 0x80 {
+                    end += 1;
+                }
+                line.extend_from_slice(&available[..end]);
+                reader.consume(end);
+                return self.read_long_line(reader, line, long_lines).map(Some);
+            }
+            line.extend_from_slice(&available[..len]);
+            reader.consume(len);
+            if is_terminated {
+                break;
+            }
+        }
+        Ok(if line.is_empty() { None } else { Some(RawLine::decode(line)) })
+    }
+
+    /// Reads the rest of a line that has more bytes than the maximum after its start,
+    /// either by skipping past them or by leaving them to be read as the next piece.
+    fn read_long_line<T: BufRead>(&self, reader: &mut T, start: Vec<u8>, long_lines: LongLines) -> std::io::Result<RawLine> {
+        Ok(match long_lines {
+            LongLines::Chunk => RawLine { is_partial: true, ..RawLine::decode(start) },
+            LongLines::Skip => {
+                let len = start.len() + skip_line(reader, self.options.terminator)?;
+                RawLine { text: String::new(), len, replaced_bytes: 0, origins: Origins::default(), is_skipped: true, is_partial: false }
+            }
+        })
+    }
+
+    /// Removes the line terminator, and a carriage return before it in CRLF mode.
+    fn trim_terminator<'l>(&self, line: &'l str) -> &'l str {
+        let line = match line.as_bytes().last() {
+            Some(&last) if last == self.options.terminator => &line[..line.len() - 1],
+            _ => line,
+        };
+        if self.options.is_crlf {
+            line.strip_suffix('\r').unwrap_or(line)
+        } else {
+            line
+        }
     }
 
-    /// Toggles the tracking of lines/captures
-    pub fn keep_lines(mut self, track_lines: bool) -> Self {
-        self.keep_lines = track_lines;
-        self
+    fn collect_tail<T: BufRead>(&self, reader: &mut T, tail: usize, path: &Path) -> std::io::Result<Matches> {
+        // The pieces of a long line are kept or dropped together, so the tail is counted
+        // in lines rather than in pieces
+        let mut lines: VecDeque<RawLine> = VecDeque::with_capacity(tail);
+        let mut kept = 0;
+        let mut total = 0;
+        let mut total_bytes = 0;
+        let mut replaced_bytes = 0;
+        let mut long_lines = 0;
+        let mut is_continued = false;
+        while let Some(line) = self.read_line(reader)? {
+            if self.is_cancelled() { break }
+            if !is_continued {
+                total += 1;
+                if self.is_ignored_file(total, &line.text) {
+                    return Ok(Matches::new());
+                }
+                if line.is_skipped || line.is_partial { long_lines += 1; }
+                if kept == tail && tail > 0 {
+                    while lines.pop_front().is_some_and(|line| line.is_partial) {}
+                    kept -= 1;
+                }
+                if tail > 0 { kept += 1; }
+            }
+            is_continued = line.is_partial;
+            total_bytes += line.len;
+            replaced_bytes += line.replaced_bytes;
+            if tail > 0 { lines.push_back(line); }
+        }
+        let mut collector = Collector::new(self, path);
+        collector.line_number = total - kept;
+        collector.byte_offset = total_bytes - lines.iter().map(|line| line.len).sum::<usize>();
+        // The bad bytes of the lines before the tail are counted along with its own
+        collector.matches.replaced_bytes = replaced_bytes - lines.iter().map(|line| line.replaced_bytes).sum::<usize>();
+        for line in lines {
+            collector.handle(line);
+            if collector.is_done() { break }
+        }
+        collector.finish();
+        // As are the long lines before it
+        collector.matches.long_lines = long_lines;
+        Ok(collector.matches)
     }
+}
+
+/// A line as it was read, decoded as UTF-8.
+struct RawLine {
+    text: String,
+    /// The number of bytes that were read, terminator included.
+    len: usize,
+    /// The number of bytes that weren't valid UTF-8, which were replaced in the text.
+    replaced_bytes: usize,
+    /// Where the replacement characters in the text were read from.
+    origins: Origins,
+    /// Whether the line was longer than the maximum and skipped, leaving no text.
+    is_skipped: bool,
+    /// Whether this is a piece of a line longer than the maximum, which continues in
+    /// the next one.
+    is_partial: bool,
+}
 
-    fn match_line(&self, line: &str) -> Option<Line> {
-        let captures: Vec<Capture> = self.regex
-            .captures_iter(&line)
-            .filter_map(|caps| caps.get(0))
-            .map(|m| {
-                Capture {
-                    start: m.start(),
-                    end: m.end(),
-                    value: m.as_str().to_string(),
+impl RawLine {
+    fn decode(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        match String::from_utf8(bytes) {
+            Ok(text) => RawLine { text, len, replaced_bytes: 0, origins: Origins::default(), is_skipped: false, is_partial: false },
+            Err(e) => {
+                let bytes = e.into_bytes();
+                let mut text = String::with_capacity(len);
+                let mut origins = Origins::default();
+                let mut read = 0;
+                for chunk in bytes.utf8_chunks() {
+                    text.push_str(chunk.valid());
+                    read += chunk.valid().len();
+                    let invalid = chunk.invalid().len();
+                    if invalid > 0 {
+                        origins.push(text.len()..text.len() + '\u{FFFD}'.len_utf8(), read..read + invalid);
+                        text.push('\u{FFFD}');
+                        read += invalid;
+                    }
                 }
-            })
-            .collect();
-        // When empty, only return if we're inverting the match
-        // When not empty, only return if we're not inverting the match
-        if !(captures.is_empty() || self.is_inverted) ||
-            (captures.is_empty() && self.is_inverted) {
-            Some(Line::new(line.to_string(), captures))
-        } else {
-            None
+                RawLine {
+                    text,
+                    len,
+                    replaced_bytes: origins.changes.iter().map(|(_, raw)| raw.len()).sum(),
+                    origins,
+                    is_skipped: false,
+                    is_partial: false,
+                }
+            }
         }
     }
+}
 
-    /// Collects all the matches from a buffer.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # extern crate regex;
-    /// # extern crate grusp_core;
-    /// # fn main() {
-    /// use grusp_core::grusp::Matcher;
-    /// use std::io::Cursor;
-    ///
-    /// let reg = regex::Regex::new(r"test").unwrap();
-    /// let mut buf_read = Cursor::new("test\nnot\ntest");
-    /// let matches = Matcher::new(&reg).collect(&mut buf_read).unwrap();
-    /// assert_eq!(matches.count, 2);
-    /// # }
-    /// ```
-    pub fn collect<T: BufRead>(&self, reader: &mut T) -> std::io::Result<Matches> {
-        let mut collector = Collector::new(&self);
-        loop {
-            let mut line = String::new();
-            match reader.read_line(&mut line) {
-                Ok(size) if size > 0 => collector.handle(&line),
-                _ => break,
+/// Where the spans of a text that were changed after it was read, such as characters
+/// that replaced bytes that weren't valid UTF-8, came from in the bytes it was read from.
+#[derive(Clone, Debug, Default)]
+struct Origins {
+    /// The changed spans of the text in order, each with the span of bytes it was read from.
+    changes: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl Origins {
+    /// Records that the span of the text was read from the span of bytes.
+    fn push(&mut self, text: Range<usize>, raw: Range<usize>) {
+        self.changes.push((text, raw));
+    }
+
+    /// Appends the changes of a text that was added at `at` in this one, and which was
+    /// read from the bytes at `raw_at`.
+    fn extend(&mut self, other: &Origins, at: usize, raw_at: usize) {
+        for (text, raw) in &other.changes {
+            self.push(text.start + at..text.end + at, raw.start + raw_at..raw.end + raw_at);
+        }
+    }
+
+    /// Returns the offset in the bytes that were read of an offset in the text. One
+    /// within a changed span is moved to where the bytes of the span start.
+    fn raw(&self, at: usize) -> usize {
+        let mut raw_at = at;
+        for (text, raw) in &self.changes {
+            if at < text.end {
+                if at > text.start {
+                    raw_at = raw.start;
+                }
+                break;
             }
-            // OPTIMIZATION
-            // If we're not keeping track of lines we can break out as soon as we find a
-            // match. On a large directory, this saves about 25%.
-            if !self.keep_lines && collector.matches.has_matches() { break }
+            raw_at = at - text.end + raw.end;
         }
-        Ok(collector.matches)
+        raw_at
     }
 }
 
@@ -251,28 +1336,55 @@ impl<'a> Matcher<'a> {
 /// `Matcher` that houses how the search should work.
 struct Collector<'a> {
     matcher: &'a Matcher<'a>,
+    path: &'a Path,
     line_number: usize,
+    byte_offset: usize,
     matches: Matches,
+    /// Set once a directive says the matches of the buffer shouldn't be reported.
+    is_ignored: bool,
+    /// Set while reading the pieces of a long line, which share its number.
+    is_continued: bool,
+    headings: Option<Headings<'a>>,
+    /// The lines of the paragraph or record being read, with the number and offset of
+    /// its first and where the text of the record was read from.
+    record: Option<(usize, usize, String, Origins)>,
 }
 
 impl<'a> Collector<'a> {
     #[inline]
-    fn new(matcher: &'a Matcher) -> Self {
+    fn new(matcher: &'a Matcher, path: &'a Path) -> Self {
+        let headings = match matcher.enclosing {
+            Some(rules) if matcher.options.keep_lines && !matcher.options.is_passthru => rules.for_path(path).map(Headings::new),
+            _ => None,
+        };
         Self {
             matcher,
+            path,
             line_number: 0,
+            byte_offset: 0,
             matches: Matches::new(),
+            is_ignored: false,
+            is_continued: false,
+            headings,
+            record: None,
         }
     }
 
     #[inline]
     fn with_line_numbers(&self) -> bool {
-        self.matcher.with_line_numbers
+        self.matcher.options.with_line_numbers
     }
 
     #[inline]
     fn track_lines(&self) -> bool {
-        self.matcher.keep_lines
+        self.matcher.options.keep_lines
+    }
+
+    /// Whether a matching line is only counted, when the lines aren't kept and no filter
+    /// has to look at them.
+    #[inline]
+    fn is_count_only(&self) -> bool {
+        !self.track_lines() && self.matcher.filters.is_none_or(|filters| filters.is_empty())
     }
 
     #[inline]
@@ -281,22 +1393,190 @@ impl<'a> Collector<'a> {
     }
 
     #[inline]
-    fn handle(&mut self, line: &str) {
-        self.increment_line_number();
-        if let Some(m) = self.matcher.match_line(&line) {
-            self.add(m);
+    fn is_in_range(&self) -> bool {
+        self.matcher.options.line_range.is_none_or(|(start, end)| {
+            start <= self.line_number && self.line_number <= end
+        }) && self.matcher.options.head.is_none_or(|head| self.line_number <= head)
+    }
+
+    #[inline]
+    fn is_past_range(&self) -> bool {
+        self.matcher.options.line_range.is_some_and(|(_, end)| self.line_number >= end) ||
+            self.matcher.options.head.is_some_and(|head| self.line_number >= head)
+    }
+
+    /// Whether or not reading any further lines could change the result.
+    #[inline]
+    fn is_done(&self) -> bool {
+        // OPTIMIZATION
+        // If only the first match matters we can break out as soon as we find it. On a
+        // large directory, this saves about 25%.
+        // A directive further down the header could still ignore the first match
+        let is_past_header = !self.matcher.options.is_directives || self.line_number >= DIRECTIVE_HEADER_LINES;
+        (self.matcher.options.is_first_match_only && !self.track_lines() && self.matches.has_matches() && is_past_header) ||
+            self.is_ignored ||
+            self.is_past_range() ||
+            self.matcher.is_cancelled()
+    }
+
+    #[inline]
+    fn handle(&mut self, line: RawLine) {
+        let is_continued = self.is_continued;
+        self.is_continued = line.is_partial;
+        if !is_continued {
+            self.increment_line_number();
+            if line.is_skipped || line.is_partial {
+                self.long_line(line.is_skipped);
+            }
+        }
+        let line_offset = self.byte_offset;
+        // Offsets count the bytes as they were read, before any were replaced
+        self.byte_offset += line.len;
+        self.matches.replaced_bytes += line.replaced_bytes;
+        if line.is_skipped { return }
+        if !is_continued && self.matcher.is_ignored_file(self.line_number, &line.text) {
+            return self.ignore();
         }
+        if !self.is_in_range() { return }
+        // Lines are matched without their terminator so that `$` anchors to the end of them
+        let text = if line.is_partial { &line.text } else { self.matcher.trim_terminator(&line.text) };
+        if self.matcher.options.is_paragraph || self.matcher.record_separator.is_some() {
+            return self.add_to_record(text, line_offset, &line.origins);
+        }
+        self.handle_text(text, self.line_number, line_offset, &line.origins);
     }
 
-    fn add(&mut self, m: Line) {
+    /// Matches the text of a line or record that starts on the line number and offset,
+    /// and that was read from the bytes there as the origins say.
+    fn handle_text(&mut self, read: &str, number: usize, offset: usize, origins: &Origins) {
+        let normalized = self.matcher.options.normalization.map(|normalization| normalization.normalize_graphemes(read));
+        let text = normalized.as_ref().map_or(read, |(text, _)| &text[..]);
+        let is_ignored_line = self.matcher.options.is_directives && text.contains(IGNORE_LINE);
+        // OPTIMIZATION
+        // A line that's only counted is just tested, without copying it or finding where
+        // each of its matches is. With --files-with-matches the search then stops there.
+        if self.is_count_only() {
+            if !is_ignored_line && self.matcher.is_match(text) {
+                self.matches.increment();
+            }
+            return;
+        }
+        let is_kept = match self.matcher.match_line(text).filter(|_| !is_ignored_line) {
+            // A line with replaced bytes is kept whole, as it can't be read back by its spans
+            Some(m) => {
+                let m = match normalized {
+                    Some((_, ref normalized)) => m.denormalize(read, normalized),
+                    None => m,
+                };
+                self.add(m.offset_by(offset, origins), number, origins.changes.is_empty())
+            }
+            // Passthru shows every line, so it never has headings to read
+            None if self.matcher.options.is_passthru => return self.pass(Line::new(text.to_string(), Vec::new()), number),
+            None => false,
+        };
+        if let Some(ref mut headings) = self.headings {
+            headings.read(text, number, is_kept);
+        }
+    }
+
+    /// Adds the line to the record being read, first matching the record when the line
+    /// ends it. A separator starts the next record, while a blank line between
+    /// paragraphs belongs to neither.
+    fn add_to_record(&mut self, line: &str, offset: usize, origins: &Origins) {
+        match self.matcher.record_separator {
+            Some(separator) if separator.is_match(line) => self.finish(),
+            Some(_) => {}
+            None if line.trim().is_empty() => return self.finish(),
+            None => {}
+        }
+        match self.record {
+            Some((_, start, ref mut text, ref mut text_origins)) => {
+                // The lines are joined by a newline, whatever terminated them
+                let at = text.len();
+                let (raw_end, raw_at) = (text_origins.raw(at), offset - start);
+                if raw_at - raw_end != 1 {
+                    text_origins.push(at..at + 1, raw_end..raw_at);
+                }
+                text.push('\n');
+                text_origins.extend(origins, text.len(), raw_at);
+                text.push_str(line);
+            }
+            None => self.record = Some((self.line_number, offset, line.to_string(), origins.clone())),
+        }
+    }
+
+    /// Counts a line longer than the maximum, which is skipped or read in pieces.
+    fn long_line(&mut self, is_skipped: bool) {
+        self.matches.long_lines += 1;
+        let max = self.matcher.options.max_line_length.map_or(0, |(max, _)| max);
+        let location = match self.path.to_str() {
+            Some("") => format!("line {}", self.line_number),
+            _ => format!("line {} of {}", self.line_number, self.path.display()),
+        };
+        if is_skipped {
+            info!("Skipped {}: it's longer than {} bytes", location, max);
+        } else {
+            info!("Searching {} in pieces: it's longer than {} bytes", location, max);
+        }
+    }
+
+    /// Drops the matches found so far and stops reporting any more, for a file ignored
+    /// by a directive. The bytes that were replaced and the long lines are still counted.
+    fn ignore(&mut self) {
+        self.is_ignored = true;
+        self.record = None;
+        self.matches = Matches {
+            replaced_bytes: self.matches.replaced_bytes,
+            long_lines: self.matches.long_lines,
+            ..Matches::new()
+        };
+    }
+
+    /// Matches the record that was still being read when the buffer ended, if any.
+    fn finish(&mut self) {
+        if self.is_ignored {
+            return;
+        }
+        if let Some((number, offset, text, origins)) = self.record.take() {
+            self.handle_text(&text, number, offset, &origins);
+        }
+    }
+
+    /// Keeps a line that didn't match without counting it.
+    fn pass(&mut self, m: Line, number: usize) {
         if self.track_lines() {
             if self.with_line_numbers() {
-                self.matches.add(m.line_number(self.line_number));
+                self.matches.push(m.line_number(number));
+            } else {
+                self.matches.push(m);
+            }
+        }
+    }
+
+    /// Adds a matching line, returning whether it was kept to be shown. Its text is only
+    /// dropped for spans when it can be read back from them.
+    fn add(&mut self, m: Line, number: usize, is_readable: bool) -> bool {
+        let m = if self.with_line_numbers() { m.line_number(number) } else { m };
+        if !self.matcher.filters.is_none_or(|filters| filters.keep(&m, self.path)) {
+            if self.matcher.options.is_passthru {
+                self.pass(Line { captures: Vec::new(), number: None, ..m }, number);
+            }
+            return false;
+        }
+        if self.track_lines() {
+            if let Some(heading) = self.headings.as_mut().and_then(|headings| headings.enclosing(&m.value)) {
+                let heading = if self.with_line_numbers() { heading } else { Line { number: None, ..heading } };
+                self.matches.push(heading);
+            }
+            if self.matcher.is_spans_only() && is_readable && !m.captures.is_empty() {
+                self.matches.add(m.without_text());
             } else {
                 self.matches.add(m);
             }
+            true
         } else {
             self.matches.increment();
+            false
         }
     }
 }
@@ -305,7 +1585,7 @@ impl<'a> Collector<'a> {
 mod tests {
     use super::*;
     use regex::Regex;
-    use std::io::Cursor;
+    use std::io::{BufReader, Cursor};
 
     #[test]
     fn finding_matches_on_a_line() {
@@ -334,15 +1614,15 @@ mod tests {
             matches.add(Line::new(
                 "some line".to_string(),
                 vec![
-                    Capture { start: 0, end: 1, value: "some".to_string(), },
-                    Capture { start: 0, end: 1, value: "some".to_string(), },
+                    Capture { start: 0, end: 1, value: "some".to_string(), offset: 0, column: 1, rule: None },
+                    Capture { start: 0, end: 1, value: "some".to_string(), offset: 0, column: 1, rule: None },
                 ],
             ));
             matches.add(Line::new(
                 "some line".to_string(),
                 vec![
-                    Capture { start: 0, end: 1, value: "some".to_string(), },
-                    Capture { start: 0, end: 1, value: "some".to_string(), },
+                    Capture { start: 0, end: 1, value: "some".to_string(), offset: 0, column: 1, rule: None },
+                    Capture { start: 0, end: 1, value: "some".to_string(), offset: 0, column: 1, rule: None },
                 ],
             ));
             children.push(thread::spawn(move || count.add(&matches)))
@@ -362,7 +1642,7 @@ mod tests {
         matches.add(Line::new(
             "some line".to_string(),
             vec![
-                Capture { start: 0, end: 1, value: "some".to_string(), },
+                Capture { start: 0, end: 1, value: "some".to_string(), offset: 0, column: 1, rule: None },
             ],
         ));
         assert!(matches.has_matches());
@@ -375,7 +1655,7 @@ mod tests {
         matches.add(Line::new(
             "some line".to_string(),
             vec![
-                Capture { start: 0, end: 1, value: "some".to_string(), },
+                Capture { start: 0, end: 1, value: "some".to_string(), offset: 0, column: 1, rule: None },
             ],
         ));
         assert_eq!(matches.count, 1);
@@ -440,6 +1720,130 @@ mod tests {
         assert_eq!(matches.lines.len(), 0)
     }
 
+    #[test]
+    fn it_counts_every_line_without_keeping_them() {
+        let reg = Regex::new(r"test").unwrap();
+        let matches = Matcher::new(&reg).keep_lines(false).collect(&mut Cursor::new("test
+no
+test
+")).unwrap();
+        assert_eq!(matches.count, 2);
+        let matches = Matcher::new(&reg)
+            .keep_lines(false)
+            .first_match_only(true)
+            .collect(&mut Cursor::new("test
+no
+test
+"))
+            .unwrap();
+        assert_eq!(matches.count, 1);
+    }
+
+    #[test]
+    fn it_stops_at_the_first_match_of_binary_content() {
+        let reg = Regex::new(r"test").unwrap();
+        let contents = b"\x00\xff\xfe binary\n\x7fELF\x00 test \xc3\n".iter().chain(&[b'x'; 4096]).cloned().collect::<Vec<u8>>();
+        let mut buf_read = Cursor::new(&contents);
+        let matches = Matcher::new(&reg).keep_lines(false).first_match_only(true).collect(&mut buf_read).unwrap();
+        assert_eq!((matches.count, matches.replaced_bytes), (1, 3));
+        assert_eq!(buf_read.position(), 24);
+        let inverted = Matcher::new(&reg).keep_lines(false).invert_match(true).collect(&mut Cursor::new(&contents)).unwrap();
+        assert_eq!(inverted.count, 2);
+        let none = Matcher::new(&reg).keep_lines(false).max_captures_per_line(Some(0)).collect(&mut Cursor::new(&contents)).unwrap();
+        assert_eq!(none.count, 0);
+    }
+
+    #[test]
+    fn it_reads_the_text_of_spans_back_as_it_was_matched() {
+        let reg = Regex::new(r"id$|é").unwrap();
+        let contents = "id\r\nx; id; é; id\r\nid\r\n";
+        let options = SearchOptions::new().spans_only(true).crlf(true).max_line_length(Some((5, LongLines::Chunk)));
+        let matcher = Matcher::new(&reg).options(options);
+        let mut matches = matcher.collect(&mut Cursor::new(contents)).unwrap();
+        assert!(matches.lines.iter().all(|line| line.value.is_empty()));
+        let expected = matcher.options(SearchOptions::new().crlf(true).max_line_length(Some((5, LongLines::Chunk))));
+        let mut matches_text = expected.collect(&mut Cursor::new(contents)).unwrap();
+        expected.read_text(&mut matches_text, &mut Cursor::new(contents)).unwrap();
+        expected.read_text(&mut matches, &mut Cursor::new(contents)).unwrap();
+        let text = |matches: &Matches| matches.lines.iter()
+            .map(|line| (line.number, line.value.clone(), line.captures.iter().map(|c| c.value.clone()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(text(&matches), text(&matches_text));
+        // The pieces of the long line are read back the way they were cut
+        assert_eq!(text(&matches)[2], (Some(2), "; é;".to_string(), vec!["é".to_string()]));
+    }
+
+    #[test]
+    fn it_numbers_and_counts_the_lines_an_inverted_search_keeps() {
+        let reg = Regex::new(r"test").unwrap();
+        let contents = "test\na\ntest\nb\nc\ntest\nd\n";
+        let numbers = |matcher: Matcher| {
+            let matches = matcher.invert_match(true).collect(&mut Cursor::new(contents)).unwrap();
+            (matches.count, matches.lines.iter().map(|line| line.number.unwrap()).collect::<Vec<_>>())
+        };
+        assert_eq!(numbers(Matcher::new(&reg)), (4, vec![2, 4, 5, 7]));
+        assert_eq!(numbers(Matcher::new(&reg).line_range(Some((3, 6)))), (2, vec![4, 5]));
+        assert_eq!(numbers(Matcher::new(&reg).head(Some(4))), (2, vec![2, 4]));
+        assert_eq!(numbers(Matcher::new(&reg).tail(Some(3))), (2, vec![5, 7]));
+        assert_eq!(numbers(Matcher::new(&reg).keep_lines(false)), (4, vec![]));
+        let stats = Stats::new();
+        stats.add(&Matcher::new(&reg).invert_match(true).collect(&mut Cursor::new(contents)).unwrap());
+        assert_eq!((stats.total(), stats.lines(), stats.captures()), (1, 4, 0));
+    }
+
+    #[test]
+    fn it_only_matches_lines_within_the_range() {
+        let reg = Regex::new(r"test").unwrap();
+        let mut buf_read = Cursor::new("test\ntest\nnot\ntest\ntest");
+        let matches = Matcher::new(&reg)
+            .line_range(Some((2, 4)))
+            .collect(&mut buf_read)
+            .unwrap();
+        assert_eq!(matches.count, 2);
+        assert_eq!(matches.lines[0].number, Some(2));
+        assert_eq!(matches.lines[1].number, Some(4));
+    }
+
+    #[test]
+    fn it_stops_reading_after_the_end_of_the_range() {
+        let reg = Regex::new(r"test").unwrap();
+        let mut buf_read = Cursor::new("test\ntest\nnot\ntest");
+        Matcher::new(&reg)
+            .line_range(Some((1, 2)))
+            .collect(&mut buf_read)
+            .unwrap();
+        assert_eq!(buf_read.position(), "test\ntest\n".len() as u64);
+    }
+
+    #[test]
+    fn it_only_searches_the_head() {
+        let reg = Regex::new(r"test").unwrap();
+        let mut buf_read = Cursor::new("test\nnot\ntest\ntest");
+        let matches = Matcher::new(&reg).head(Some(2)).collect(&mut buf_read).unwrap();
+        assert_eq!(matches.count, 1);
+        assert_eq!(matches.lines[0].number, Some(1));
+        assert_eq!(buf_read.position(), "test\nnot\n".len() as u64);
+    }
+
+    #[test]
+    fn it_only_searches_the_tail() {
+        let reg = Regex::new(r"test").unwrap();
+        let mut buf_read = Cursor::new("test\nnot\ntest\ntest");
+        let matches = Matcher::new(&reg).tail(Some(3)).collect(&mut buf_read).unwrap();
+        assert_eq!(matches.count, 2);
+        assert_eq!(matches.lines[0].number, Some(3));
+        assert_eq!(matches.lines[1].number, Some(4));
+    }
+
+    #[test]
+    fn it_handles_a_tail_longer_than_the_buffer() {
+        let reg = Regex::new(r"test").unwrap();
+        let mut buf_read = Cursor::new("test\nnot");
+        let matches = Matcher::new(&reg).tail(Some(10)).collect(&mut buf_read).unwrap();
+        assert_eq!(matches.count, 1);
+        assert_eq!(matches.lines[0].number, Some(1));
+    }
+
     #[test]
     fn inverts_the_matches() {
         let reg = Regex::new(r"test").unwrap();
@@ -453,4 +1857,302 @@ mod tests {
         assert_eq!(matches.lines[0].number, Some(2));
         assert_eq!(matches.lines[0].captures.len(), 0);
     }
+
+    #[test]
+    fn it_splits_lines_on_a_custom_terminator() {
+        let reg = Regex::new(r"^b").unwrap();
+        let mut buf_read = Cursor::new("a\nb\0b\0c");
+        let matches = Matcher::new(&reg).line_terminator(b'\0').collect(&mut buf_read).unwrap();
+        assert_eq!(matches.count, 1);
+        assert_eq!(matches.lines[0].number, Some(2));
+        assert_eq!(matches.lines[0].value, "b");
+    }
+
+    #[test]
+    fn it_only_strips_carriage_returns_in_crlf_mode() {
+        let reg = Regex::new(r"test$").unwrap();
+        let matches = Matcher::new(&reg).collect(&mut Cursor::new("test\r\n")).unwrap();
+        assert_eq!(matches.count, 0);
+        let matches = Matcher::new(&reg).crlf(true).collect(&mut Cursor::new("test\r\n")).unwrap();
+        assert_eq!(matches.count, 1);
+    }
+
+    #[test]
+    fn it_passes_through_lines_that_do_not_match() {
+        let reg = Regex::new(r"test").unwrap();
+        let matches = Matcher::new(&reg)
+            .passthru(true)
+            .collect(&mut Cursor::new("not\ntest\nnot"))
+            .unwrap();
+        assert_eq!(matches.count, 1);
+        let numbers: Vec<_> = matches.lines.iter().map(|l| l.number).collect();
+        assert_eq!(numbers, vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(matches.lines[1].captures.len(), 1);
+    }
+
+    #[test]
+    fn it_streams_each_line() {
+        let reg = Regex::new(r"test").unwrap();
+        let mut values = Vec::new();
+        let count = Matcher::new(&reg)
+            .tail(Some(2))
+            .for_each_line(&mut Cursor::new("test\nnot\ntest"), |l| values.push(l.value))
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(values, vec!["test".to_string()]);
+    }
+
+    #[test]
+    fn it_limits_the_captures_per_line() {
+        let reg = Regex::new(r"test").unwrap();
+        let line = "test test test";
+        assert_eq!(Matcher::new(&reg).match_line(line).unwrap().captures.len(), 3);
+        let m = Matcher::new(&reg).max_captures_per_line(Some(2)).match_line(line).unwrap();
+        assert_eq!(m.captures.len(), 2);
+        let m = Matcher::new(&reg).max_captures_per_line(Some(1)).match_line(line).unwrap();
+        assert_eq!(m.captures.len(), 1);
+        assert_eq!(m.captures[0].start, 0);
+        let m = Matcher::new(&reg).max_captures_per_line(Some(0)).match_line(line);
+        assert!(m.is_none());
+    }
+
+    #[test]
+    fn it_reports_offsets_within_the_buffer() {
+        let reg = Regex::new(r"test").unwrap();
+        let matches = Matcher::new(&reg).collect(&mut Cursor::new("a test\nnot\ntest")).unwrap();
+        assert_eq!(matches.lines[0].captures[0].offset, 2);
+        assert_eq!(matches.lines[1].captures[0].start, 0);
+        assert_eq!(matches.lines[1].captures[0].offset, 11);
+
+        let matches = Matcher::new(&reg).tail(Some(1)).collect(&mut Cursor::new("a test\nnot\ntest")).unwrap();
+        assert_eq!(matches.lines[0].captures[0].offset, 11);
+    }
+
+    #[test]
+    fn it_counts_columns_in_graphemes() {
+        let reg = Regex::new(r"test").unwrap();
+        // The e with an acute accent is two code points and three bytes
+        let m = Matcher::new(&reg).match_line("cafe\u{301} test").unwrap();
+        assert_eq!(m.captures[0].start, 7);
+        assert_eq!(m.captures[0].column, 6);
+    }
+
+    #[test]
+    fn it_normalizes_lines_before_matching() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let reg = Regex::new(composed).unwrap();
+        let matches = Matcher::new(&reg).collect(&mut Cursor::new(decomposed)).unwrap();
+        assert_eq!(matches.count, 0);
+
+        let matches = Matcher::new(&reg)
+            .normalization(Some(Normalization::Nfc))
+            .collect(&mut Cursor::new(decomposed))
+            .unwrap();
+        assert_eq!(matches.count, 1);
+        assert_eq!(matches.lines[0].value, composed);
+
+        let reg = Regex::new(&Normalization::Nfd.normalize(composed)).unwrap();
+        let matches = Matcher::new(&reg)
+            .normalization(Some(Normalization::Nfd))
+            .collect(&mut Cursor::new(composed))
+            .unwrap();
+        assert_eq!(matches.count, 1);
+    }
+
+    #[test]
+    fn it_reports_captures_of_normalized_lines_where_they_were_read() {
+        let reg = Regex::new(r"test").unwrap();
+        let capture = |normalization, text: &[u8]| {
+            let matcher = Matcher::new(&reg).normalization(Some(normalization));
+            let c = &matcher.collect(&mut Cursor::new(text)).unwrap().lines[0].captures[0];
+            (c.start, c.offset, c.column)
+        };
+        // The decomposed e with an acute accent is a byte longer than the composed one
+        assert_eq!(capture(Normalization::Nfc, "cafe\u{301} test".as_bytes()), (6, 7, 6));
+        assert_eq!(capture(Normalization::Nfd, "caf\u{e9} test".as_bytes()), (7, 6, 6));
+        assert_eq!(capture(Normalization::Nfc, b"e\xcc\x81\xff test"), (6, 5, 4));
+    }
+
+    #[test]
+    fn it_drops_lines_rejected_by_the_filters() {
+        let reg = Regex::new(r"test").unwrap();
+        let filters = Filters::new().with(|_: &Line, path: &Path| path.ends_with("keep.rs"));
+        let matcher = Matcher::new(&reg).filters(&filters);
+        let matches = matcher.collect_path(&mut Cursor::new("test"), Path::new("drop.rs")).unwrap();
+        assert_eq!(matches.count, 0);
+        let matches = matcher.collect_path(&mut Cursor::new("test"), Path::new("src/keep.rs")).unwrap();
+        assert_eq!(matches.count, 1);
+        let matches = matcher.keep_lines(false).collect_path(&mut Cursor::new("test"), Path::new("drop.rs")).unwrap();
+        assert_eq!(matches.count, 0);
+    }
+
+    #[test]
+    fn it_matches_the_last_paragraph_without_a_trailing_blank_line() {
+        let reg = Regex::new(r"b|c").unwrap();
+        let mut lines = Vec::new();
+        let count = Matcher::new(&reg)
+            .paragraph(true)
+            .for_each_line(&mut Cursor::new("a\nb\n\n\n\nc\nd"), |line| lines.push(line))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(lines[0].number, Some(1));
+        assert_eq!(lines[0].value, "a\nb");
+        assert_eq!(lines[1].number, Some(6));
+        assert_eq!(lines[1].value, "c\nd");
+        assert_eq!(lines[1].captures[0].offset, 7);
+    }
+
+    #[test]
+    fn it_keeps_lines_before_the_first_separator_as_a_record() {
+        let reg = Regex::new(r"a|c").unwrap();
+        let separator = Regex::new(r"^#").unwrap();
+        let matches = Matcher::new(&reg)
+            .record_separator(Some(&separator))
+            .collect(&mut Cursor::new("a\nb\n# 1\nc\n# 2\nd\n"))
+            .unwrap();
+        assert_eq!(matches.count, 2);
+        assert_eq!(matches.lines[0].number, Some(1));
+        assert_eq!(matches.lines[0].value, "a\nb");
+        assert_eq!(matches.lines[1].number, Some(3));
+        assert_eq!(matches.lines[1].value, "# 1\nc");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_round_trips_matches_through_serde() {
+        let reg = Regex::new(r"b+").unwrap();
+        let matches = Matcher::new(&reg)
+            .collect_path(&mut Cursor::new("abbc\nd\nb"), Path::new("a.txt"))
+            .unwrap();
+        let json = ::serde_json::to_string(&matches).unwrap();
+        let read: Matches = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(read.path(), Some(Path::new("a.txt")));
+        assert_eq!(read.count(), 2);
+        assert_eq!(read.lines()[0].value, "abbc");
+        assert_eq!((read.lines()[0].captures[0].start, read.lines()[0].captures[0].end), (1, 3));
+        assert_eq!(read.lines()[1].number, Some(3));
+    }
+
+    #[test]
+    fn it_honors_ignore_directives_only_when_asked_to() {
+        let reg = Regex::new(r"TODO").unwrap();
+        let text = "TODO a\nTODO b grusp:ignore-line\nok\n";
+        assert_eq!(Matcher::new(&reg).collect(&mut Cursor::new(text)).unwrap().count(), 2);
+        let matcher = Matcher::new(&reg).directives(true);
+        let matches = matcher.collect(&mut Cursor::new(text)).unwrap();
+        assert_eq!(matches.count(), 1);
+        let inverted = matcher.invert_match(true).collect(&mut Cursor::new(text)).unwrap();
+        assert_eq!(inverted.lines().iter().map(|line| line.number).collect::<Vec<_>>(), vec![Some(3)]);
+        // Only the header of a file is read for the file directive, even when just
+        // finding out whether it matched
+        let header = "TODO\n\n\n\n# grusp:ignore-file\nTODO\n";
+        let first_only = Matcher::new(&reg).directives(true).keep_lines(false).first_match_only(true);
+        assert_eq!(first_only.collect(&mut Cursor::new(header)).unwrap().count(), 0);
+        assert_eq!(Matcher::new(&reg).directives(true).tail(Some(1)).collect(&mut Cursor::new(header)).unwrap().count(), 0);
+        let too_late = "\n\n\n\n\n# grusp:ignore-file\nTODO\n";
+        assert_eq!(first_only.collect(&mut Cursor::new(too_late)).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn it_keeps_reading_past_invalid_utf8() {
+        let reg = Regex::new(r"ok").unwrap();
+        let text: &[u8] = b"\xc3(\nbad \xff ok\nok\n\xe2\x82";
+        let matches = Matcher::new(&reg).collect(&mut Cursor::new(text)).unwrap();
+        assert_eq!(matches.count(), 2);
+        assert_eq!(matches.replaced_bytes(), 4);
+        assert_eq!(matches.lines()[0].value, "bad \u{FFFD} ok");
+        // Offsets count the bytes as they were read
+        assert_eq!(matches.lines()[1].number, Some(3));
+        assert_eq!(matches.lines()[1].captures[0].offset, 12);
+        let tail = Matcher::new(&reg).tail(Some(2)).collect(&mut Cursor::new(text)).unwrap();
+        assert_eq!(tail.count(), 1);
+        assert_eq!(tail.replaced_bytes(), 4);
+        assert_eq!(tail.lines()[0].captures[0].offset, 12);
+    }
+
+    #[test]
+    fn it_reports_offsets_of_the_bytes_before_they_were_replaced() {
+        let reg = Regex::new(r"ok").unwrap();
+        // Each bad byte is replaced by a character of three bytes
+        let m = &Matcher::new(&reg).collect(&mut Cursor::new(&b"a \xff\xfe ok\n"[..])).unwrap().lines[0];
+        assert_eq!((m.captures[0].start, m.captures[0].offset, m.captures[0].column), (9, 5, 6));
+        let text: &[u8] = b"one\r\ntwo \xff ok\r\n";
+        let m = &Matcher::new(&reg).paragraph(true).crlf(true).collect(&mut Cursor::new(text)).unwrap().lines[0];
+        assert_eq!((m.captures[0].start, m.captures[0].offset), (12, 11));
+        // The text of a line with replaced bytes can't be read back by its spans
+        let options = SearchOptions::new().spans_only(true);
+        let matches = Matcher::new(&reg).options(options).collect(&mut Cursor::new(&b"a \xff ok\n"[..])).unwrap();
+        assert_eq!(matches.lines[0].value, "a \u{FFFD} ok");
+    }
+
+    #[test]
+    fn it_collects_the_same_matches_in_chunks() {
+        let path = ::std::env::temp_dir().join("grusp-chunked-test.log");
+        let contents: String = (0..200)
+            .map(|i| if i % 7 == 0 { format!("{} error {}\n", i, "x".repeat(i % 13)) } else { format!("{} ok\n", i) })
+            .collect();
+        ::std::fs::write(&path, contents.trim_end()).unwrap();
+        let reg = Regex::new(r"error|\d+9\b").unwrap();
+        let matcher = Matcher::new(&reg);
+        let expected = matcher.collect(&mut Cursor::new(contents.trim_end())).unwrap();
+        for &chunk_size in &[1, 5, 64, 1000, 10000] {
+            let matches = matcher.collect_chunked(&path, chunk_size).unwrap();
+            assert_eq!(matches.count, expected.count);
+            let spans = |m: &Matches| -> Vec<(Option<usize>, String, Vec<usize>)> {
+                m.lines.iter().map(|l| (l.number, l.value.clone(), l.captures.iter().map(|c| c.offset).collect())).collect()
+            };
+            assert_eq!(spans(&matches), spans(&expected), "chunks of {} bytes", chunk_size);
+        }
+    }
+
+    #[test]
+    fn it_skips_or_chunks_lines_longer_than_the_max() {
+        let reg = Regex::new(r"ok").unwrap();
+        let text = format!("ok\n{}ok\né ok\nok", "x".repeat(20));
+        // A small buffer makes the long line span several reads
+        let read = |matcher: &Matcher| matcher.collect(&mut BufReader::with_capacity(3, text.as_bytes())).unwrap();
+        let numbers = |m: &Matches| m.lines.iter().map(|l| (l.number, l.value.clone())).collect::<Vec<_>>();
+        let skip = read(&Matcher::new(&reg).max_line_length(Some((8, LongLines::Skip))));
+        assert_eq!(numbers(&skip), vec![(Some(1), "ok".to_string()), (Some(3), "é ok".to_string()), (Some(4), "ok".to_string())]);
+        assert_eq!(skip.long_lines(), 1);
+        assert_eq!(skip.lines[2].captures[0].offset, 32);
+        let chunk = read(&Matcher::new(&reg).max_line_length(Some((8, LongLines::Chunk))));
+        assert_eq!(chunk.lines[1].number, Some(2));
+        assert_eq!(chunk.lines[1].value, "xxxxok");
+        assert_eq!(chunk.lines[1].captures[0].offset, 23);
+        assert_eq!(chunk.lines[2].number, Some(3));
+        assert_eq!(chunk.long_lines(), 1);
+        // A character isn't cut in two at the maximum
+        let split = Matcher::new(&reg).max_line_length(Some((1, LongLines::Chunk)));
+        let matches = split.collect(&mut Cursor::new("é ok")).unwrap();
+        assert_eq!(matches.replaced_bytes(), 0);
+        // The tail counts lines, not pieces
+        let tail = Matcher::new(&reg).max_line_length(Some((8, LongLines::Chunk))).tail(Some(3));
+        let matches = tail.collect(&mut Cursor::new(text.as_str())).unwrap();
+        assert_eq!(numbers(&matches).iter().map(|line| line.0).collect::<Vec<_>>(), vec![Some(2), Some(3), Some(4)]);
+        assert_eq!(matches.long_lines(), 1);
+    }
+
+    /// Reads its text and then fails, like a file on a disk that goes away partway.
+    struct FailingReader(Cursor<&'static str>);
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => Err(std::io::Error::other("disk went away")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn it_errors_when_a_read_fails_partway() {
+        let reg = Regex::new("ok").unwrap();
+        for matcher in &[Matcher::new(&reg), Matcher::new(&reg).max_line_length(Some((8, LongLines::Skip)))] {
+            let mut reader = BufReader::with_capacity(4, FailingReader(Cursor::new("ok\nstill ok\nno newline ")));
+            let error = matcher.collect(&mut reader).unwrap_err();
+            assert_eq!(error.to_string(), "disk went away");
+        }
+    }
 }
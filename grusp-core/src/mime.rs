@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str;
+
+/// How many bytes from the start of a file are read to tell its content type.
+const SNIFF_LEN: u64 = 1024;
+
+/// The content types recognized by the magic numbers their files start with.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"II*\x00", "image/tiff"),
+    (b"MM\x00*", "image/tiff"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"PK\x05\x06", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (b"\x00asm", "application/wasm"),
+    (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Returns the content type of a file from the bytes it starts with. Files with a known
+/// magic number get the type of it, other files are `text/plain` when they read as text
+/// and `application/octet-stream` otherwise. XML and HTML are told apart from plain text.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::detect_mime;
+/// assert_eq!(detect_mime(b"\x89PNG\r\n\x1a\n\x00\x00"), "image/png");
+/// assert_eq!(detect_mime(b"<?xml version=\"1.0\"?>"), "text/xml");
+/// assert_eq!(detect_mime(b"fn main() {}\n"), "text/plain");
+/// assert_eq!(detect_mime(b"\x00\x01\x02"), "application/octet-stream");
+/// ```
+pub fn detect_mime(start: &[u8]) -> &'static str {
+    if let Some(&(_, mime)) = MAGIC.iter().find(|&&(magic, _)| start.starts_with(magic)) {
+        return mime;
+    }
+    if start.len() >= 12 && &start[..4] == b"RIFF" {
+        match &start[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            b"AVI " => return "video/x-msvideo",
+            _ => {}
+        }
+    }
+    if start.len() >= 12 && &start[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+    if start.starts_with(b"\xff\xfe") || start.starts_with(b"\xfe\xff") {
+        return "text/plain";
+    }
+    if !is_text(start) {
+        return "application/octet-stream";
+    }
+    let start = start.strip_prefix(b"\xef\xbb\xbf").unwrap_or(start);
+    let head = String::from_utf8_lossy(&start[..start.len().min(64)]).trim_start().to_lowercase();
+    if head.starts_with("<?xml") {
+        "text/xml"
+    } else if head.starts_with("<!doctype html") || head.starts_with("<html") {
+        "text/html"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Whether the bytes are UTF-8 without any NUL bytes. A character cut off by the end of
+/// the bytes that were read doesn't count against them.
+fn is_text(start: &[u8]) -> bool {
+    if start.contains(&0) {
+        return false;
+    }
+    match str::from_utf8(start) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// A filter on the content types of files, told from the bytes they start with rather
+/// than their names. Each type is either exact, such as `image/png`, or takes every
+/// subtype, such as `text/*`.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::MimeFilter;
+/// let filter = MimeFilter::new(&["text/*", "application/json"]).unwrap();
+/// assert!(filter.matches("text/plain"));
+/// assert!(filter.matches("application/JSON"));
+/// assert!(!filter.matches("image/png"));
+/// assert!(MimeFilter::new(&["text"]).is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeFilter {
+    types: Vec<String>,
+}
+
+impl MimeFilter {
+    /// Creates a filter that keeps files of any of the types, or None when one of them
+    /// isn't a `type/subtype`.
+    pub fn new<S: AsRef<str>>(types: &[S]) -> Option<Self> {
+        let types: Vec<String> = types.iter().map(|mime| mime.as_ref().trim().to_lowercase()).collect();
+        let is_valid = |mime: &String| match mime.split_once('/') {
+            Some((kind, subtype)) => !kind.is_empty() && !subtype.is_empty() && !subtype.contains('/'),
+            None => mime == "*",
+        };
+        if types.is_empty() || !types.iter().all(is_valid) {
+            return None;
+        }
+        Some(MimeFilter { types })
+    }
+
+    /// Whether the content type is one of the types of the filter.
+    pub fn matches(&self, mime: &str) -> bool {
+        let mime = mime.to_lowercase();
+        self.types.iter().any(|filter| match filter.strip_suffix("/*") {
+            Some(kind) => mime.split('/').next() == Some(kind) || kind == "*",
+            None => *filter == mime || filter == "*",
+        })
+    }
+
+    /// Reads the start of the file and returns whether its content type is one of the
+    /// types of the filter.
+    pub fn is_wanted(&self, path: &Path) -> io::Result<bool> {
+        let mut start = Vec::new();
+        File::open(path)?.take(SNIFF_LEN).read_to_end(&mut start)?;
+        Ok(self.matches(detect_mime(&start)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_detects_text_cut_off_mid_character() {
+        let text = "naïve".as_bytes();
+        assert_eq!(detect_mime(&text[..3]), "text/plain");
+        assert_eq!(detect_mime(b"\xef\xbb\xbf<!DOCTYPE html>"), "text/html");
+        assert_eq!(detect_mime(b"caf\xe9 latin-1"), "application/octet-stream");
+        assert_eq!(detect_mime(b"RIFF\x00\x00\x00\x00WEBPVP8 "), "image/webp");
+        assert_eq!(detect_mime(b""), "text/plain");
+    }
+
+    #[test]
+    fn it_matches_every_subtype_of_a_wildcard() {
+        let filter = MimeFilter::new(&["image/*"]).unwrap();
+        assert!(filter.matches("image/png"));
+        assert!(!filter.matches("text/plain"));
+        assert!(MimeFilter::new(&["*/*"]).unwrap().matches("application/zip"));
+        assert!(MimeFilter::new(&["text/plain/x"]).is_none());
+        assert!(MimeFilter::new::<&str>(&[]).is_none());
+    }
+}
@@ -0,0 +1,437 @@
+use matcher::{LongLines, Normalization};
+
+/// The options of a search that don't borrow anything, such as whether it's inverted or
+/// which lines it's limited to, gathered so they can be built once and handed to each
+/// `Matcher` with `Matcher::options`. Whether the search ignores case is part of its
+/// regex.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{Matcher, SearchOptions};
+///
+/// let reg = regex::Regex::new(r"(?i)test").unwrap();
+/// let options = SearchOptions::new().line_terminator(b'\0').max_captures_per_line(Some(1));
+/// let matches = Matcher::new(&reg).options(options).collect(&mut "Test test\0not\0".as_bytes()).unwrap();
+/// assert_eq!(matches.count(), 1);
+/// assert_eq!(matches.lines()[0].captures.len(), 1);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    pub(crate) with_line_numbers: bool,
+    pub(crate) keep_lines: bool,
+    pub(crate) is_first_match_only: bool,
+    pub(crate) is_directives: bool,
+    pub(crate) is_inverted: bool,
+    pub(crate) line_range: Option<(usize, usize)>,
+    pub(crate) head: Option<usize>,
+    pub(crate) tail: Option<usize>,
+    pub(crate) terminator: u8,
+    pub(crate) is_crlf: bool,
+    pub(crate) is_passthru: bool,
+    pub(crate) is_paragraph: bool,
+    pub(crate) max_captures_per_line: Option<usize>,
+    pub(crate) normalization: Option<Normalization>,
+    pub(crate) max_line_length: Option<(usize, LongLines)>,
+    pub(crate) is_spans_only: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            with_line_numbers: true,
+            keep_lines: true,
+            is_first_match_only: false,
+            is_directives: false,
+            is_inverted: false,
+            line_range: None,
+            head: None,
+            tail: None,
+            terminator: b'\n',
+            is_crlf: false,
+            is_passthru: false,
+            is_paragraph: false,
+            max_captures_per_line: None,
+            normalization: None,
+            max_line_length: None,
+            is_spans_only: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Creates the options of a search that keeps every matching line with its number.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Toggle the tracking of line numbers. If set to false, the returned matches
+    /// will not include the line numbers. Useful when the buffer is not actually a file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().with_line_numbers(false)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.lines()[0].number, None);
+    /// # }
+    /// ```
+    pub fn with_line_numbers(mut self, w: bool) -> Self {
+        self.with_line_numbers = w;
+        self
+    }
+
+    /// Toggles the tracking of lines/captures
+    pub fn keep_lines(mut self, track_lines: bool) -> Self {
+        self.keep_lines = track_lines;
+        self
+    }
+
+    /// Stops reading a buffer once it has a match, when all that matters is whether it
+    /// matched, such as when only the names of the files that matched are printed. The
+    /// count is then at most one. Lines are still read to the end when they're kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::Cursor;
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("a test\nnot\ntest\n");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().keep_lines(false).first_match_only(true)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// assert_eq!(buf_read.position(), 7);
+    /// # }
+    /// ```
+    pub fn first_match_only(mut self, is_first_match_only: bool) -> Self {
+        self.is_first_match_only = is_first_match_only;
+        self
+    }
+
+    /// Honors the directives written in comments, so exceptions to a policy can be kept
+    /// beside the code they're about. Matches on a line with `grusp:ignore-line` aren't
+    /// reported, and nothing in a file with `grusp:ignore-file` in one of its first five
+    /// lines is. When lines are handled as they're read, the matches before a file's
+    /// directive have already been handled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    ///
+    /// let reg = regex::Regex::new(r"unwrap\(\)").unwrap();
+    /// let matcher = Matcher::new(&reg).options(SearchOptions::new().directives(true));
+    /// let code = "a.unwrap();\nb.unwrap(); // grusp:ignore-line\n";
+    /// assert_eq!(matcher.collect(&mut code.as_bytes()).unwrap().count(), 1);
+    /// let ignored = "// grusp:ignore-file generated code\na.unwrap();\n";
+    /// assert_eq!(matcher.collect(&mut ignored.as_bytes()).unwrap().count(), 0);
+    /// # }
+    /// ```
+    pub fn directives(mut self, is_directives: bool) -> Self {
+        self.is_directives = is_directives;
+        self
+    }
+
+    /// Can tell the matcher to match on non-matching lines instead of matching lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().invert_match(true)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.lines().len(), 1);
+    /// assert_eq!(matches.lines()[0].number, Some(2));
+    /// assert_eq!(matches.lines()[0].captures.len(), 0);
+    /// # }
+    /// ```
+    pub fn invert_match(mut self, is_inverted: bool) -> Self {
+        self.is_inverted = is_inverted;
+        self
+    }
+
+    /// Restricts matching to an inclusive range of line numbers, counting from one. Lines
+    /// outside of the range are never matched and reading stops as soon as the end of
+    /// the range has been passed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest\ntest");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().line_range(Some((2, 3)))).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// assert_eq!(matches.lines()[0].number, Some(3));
+    /// # }
+    /// ```
+    pub fn line_range(mut self, line_range: Option<(usize, usize)>) -> Self {
+        self.line_range = line_range;
+        self
+    }
+
+    /// Only searches the first `head` lines of the buffer and stops reading after that.
+    pub fn head(mut self, head: Option<usize>) -> Self {
+        self.head = head;
+        self
+    }
+
+    /// Only searches the last `tail` lines of the buffer. The whole buffer still has to
+    /// be read, but only the trailing lines are kept around to be matched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().tail(Some(2))).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// assert_eq!(matches.lines()[0].number, Some(3));
+    /// # }
+    /// ```
+    pub fn tail(mut self, tail: Option<usize>) -> Self {
+        self.tail = tail;
+        self
+    }
+
+    /// Sets the byte that separates lines, which is a newline by default. Use a NUL byte
+    /// to search records such as the output of `find -print0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"^test$").unwrap();
+    /// let mut buf_read = Cursor::new("test\0not\0test\0");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().line_terminator(b'\0')).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 2);
+    /// assert_eq!(matches.lines()[1].number, Some(3));
+    /// # }
+    /// ```
+    pub fn line_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Strips a carriage return from the end of every line before matching, so that
+    /// `$` matches at the end of lines in files with CRLF line endings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test$").unwrap();
+    /// let mut buf_read = Cursor::new("test\r\nnot\r\n");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().crlf(true)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// assert_eq!(matches.lines()[0].value, "test");
+    /// # }
+    /// ```
+    pub fn crlf(mut self, is_crlf: bool) -> Self {
+        self.is_crlf = is_crlf;
+        self
+    }
+
+    /// Keeps every line, not just the matching ones, so the whole buffer can be displayed
+    /// with its matches highlighted. Only the matching lines are counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let mut buf_read = Cursor::new("test\nnot\ntest");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().passthru(true)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 2);
+    /// assert_eq!(matches.lines().len(), 3);
+    /// assert_eq!(matches.lines()[1].captures.len(), 0);
+    /// # }
+    /// ```
+    pub fn passthru(mut self, is_passthru: bool) -> Self {
+        self.is_passthru = is_passthru;
+        self
+    }
+
+    /// Matches paragraphs, which are separated by blank lines, instead of lines. A
+    /// paragraph is kept as a single line holding all of its lines, numbered by its
+    /// first line, so a stack trace or a block of YAML is kept whole when any part of it
+    /// matches. The blank lines between paragraphs are never matched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"NullPointer").unwrap();
+    /// let mut buf_read = Cursor::new("ok\n\nError\n  at NullPointer\n  at main\n\nok\n");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().paragraph(true)).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// assert_eq!(matches.lines()[0].number, Some(3));
+    /// assert_eq!(matches.lines()[0].value, "Error\n  at NullPointer\n  at main");
+    /// # }
+    /// ```
+    pub fn paragraph(mut self, is_paragraph: bool) -> Self {
+        self.is_paragraph = is_paragraph;
+        self
+    }
+
+    /// Stops scanning a line once it has this many captures. A line is found to match
+    /// on its first capture, so use `Some(1)` when the rest aren't needed, such as when
+    /// counting lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    ///
+    /// let reg = regex::Regex::new(r"test").unwrap();
+    /// let line = Matcher::new(&reg).options(SearchOptions::new().max_captures_per_line(Some(1))).match_line("test test").unwrap();
+    /// assert_eq!(line.captures.len(), 1);
+    /// # }
+    /// ```
+    pub fn max_captures_per_line(mut self, max: Option<usize>) -> Self {
+        self.max_captures_per_line = max;
+        self
+    }
+
+    /// Normalizes every line before it's matched. The regex should be built from a pattern
+    /// normalized to the same form. Matched lines hold the normalized text, so capture
+    /// offsets are into the normalized line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, Normalization, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let pattern = Normalization::Nfc.normalize("caf\u{e9}");
+    /// let reg = regex::Regex::new(&pattern).unwrap();
+    /// let mut buf_read = Cursor::new("cafe\u{301}");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().normalization(Some(Normalization::Nfc))).collect(&mut buf_read).unwrap();
+    /// assert_eq!(matches.count(), 1);
+    /// # }
+    /// ```
+    pub fn normalization(mut self, normalization: Option<Normalization>) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Limits how many bytes of a line are read at once, not counting its terminator,
+    /// so a file without line breaks, such as minified JSON, doesn't have to fit in
+    /// memory. Longer lines are skipped or searched in pieces. Either way they still
+    /// count towards the line numbers, and `Matches::long_lines` tells how many there
+    /// were.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{LongLines, Matcher, SearchOptions};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// let reg = regex::Regex::new(r"id").unwrap();
+    /// let matcher = Matcher::new(&reg).options(SearchOptions::new().max_line_length(Some((5, LongLines::Chunk))));
+    /// let matches = matcher.collect(&mut Cursor::new("id; id; id\nid")).unwrap();
+    /// let lines: Vec<_> = matches.lines().iter().map(|line| (line.number, line.value.as_str())).collect();
+    /// // The second id is cut in two, so it isn't found
+    /// assert_eq!(lines, vec![(Some(1), "id; i"), (Some(1), "d; id"), (Some(2), "id")]);
+    /// # }
+    /// ```
+    pub fn max_line_length(mut self, max_line_length: Option<(usize, LongLines)>) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /// Keeps just the number of each matching line and the spans of its captures, leaving
+    /// the text of the line and of its captures empty, so a search with many matches
+    /// doesn't hold on to a copy of every matching line when only the counts or the spans
+    /// are needed. `Matcher::read_text` reads the text back from the buffer when it's
+    /// needed after all, such as to display the lines of a file.
+    ///
+    /// Lines without captures, such as inverted or enclosing lines, keep their text, as
+    /// do paragraphs, records and normalized lines, which can't be read back by their
+    /// spans.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate regex;
+    /// # extern crate grusp_core;
+    /// # fn main() {
+    /// use grusp_core::grusp::{Matcher, SearchOptions};
+    /// use std::io::Cursor;
+    ///
+    /// let reg = regex::Regex::new(r"ERROR").unwrap();
+    /// let mut log = Cursor::new("ok\nan ERROR\nok\n");
+    /// let matches = Matcher::new(&reg).options(SearchOptions::new().spans_only(true)).collect(&mut log).unwrap();
+    /// assert_eq!(matches.lines()[0].value, "");
+    /// assert_eq!((matches.lines()[0].captures[0].start, matches.lines()[0].captures[0].end), (3, 8));
+    /// # }
+    /// ```
+    pub fn spans_only(mut self, is_spans_only: bool) -> Self {
+        self.is_spans_only = is_spans_only;
+        self
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+use glob::{glob, Pattern, PatternError};
+
+/// Expands a query into the paths it names. A query that names an existing path is taken
+/// literally even if it has glob metacharacters, so directories like `[id]` can be
+/// searched. Braces are expanded before globbing, so `src/**/*.{rs,toml}` finds both
+/// kinds of files. On Windows the query is first normalized so drive letters, UNC paths
+/// and backslashes work as they do in the shell. Fails when the query isn't a valid glob.
+pub fn expand(query: &str) -> Result<Vec<PathBuf>, PatternError> {
+    let query = normalize(query, cfg!(windows));
+    let has_metacharacters = query.contains(['*', '?', '[', '{']);
+    if has_metacharacters && Path::new(&query).exists() {
+        return Ok(vec![PathBuf::from(query)]);
+    }
+    let mut paths = Vec::new();
+    for query in expand_braces(&query) {
+        paths.extend(glob(&query)?.filter_map(|p| p.ok()));
+    }
+    Ok(paths)
+}
+
+/// Returns the glob of a query starting with `!`, such as `!**/tests/**`, which leaves
+/// out the paths it matches from those found by the other queries. A query naming an
+/// existing path that starts with `!` is a path like any other.
+pub fn negation(query: &str) -> Option<&str> {
+    query.strip_prefix('!').filter(|_| !Path::new(query).exists())
+}
+
+/// Parses the glob of a negated query, with its braces expanded, into the patterns it
+/// matches paths with. A leading `./` is dropped, as it is from the paths matched.
+pub fn negation_patterns(glob: &str) -> Result<Vec<Pattern>, PatternError> {
+    let glob = normalize(glob, cfg!(windows));
+    expand_braces(glob.trim_start_matches("./")).iter().map(|glob| Pattern::new(glob)).collect()
+}
+
+/// Expands each group of braces with a comma in the query into a query for each of its
+/// alternatives, like a shell does: `*.{rs,toml}` is `*.rs` and `*.toml`. Groups can be
+/// nested. Braces without a comma, or that aren't closed, are kept as they are.
+pub fn expand_braces(query: &str) -> Vec<String> {
+    for (start, _) in query.match_indices('{') {
+        let mut depth = 0;
+        let mut bounds = vec![start];
+        for (i, c) in query[start..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                ',' if depth == 1 => bounds.push(start + i),
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        bounds.push(start + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if depth > 0 || bounds.len() < 3 {
+            continue;
+        }
+        let (prefix, suffix) = (&query[..start], &query[bounds[bounds.len() - 1] + 1..]);
+        return bounds
+            .windows(2)
+            .flat_map(|alternative| {
+                let alternative = &query[alternative[0] + 1..alternative[1]];
+                expand_braces(&format!("{}{}{}", prefix, alternative, suffix))
+            })
+            .collect();
+    }
+    vec![query.to_string()]
+}
+
+/// Rewrites a Windows path into the form the glob crate handles: verbatim `\\?\` prefixes
+/// are dropped, since globbing them finds nothing, and repeated or mixed separators after
+/// the drive letter or UNC share are collapsed into single backslashes. Other platforms
+/// are left untouched.
+fn normalize(query: &str, is_windows: bool) -> String {
+    if !is_windows {
+        return query.to_string();
+    }
+    let query = if let Some(rest) = query.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else {
+        query.strip_prefix(r"\\?\").unwrap_or(query).to_string()
+    };
+    let is_separator = |c: char| c == '/' || c == '\\';
+    let (prefix, rest) = if query.starts_with(is_separator) && query[1..].starts_with(is_separator) {
+        // A UNC share, \\server\share, is the root of the path
+        let mut parts = query[2..].splitn(3, is_separator);
+        let server = parts.next().unwrap_or("");
+        let share = parts.next().unwrap_or("");
+        let rest = parts.next().map(|rest| format!(r"\{}", rest)).unwrap_or_default();
+        (format!(r"\\{}\{}", server, share), rest)
+    } else if query.len() >= 2 && query.as_bytes()[0].is_ascii_alphabetic() && query.as_bytes()[1] == b':' {
+        (query[..2].to_string(), query[2..].to_string())
+    } else {
+        (String::new(), query.clone())
+    };
+    let mut path = prefix;
+    let mut last_was_separator = false;
+    for c in rest.chars() {
+        if is_separator(c) {
+            if !last_was_separator {
+                path.push('\\');
+            }
+            last_was_separator = true;
+        } else {
+            path.push(c);
+            last_was_separator = false;
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_leaves_queries_alone_off_windows() {
+        assert_eq!(normalize(r"src\\*.rs", false), r"src\\*.rs");
+    }
+
+    #[test]
+    fn it_normalizes_drive_letters_and_separators() {
+        assert_eq!(normalize(r"C:\Users\\me/src\*", true), r"C:\Users\me\src\*");
+        assert_eq!(normalize("C:/Users/", true), r"C:\Users\");
+        assert_eq!(normalize(r"src//lib\", true), r"src\lib\");
+    }
+
+    #[test]
+    fn it_keeps_unc_shares() {
+        assert_eq!(normalize(r"\\server\share\logs//*.log", true), r"\\server\share\logs\*.log");
+        assert_eq!(normalize("//server/share", true), r"\\server\share");
+    }
+
+    #[test]
+    fn it_drops_verbatim_prefixes() {
+        assert_eq!(normalize(r"\\?\C:\src\*", true), r"C:\src\*");
+        assert_eq!(normalize(r"\\?\UNC\server\share\*", true), r"\\server\share\*");
+    }
+
+    #[test]
+    fn it_takes_existing_paths_with_metacharacters_literally() {
+        use std::fs;
+
+        let dir = ::std::env::temp_dir().join("grusp-literal-path-test").join("[id]");
+        fs::create_dir_all(&dir).unwrap();
+        let query = dir.to_str().unwrap();
+        assert_eq!(expand(query).unwrap(), vec![dir.clone()]);
+    }
+
+    #[test]
+    fn it_expands_braces() {
+        assert_eq!(expand_braces("src/**/*.{rs,toml}"), vec!["src/**/*.rs", "src/**/*.toml"]);
+        assert_eq!(expand_braces("{a,b{c,d}}/{x,y}"), vec!["a/x", "a/y", "bc/x", "bc/y", "bd/x", "bd/y"]);
+        assert_eq!(expand_braces("{id}/{a,"), vec!["{id}/{a,"]);
+        assert_eq!(expand_braces("{,.}rc"), vec!["rc", ".rc"]);
+    }
+
+    #[test]
+    fn it_reads_negated_queries() {
+        assert_eq!(negation("!**/tests/**"), Some("**/tests/**"));
+        assert_eq!(negation("src"), None);
+        let patterns = negation_patterns("./**/*.{md,txt}").unwrap();
+        assert!(patterns[1].matches("docs/notes.txt"));
+    }
+
+    #[test]
+    fn it_fails_on_an_invalid_glob() {
+        let error = expand("src/[").unwrap_err();
+        assert_eq!(error.msg, "invalid range pattern");
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use regex::Captures;
+
+/// How serious a match of a pattern is, from least to most, so a scan can report every
+/// match while only failing on some of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Worth knowing about, such as a TODO.
+    Info,
+    /// Should be fixed, but doesn't have to be right away.
+    Warn,
+    /// Has to be fixed. Patterns without a severity are errors.
+    Error,
+}
+
+impl Severity {
+    /// Returns the severity named `info`, `warn` or `error`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "info" => Some(Severity::Info),
+            "warn" => Some(Severity::Warn),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warn => write!(f, "warn"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A pattern searched for along with the others, and the group it's found by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    name: Option<String>,
+    severity: Severity,
+    regex: String,
+    group: String,
+}
+
+/// Several patterns searched for at once, as a single regex matching any of them. A
+/// pattern can be given a name, such as `no-unwrap=\.unwrap\(\)`, so each match of a
+/// lint-style scan is tagged with the rule that found it. A named pattern can also have
+/// a severity, such as `todo:info=TODO`, and is otherwise an error.
+///
+/// Named patterns are wrapped in a group of their own to tell which one matched, so the
+/// numbers of the groups within them are shifted by one for each named pattern before
+/// them. Names of groups are kept as they are.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::{Matcher, Patterns};
+///
+/// let mut patterns = Patterns::new();
+/// patterns.add(r"no-unwrap=\.unwrap\(\)").unwrap();
+/// patterns.add("TODO").unwrap();
+/// let regex = regex::Regex::new(&patterns.pattern()).unwrap();
+/// let line = Matcher::new(&regex).patterns(Some(&patterns)).match_line("x.unwrap(); // TODO").unwrap();
+/// let rules: Vec<_> = line.captures.iter().map(|capture| capture.rule.as_deref()).collect();
+/// assert_eq!(rules, vec![Some("no-unwrap"), None]);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patterns {
+    patterns: Vec<Pattern>,
+}
+
+impl Patterns {
+    /// Creates an empty set of patterns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pattern, either a regex, `NAME=REGEX` or `NAME:SEVERITY=REGEX`. A name
+    /// starts with a letter and is made of letters, digits, `-`, `_` and `.`, so a regex
+    /// that looks like a name followed by `=`, such as `a=b`, has to be written another
+    /// way, such as `(?:a)=b`. The error names a severity that isn't `info`, `warn` or
+    /// `error`.
+    pub fn add(&mut self, pattern: &str) -> Result<(), String> {
+        let (rule, regex) = pattern.split_once('=').unwrap_or(("", pattern));
+        let (name, severity) = match rule.split_once(':') {
+            Some((name, severity)) if is_name(name) && is_name(severity) => {
+                let severity = Severity::parse(severity)
+                    .ok_or_else(|| format!("Unknown severity '{}' of {}, expected info, warn or error", severity, name))?;
+                (name, severity)
+            }
+            _ => (rule, Severity::Error),
+        };
+        if is_name(name) {
+            self.add_named(Some(name), severity, regex);
+        } else {
+            self.add_named(None, Severity::Error, pattern);
+        }
+        Ok(())
+    }
+
+    /// Adds a regex under the name, if any, with the severity of its matches.
+    pub fn add_named(&mut self, name: Option<&str>, severity: Severity, regex: &str) {
+        let group = format!("grusp_rule_{}", self.patterns.len());
+        self.patterns.push(Pattern { name: name.map(str::to_string), severity, regex: regex.to_string(), group });
+    }
+
+    /// Returns true if there aren't any patterns.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns the name and regex of each pattern, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&str>, &str)> {
+        self.patterns.iter().map(|pattern| (pattern.name.as_deref(), pattern.regex.as_str()))
+    }
+
+    /// Returns a regex matching any of the patterns, where the ones listed first win
+    /// when several match at the same place. A single unnamed pattern is returned as is.
+    pub fn pattern(&self) -> String {
+        match self.patterns.as_slice() {
+            [Pattern { name: None, regex, .. }] => regex.clone(),
+            patterns => patterns
+                .iter()
+                .map(|pattern| match pattern.name {
+                    Some(_) => format!("(?P<{}>{})", pattern.group, pattern.regex),
+                    None => format!("(?:{})", pattern.regex),
+                })
+                .collect::<Vec<_>>()
+                .join("|"),
+        }
+    }
+
+    /// Returns the name of the pattern that matched, given the captures of a match of
+    /// the regex made by `pattern`, or None when the pattern has no name.
+    pub fn rule(&self, captures: &Captures) -> Option<&str> {
+        self.patterns
+            .iter()
+            .filter(|pattern| pattern.name.is_some())
+            .find(|pattern| captures.name(&pattern.group).is_some())
+            .and_then(|pattern| pattern.name.as_deref())
+    }
+
+    /// Returns the severity of the matches of a rule, as tagged on each capture. Matches
+    /// without a rule, or of a rule that isn't one of the patterns, are errors.
+    pub fn severity(&self, rule: Option<&str>) -> Severity {
+        self.patterns
+            .iter()
+            .find(|pattern| pattern.name.is_some() && pattern.name.as_deref() == rule)
+            .map_or(Severity::Error, |pattern| pattern.severity)
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn it_only_names_patterns_starting_with_a_name() {
+        let mut patterns = Patterns::new();
+        patterns.add("todo=TODO|FIXME").unwrap();
+        patterns.add("(?:a)=b").unwrap();
+        patterns.add("=x").unwrap();
+        let added: Vec<_> = patterns.iter().collect();
+        assert_eq!(added, vec![(Some("todo"), "TODO|FIXME"), (None, "(?:a)=b"), (None, "=x")]);
+        assert_eq!(patterns.pattern(), "(?P<grusp_rule_0>TODO|FIXME)|(?:(?:a)=b)|(?:=x)");
+    }
+
+    #[test]
+    fn it_finds_the_rule_of_a_match() {
+        let mut patterns = Patterns::new();
+        patterns.add("short=ab").unwrap();
+        patterns.add("long=abc").unwrap();
+        let regex = Regex::new(&patterns.pattern()).unwrap();
+        let rules: Vec<_> = regex.captures_iter("abc xabx").map(|caps| patterns.rule(&caps).map(str::to_string)).collect();
+        assert_eq!(rules, vec![Some("short".to_string()), Some("short".to_string())]);
+        let mut single = Patterns::new();
+        single.add("a|b").unwrap();
+        assert_eq!(single.pattern(), "a|b");
+    }
+
+    #[test]
+    fn it_reads_the_severity_of_a_rule() {
+        let mut patterns = Patterns::new();
+        patterns.add("todo:info=TODO").unwrap();
+        patterns.add("no-unwrap=unwrap").unwrap();
+        patterns.add("a:b:c=d").unwrap();
+        assert_eq!(patterns.severity(Some("todo")), Severity::Info);
+        assert_eq!(patterns.severity(Some("no-unwrap")), Severity::Error);
+        assert_eq!(patterns.severity(None), Severity::Error);
+        assert_eq!(patterns.iter().last(), Some((None, "a:b:c=d")));
+        assert_eq!(patterns.add("todo:fatal=TODO").unwrap_err(), "Unknown severity 'fatal' of todo, expected info, warn or error");
+        assert!(Severity::Info < Severity::Warn && Severity::Warn < Severity::Error);
+    }
+}
@@ -0,0 +1,223 @@
+use regex::{Captures, Regex, RegexSet};
+use matcher::{Capture, Line};
+
+/// Replaces the matches of a regex with a template, where `$1` or `${name}` expand to
+/// the groups each match captured, as in `Regex::replace`.
+///
+/// With the case preserved, a replacement follows the case of the text it replaces: all
+/// uppercase matches are replaced in uppercase, capitalized matches are capitalized and
+/// the rest are replaced as the template expands.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::Replacement;
+/// use regex::Regex;
+///
+/// let regex = Regex::new("(?i)foo").unwrap();
+/// let replacement = Replacement::new(&regex, "bar").preserve_case(true);
+/// assert_eq!(replacement.apply("foo Foo FOO fOO"), "bar Bar BAR bar");
+///
+/// let regex = Regex::new(r"(?P<key>\w+)=(?P<value>\w+)").unwrap();
+/// assert_eq!(Replacement::new(&regex, "$value=$key").apply("a=1, b=2"), "1=a, 2=b");
+/// # }
+/// ```
+///
+/// A replacement can also be made of several rules, each a regex and its template, that
+/// are all applied in a single pass:
+///
+/// ```
+/// # extern crate regex;
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::Replacement;
+/// use regex::Regex;
+///
+/// let rules = vec![
+///     (Regex::new("colour").unwrap(), "color".to_string()),
+///     (Regex::new("color").unwrap(), "hue".to_string()),
+/// ];
+/// let replacement = Replacement::rules(rules).unwrap();
+/// assert_eq!(replacement.apply("colour and color"), "color and hue");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    rules: Vec<(Regex, String)>,
+    /// Finds which of several rules match a line, so the rest aren't searched for.
+    set: Option<RegexSet>,
+    is_case_preserved: bool,
+}
+
+/// A match of one of the rules and the text it's replaced with.
+struct Found {
+    start: usize,
+    end: usize,
+    replacement: String,
+}
+
+impl Replacement {
+    /// Creates a replacement of the regex's matches with the template.
+    pub fn new(regex: &Regex, template: &str) -> Self {
+        Replacement { rules: vec![(regex.clone(), template.to_string())], set: None, is_case_preserved: false }
+    }
+
+    /// Creates a replacement out of rules, each a regex and the template its matches are
+    /// replaced with. The matches of the rules don't overlap: the one that starts first
+    /// is replaced, and of matches that start at the same place, the one of the first
+    /// rule listed. Replaced text isn't matched again by the rules after it.
+    ///
+    /// The rules are found together through a set made from the patterns of the regexes,
+    /// so flags have to be written into the patterns, as in `(?i)colou?r`, rather than
+    /// given to a `RegexBuilder`.
+    pub fn rules(rules: Vec<(Regex, String)>) -> Result<Self, ::regex::Error> {
+        let set = RegexSet::new(rules.iter().map(|(regex, _)| regex.as_str()))?;
+        Ok(Replacement { rules, set: Some(set), is_case_preserved: false })
+    }
+
+    /// Consumes the replacement and enables/disables following the case of each match.
+    pub fn preserve_case(self, is_case_preserved: bool) -> Self {
+        Self { is_case_preserved, ..self }
+    }
+
+    fn expand(&self, template: &str, captures: &Captures) -> String {
+        let mut expanded = String::new();
+        captures.expand(template, &mut expanded);
+        if !self.is_case_preserved {
+            return expanded;
+        }
+        let matched = captures.get(0).map_or("", |m| m.as_str());
+        transfer_case(matched, &expanded)
+    }
+
+    /// Returns the matches of the rules in the text, in order and without overlaps.
+    fn find(&self, text: &str) -> Vec<Found> {
+        let mut found: Vec<(usize, Found)> = Vec::new();
+        let matched: Vec<usize> = match self.set {
+            Some(ref set) => set.matches(text).into_iter().collect(),
+            None => (0..self.rules.len()).collect(),
+        };
+        for i in matched {
+            let (ref regex, ref template) = self.rules[i];
+            for captures in regex.captures_iter(text) {
+                if let Some(whole) = captures.get(0) {
+                    let replacement = self.expand(template, &captures);
+                    found.push((i, Found { start: whole.start(), end: whole.end(), replacement }));
+                }
+            }
+        }
+        found.sort_by_key(|&(i, ref found)| (found.start, i));
+        let mut end = 0;
+        found
+            .into_iter()
+            .map(|(_, found)| found)
+            .filter(|found| {
+                // An empty match right after another is still kept, as replace_all does
+                let is_kept = found.start >= end;
+                if is_kept {
+                    end = found.end.max(found.start + usize::from(found.start == found.end));
+                }
+                is_kept
+            })
+            .collect()
+    }
+
+    /// Returns the text with every match of the rules replaced.
+    pub fn apply(&self, text: &str) -> String {
+        let mut value = String::new();
+        let mut last = 0;
+        for found in self.find(text) {
+            value.push_str(&text[last..found.start]);
+            value.push_str(&found.replacement);
+            last = found.end;
+        }
+        value.push_str(&text[last..]);
+        value
+    }
+
+    /// Replaces the captures of a matched line, moving the captures onto the
+    /// replacements so they're still highlighted. Only the matches the line kept are
+    /// replaced, so a line that stopped at its first capture keeps the rest as they were.
+    pub fn line(&self, line: &Line) -> Line {
+        let mut value = String::new();
+        let mut captures = Vec::new();
+        let mut last = 0;
+        for found in self.find(&line.value) {
+            let cap = match line.captures.iter().find(|cap| cap.start == found.start) {
+                Some(cap) => cap,
+                None => continue,
+            };
+            value.push_str(&line.value[last..found.start]);
+            let start = value.len();
+            value.push_str(&found.replacement);
+            captures.push(Capture { start, end: value.len(), value: found.replacement, ..cap.clone() });
+            last = found.end;
+        }
+        value.push_str(&line.value[last..]);
+        Line { number: line.number, value, captures }
+    }
+}
+
+/// Returns the replacement in the case of the text it replaces.
+fn transfer_case(matched: &str, replacement: &str) -> String {
+    let mut letters = matched.chars().filter(|c| c.is_alphabetic());
+    let first = match letters.next() {
+        Some(first) => first,
+        None => return replacement.to_string(),
+    };
+    let rest: Vec<char> = letters.collect();
+    if first.is_uppercase() && !rest.is_empty() && rest.iter().all(|c| !c.is_lowercase()) {
+        return replacement.to_uppercase();
+    }
+    if first.is_uppercase() {
+        let mut chars = replacement.chars();
+        return match chars.next() {
+            Some(c) => c.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        };
+    }
+    replacement.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matcher::Matcher;
+
+    #[test]
+    fn it_transfers_the_case_of_the_match() {
+        assert_eq!(transfer_case("FOO", "bar"), "BAR");
+        assert_eq!(transfer_case("Foo", "bar"), "Bar");
+        assert_eq!(transfer_case("Foo", "barBaz"), "BarBaz");
+        assert_eq!(transfer_case("F", "bar"), "Bar");
+        assert_eq!(transfer_case("foo", "barBaz"), "barBaz");
+        assert_eq!(transfer_case("FOO_1", "bar_2"), "BAR_2");
+        assert_eq!(transfer_case("123", "bar"), "bar");
+    }
+
+    #[test]
+    fn it_replaces_only_the_captures_the_line_kept() {
+        let regex = Regex::new("o").unwrap();
+        let line = Matcher::new(&regex).max_captures_per_line(Some(1)).match_line("foo").unwrap();
+        let replaced = Replacement::new(&regex, "0").line(&line);
+        assert_eq!(replaced.value, "f0o");
+        assert_eq!((replaced.captures[0].start, replaced.captures[0].end), (1, 2));
+        assert_eq!(replaced.captures[0].column, 2);
+    }
+
+    #[test]
+    fn it_applies_the_first_rule_of_overlapping_matches() {
+        let rules = vec![
+            (Regex::new("ab").unwrap(), "1".to_string()),
+            (Regex::new("abc").unwrap(), "2".to_string()),
+            (Regex::new("bcd").unwrap(), "3".to_string()),
+        ];
+        let replacement = Replacement::rules(rules).unwrap();
+        assert_eq!(replacement.apply("abcd"), "1cd");
+        assert_eq!(replacement.apply("xbcd abc"), "x3 1c");
+        assert_eq!(Replacement::new(&Regex::new("x*").unwrap(), "-").apply("ab"), "-a-b-");
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use std::path::{Component, Path};
+use std::sync::Mutex;
+use colored::*;
+use color;
+
+/// The files and subdirectories of a directory, by name.
+#[derive(Debug, Default)]
+struct Dir {
+    dirs: BTreeMap<String, Dir>,
+    files: BTreeMap<String, String>,
+}
+
+/// Collects the displayed matches of each file and shows them beneath the directories
+/// they're in, so each directory is printed once rather than at the start of every path.
+/// Directories with a single subdirectory and no matching files of their own are joined
+/// into one heading, keeping deep trees narrow. Files can be added from several threads.
+///
+/// The matches of a file are added already displayed, with the name of the file in
+/// place of its path, so any display options apply. Their lines are indented beneath
+/// the heading of the file's directory, with the files of a directory before its
+/// subdirectories.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::PathTree;
+/// use std::path::Path;
+///
+/// let tree = PathTree::new().color(false);
+/// tree.add(Path::new("./src/app/models/user.rs"), "user.rs matched 1 time\n3:TODO".to_string());
+/// tree.add(Path::new("./src/app/main.rs"), "main.rs matched 1 time\n9:TODO".to_string());
+/// tree.add(Path::new("./src/app/models/post.rs"), "post.rs matched 1 time\n1:TODO".to_string());
+/// assert_eq!(
+///     tree.to_string(),
+///     "src/app/\n  main.rs matched 1 time\n  9:TODO\n  models/\n    post.rs matched 1 time\n    1:TODO\n    user.rs matched 1 time\n    3:TODO\n"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct PathTree {
+    is_colored: bool,
+    files: Mutex<BTreeMap<Vec<String>, String>>,
+    /// About how many bytes the files take up, kept beside them under their lock.
+    size: Mutex<usize>,
+}
+
+impl Default for PathTree {
+    fn default() -> Self {
+        PathTree { is_colored: color::is_colored(), files: Mutex::new(BTreeMap::new()), size: Mutex::new(0) }
+    }
+}
+
+impl PathTree {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consumes the tree and enables/disables colored directories, instead of following
+    /// the `ColorChoice` applied.
+    pub fn color(self, is_colored: bool) -> Self {
+        Self { is_colored, ..self }
+    }
+
+    /// Adds the displayed matches of the file at the path.
+    pub fn add(&self, path: &Path, displayed: String) {
+        let parts: Vec<String> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::CurDir => None,
+                Component::RootDir | Component::Prefix(_) => Some(String::new()),
+                c => Some(c.as_os_str().to_string_lossy().into_owned()),
+            })
+            .collect();
+        let mut files = self.files.lock().expect("Tree lock poisoned");
+        let mut size = self.size.lock().expect("Tree lock poisoned");
+        *size += parts.iter().map(|part| part.len() + mem::size_of::<String>()).sum::<usize>() + displayed.len();
+        files.insert(parts, displayed);
+    }
+
+    /// Returns true if no files have been added.
+    pub fn is_empty(&self) -> bool {
+        self.files.lock().expect("Tree lock poisoned").is_empty()
+    }
+
+    /// Returns about how many bytes of memory the files added so far take up.
+    pub fn size(&self) -> usize {
+        *self.size.lock().expect("Tree lock poisoned")
+    }
+
+    /// Moves the files added so far into a new tree with the same options, leaving this
+    /// one empty. This lets a large tree be displayed in parts as it's added to.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use grusp_core::grusp::PathTree;
+    /// use std::path::Path;
+    ///
+    /// let tree = PathTree::new().color(false);
+    /// tree.add(Path::new("src/main.rs"), "main.rs matched 1 time\n9:TODO".to_string());
+    /// let taken = tree.take();
+    /// assert_eq!(taken.to_string(), "src/\n  main.rs matched 1 time\n  9:TODO\n");
+    /// assert!(tree.is_empty());
+    /// assert_eq!(tree.size(), 0);
+    /// ```
+    pub fn take(&self) -> Self {
+        let mut files = self.files.lock().expect("Tree lock poisoned");
+        let mut size = self.size.lock().expect("Tree lock poisoned");
+        PathTree {
+            is_colored: self.is_colored,
+            files: Mutex::new(mem::take(&mut *files)),
+            size: Mutex::new(mem::replace(&mut *size, 0)),
+        }
+    }
+
+    fn write_dir(&self, f: &mut fmt::Formatter, dir: &Dir, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        for displayed in dir.files.values() {
+            for line in displayed.lines().filter(|line| !line.is_empty()) {
+                writeln!(f, "{}{}", indent, line)?;
+            }
+        }
+        for (name, mut sub) in &dir.dirs {
+            let mut heading = name.clone();
+            while sub.files.is_empty() && sub.dirs.len() == 1 {
+                let (name, only) = sub.dirs.iter().next().expect("One subdirectory");
+                heading = format!("{}/{}", heading, name);
+                sub = only;
+            }
+            heading.push('/');
+            if self.is_colored {
+                writeln!(f, "{}{}", indent, heading.bright_green())?;
+            } else {
+                writeln!(f, "{}{}", indent, heading)?;
+            }
+            self.write_dir(f, sub, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PathTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut root = Dir::default();
+        for (parts, displayed) in self.files.lock().expect("Tree lock poisoned").iter() {
+            if let Some((name, dirs)) = parts.split_last() {
+                let dir = dirs.iter().fold(&mut root, |dir, part| dir.dirs.entry(part.clone()).or_default());
+                dir.files.insert(name.clone(), displayed.clone());
+            }
+        }
+        self.write_dir(f, &root, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_absolute_paths_rooted() {
+        let tree = PathTree::new().color(false);
+        tree.add(Path::new("/tmp/a/x.rs"), "x.rs".to_string());
+        tree.add(Path::new("/tmp/b/y.rs"), "y.rs".to_string());
+        assert_eq!(tree.to_string(), "/tmp/\n  a/\n    x.rs\n  b/\n    y.rs\n");
+    }
+
+    #[test]
+    fn it_lists_files_at_the_root_without_a_heading() {
+        let tree = PathTree::new().color(false);
+        tree.add(Path::new("Cargo.toml"), "Cargo.toml matched 2 times\n1:a\n\n2:b\n".to_string());
+        assert_eq!(tree.to_string(), "Cargo.toml matched 2 times\n1:a\n2:b\n");
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use glob::{MatchOptions, Pattern};
+
+/// The file types known without any definitions, by name.
+const BUILT_IN: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rb", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// A registry of named file types, each a list of globs matched against file names, so
+/// a search can be limited to a language with `--type rust` rather than spelling out
+/// its extensions.
+///
+/// Types are defined as `NAME:GLOB,GLOB`, such as `web:*.html,*.css,*.js`. A definition
+/// adds its globs to the type, creating it if it's new, so definitions can extend the
+/// built-in types. A definition without globs, such as `web:`, clears the type so the
+/// definitions after it replace it. The registry is displayed as the definitions of its
+/// types, one per line, which can be added back to recreate it.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::FileTypes;
+/// use std::path::Path;
+///
+/// let mut types = FileTypes::new();
+/// types.add("web:*.html,*.css,*.js").unwrap();
+/// types.add("rust:*.rs.in").unwrap();
+/// let matcher = types.matcher(&["web", "rust"]).unwrap();
+/// assert!(matcher.is_match(Path::new("site/index.html")));
+/// assert!(matcher.is_match(Path::new("build/main.rs.in")));
+/// assert!(!matcher.is_match(Path::new("README.md")));
+/// assert!(types.matcher(&["cobol"]).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTypes {
+    types: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for FileTypes {
+    fn default() -> Self {
+        let types = BUILT_IN
+            .iter()
+            .map(|&(name, globs)| (name.to_string(), globs.iter().map(|glob| glob.to_string()).collect()))
+            .collect();
+        FileTypes { types }
+    }
+}
+
+impl FileTypes {
+    /// Creates a registry of the built-in types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a definition of a type, `NAME:GLOB,GLOB`, to the registry. The error
+    /// describes why the definition is invalid.
+    pub fn add(&mut self, definition: &str) -> Result<(), String> {
+        let invalid = |reason: String| format!("Invalid file type '{}': {}", definition, reason);
+        let (name, globs) = definition
+            .split_once(':')
+            .ok_or_else(|| invalid("expected NAME:GLOB,GLOB".to_string()))?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            return Err(invalid("the name can only have letters, digits, '-' and '_'".to_string()));
+        }
+        let globs: Vec<&str> = globs.split(',').map(str::trim).filter(|glob| !glob.is_empty()).collect();
+        for glob in &globs {
+            Pattern::new(glob).map_err(|e| invalid(format!("{} in '{}'", e.msg, glob)))?;
+        }
+        let existing = self.types.entry(name.to_string()).or_default();
+        if globs.is_empty() {
+            existing.clear();
+        }
+        for glob in globs {
+            if !existing.iter().any(|existing| existing == glob) {
+                existing.push(glob.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the globs of the type, if it's defined.
+    pub fn globs(&self, name: &str) -> Option<&[String]> {
+        self.types.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns a matcher for the files of any of the types, or an error naming the first
+    /// type that isn't defined.
+    pub fn matcher<S: AsRef<str>>(&self, names: &[S]) -> Result<TypeMatcher, String> {
+        let mut patterns = Vec::new();
+        for name in names {
+            let globs = self
+                .globs(name.as_ref())
+                .ok_or_else(|| format!("Unknown file type '{}', list them with --type-list", name.as_ref()))?;
+            patterns.extend(globs.iter().map(|glob| Pattern::new(glob).expect("Globs are checked when added")));
+        }
+        Ok(TypeMatcher { patterns })
+    }
+}
+
+impl fmt::Display for FileTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, globs) in &self.types {
+            writeln!(f, "{}:{}", name, globs.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Matches the names of files against the globs of the file types it was made from.
+#[derive(Debug, Clone)]
+pub struct TypeMatcher {
+    patterns: Vec<Pattern>,
+}
+
+impl TypeMatcher {
+    /// Whether the name of the file matches any of the globs.
+    pub fn is_match(&self, path: &Path) -> bool {
+        let options = MatchOptions { case_sensitive: true, require_literal_separator: true, require_literal_leading_dot: false };
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => self.patterns.iter().any(|pattern| pattern.matches_with(name, &options)),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_extends_or_clears_a_type() {
+        let mut types = FileTypes::new();
+        types.add("rust:*.rs,*.ron").unwrap();
+        assert_eq!(types.globs("rust").unwrap(), &["*.rs".to_string(), "*.ron".to_string()]);
+        types.add("rust:").unwrap();
+        types.add("rust:*.rlib").unwrap();
+        assert_eq!(types.globs("rust").unwrap(), &["*.rlib".to_string()]);
+    }
+
+    #[test]
+    fn it_round_trips_through_its_display() {
+        let mut types = FileTypes::new();
+        types.add("web:*.html, *.css").unwrap();
+        let mut parsed = FileTypes { types: BTreeMap::new() };
+        for definition in types.to_string().lines() {
+            parsed.add(definition).unwrap();
+        }
+        assert_eq!(parsed, types);
+        assert!(types.to_string().contains("web:*.html,*.css\n"));
+    }
+
+    #[test]
+    fn it_rejects_invalid_definitions() {
+        let mut types = FileTypes::new();
+        assert_eq!(types.add("web").unwrap_err(), "Invalid file type 'web': expected NAME:GLOB,GLOB");
+        assert!(types.add("a b:*.x").is_err());
+        assert!(types.add("web:[").unwrap_err().contains("in '['"));
+        assert_eq!(types, FileTypes::new());
+    }
+}
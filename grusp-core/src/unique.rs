@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use filter::MatchFilter;
+use matcher::Line;
+
+/// Where a line has to be unique to be kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniqueScope {
+    /// A line is dropped if it was already kept for the same file.
+    File,
+    /// A line is dropped if it was already kept for any file.
+    Global,
+}
+
+/// A filter that drops lines identical to one it already kept, ignoring leading and
+/// trailing whitespace. This keeps repetitive logs readable.
+///
+/// The filter remembers every line it keeps, so one filter should be used per search.
+/// When files are searched concurrently, which of the identical lines is kept across
+/// files depends on the order they are searched in.
+///
+/// ### Examples
+///
+/// ```
+/// use grusp_core::grusp::{Line, MatchFilter, UniqueLinesFilter, UniqueScope};
+/// use std::path::Path;
+///
+/// let line = |value: &str| Line { number: None, value: value.to_string(), captures: Vec::new() };
+/// let filter = UniqueLinesFilter::new(UniqueScope::File);
+/// assert!(filter.keep(&line("error"), Path::new("a.log")));
+/// assert!(!filter.keep(&line("  error"), Path::new("a.log")));
+/// assert!(filter.keep(&line("error"), Path::new("b.log")));
+/// ```
+#[derive(Debug)]
+pub struct UniqueLinesFilter {
+    scope: UniqueScope,
+    seen: Mutex<HashSet<(PathBuf, String)>>,
+}
+
+impl UniqueLinesFilter {
+    /// Creates a filter that keeps the first of each line within the scope.
+    pub fn new(scope: UniqueScope) -> Self {
+        UniqueLinesFilter { scope, seen: Mutex::new(HashSet::new()) }
+    }
+}
+
+impl MatchFilter for UniqueLinesFilter {
+    fn keep(&self, line: &Line, path: &Path) -> bool {
+        let path = match self.scope {
+            UniqueScope::File => path.to_path_buf(),
+            UniqueScope::Global => PathBuf::new(),
+        };
+        let mut seen = self.seen.lock().expect("Unique lines lock poisoned");
+        seen.insert((path, line.value.trim().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(value: &str) -> Line {
+        Line { number: Some(1), value: value.to_string(), captures: Vec::new() }
+    }
+
+    #[test]
+    fn it_drops_repeated_lines_in_the_same_file() {
+        let filter = UniqueLinesFilter::new(UniqueScope::File);
+        assert!(filter.keep(&line("a"), Path::new("x.log")));
+        assert!(!filter.keep(&line("a\t"), Path::new("x.log")));
+        assert!(filter.keep(&line("b"), Path::new("x.log")));
+        assert!(filter.keep(&line("a"), Path::new("y.log")));
+    }
+
+    #[test]
+    fn it_drops_repeated_lines_across_files_when_global() {
+        let filter = UniqueLinesFilter::new(UniqueScope::Global);
+        assert!(filter.keep(&line("a"), Path::new("x.log")));
+        assert!(!filter.keep(&line("a"), Path::new("y.log")));
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs::{self, Metadata, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Replaces the contents of a file without leaving it half written, for edits made in
+/// place. The contents are written to a temporary file next to it, which takes the mode
+/// and owner of the file and is then renamed over it.
+///
+/// A symlink is followed, so the file it points to is edited and the link is kept. When
+/// the temporary file can't be used, such as when it can't be created in the directory,
+/// can't be given the owner of the file or can't be renamed across devices, the file is
+/// overwritten in place instead, which keeps its mode and owner as they are. Files with
+/// more than one hard link are always overwritten in place so the links still share them.
+///
+/// ### Examples
+///
+/// ```
+/// # extern crate grusp_core;
+/// # fn main() {
+/// use grusp_core::grusp::safe_write;
+/// use std::fs;
+///
+/// let path = std::env::temp_dir().join("grusp-safe-write-doc.txt");
+/// fs::write(&path, "old").unwrap();
+/// safe_write(&path, b"new").unwrap();
+/// assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+/// # }
+/// ```
+pub fn safe_write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let target = match fs::canonicalize(path) {
+        Ok(target) => target,
+        // A new file, or a symlink to one, is simply created
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return fs::write(path, contents),
+        Err(e) => return Err(e),
+    };
+    let metadata = fs::metadata(&target)?;
+    if is_hard_linked(&metadata) {
+        return overwrite(&target, contents);
+    }
+    match replace(&target, contents, &metadata) {
+        Err(ref e) if e.kind() == ErrorKind::PermissionDenied || e.kind() == ErrorKind::CrossesDevices => {
+            overwrite(&target, contents)
+        }
+        result => result,
+    }
+}
+
+/// Writes the contents to a temporary file beside the target and renames it over it.
+fn replace(target: &Path, contents: &[u8], metadata: &Metadata) -> io::Result<()> {
+    let temp = temp_path(target);
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&temp)?;
+    let result = file
+        .write_all(contents)
+        .and_then(|_| fs::set_permissions(&temp, metadata.permissions()))
+        .and_then(|_| set_owner(&temp, metadata))
+        .and_then(|_| file.sync_all())
+        .and_then(|_| fs::rename(&temp, target));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Writes a file whole or not at all, for files grusp owns like the shards of an index.
+/// The contents are written to a temporary file beside it, which is then renamed over it,
+/// so an interrupted write leaves the file as it was.
+pub(crate) fn write_whole(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path);
+    let result = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Truncates the target and writes the contents into it, which keeps the file itself.
+fn overwrite(target: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(target)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    target.with_file_name(format!(".{}.grusp-{}.tmp", name, process::id()))
+}
+
+#[cfg(unix)]
+fn set_owner(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{chown, MetadataExt};
+
+    chown(path, Some(metadata.uid()), Some(metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _metadata: &Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_hard_linked(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn is_hard_linked(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn it_replaces_the_contents_without_leaving_a_temporary_file() {
+        let dir = dir("grusp-safe-write-test");
+        let path = dir.join("a.txt");
+        fs::write(&path, "a much longer old line\n").unwrap();
+        safe_write(&path, b"new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        safe_write(&dir.join("b.txt"), b"created\n").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "created\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_keeps_the_mode_symlinks_and_hard_links() {
+        use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+
+        let dir = dir("grusp-safe-write-links-test");
+        let (target, link, hard) = (dir.join("target.sh"), dir.join("link.sh"), dir.join("hard.sh"));
+        fs::write(&target, "old\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o751)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        safe_write(&link, b"new\n").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+        assert_eq!(fs::metadata(&target).unwrap().mode() & 0o777, 0o751);
+
+        fs::hard_link(&target, &hard).unwrap();
+        safe_write(&hard, b"shared\n").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "shared\n");
+        assert_eq!(fs::metadata(&target).unwrap().ino(), fs::metadata(&hard).unwrap().ino());
+    }
+}
@@ -0,0 +1,88 @@
+//! Property tests comparing the matcher's line decisions with using the regex directly,
+//! which is how grep decides whether a line matches.
+extern crate proptest;
+extern crate grusp_core;
+extern crate regex;
+
+use grusp_core::grusp::Matcher;
+use proptest::prelude::*;
+use regex::{Regex, RegexBuilder};
+use std::io::Cursor;
+
+/// Small patterns over a small alphabet so that they actually match the generated lines.
+fn pattern() -> impl Strategy<Value = String> {
+    let atom = prop_oneof![
+        Just("a"), Just("b"), Just("A"), Just("."), Just("[ab]"), Just("[^a]"),
+        Just("a*"), Just("b+"), Just("(a|B)"), Just(r"\d"), Just(r"\s"),
+    ];
+    (any::<bool>(), prop::collection::vec(atom, 1..4), any::<bool>()).prop_map(|(start, atoms, end)| {
+        format!("{}{}{}", if start { "^" } else { "" }, atoms.concat(), if end { "$" } else { "" })
+    })
+}
+
+fn lines() -> impl Strategy<Value = Vec<String>> {
+    prop::collection::vec("[abAB1 ]{0,8}", 0..12)
+}
+
+fn regex(pattern: &str, is_case_insensitive: bool) -> Regex {
+    RegexBuilder::new(pattern).case_insensitive(is_case_insensitive).build().unwrap()
+}
+
+proptest! {
+    #[test]
+    fn it_decides_lines_like_the_regex(pattern in pattern(), line in "[abAB1 ]{0,8}", is_inverted: bool) {
+        let reg = Regex::new(&pattern).unwrap();
+        let matched = Matcher::new(&reg).invert_match(is_inverted).match_line(&line);
+        prop_assert_eq!(matched.is_some(), reg.is_match(&line) != is_inverted);
+    }
+
+    #[test]
+    fn it_captures_what_the_regex_finds(pattern in pattern(), line in "[abAB1 ]{0,8}") {
+        let reg = Regex::new(&pattern).unwrap();
+        // find_iter in regex 0.2 can match `^` more than once, e.g. `^a` twice in "aa"
+        let expected: Vec<_> = reg
+            .captures_iter(&line)
+            .filter_map(|caps| caps.get(0))
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        let captures: Vec<_> = Matcher::new(&reg)
+            .match_line(&line)
+            .map(|l| l.captures.iter().map(|c| (c.start, c.end)).collect())
+            .unwrap_or_default();
+        prop_assert_eq!(captures, expected);
+    }
+
+    #[test]
+    fn it_counts_lines_like_grep(pattern in pattern(),
+                                 lines in lines(),
+                                 is_inverted: bool,
+                                 is_case_insensitive: bool) {
+        let reg = regex(&pattern, is_case_insensitive);
+        let input = lines.join("\n");
+        let expected: Vec<usize> = input
+            .split_terminator('\n')
+            .enumerate()
+            .filter(|&(_, line)| reg.is_match(line) != is_inverted)
+            .map(|(i, _)| i + 1)
+            .collect();
+        let matches = Matcher::new(&reg)
+            .invert_match(is_inverted)
+            .collect(&mut Cursor::new(input.as_bytes()))
+            .unwrap();
+        prop_assert_eq!(matches.count() as usize, expected.len());
+        let numbers: Vec<usize> = matches.iter().filter_map(|r| r.line_number()).collect();
+        prop_assert_eq!(numbers, expected);
+    }
+
+    #[test]
+    fn it_anchors_to_each_line(lines in lines()) {
+        let reg = Regex::new("^a|b$").unwrap();
+        let input = lines.join("\n");
+        let expected = input
+            .split_terminator('\n')
+            .filter(|line| line.starts_with('a') || line.ends_with('b'))
+            .count();
+        let matches = Matcher::new(&reg).collect(&mut Cursor::new(input.as_bytes())).unwrap();
+        prop_assert_eq!(matches.count() as usize, expected);
+    }
+}
@@ -1,18 +1,142 @@
 use regex;
 use regex::{Regex, RegexBuilder};
+use regex_syntax::{ErrorKind, ExprBuilder};
+use regex_syntax::Error as SyntaxError;
+use std::fmt;
 use atty;
 use atty::Stream;
-use clap::{Values, Arg, App, AppSettings};
+use clap::{Values, Arg, ArgGroup, ArgMatches, App, AppSettings, Shell, SubCommand};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::process;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use config::Config;
+use defaults;
+use edit::Edit;
+use fields::{JsonField, JsonFields};
+use man;
+use presets;
+use rewrite::{Backup, Rewrite};
+use policy::Policy;
+use html;
+use listing::FileList;
+use output::Output;
+use sarif;
+use schedule::Priority;
+use secrets;
+use summary;
+use timeline::{self, Timeline};
+use grusp_core::corpus;
+use grusp_core::grusp::{CodeContext, ColorChoice, EnclosingRules, HighlightRules, IgnoreRules, LongLines, MimeFilter, TypeMatcher, Normalization, NumberWidth, PathTree, Permissions, Traversal};
+use grusp_core::grusp::{AggregateGroup, Aggregation, Aggregator, FileCollector, FileTypes, GroupedMatches, Index, IndexUpdate, Patterns, Redaction, Replacement, Severity, UniqueScope};
 
 pub struct Opts {
+    pub command: Command,
     pub regex: Regex,
+    pub is_case_insensitive: bool,
+    pub is_explain: bool,
+    pub debug: Option<usize>,
+    pub summary_json: Option<i32>,
+    pub default_flags: Vec<String>,
+    pub verbosity: u64,
+    pub is_no_messages: bool,
+    pub is_suggest: bool,
+    pub is_index_search: bool,
+    pub serve: Option<String>,
+    pub secrets: Option<secrets::Format>,
+    pub patterns: Option<Patterns>,
+    pub policy: Option<Policy>,
+    pub cache_dir: Option<PathBuf>,
     pub queries: Option<Vec<String>>,
     pub is_count_only: bool,
+    pub is_count_total: bool,
     pub is_concurrent: bool,
-    pub is_colored: bool,
+    pub chunk_size: Option<u64>,
+    pub max_line_length: Option<(usize, LongLines)>,
+    pub max_memory: Option<usize>,
+    pub color: ColorChoice,
+    pub is_trimmed: bool,
+    pub number_width: Option<NumberWidth>,
+    pub max_columns: Option<usize>,
+    pub is_vimgrep: bool,
+    pub is_paged: bool,
+    pub is_passthru: bool,
+    pub is_paragraph: bool,
+    pub record_separator: Option<Regex>,
+    pub split_on: Option<Regex>,
+    pub json_fields: Option<JsonFields>,
     pub is_inverted: bool,
+    pub is_crlf: bool,
+    pub is_null_data: bool,
     pub max_depth: Option<usize>,
+    pub query_max_depths: Vec<(String, usize)>,
+    pub line_range: Option<(usize, usize)>,
+    pub search_head: Option<usize>,
+    pub search_tail: Option<usize>,
+    pub max_count_per_line: Option<usize>,
+    pub normalization: Option<Normalization>,
+    pub code_context: Option<CodeContext>,
+    pub enclosing: Option<EnclosingRules>,
+    pub highlights: HighlightRules,
+    pub redaction: Option<Redaction>,
+    pub replacement: Option<Replacement>,
+    pub rewrite: Option<Rewrite>,
+    pub unique: Option<UniqueScope>,
+    pub aggregator: Option<Aggregator>,
+    pub timeline: Option<Timeline>,
+    pub grouped: Option<GroupedMatches>,
+    pub tree: Option<PathTree>,
+    pub file_list: Option<FileList>,
+    pub edit: Option<Edit>,
+    pub top: Option<usize>,
+    pub sarif: Option<sarif::Report>,
+    pub html: Option<html::Report>,
+    pub output: Output,
+    pub diff_trees: Option<(String, String)>,
+    pub baseline: Option<PathBuf>,
+    pub is_check_baseline: bool,
+    pub max_total: Option<u64>,
+    pub max_files: Option<u64>,
+    pub exit_codes: ExitCodes,
     pub just_files: JustFiles,
+    pub exclude: IgnoreRules,
+    pub is_default_excludes: bool,
+    pub is_directives: bool,
+    pub is_git_only: bool,
+    pub git_rev: Option<String>,
+    pub newer_than: Option<SystemTime>,
+    pub older_than: Option<SystemTime>,
+    pub owner: Option<u32>,
+    pub permissions: Option<Permissions>,
+    pub mime: Option<MimeFilter>,
+    pub types: Option<TypeMatcher>,
+    pub traversal: Traversal,
+    pub priority: Option<Priority>,
+}
+
+impl Opts {
+    /// A collector for the files the queries find, filtered by the options.
+    pub fn file_collector<'a>(&self, queries: &'a Vec<String>) -> FileCollector<'a> {
+        self.query_max_depths
+            .iter()
+            .fold(FileCollector::new(queries), |collector, &(ref query, depth)| {
+                collector.query_max_depth(query, depth)
+            })
+            .max_depth(self.max_depth)
+            .ignore(self.exclude.clone())
+            .default_excludes(self.is_default_excludes)
+            .types(self.types.clone())
+            .git_only(self.is_git_only)
+            .modified_after(self.newer_than)
+            .modified_before(self.older_than)
+            .owner(self.owner)
+            .permissions(self.permissions)
+            .traversal(self.traversal)
+    }
 }
 
 #[derive(Eq, PartialEq)]
@@ -38,44 +162,332 @@ impl JustFiles {
     }
 }
 
+/// What grusp was asked to do, by the command it was given. Without one, it searches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Search,
+    /// Lists the files that would be searched, without reading them.
+    Files,
+    /// Replaces the matches with the template given after the regex.
+    Replace,
+    /// Searches indexed directories, only reading the files that can match.
+    Index,
+}
+
+/// The exit code of each way a search can fail, set with `--error-exit-codes`. A code of
+/// 0 lets the search succeed anyway.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExitCodes {
+    pub no_match: i32,
+    pub unreadable: i32,
+    pub max_total: i32,
+    pub fail_on: i32,
+}
+
+impl Default for ExitCodes {
+    fn default() -> Self {
+        ExitCodes { no_match: 1, unreadable: 2, max_total: 3, fail_on: 1 }
+    }
+}
+
+impl ExitCodes {
+    /// The code to exit a search with, from the first of its failures that has a nonzero
+    /// code. Errors come before too many matches, then matches `--fail-on` fails at and
+    /// last no matches at all.
+    pub fn code(&self, is_unreadable: bool, is_over_max_total: bool, is_failed: bool, is_found: bool) -> i32 {
+        [
+            (is_unreadable, self.unreadable),
+            (is_over_max_total, self.max_total),
+            (is_failed, self.fail_on),
+            (!is_found, self.no_match),
+        ]
+            .iter()
+            .find(|&&(failed, code)| failed && code != 0)
+            .map_or(0, |&(_, code)| code)
+    }
+}
+
 #[derive(Debug)]
 pub enum ArgError {
-    InvalidRegex(String),
+    InvalidRegex(RegexError),
+    InvalidLineRange(String),
+    InvalidExclude(String),
+    InvalidTime(String),
+    InvalidOwner(String),
+    InvalidPermissions(String),
+    InvalidHighlightRules(String),
+    InvalidAggregateGroup(String),
+    InvalidTimeline(String),
+    InvalidExitCodes(String),
+    InvalidJsonField(String),
+    InvalidRules(String),
+    InvalidChunkSize(String),
+    InvalidMime(String),
+    InvalidMaxMemory(String),
+    InvalidFileTypes(String),
+    InvalidPatterns(String),
+    InvalidOutput(String),
+    InvalidMaxLineLength(String),
+    InvalidTemplate(String),
+    InvalidDefaultFlags(String),
+    InvalidNumber(String),
     _Incomplete,
 }
 
-fn get_regex(regex: &str, case_insensitive: bool) -> Result<Regex, ArgError> {
-    let regex = match RegexBuilder::new(&regex)
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArgError::InvalidRegex(ref e) => write!(f, "{}", e),
+            ArgError::InvalidLineRange(ref msg) |
+            ArgError::InvalidExclude(ref msg) |
+            ArgError::InvalidTime(ref msg) |
+            ArgError::InvalidOwner(ref msg) |
+            ArgError::InvalidPermissions(ref msg) |
+            ArgError::InvalidHighlightRules(ref msg) |
+            ArgError::InvalidAggregateGroup(ref msg) |
+            ArgError::InvalidTimeline(ref msg) |
+            ArgError::InvalidExitCodes(ref msg) |
+            ArgError::InvalidJsonField(ref msg) |
+            ArgError::InvalidRules(ref msg) |
+            ArgError::InvalidChunkSize(ref msg) |
+            ArgError::InvalidMime(ref msg) |
+            ArgError::InvalidMaxMemory(ref msg) |
+            ArgError::InvalidFileTypes(ref msg) |
+            ArgError::InvalidPatterns(ref msg) |
+            ArgError::InvalidOutput(ref msg) |
+            ArgError::InvalidTemplate(ref msg) |
+            ArgError::InvalidDefaultFlags(ref msg) |
+            ArgError::InvalidNumber(ref msg) |
+            ArgError::InvalidMaxLineLength(ref msg) => write!(f, "{}", msg),
+            ArgError::_Incomplete => write!(f, "Incomplete arguments"),
+        }
+    }
+}
+
+/// A regex that failed to compile. When the syntax is at fault it's displayed with
+/// a caret under the offending character and, for common mistakes, a hint.
+#[derive(Debug)]
+pub struct RegexError {
+    pattern: String,
+    message: String,
+    position: Option<usize>,
+    hint: Option<String>,
+}
+
+impl RegexError {
+    pub fn new(pattern: &str, message: &str) -> Self {
+        RegexError {
+            pattern: pattern.to_string(),
+            message: message.to_string(),
+            position: None,
+            hint: None,
+        }
+    }
+
+    pub fn from_syntax(pattern: &str, error: &SyntaxError) -> Self {
+        RegexError {
+            pattern: pattern.to_string(),
+            message: error.kind().to_string(),
+            position: Some(error.position()),
+            hint: Self::hint(error.kind()),
+        }
+    }
+
+    fn hint(kind: &ErrorKind) -> Option<String> {
+        let hint = match *kind {
+            ErrorKind::UnclosedParen => "unclosed group - did you mean \\( to match a literal parenthesis?".to_string(),
+            ErrorKind::UnopenedParen => "unopened group - did you mean \\) to match a literal parenthesis?".to_string(),
+            ErrorKind::UnexpectedClassEof => "unclosed class - did you mean \\[ to match a literal bracket?".to_string(),
+            ErrorKind::UnclosedRepeat | ErrorKind::MissingBase10 | ErrorKind::InvalidBase10(_) => {
+                "counted repetition - did you mean \\{ to match a literal brace?".to_string()
+            }
+            ErrorKind::RepeaterExpectsExpr => "nothing to repeat - did you mean \\*, \\+ or \\? to match the literal character?".to_string(),
+            ErrorKind::RepeaterUnexpectedExpr(_) => "repeated repetition - wrap it in a group like (a+)* to repeat it again".to_string(),
+            ErrorKind::UnexpectedEscapeEof => "trailing backslash - did you mean \\\\ to match a literal backslash?".to_string(),
+            ErrorKind::UnrecognizedEscape(c) => format!("unknown escape - did you mean \\\\{} to match a literal backslash?", c),
+            ErrorKind::InvalidClassRange { start, end } => format!("backwards range - did you mean [{}-{}]?", end, start),
+            ErrorKind::InvalidRepeatRange { min, max } => format!("backwards range - did you mean {{{},{}}}?", max, min),
+            ErrorKind::UnsupportedClassChar(c) => format!("reserved in classes - did you mean \\{}?", c),
+            _ => return None,
+        };
+        Some(hint)
+    }
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Invalid regex: {}", self.message)?;
+        writeln!(f)?;
+        write!(f, "    {}", self.pattern)?;
+        if let Some(position) = self.position {
+            write!(f, "\n    {:width$}^", "", width = position)?;
+        }
+        if let Some(ref hint) = self.hint {
+            write!(f, "\n\nHint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+fn get_regex(pattern: &str, case_insensitive: bool) -> Result<Regex, ArgError> {
+    let regex = match RegexBuilder::new(pattern)
         .case_insensitive(case_insensitive)
         .build() {
         Ok(regex) => regex,
-        Err(regex::Error::Syntax(msg)) => return Err(ArgError::InvalidRegex(msg)),
+        Err(regex::Error::Syntax(msg)) => {
+            // The regex crate flattens its syntax errors into a string, so parse the
+            // pattern again to recover where the error is.
+            let error = match ExprBuilder::new().case_insensitive(case_insensitive).parse(pattern) {
+                Err(e) => RegexError::from_syntax(pattern, &e),
+                Ok(_) => RegexError::new(pattern, &msg),
+            };
+            return Err(ArgError::InvalidRegex(error));
+        }
         Err(regex::Error::CompiledTooBig(_)) => {
-            return Err(ArgError::InvalidRegex("Regex too large".to_string()))
+            return Err(ArgError::InvalidRegex(RegexError::new(pattern, "Regex too large")))
         }
         Err(_) => {
             return Err(ArgError::InvalidRegex(
-                "Unknown regex parsing error".to_string(),
+                RegexError::new(pattern, "Unknown regex parsing error"),
             ))
         }
     };
     Ok(regex)
 }
 
-fn collect_queries(values: Option<Values>) -> Option<Vec<String>> {
-    values
-        .map(|queries| { queries.map(|p| p.to_owned()).collect() })
-        .or_else(|| {
-            if atty::is(Stream::Stdin) {
-                // Search in current directory if it's a TTY
-                Some(vec![".".to_string()])
-            } else {
-                None // There is probably a pipe stream coming in
-            }
-        })
+fn collect_queries(values: Option<Vec<&str>>) -> Option<Vec<String>> {
+    match values {
+        // Searching just `-` is searching stdin, even from a terminal
+        Some(ref queries) if queries == &["-"] => None,
+        Some(queries) => Some(queries.into_iter().map(|p| p.to_owned()).collect()),
+        // Search in current directory if it's a TTY
+        None if atty::is(Stream::Stdin) => Some(vec![".".to_string()]),
+        None => None, // There is probably a pipe stream coming in
+    }
+}
+
+/// Detects the regex and path being swapped: the regex names a file or directory that
+/// exists while the only path neither exists nor is a glob.
+fn swapped_args_hint(regex: &str, queries: &[String]) -> Option<String> {
+    let query = match queries {
+        [query] => query,
+        _ => return None,
+    };
+    let is_glob = query.contains(['*', '?', '[']);
+    if Path::new(regex).exists() && !is_glob && !Path::new(query).exists() {
+        Some(format!(
+            "Hint: '{}' is a path but '{}' isn't, did you mean `grusp {} {}`? Searching anyway",
+            regex, query, query, regex
+        ))
+    } else {
+        None
+    }
+}
+
+/// Splits a `PATH::DEPTH` query into the path and the max depth to search it to.
+fn parse_query_depth(query: &str) -> (&str, Option<usize>) {
+    if let Some(index) = query.rfind("::") {
+        if let Ok(depth) = query[index + 2..].parse() {
+            return (&query[..index], Some(depth));
+        }
+    }
+    (query, None)
+}
+
+fn parse_line_range(range: &str) -> Result<(usize, usize), ArgError> {
+    let invalid = || ArgError::InvalidLineRange(format!("Expected START:END but got '{}'", range));
+    let mut parts = range.splitn(2, ':');
+    let start = parts.next().ok_or_else(invalid)?;
+    let end = parts.next().ok_or_else(invalid)?;
+    let start = if start.is_empty() { 1 } else { start.parse().map_err(|_| invalid())? };
+    let end = if end.is_empty() { usize::MAX } else { end.parse().map_err(|_| invalid())? };
+    if start == 0 || start > end {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// Parses the value of a flag taking a number, like the `5` of `--search-head 5`.
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T, ArgError> {
+    value.parse().map_err(|_| ArgError::InvalidNumber(format!("Expected a number for --{} but got '{}'", flag, value)))
+}
+
+/// Parses the value of a flag taking a count that has to be at least 1.
+fn parse_positive(flag: &str, value: &str) -> Result<usize, ArgError> {
+    value.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+        ArgError::InvalidNumber(format!("Expected a positive number for --{} but got '{}'", flag, value))
+    })
+}
+
+fn get_exclude_rules(values: Option<Values>) -> Result<IgnoreRules, ArgError> {
+    let mut rules = IgnoreRules::new();
+    for rule in values.into_iter().flatten() {
+        rules.add(rule).map_err(|e| ArgError::InvalidExclude(format!("{}: {}", rule, e.msg)))?;
+    }
+    Ok(rules)
+}
+
+/// Parses either a duration before `now` such as `30m` or `2d`, or a UTC date
+/// formatted as `YYYY-MM-DD` with an optional `THH:MM:SS` time.
+fn parse_time(value: &str, now: SystemTime) -> Result<SystemTime, ArgError> {
+    let invalid = || ArgError::InvalidTime(format!("Expected a duration like '2d' or a date like '2017-12-31' but got '{}'", value));
+    if let Some(unit) = value.chars().last().filter(|c| c.is_alphabetic()) {
+        let amount: u64 = value[..value.len() - 1].parse().map_err(|_| invalid())?;
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 60 * 60 * 24,
+            'w' => 60 * 60 * 24 * 7,
+            _ => return Err(invalid()),
+        };
+        return now.checked_sub(Duration::from_secs(amount * seconds)).ok_or_else(invalid);
+    }
+    let (date, time) = match value.find(['T', ' ']) {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, "00:00:00"),
+    };
+    let date: Vec<i64> = date.split('-').map(|p| p.parse()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    let time: Vec<u64> = time.split(':').map(|p| p.parse()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    if date.len() != 3 || time.is_empty() || time.len() > 3 {
+        return Err(invalid());
+    }
+    let (year, month, day) = (date[0], date[1], date[2]);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return Err(invalid());
+    }
+    let seconds = time.iter().chain([0, 0].iter()).take(3).fold(0, |acc, part| acc * 60 + part);
+    Ok(UNIX_EPOCH + Duration::from_secs(days as u64 * 60 * 60 * 24 + seconds))
+}
+
+/// The number of days since 1970-01-01 in the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Resolves a user name, or a numeric user id, to a user id using the passwd database.
+fn get_uid(user: &str, passwd: &str) -> Result<u32, ArgError> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 2 && fields[0] == user)
+        .and_then(|fields| fields[2].parse().ok())
+        .ok_or_else(|| ArgError::InvalidOwner(format!("Unknown user '{}'", user)))
 }
 
-const EXAMPLES: &'static str = "EXAMPLES:
+const EXAMPLES: &str = "EXAMPLES:
 
 - Use grusp to search from STDIN
 
@@ -93,31 +505,489 @@ const EXAMPLES: &'static str = "EXAMPLES:
 
     $ grusp \'fn.*\\(\' .
 
-- Find all strings that have 'fn' or 'FN', ignoring case. This option is incompatible with '-s'.
+- Find all strings that have 'fn' or 'FN', ignoring case. This option is incompatible with '--case-sensitive'.
 
     $ grusp -i fn .
 
 - Find all strings that have 'fn' only with strict case. This option is incompatible with '-i'.
 
-    $ grusp -s fn .
+    $ grusp --case-sensitive fn .
+
+- Find all strings that have 'fn' between lines 100 and 200 of a file. Either side of the range
+can be left off to search from the start or to the end of the file.
+
+    $ grusp --line-range 100:200 fn src/main.rs
+
+- Find all lines with 'ERROR' in the last 50 lines of every log file. Use --search-head to
+search the start of each file instead.
+
+    $ grusp --search-tail 50 ERROR logs/
+
+- Find all lines with 'fn', highlighting just the first one on each line. Scanning stops at the
+first match, which is faster on long lines.
+
+    $ grusp --max-count-per-line 1 fn .
+
+- Find all lines with 'café' whether the accent is stored as a single character or as a combining
+mark, by normalizing the pattern and every line to the composed form.
+
+    $ grusp --unicode-normalize nfc café docs/
+
+- Find all TODOs left in comments, skipping identifiers and strings that happen to contain TODO.
+Comments and strings are found for C-like languages, Rust, scripting languages and SQL.
+
+    $ grusp --code-context comments TODO src/
+
+- Find all strings that have 'fn' in a large tree many times over. Build a trigram index once, then
+search with it to only read the files that contain the literal parts of the regex. Files added
+after the index is built aren't searched until it's updated, which only reads the files that were
+added or changed.
+
+    $ grusp index build monorepo/
+    $ grusp index update monorepo/
+    $ grusp index search 'fn [a-z_]+_handler' monorepo/
+
+- Find all strings that have 'fn' over and over, only matching the files that changed since the
+last identical search. Matches are cached by the contents of each file and the search options.
+
+    $ grusp --cache ~/.cache/grusp fn .
+
+- Serve searches to an editor plugin, which POSTs requests like {\"regex\": \"fn\", \"paths\": [\"src\"]}
+and gets the matching lines back as JSON. Pass a path instead of a port to use a unix socket.
+
+    $ grusp --serve 7878
+
+- Let an editor extension stream search results over stdin and stdout with JSON-RPC. Each matching
+file is sent as a 'search/partial' notification and '$/cancelRequest' stops a search early.
+
+    $ grusp --serve stdio
+
+- Find out why nothing matched, by listing the files that match when ignoring case or that were
+skipped by --exclude, --depth or the other file filters.
+
+    $ grusp --suggest --exclude 'target/' --depth 2 fixme .
+
+- Find all strings that have 'fn' in src/ and its subdirectories, but only in the top level of
+vendor/. Appending ::NUM to a path overrides --depth for that path.
+
+    $ grusp fn src/ vendor/::0
+
+- Find all TODOs in the Rust sources and manifests of a workspace, leaving out its tests. Quote the
+globs so the shell passes them on, braces and all; a glob starting with ! leaves out what it matches.
+
+    $ grusp TODO 'crates/**/*.{rs,toml}' '!**/tests/**'
+
+- Find all the distinct errors in a log, skipping lines that were already printed. Use
+--unique-global to also skip lines printed for other files.
+
+    $ grusp --unique-lines ERROR logs/
+
+- Follow a log with every line shown, coloring errors red and warnings yellow on top of the
+highlighted matches. Each line of the rules file is a regex, '=' and a color, such as 'ERROR=red'.
+
+    $ tail -f app.log | grusp --passthru --highlight-rules severities.rules 'user=[a-z]+'
+
+- Find credentials that were committed by mistake, using the built-in secrets preset. Presets stand
+in for the regex and can be combined; --preset-list shows them all.
+
+    $ grusp --preset secrets --preset email .
+
+- Upload the credentials found in a repository to GitHub code scanning. Each preset becomes a rule
+of the SARIF report.
+
+    $ grusp --format sarif --preset aws-keys --preset private-keys . > grusp.sarif
+
+- Share the results of an audit with people who don't use the command line, as a page that opens
+in any browser.
+
+    $ grusp --format html --output audit.html 'password|secret' src/
+
+- Keep the results of a long search in a file to go through later, while still watching them come
+in. Colors are kept on the terminal but not in the file.
+
+    $ grusp --output todos.txt --tee TODO .
+
+- Run several lint rules over a project at once, each a NAME=REGEX, so every match shows the rule
+it broke. The rules can also be kept in a file, one per line, and read with -f.
+
+    $ grusp -e 'no-unwrap=[.]unwrap[(][)]' -e 'no-println=println!' src/
+
+- Search a list of paths from a script for a pattern starting with a dash. The pattern is given
+with --regexp wherever it goes, and every argument after -- is a path even when it starts with a
+dash. A regex starting with a dash can also be given after -- itself, before the paths.
+
+    $ grusp --regexp '--verbose' -- \"$@\"
+    $ grusp -- '-v' src/
+
+- Check a policy in CI, showing every match but only failing the build on errors. Rules can be
+given a severity of info, warn or error, and are errors without one.
+
+    $ grusp --fail-on error -e 'todo:info=TODO' -e 'no-dbg:warn=dbg!' -e 'no-unsafe=unsafe' src/
+
+- Stop new TODOs from being added in CI while the existing ones are worked through. Record the
+current ones once, then check against them; only new TODOs are printed and fail the check.
+
+    $ grusp --baseline todos.baseline TODO src/
+    $ grusp --baseline todos.baseline --check-baseline TODO src/
+
+- Check how a refactor changed the uses of a deprecated API, comparing a checkout of the old branch
+with the current tree. Each file whose number of matching lines changed is printed with the change.
+
+    $ grusp --diff-trees ../before . 'old_api[(]'
+
+- Fail CI when more than 20 TODOs are left, without failing when there are none. Files that can't
+be read are reported and exit with 2 by default, so an error can be told apart from a failed check.
+
+    $ grusp --max-total 20 --error-exit-codes no-match=0,max-total=1 TODO src/
+
+- Show what a search resolved its options to and the first 20 files it will search, when
+reporting a bug or working out why a file isn't searched.
+
+    $ grusp --debug=20 fn src/
+
+- Find out why a file wasn't searched, by printing each file that was skipped and the reason as the
+search goes. Give --verbose twice to also see each file searched and how long it took.
+
+    $ grusp --verbose fn src/
+
+- Find the hotspots of a deprecated API, the ten files that call it the most.
+
+    $ grusp --top 10 'old_api[(]' src/
+
+- Check whether a function is still called anywhere in a huge tree, showing just a few
+examples and stopping the search as soon as they're found.
+
+    $ grusp --max-files-with-matches 3 'old_api[(]' .
+
+- Fill Vim's quickfix list with every match, one entry for each, by searching from Vim.
+
+    :set grepprg=grusp\\ --vimgrep grepformat=%f:%l:%c:%m
+    :grep 'old_api[(]' src/
+
+- Preview renaming a function, keeping the case of each use of it
+
+    $ grusp -i --preserve-case -r new_name old_name src/
+
+- Rename a function across a project with the replace command, which takes the replacement after
+the regex, then list the files a search of the project would read without searching them.
+
+    $ grusp replace --in-place 'old_name[(]' 'new_name(' src/
+    $ grusp files --type rust src/
+
+- Rename a function across a project, confirming each line that changes
+
+    $ grusp -r new_name --in-place --interactive 'old_name[(]' src/
+
+- Apply a codemod made of several 'PATTERN -> REPLACEMENT' rules to a project in a single pass
+
+    $ grusp --rules codemod.rules --in-place src/
+
+- Page through the results with a pager of your own, or print them all at once
+
+    $ PAGER='most' grusp fn src/
+    $ grusp --no-pager fn src/
+
+- Replace a hostname everywhere, keeping the originals in a directory of their own
+
+    $ grusp -r db.internal --in-place --backup-dir /tmp/before-rename db[.]local config/
+
+- Open every file that still calls a function in your editor, starting at the first call.
+
+    $ grusp --edit 'old_api[(]' src/
+
+- Group the requests of an access log by their status code, listing the requests under each code.
+
+    $ grusp --group-by status '\" (?P<status>[0-9]{3}) ' access.log
+
+- Group the requests of every rotated log by their status code without holding more than about
+256M of them at once. Once the budget is exceeded the groups so far are printed and a new block
+starts, so a code can be listed in more than one block.
+
+    $ grusp --max-memory 256M --group-by status '\" (?P<status>[0-9]{3}) ' logs/
+
+- Add up the time spent in each endpoint of an access log, like an awk one-liner would.
+
+    $ grusp --agg sum --agg-by endpoint '(?P<endpoint>/[a-z/]+) took ([0-9.]+)ms' access.log
+
+- See when an incident started by counting the errors logged each minute. Empty minutes are
+shown too, so quiet periods stand out.
+
+    $ grusp --timeline iso8601 --timeline-bucket minute ERROR app.log
+
+- Share the requests of a user from a log without the tokens they sent. Each token is replaced by a
+short hash, so the same token can still be followed from line to line.
+
+    $ grusp --redact=hash 'token=[A-Za-z0-9]+' app.log
+
+- Block commits that add credentials from a pre-commit hook. --secrets also reports random looking
+strings assigned to keys, tokens and passwords, with a severity for each, and exits 1 when any are
+found. Use --secrets-format json for other tools to read.
+
+    $ git diff --cached --name-only | xargs grusp --secrets
+
+- Find all strings that have 'fn', skipping generated code but still searching build.rs. Exclude
+rules follow .gitignore semantics: later rules win, '!' re-includes and a trailing '/' only matches
+directories.
+
+    $ grusp --exclude 'target/' --exclude '*.rs' --exclude '!build.rs' fn .
+
+- Fail a CI job when code calls unwrap, except on lines marked '// grusp:ignore-line' and in files
+with 'grusp:ignore-file' in their first 5 lines, such as generated code.
+
+    $ grusp --directives --count 'unwrap[(][)]' src/ && exit 1
+
+- Keep metrics of a search in a CI job while its matches are printed as usual. The stats are
+written as a line of JSON to file descriptor 3, or to stderr with just --summary-json.
+
+    $ grusp --summary-json=3 'unwrap[(][)]' src/ 3>summary.json
+
+- Find where a function is called, starting with the files that were just edited so the code
+being worked on shows up first.
+
+    $ grusp --prioritize recent-first 'parse_config[(]' .
+
+- Count the lines that call unwrap in each file, ending with a 'TOTAL: N' line that sums them
+when more than one file matched. Use --no-total to leave it out.
+
+    $ grusp --count 'unwrap[(][)]' src/
+
+- Search every file under assets/, including the images, archives and compiled files that are
+skipped by default when walking directories.
+
+    $ grusp --no-default-excludes 'Copyright' assets/
+
+- Find all strings that have 'fn' in the files of the current directory that are tracked by git.
+
+    $ grusp --git fn .
+
+- Find all strings that have 'fn' in the src directory as it was three commits ago. Files are
+reported with virtual paths such as 'HEAD~3:src/main.rs'.
+
+    $ grusp --git-rev HEAD~3 fn src
+
+- Find all errors in log files that were modified in the last 2 days. Dates such as 2017-12-31
+are also accepted, as is --older-than to find files that haven't changed recently.
+
+    $ grusp --newer-than 2d ERROR logs/
+
+- Find all passwords in world-writable config files owned by the www-data user. Modes follow
+find: 644 matches exactly, -220 requires all of the bits and /002 requires any of them.
+
+    $ grusp --owner www-data --perm /002 password /etc
+
+- Find all TODOs in the text files of a directory mixed with images and builds, whatever they're
+named. Types are told from the first bytes of each file, and text/* takes every subtype.
+
+    $ grusp --mime 'text/*' TODO assets/
+
+- Find all TODOs in the web files of a project, defining the type on the fly. Definitions add to
+the built-in types, list them with --type-list. Add 'type-add web:*.html,*.css,*.js' to
+~/.config/grusp/config, or the file $GRUSP_CONFIG names, to keep a definition for every search.
+
+    $ grusp --type-add 'web:*.html,*.css,*.js' --type web TODO .
+
+- Trim every search and leave target/ out by default, without a config file. The flags given on
+the command line override the defaults, and --no-env skips them for a single search.
+
+    $ export GRUSP_DEFAULT_FLAGS='--trim --exclude target/'
+    $ grusp --no-env fn .
+
+- Explain how a pattern is parsed, which literals can be used to speed up the search and how
+complex it is, without searching anything. Useful for finding out why a search is slow.
+
+    $ grusp --explain '\\bfn\\s+\\w+\\('
+
+- Install completions for bash. zsh, fish and powershell are also supported.
+
+    $ grusp --completions bash > /etc/bash_completion.d/grusp
+
+- Install the man page.
+
+    $ grusp --generate-man > /usr/local/share/man/man1/grusp.1
+
+- Find all files with 'test' in their name, reading NUL separated paths from find. Use --crlf
+to search files with Windows line endings, so that $ matches at the end of each line.
+
+    $ find . -print0 | grusp -z 'test[^/]*$'
+
+- Highlight errors in a log as it's written while still showing every other line.
+
+    $ tail -f app.log | grusp --passthru ERROR
+
+- Search a compressed log. Gzip and zstd streams on stdin are decompressed before matching.
+
+    $ cat app.log.gz | grusp ERROR
+
+- Search the output of a command along with files, like grep. A - among the paths is stdin,
+shown as <stdin>.
+
+    $ journalctl -u app | grusp ERROR app.log - old.log
+
+- Find the stack traces in a log that pass through a function, printing each whole trace
+rather than just the line that names it.
+
+    $ grusp -p 'at parse_config' app.log
+
+- Find the log entries that mention a user, where each entry starts with a timestamp and can
+span several lines.
+
+    $ grusp --record-separator '^[0-9]{4}-[0-9]{2}-[0-9]{2} ' 'user=alice' app.log
+
+- Find the debug prints added by a commit, reported under the diff of each file they were added to
+rather than all together as stdin.
+
+    $ git show HEAD | grusp --split-on '^diff --git ' '^[+].*dbg!'
+
+- Find the errors in a JSON-lines log without matching the word in any other field, printing
+each whole line. Nested fields are named with dots.
+
+    $ grusp --json-field level=error --json-field user.name=alice app.log
 
 - Find all strings that have 'fn', using un-colored output. This can be used for an extremely small
 speed boost, or compatibility with terminals without ANSI Color support.
 
     $ grusp --nocolor fn .
 
+- Find the TODOs in a project along with the function each of them is in, which is shown above
+the TODOs like a heading.
+
+    $ grusp --show-enclosing TODO src/
+
+- Find all strings that have 'fn' in a large tree, printing each directory once with its
+matching files indented beneath it.
+
+    $ grusp --tree fn .
+
+- Find all strings that have 'fn', with the line numbers of each file right aligned in a column.
+
+    $ grusp --number-width auto fn .
+
+- Find a function call in minified JavaScript, showing just the part of each line around it
+
+    $ grusp --max-columns 80 'fetch[(]' dist/
+
+- Search a huge log on every core, a chunk of 64 MiB on each thread at a time
+
+    $ grusp --chunk-size 64M 'status=5[0-9][0-9]' access.log
+
+- Search a directory that has huge files without line breaks, such as minified bundles or JSON
+dumps, without reading each of them into memory. Lines longer than 1M are searched in pieces.
+
+    $ grusp --max-line-length 1M --long-lines chunk 'api_key' data/
+
+- Keep the colors when paging through the results. Output is only colored in a terminal, and never
+when NO_COLOR is set, like other command line tools.
+
+    $ CLICOLOR_FORCE=1 grusp fn . | less -R
+
 - Find all strings that have 'fn', run on a single thread. By default grusp will attempt to use multiple
 threads to speed up the search process. If this is un-desired in your environment, set the --unthreaded flag
 
     $ grusp --unthreaded fn .
 ";
 
-pub fn get_opts() -> Result<Opts, ArgError> {
-    let matches = App::new("Grusp")
+/// The options that a replacement can't be shown with, for --replace and the template
+/// of `grusp replace`.
+const REPLACE_CONFLICTS: &[&str] = &[
+    "redact",
+    "count",
+    "files-with-matches",
+    "files-without-matches",
+    "invert-match",
+    "agg",
+    "timeline",
+    "group-by",
+    "top",
+    "format",
+];
+
+/// The commands of the app, so that a first word naming one isn't taken for the regex.
+const COMMANDS: &[&str] = &["search", "files", "replace", "index"];
+
+/// The actions of `grusp index`.
+const INDEX_ACTIONS: &[&str] = &["build", "update", "search"];
+
+fn build_app() -> App<'static, 'static> {
+    let app = App::new("Grusp")
         .setting(AppSettings::ArgRequiredElseHelp)
+        // Any argument before a command makes it the regex or a path, so `grusp -e files .`
+        // searches for files and `grusp fn search` searches the directory search
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .setting(AppSettings::SubcommandsNegateReqs)
         .after_help(EXAMPLES)
         .author("Kevin C. <chewbacha@gmail.com>; Charlie K. <bringking@gmail.com>")
         .about("Searches with regex through files. For fun!")
+        .subcommand(search_args(
+            SubCommand::with_name("search")
+                .about("Searches with regex through files, the same as without a command. Use it to search for a regex named like a command"),
+            Command::Search,
+        ))
+        .subcommand(search_args(
+            SubCommand::with_name("files")
+                .about("Lists the files that would be searched, after every filter, without searching them"),
+            Command::Files,
+        ))
+        .subcommand(search_args(
+            SubCommand::with_name("replace")
+                .about("Prints each match replaced with TEMPLATE, or writes the replacements into the files with --in-place"),
+            Command::Replace,
+        ))
+        .subcommand(
+            SubCommand::with_name("index")
+                .about("Builds, updates or searches with trigram indexes of directories")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("build")
+                        .about("Builds a trigram index of DIR")
+                        .arg(Arg::with_name("DIR").index(1).required(true).help("The directory to index")),
+                )
+                .subcommand(
+                    SubCommand::with_name("update")
+                        .about("Updates the index of DIR by reading only the files that were added or changed")
+                        .arg(Arg::with_name("DIR").index(1).required(true).help("The indexed directory")),
+                )
+                .subcommand(search_args(
+                    SubCommand::with_name("search")
+                        .about("Searches the indexed directories given as paths, skipping the files that can't match"),
+                    Command::Index,
+                )),
+        );
+    search_args(app, Command::Search)
+}
+
+/// Makes a first word that isn't exactly a command the regex of a search, so `grusp hello .`
+/// searches for hello where clap would take it for a mistyped `help` and fail.
+fn search_unless_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    let is_search = args.get(1).is_some_and(|arg| {
+        !arg.to_string_lossy().starts_with('-') && arg != "help" && !COMMANDS.iter().any(|command| arg == command)
+    });
+    if is_search {
+        args.insert(1, OsString::from("search"));
+    }
+    args
+}
+
+/// Returns how many of the arguments, after the binary, name the command and the action
+/// of `grusp index`.
+fn command_len(args: &[OsString]) -> usize {
+    let is_in = |names: &[&str], i: usize| args.get(i).is_some_and(|arg| names.iter().any(|name| arg == name));
+    match (is_in(COMMANDS, 1), is_in(&["index"], 1)) {
+        (true, true) => 1 + is_in(INDEX_ACTIONS, 2) as usize,
+        (true, false) => 1,
+        _ => 0,
+    }
+}
+
+/// Adds the options of a search to the app or to one of its commands, followed by the
+/// positional arguments the command takes.
+fn search_args(app: App<'static, 'static>, command: Command) -> App<'static, 'static> {
+    // The template of `grusp replace` is a replacement too. With --rules it's a path
+    let replacement = match command {
+        Command::Replace => ArgGroup::with_name("replacement").args(&["replace", "rules", "TEMPLATE"]).multiple(true),
+        _ => ArgGroup::with_name("replacement").args(&["replace", "rules"]),
+    };
+    let app = app
+        .group(replacement)
         .arg(
             Arg::with_name("case-sensitive")
                 .long("case-sensitive")
@@ -132,58 +1002,883 @@ pub fn get_opts() -> Result<Opts, ArgError> {
                 .help("Regex is matched case insensitively"),
         )
         .arg(Arg::with_name("count").short("c").long("count").help(
-            "Just counts the matches found",
-        ))
-        .arg(Arg::with_name("unthreaded").long("unthreaded").help(
-            "Runs in a single thread",
-        ))
-        .arg(Arg::with_name("notcolored").long("nocolor").help(
-            "Output is not colored",
-        ))
-        .arg(Arg::with_name("invert-match").long("invert-match").short("v").help(
-            "Match every line not containing the specified pattern"
-        ))
-        .arg(Arg::with_name("files-with-matches").long("files-with-matches").help(
-            "Only print the names of files containing matches, not the matching lines. An empty query will print all files that would be searched.",
+            "Just counts the matches found, ending with a TOTAL line when more than one file matched",
         ))
         .arg(
-            Arg::with_name("files-without-matches")
-                .long("files-without-matches")
-                .conflicts_with("files-with-matches")
-                .help(
-                "Only print the names of files not containing matches. An empty query will print no files.",
-                )
+            Arg::with_name("no-total")
+                .long("no-total")
+                .help("Don't end --count with the TOTAL line")
         )
         .arg(
-            Arg::with_name("depth")
-                .takes_value(true)
-                .value_name("NUM")
-                .long("depth")
-                .help("Search up to NUM directories deep")
+            Arg::with_name("passthru")
+                .long("passthru")
+                .conflicts_with_all(&["count", "files-with-matches", "files-without-matches"])
+                .help("Prints every line, highlighting the matches. Lines from stdin are printed as soon as they're read")
         )
         .arg(
-            Arg::with_name("REGEX")
-                .index(1)
-                .value_name("REGEX")
-                .required(true)
-                .help("The pattern that should be matched. This can be any valid Perl-style
-Regular expression, with a few caveats. See the \
-Rust Regex documentation \
-for detailed information https://doc.rust-lang.org/regex/regex/index.html."),
+            Arg::with_name("paragraph")
+                .long("paragraph")
+                .short("p")
+                .conflicts_with("passthru")
+                .help("Matches paragraphs separated by blank lines instead of lines, printing the whole of each that matches")
+        )
+        .arg(
+            Arg::with_name("record-separator")
+                .takes_value(true)
+                .value_name("REGEX")
+                .long("record-separator")
+                .conflicts_with_all(&["paragraph", "passthru"])
+                .help("Matches records instead of lines, each starting at a line matching REGEX, printing the whole of each that matches")
+        )
+        .arg(
+            Arg::with_name("split-on")
+                .takes_value(true)
+                .value_name("REGEX")
+                .long("split-on")
+                .conflicts_with_all(&["PATTERN", "edit", "in-place", "interactive", "git-rev", "index", "baseline", "diff-trees", "secrets"])
+                .help("Splits stdin into documents, each starting at a line matching REGEX, and reports the matches of each like those of a file named by that line. Lines are numbered from the one after it")
+        )
+        .arg(Arg::with_name("trim").long("trim").help(
+            "Removes leading whitespace from displayed lines",
+        ))
+        .arg(Arg::with_name("unthreaded").long("unthreaded").help(
+            "Runs in a single thread. A few small files are always searched in one, as starting the threads would take longer",
+        ))
+        .arg(Arg::with_name("explain").long("explain").help(
+            "Explains how the regex is parsed and optimized instead of searching",
+        ))
+        .arg(
+            Arg::with_name("debug")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("N")
+                .long("debug")
+                .help("Prints the resolved options, the parsed regex, the ignore rules and the first N files that will be searched, 10 by default, to stderr before searching, and each file that couldn't be read after. Attach it to bug reports")
+        )
+        .arg(
+            Arg::with_name("summary-json")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("FD")
+                .long("summary-json")
+                .help("Writes the stats of the search as a line of JSON to stderr once it's done, or to the file descriptor FD, like --summary-json=3 with 3>summary.json, so scripts can read them apart from the matches")
+        )
+        .arg(Arg::with_name("verbose").long("verbose").multiple(true).help(
+            "Prints each file skipped and why, and how long the search took, to stderr. Given twice, also prints each file searched, what matched in it and how long it took. -v is --invert-match, so it has no short form",
+        ))
+        .arg(Arg::with_name("no-messages").long("no-messages").help(
+            "Don't report the files that couldn't be read or don't exist. The exit code still reflects them",
+        ))
+        .arg(Arg::with_name("suggest").long("suggest").help(
+            "When nothing matches, reports files that match when ignoring case or that were skipped by the file filters",
+        ))
+        .arg(Arg::with_name("no-hints").long("no-hints").help(
+            "Don't warn when the regex looks like a path and the path looks like a regex",
+        ))
+        .arg(
+            Arg::with_name("index")
+                .takes_value(true)
+                .value_name("ACTION")
+                .long("index")
+                .possible_values(&["build", "update", "search"])
+                .help("Build a trigram index of the directory given in place of the regex, update it by reading only the files that changed, or search the indexed directories using their index to skip files that can't match")
+        )
+        .arg(
+            Arg::with_name("cache")
+                .takes_value(true)
+                .value_name("DIR")
+                .long("cache")
+                .help("Cache the matches of each file in DIR, keyed by its contents and the search options, and reuse them while the file is unchanged")
+        )
+        .arg(
+            Arg::with_name("serve")
+                .takes_value(true)
+                .value_name("SOCKET|PORT")
+                .long("serve")
+                .help("Answer JSON search requests over HTTP on a localhost PORT, or one per line on a unix SOCKET or 'stdio', keeping the files found between searches. Lines can also be JSON-RPC calls which stream results and can be cancelled")
+        )
+        .arg(
+            Arg::with_name("bench-corpus")
+                .takes_value(true)
+                .value_name("DIR")
+                .long("bench-corpus")
+                .hidden(true)
+                .help("Writes the synthetic benchmark corpora to DIR instead of searching")
+        )
+        .arg(
+            Arg::with_name("completions")
+                .takes_value(true)
+                .value_name("SHELL")
+                .long("completions")
+                .possible_values(&Shell::variants())
+                .help("Prints a completion script for SHELL instead of searching")
+        )
+        .arg(Arg::with_name("generate-man").long("generate-man").help(
+            "Prints a roff man page instead of searching",
+        ))
+        .arg(Arg::with_name("git").long("git").help(
+            "Only search files that are tracked by git",
+        ))
+        .arg(Arg::with_name("notcolored").long("nocolor").help(
+            "Output is not colored. Without it output is colored when written to a terminal, unless NO_COLOR is set or CLICOLOR is 0. Set CLICOLOR_FORCE to color piped output",
+        ))
+        .arg(Arg::with_name("no-pager").long("no-pager").help(
+            "Print the results straight to the terminal. Without it results that don't fit on a screen are shown in $PAGER, or less when it isn't set",
+        ))
+        .arg(Arg::with_name("crlf").long("crlf").help(
+            "Strips carriage returns from the end of lines so that $ matches at the end of CRLF lines",
+        ))
+        .arg(Arg::with_name("null-data").long("null-data").short("z").help(
+            "Lines are separated by NUL bytes instead of newlines",
+        ))
+        .arg(Arg::with_name("invert-match").long("invert-match").short("v").help(
+            "Match every line not containing the specified pattern. Counts, line numbers and the headings of --show-enclosing are those of the lines kept"
+        ))
+        .arg(Arg::with_name("files-with-matches").long("files-with-matches").help(
+            "Only print the names of files containing matches, not the matching lines, sorted by path once the search is done. An empty query will print all files that would be searched.",
+        ))
+        .arg(
+            Arg::with_name("max-files-with-matches")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("max-files-with-matches")
+                .conflicts_with_all(&["files-without-matches", "top", "diff-trees"])
+                .help("Stops the search once NUM files have matched. The files are the first NUM searched when --unthreaded, otherwise the first NUM to finish")
+        )
+        .arg(
+            Arg::with_name("files-without-matches")
+                .long("files-without-matches")
+                .conflicts_with("files-with-matches")
+                .help(
+                "Only print the names of files not containing matches, sorted by path once the search is done. An empty query will print no files.",
+                )
+        )
+        .arg(
+            Arg::with_name("depth")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("depth")
+                .help("Search up to NUM directories deep")
+        )
+        .arg(
+            Arg::with_name("line-range")
+                .takes_value(true)
+                .value_name("START:END")
+                .long("line-range")
+                .help("Only match lines between START and END (inclusive) and stop reading after END")
+        )
+        .arg(
+            Arg::with_name("search-head")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("search-head")
+                .conflicts_with("search-tail")
+                .help("Only search the first NUM lines of each file")
+        )
+        .arg(
+            Arg::with_name("search-tail")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("search-tail")
+                .help("Only search the last NUM lines of each file")
+        )
+        .arg(
+            Arg::with_name("show-enclosing")
+                .long("show-enclosing")
+                .conflicts_with_all(&["passthru", "count", "files-with-matches", "files-without-matches", "agg", "timeline", "group-by", "top", "format"])
+                .help("Shows the function, type or Markdown section each group of matching lines is in above them. Supports Rust, Python, Ruby, Go, JavaScript, TypeScript, C, C++, shell scripts and Markdown")
+        )
+        .arg(
+            Arg::with_name("tree")
+                .long("tree")
+                .conflicts_with_all(&["agg", "timeline", "group-by", "top", "format", "diff-trees"])
+                .help("Prints the matching files beneath their directories, so the directories shared by many files are only printed once")
+        )
+        .arg(
+            Arg::with_name("vimgrep")
+                .long("vimgrep")
+                .conflicts_with_all(&[
+                    "count", "files-with-matches", "files-without-matches", "invert-match", "passthru", "paragraph",
+                    "record-separator", "agg", "timeline", "group-by", "top", "format", "tree", "diff-trees",
+                ])
+                .help("Prints each match on a line of its own as path:line:column:text, which Vim reads with :set grepprg=grusp\\ --vimgrep grepformat=%f:%l:%c:%m")
+        )
+        .arg(
+            Arg::with_name("edit")
+                .long("edit")
+                .conflicts_with_all(&["files-without-matches", "diff-trees"])
+                .help("Opens the matching files in $EDITOR after searching, with the first at the line of its first match when the editor supports it")
+        )
+        .arg(
+            Arg::with_name("number-width")
+                .takes_value(true)
+                .value_name("N|auto")
+                .long("number-width")
+                .help("Right aligns the line numbers of each file in a column N digits wide, or as wide as the file's largest line number with auto")
+        )
+        .arg(
+            Arg::with_name("chunk-size")
+                .takes_value(true)
+                .value_name("SIZE")
+                .long("chunk-size")
+                .help("Search each file larger than SIZE in chunks of about SIZE bytes on several threads, such as 64M for a multi-gigabyte log. SIZE can end in K, M or G. Options that read a file in order, like --head or --paragraph, still search it in one go")
+        )
+        .arg(
+            Arg::with_name("max-line-length")
+                .takes_value(true)
+                .value_name("SIZE")
+                .long("max-line-length")
+                .help("Read at most SIZE bytes of a line at once, such as 1M, so a file without line breaks like a minified bundle doesn't have to fit in memory. Longer lines are skipped, or searched in pieces with --long-lines chunk, and how many there were is reported. SIZE can end in K, M or G")
+        )
+        .arg(
+            Arg::with_name("long-lines")
+                .takes_value(true)
+                .value_name("ACTION")
+                .long("long-lines")
+                .possible_values(&["skip", "chunk"])
+                .requires("max-line-length")
+                .help("What to do with lines longer than --max-line-length: skip them, the default, or search them in pieces of that length, where a match cut in two isn't found")
+        )
+        .arg(
+            Arg::with_name("max-memory")
+                .takes_value(true)
+                .value_name("SIZE")
+                .long("max-memory")
+                .help("Keep about SIZE bytes of results in memory for --group-by and --tree, such as 256M. Once exceeded the results so far are printed as a block of their own, so a group or directory can appear in more than one block. SIZE can end in K, M or G")
+        )
+        .arg(
+            Arg::with_name("max-columns")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("max-columns")
+                .short("M")
+                .conflicts_with("vimgrep")
+                .help("Show at most NUM characters of each line, centered on its first match, with ellipses where it's cut off. Matches in long lines, such as in minified files, are still shown")
         )
         .arg(
-            Arg::with_name("PATTERN")
-                .index(2)
+            Arg::with_name("max-count-per-line")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("max-count-per-line")
+                .help("Stop scanning a line after NUM matches. Only the first match is needed when counting, so that's the default for --count")
+        )
+        .arg(
+            Arg::with_name("unicode-normalize")
+                .takes_value(true)
+                .value_name("FORM")
+                .long("unicode-normalize")
+                .possible_values(&["nfc", "nfd"])
+                .help("Normalize the regex and every line to FORM before matching, so composed and decomposed accents match each other")
+        )
+        .arg(
+            Arg::with_name("code-context")
+                .takes_value(true)
+                .value_name("CONTEXT")
+                .long("code-context")
+                .possible_values(&["comments", "strings", "code"])
+                .help("Only keep matches inside comments, inside string literals or in the rest of the code. Only files in known languages are searched")
+        )
+        .arg(
+            Arg::with_name("unique-lines")
+                .long("unique-lines")
+                .conflicts_with("passthru")
+                .help("Don't print a matching line if an identical line, ignoring surrounding whitespace, was already printed for the same file")
+        )
+        .arg(
+            Arg::with_name("unique-global")
+                .long("unique-global")
+                .conflicts_with("passthru")
+                .help("Don't print a matching line if an identical line was already printed for any file")
+        )
+        .arg(
+            Arg::with_name("highlight-rules")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("highlight-rules")
+                .help("Color the parts of each line matching the rules in FILE, one REGEX=COLOR per line. Later rules win")
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .takes_value(true)
                 .multiple(true)
+                .number_of_values(1)
+                .value_name("GLOB")
+                .long("exclude")
+                .help("Skip files and directories matching the gitignore-style GLOB. Can be repeated, later rules take precedence")
+        )
+        .arg(
+            Arg::with_name("directives")
+                .long("directives")
+                .help("Skip the matches on lines with a grusp:ignore-line comment, and in files with grusp:ignore-file in their first 5 lines, for exceptions to a policy checked in CI")
+        )
+        .arg(
+            Arg::with_name("no-default-excludes")
+                .long("no-default-excludes")
+                .help("Search files with the extension of a binary format, such as .png, .zip or .class, which are skipped by default when walking directories")
+        )
+        .arg(
+            Arg::with_name("git-rev")
+                .takes_value(true)
+                .value_name("REV")
+                .long("git-rev")
+                .help("Search the files as they were in the git revision REV instead of the working tree")
+        )
+        .arg(
+            Arg::with_name("newer-than")
+                .takes_value(true)
+                .value_name("DURATION|DATE")
+                .long("newer-than")
+                .help("Only search files modified within DURATION (e.g. 30m, 2d, 1w) or since DATE (e.g. 2017-12-31)")
+        )
+        .arg(
+            Arg::with_name("older-than")
+                .takes_value(true)
+                .value_name("DURATION|DATE")
+                .long("older-than")
+                .help("Only search files last modified more than DURATION ago or before DATE")
+        )
+        .arg(
+            Arg::with_name("owner")
+                .takes_value(true)
+                .value_name("USER")
+                .long("owner")
+                .help("Only search files owned by USER, given as a name or a user id (unix only)")
+        )
+        .arg(
+            Arg::with_name("perm")
+                .takes_value(true)
+                .value_name("MODE")
+                .long("perm")
+                .help("Only search files whose permission bits are exactly the octal MODE, have all of the bits in -MODE or any of the bits in /MODE (unix only)")
+        )
+        .arg(
+            Arg::with_name("mime")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("TYPE")
+                .long("mime")
+                .help("Only search files whose content type, told from the first bytes of the file, is TYPE, e.g. text/plain or text/*. Can be repeated")
+        )
+        .arg(
+            Arg::with_name("type")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("TYPE")
+                .short("t")
+                .long("type")
+                .help("Only search files of the TYPE, such as rust or py, when walking directories. Can be repeated to search any of them")
+        )
+        .arg(
+            Arg::with_name("type-add")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME:GLOBS")
+                .long("type-add")
+                .help("Defines a file type, such as 'web:*.html,*.css,*.js', adding the globs to the type if it's already defined. 'NAME:' clears the type. Can be repeated")
+        )
+        .arg(Arg::with_name("type-list").long("type-list").help(
+            "Lists the file types and their globs, including the ones defined in the config file and with --type-add",
+        ))
+        .arg(Arg::with_name("no-config").long("no-config").help(
+            "Skips the config file, at $GRUSP_CONFIG or ~/.config/grusp/config",
+        ))
+        .arg(Arg::with_name("no-env").long("no-env").help(
+            "Ignores the default flags of $GRUSP_DEFAULT_FLAGS, which come before the flags given and are overridden by them",
+        ))
+        .arg(
+            Arg::with_name("traversal")
+                .takes_value(true)
+                .value_name("ORDER")
+                .long("traversal")
+                .possible_values(&["dfs", "bfs"])
+                .default_value("dfs")
+                .help("Walk directories depth first or breadth first. Breadth first shows matches closest to the top first, most noticeably with --unthreaded")
+        )
+        .arg(
+            Arg::with_name("prioritize")
+                .takes_value(true)
+                .value_name("ORDER")
+                .long("prioritize")
+                .possible_values(&["small-first", "recent-first"])
+                .help("Search the smallest or the most recently modified files first, once all the files to search are found, so useful matches show up sooner. Files of --git-rev are searched in the order they're found")
+        )
+        .arg(
+            Arg::with_name("preset")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME")
+                .long("preset")
+                .possible_values(&presets::names())
+                .help("Search for a curated regex instead of giving one, such as todos, ipv4 or secrets. Can be repeated to match any of them")
+        )
+        .arg(
+            Arg::with_name("regexp")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .value_name("PATTERN")
-                .help("The files to search. This is optional and not used if grusp is searching from stdin"),
+                .short("e")
+                .long("regexp")
+                // Like grep, the value is taken as it is, so a pattern can start with -
+                .allow_hyphen_values(true)
+                .conflicts_with_all(&["preset", "secrets", "json-field", "rules"])
+                .help("Search for PATTERN instead of giving REGEX, so every positional argument is a path. PATTERN can start with - and come after the paths, so scripts can search any paths safely with -e PATTERN -- PATH... A pattern like NAME=REGEX shows NAME beside each of its matches. Can be repeated to match any of them, where the one listed first wins when several match at the same place")
+        )
+        .arg(
+            Arg::with_name("regexp-file")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FILE")
+                .short("f")
+                .long("regexp-file")
+                .conflicts_with_all(&["preset", "secrets", "json-field", "rules"])
+                .help("Search for the patterns in FILE, one per line like --regexp, after those of --regexp. Blank lines and lines starting with # are skipped")
+        )
+        .arg(
+            Arg::with_name("fail-on")
+                .takes_value(true)
+                .value_name("SEVERITY")
+                .long("fail-on")
+                .possible_values(&["warn", "error"])
+                .conflicts_with_all(&["invert-match", "diff-trees"])
+                .help("Fail only when a match is at least as severe as SEVERITY, where patterns like NAME:SEVERITY=REGEX are info, warn or error and the rest are errors. Every match is still shown, and no matches at all isn't a failure")
+        )
+        .arg(Arg::with_name("preset-list").long("preset-list").help(
+            "Lists the presets and their regexes",
+        ))
+        .arg(
+            Arg::with_name("json-field")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FIELD=REGEX")
+                .long("json-field")
+                .conflicts_with_all(&["preset", "secrets", "invert-match", "passthru", "paragraph", "record-separator"])
+                .help("Parses each line as JSON and matches REGEX against FIELD only, instead of giving a regex. Dots name nested fields. Can be repeated to match all of them")
+        )
+        .arg(
+            Arg::with_name("agg")
+                .takes_value(true)
+                .value_name("STAT")
+                .long("agg")
+                .possible_values(&["sum", "avg", "min", "max", "count"])
+                .conflicts_with_all(&["count", "files-with-matches", "files-without-matches", "invert-match", "passthru"])
+                .help("Prints a statistic of the numbers in the first capture group of each match, or in the whole match when the regex has no groups, instead of the matches")
+        )
+        .arg(
+            Arg::with_name("agg-by")
+                .takes_value(true)
+                .value_name("GROUP")
+                .long("agg-by")
+                .requires("agg")
+                .help("Prints the --agg statistic for each value of a capture group, given by its number or name. The numbers are then taken from the first other group")
+        )
+        .arg(
+            Arg::with_name("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .long("format")
+                .possible_values(&["text", "sarif", "html"])
+                .conflicts_with_all(&["count", "files-with-matches", "files-without-matches", "invert-match", "passthru", "agg", "timeline", "group-by", "top", "diff-trees"])
+                .help("Print the matches as text, the default, as a SARIF 2.1 report for code scanning dashboards with a rule for each preset, or as a standalone HTML page with a section for each file")
+        )
+        .arg(
+            Arg::with_name("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("output")
+                .help("Write the results to FILE, without colors, instead of printing them")
+        )
+        .arg(
+            Arg::with_name("tee")
+                .long("tee")
+                .requires("output")
+                .help("Print the results as well as writing them to the file of --output")
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("baseline")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("baseline")
+                .conflicts_with_all(&["agg", "timeline", "group-by", "top", "diff-trees"])
+                .help("Records where the regex matches to FILE, by path and a hash of each line, to check against later with --check-baseline")
+        )
+        .arg(
+            Arg::with_name("check-baseline")
+                .long("check-baseline")
+                .requires("baseline")
+                .help("Prints only the matches that aren't in the --baseline file, failing if there are any. Use it in CI to stop new uses of something without fixing the existing ones first")
+        )
+        .arg(
+            Arg::with_name("diff-trees")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["OLD", "NEW"])
+                .long("diff-trees")
+                .conflicts_with_all(&["PATTERN", "top", "agg", "timeline", "group-by", "passthru"])
+                .help("Searches two copies of a tree, such as checkouts of two branches, and prints the files whose number of matching lines changed between them")
+        )
+        .arg(
+            Arg::with_name("max-total")
+                .takes_value(true)
+                .value_name("NUM")
+                .long("max-total")
+                .help("Fails the search when more than NUM lines match in total, with the max-total code of --error-exit-codes")
+        )
+        .arg(
+            Arg::with_name("error-exit-codes")
+                .takes_value(true)
+                .value_name("OUTCOME=CODE,...")
+                .long("error-exit-codes")
+                .help("Sets the exit code of each way a search can fail: no-match when nothing matched (1), unreadable when a file couldn't be read (2) and max-total when more lines matched than --max-total allows (3) and fail-on when a match is as severe as --fail-on (1). A code of 0 lets the search succeed anyway")
+        )
+        .arg(
+            Arg::with_name("top")
+                .takes_value(true)
+                .value_name("N")
+                .long("top")
+                .conflicts_with_all(&["agg", "timeline", "group-by", "files-with-matches", "files-without-matches", "passthru"])
+                .help("Prints just the N files with the most matching lines, from the most to the fewest, once the search is done")
+        )
+        .arg(
+            Arg::with_name("group-by")
+                .takes_value(true)
+                .value_name("GROUP")
+                .long("group-by")
+                .conflicts_with_all(&["agg", "timeline", "count", "files-with-matches", "files-without-matches", "invert-match", "passthru"])
+                .help("Lists the matching lines under each distinct value of a capture group, given by its number or name, with how many lines had it")
+        )
+        .arg(
+            Arg::with_name("timeline")
+                .takes_value(true)
+                .value_name("FORMAT|REGEX")
+                .long("timeline")
+                .conflicts_with_all(&["agg", "count", "files-with-matches", "files-without-matches", "invert-match", "passthru"])
+                .help("Prints a histogram of when the matching lines were logged instead of the lines. The timestamps are found by one of iso8601, clf, syslog or epoch, or by a regex with the named groups year, month, day, hour and minute")
+        )
+        .arg(
+            Arg::with_name("timeline-bucket")
+                .takes_value(true)
+                .value_name("SIZE")
+                .long("timeline-bucket")
+                .possible_values(&["minute", "hour", "day"])
+                .default_value("hour")
+                .help("The time each bar of the --timeline histogram covers")
+        )
+        .arg(
+            Arg::with_name("redact")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("MASK")
+                .long("redact")
+                .help("Replace the matched text with MASK, which defaults to ****. Use --redact=hash to replace each match with a short hash of it, so equal values can still be told apart")
+        )
+        .arg(
+            Arg::with_name("replace")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .long("replace")
+                .short("r")
+                .conflicts_with("rules")
+                .conflicts_with_all(REPLACE_CONFLICTS)
+                .help("Print each match replaced with TEMPLATE, where $1 or ${name} expand to the groups the match captured. Files are left as they are")
+        )
+        .arg(
+            Arg::with_name("rules")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("rules")
+                .conflicts_with_all(&[
+                    "preset",
+                    "secrets",
+                    "json-field",
+                    "redact",
+                    "count",
+                    "files-with-matches",
+                    "files-without-matches",
+                    "invert-match",
+                    "agg",
+                    "timeline",
+                    "group-by",
+                    "top",
+                    "format",
+                ])
+                .help("Replace the matches of every rule in FILE at once, where each line is a rule like 'PATTERN -> REPLACEMENT' and lines starting with # are comments. The rules take the place of REGEX, so every positional argument is a path. Where matches overlap, the one that starts first is replaced, or the one of the rule listed first")
+        )
+        .arg(
+            Arg::with_name("preserve-case")
+                .long("preserve-case")
+                .requires("replacement")
+                .help("Replace each match in its own case, so replacing foo with bar turns Foo into Bar and FOO into BAR")
+        )
+        .arg(
+            Arg::with_name("in-place")
+                .long("in-place")
+                .requires("replacement")
+                .conflicts_with_all(&[
+                    "paragraph",
+                    "record-separator",
+                    "unicode-normalize",
+                    "git-rev",
+                    "passthru",
+                    "tree",
+                    "edit",
+                    "vimgrep",
+                    "baseline",
+                    "diff-trees",
+                ])
+                .help("Write the replacements of --replace, --rules or `grusp replace` into the files that matched, rather than printing them")
+        )
+        .arg(
+            Arg::with_name("backup")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("SUFFIX")
+                .long("backup")
+                .requires("in-place")
+                .help("Copy each file that --in-place changes to a backup named with SUFFIX added, which defaults to .bak, before writing it")
+        )
+        .arg(
+            Arg::with_name("backup-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .long("backup-dir")
+                .requires("in-place")
+                .help("Copy each file that --in-place changes into DIR before writing it, at the path it was searched at. The names only get a suffix when --backup is given too")
+        )
+        .arg(
+            Arg::with_name("interactive")
+                .long("interactive")
+                .requires("in-place")
+                .help("Show each replacement of --in-place with the lines around it and ask whether to write it: y writes it, n skips it, a writes it and every one after it and q skips it and every one after it. Files are searched one at a time")
+        )
+        .arg(
+            Arg::with_name("secrets")
+                .long("secrets")
+                .conflicts_with("preset")
+                .help("Scans for credentials and random looking strings assigned to secrets, reporting each with a severity. Exits 1 when any are found, for use in pre-commit hooks")
+        )
+        .arg(
+            Arg::with_name("secrets-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .long("secrets-format")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Report the findings of --secrets as text or as JSON")
+        );
+    let regex = Arg::with_name("REGEX")
+        .value_name("REGEX")
+        .required_unless_one(&["bench-corpus", "completions", "generate-man", "serve", "preset", "preset-list", "type-list", "secrets", "json-field", "rules", "regexp", "regexp-file"])
+        .help("The pattern that should be matched. This can be any valid Perl-style
+Regular expression, with a few caveats. See the \
+Rust Regex documentation \
+for detailed information https://doc.rust-lang.org/regex/regex/index.html. \
+A regex named like a command, such as files, is searched for with `grusp search files`.");
+    let paths = Arg::with_name("PATTERN")
+        .multiple(true)
+        .value_name("PATTERN")
+        .help("The files to search. Append ::NUM to search a path up to NUM directories deep, overriding --depth. Globs can have braces, like src/**/*.{rs,toml}, and one starting with ! leaves out what it matches, like '!**/tests/**'. A - searches stdin in its place among the files, and is empty after the first one. This is optional and not used if grusp is searching from stdin");
+    match command {
+        Command::Files => app.arg(paths.index(1)),
+        Command::Replace => app
+            .arg(regex.index(1))
+            .arg(
+                Arg::with_name("TEMPLATE")
+                    .index(2)
+                    .value_name("TEMPLATE")
+                    .conflicts_with("replace")
+                    .conflicts_with_all(REPLACE_CONFLICTS)
+                    .help("What each match is replaced with, where $1 or ${name} expand to the groups the match captured. With --rules every positional argument is a path"),
+            )
+            .arg(paths.index(3)),
+        Command::Search | Command::Index => app.arg(regex.index(1)).arg(paths.index(2)),
+    }
+}
 
-    let regex = matches.value_of("REGEX").expect("Regex required!");
-    let is_colored = !matches.is_present("notcolored");
-    let queries = collect_queries(matches.values_of("PATTERN"));
-    let is_concurrent = !matches.is_present("unthreaded");
+pub fn get_opts() -> Result<Opts, ArgError> {
+    let args = search_unless_command(env::args_os().collect());
+    let command_end = 1 + command_len(&args);
+    let (args, default_flags) = match env::var(defaults::VAR) {
+        Ok(flags) => defaults::merge(&build_app(), args, command_end, &flags)
+            .map_err(|e| ArgError::InvalidDefaultFlags(format!("{}: {}", defaults::VAR, e)))?,
+        Err(env::VarError::NotPresent) => (args, Vec::new()),
+        Err(env::VarError::NotUnicode(_)) => {
+            return Err(ArgError::InvalidDefaultFlags(format!("{}: Expected valid UTF-8", defaults::VAR)));
+        }
+    };
+    let app_matches = build_app().get_matches_from(args);
+    let (command, matches) = match app_matches.subcommand() {
+        ("search", Some(matches)) => (Command::Search, matches),
+        ("files", Some(matches)) => (Command::Files, matches),
+        ("replace", Some(matches)) => (Command::Replace, matches),
+        ("index", Some(index)) => match index.subcommand() {
+            ("build", Some(matches)) => build_index(matches.value_of("DIR").expect("Directory required!")),
+            ("update", Some(matches)) => update_index(matches.value_of("DIR").expect("Directory required!")),
+            (_, matches) => (Command::Index, matches.expect("An index action is required")),
+        },
+        _ => (Command::Search, &app_matches),
+    };
+
+    if let Some(shell) = matches.value_of("completions") {
+        let shell = shell.parse().expect("Shell must be one of the possible values");
+        build_app().gen_completions_to("grusp", shell, &mut io::stdout());
+        process::exit(0);
+    }
+    if matches.is_present("preset-list") {
+        print!("{}", presets::List);
+        process::exit(0);
+    }
+    let file_types = get_file_types(matches)?;
+    if matches.is_present("type-list") {
+        print!("{}", file_types);
+        process::exit(0);
+    }
+    // `--index build DIR` and `--index update DIR` give the directory in place of the regex
+    match matches.value_of("index") {
+        Some("build") => build_index(matches.value_of("REGEX").expect("Directory required!")),
+        Some("update") => update_index(matches.value_of("REGEX").expect("Directory required!")),
+        _ => {}
+    }
+    if let Some(dir) = matches.value_of("bench-corpus") {
+        corpus::write(Path::new(dir)).expect("Could not write the benchmark corpus");
+        process::exit(0);
+    }
+    if matches.is_present("generate-man") {
+        print!("{}", man::render(&build_app(), EXAMPLES));
+        process::exit(0);
+    }
+
+    let normalization = match matches.value_of("unicode-normalize") {
+        Some("nfc") => Some(Normalization::Nfc),
+        Some("nfd") => Some(Normalization::Nfd),
+        _ => None,
+    };
+    let presets: Vec<&presets::Preset> = matches
+        .values_of("preset")
+        .map(|names| names.map(|name| presets::find(name).expect("Preset must be one of the possible values")).collect())
+        .unwrap_or_default();
+    let secrets = if matches.is_present("secrets") {
+        match matches.value_of("secrets-format") {
+            Some("json") => Some(secrets::Format::Json),
+            _ => Some(secrets::Format::Text),
+        }
+    } else {
+        None
+    };
+    let rules = match matches.value_of("rules") {
+        Some(path) => Some(get_rules(path)?),
+        None => None,
+    };
+    let patterns = get_patterns(matches.values_of("regexp"), matches.values_of("regexp-file"))?;
+    // clap names the positional arguments by their position, but what each one is depends
+    // on the options, such as a preset taking the place of the regex, so they're read in
+    // order: the regex, the template of `grusp replace`, then the paths
+    let mut positionals = matches
+        .value_of("REGEX")
+        .into_iter()
+        .chain(matches.value_of("TEMPLATE"))
+        .chain(matches.values_of("PATTERN").into_iter().flatten());
+    let regex = if command != Command::Files && presets.is_empty() && secrets.is_none() && !matches.is_present("json-field") && rules.is_none() && patterns.is_none() {
+        // Only --serve runs without a regex, it takes them from each request instead
+        positionals.next().unwrap_or("").to_string()
+    } else {
+        // A preset, --secrets, --json-field, --rules or --regexp takes the place of the
+        // regex, and files are listed without one
+        match rules {
+            // Lines are found by any of the rules, and replaced by the rules that match
+            Some(ref rules) => {
+                let patterns: Vec<String> = rules.iter().map(|(pattern, _)| format!("(?:{})", pattern)).collect();
+                patterns.join("|")
+            }
+            None if patterns.is_some() => patterns.as_ref().map(Patterns::pattern).unwrap_or_default(),
+            None if presets.is_empty() => String::new(),
+            None => presets::pattern(&presets),
+        }
+    };
+    // The rules of --rules come with their own templates
+    let template = match command {
+        Command::Replace if rules.is_none() => Some(
+            positionals
+                .next()
+                .ok_or_else(|| ArgError::InvalidTemplate("Expected a TEMPLATE to replace the matches with".to_string()))?,
+        ),
+        _ => matches.value_of("replace"),
+    };
+    let paths: Vec<&str> = positionals.collect();
+    let paths = match command {
+        // Files are listed from the current directory even when stdin is a pipe
+        Command::Files if paths.is_empty() => Some(vec!["."]),
+        _ if paths.is_empty() => None,
+        _ => Some(paths),
+    };
+    let regex = match normalization {
+        Some(normalization) => normalization.normalize(&regex),
+        None => regex,
+    };
+    let color = if matches.is_present("notcolored") { ColorChoice::Never } else { ColorChoice::Auto };
+    // Every display made from here on follows the choice
+    color.apply(atty::is(Stream::Stdout));
+    let is_trimmed = matches.is_present("trim");
+    let number_width = match matches.value_of("number-width") {
+        Some("auto") => Some(NumberWidth::Auto),
+        Some(v) => Some(NumberWidth::Fixed(v.parse().map_err(|_| {
+            ArgError::InvalidNumber(format!("Expected a number or auto for --number-width but got '{}'", v))
+        })?)),
+        None => None,
+    };
+    let max_columns: Option<usize> = matches.value_of("max-columns").map(|v| parse_number("max-columns", v)).transpose()?;
+    let is_passthru = matches.is_present("passthru");
+    let is_paragraph = matches.is_present("paragraph");
+    let mut query_max_depths = Vec::new();
+    let queries = collect_queries(paths).map(|queries| {
+        queries
+            .iter()
+            .map(|query| {
+                let (path, depth) = parse_query_depth(query);
+                if let Some(depth) = depth {
+                    query_max_depths.push((path.to_string(), depth));
+                }
+                path.to_string()
+            })
+            .collect::<Vec<String>>()
+    });
+    // Asking about each replacement has to go one file at a time
+    let is_concurrent = !matches.is_present("unthreaded") && !matches.is_present("interactive");
+    let chunk_size = match matches.value_of("chunk-size") {
+        Some(size) => Some(parse_size(size).ok_or_else(|| {
+            ArgError::InvalidChunkSize(format!("Expected a chunk size like 64M but got '{}'", size))
+        })?),
+        None => None,
+    };
+    let max_line_length = match matches.value_of("max-line-length") {
+        Some(size) => {
+            let max = parse_size(size).map(|size| size as usize).ok_or_else(|| {
+                ArgError::InvalidMaxLineLength(format!("Expected a line length like 1M but got '{}'", size))
+            })?;
+            let long_lines = match matches.value_of("long-lines") {
+                Some("chunk") => LongLines::Chunk,
+                _ => LongLines::Skip,
+            };
+            Some((max, long_lines))
+        }
+        None => None,
+    };
+    let max_memory = match matches.value_of("max-memory") {
+        Some(size) => Some(parse_size(size).map(|size| size as usize).ok_or_else(|| {
+            ArgError::InvalidMaxMemory(format!("Expected a memory budget like 256M but got '{}'", size))
+        })?),
+        None => None,
+    };
     let case_insensitive = matches.is_present("ignore-case") &&
         !matches.is_present("case-sensitive");
     let is_count_only = matches.is_present("count");
@@ -195,23 +1890,526 @@ for detailed information https://doc.rust-lang.org/regex/regex/index.html."),
     } else {
         JustFiles::None
     };
+    // The files listed by -l or -L and the top files with --top have nothing to sum up
+    let is_count_total = is_count_only && !matches.is_present("no-total") && !just_files.is_some() && !matches.is_present("top");
     let is_inverted = matches.is_present("invert-match");
+    let is_crlf = matches.is_present("crlf");
+    let is_null_data = matches.is_present("null-data");
+    let line_range = match matches.value_of("line-range") {
+        Some(range) => Some(parse_line_range(range)?),
+        None => None,
+    };
+    let search_head: Option<usize> = matches.value_of("search-head").map(|v| parse_number("search-head", v)).transpose()?;
+    let search_tail: Option<usize> = matches.value_of("search-tail").map(|v| parse_number("search-tail", v)).transpose()?;
+    let max_count_per_line = matches.value_of("max-count-per-line").map(|v| parse_positive("max-count-per-line", v)).transpose()?;
+    let code_context = match matches.value_of("code-context") {
+        Some("comments") => Some(CodeContext::Comments),
+        Some("strings") => Some(CodeContext::Strings),
+        Some("code") => Some(CodeContext::Code),
+        _ => None,
+    };
+    let enclosing = if matches.is_present("show-enclosing") { Some(EnclosingRules::new()) } else { None };
+    let highlights = match matches.value_of("highlight-rules") {
+        Some(path) => get_highlight_rules(path)?,
+        None => HighlightRules::default(),
+    };
+    let redaction = if matches.is_present("redact") {
+        match matches.value_of("redact") {
+            Some("hash") => Some(Redaction::Hash),
+            Some(mask) => Some(Redaction::Mask(mask.to_string())),
+            None => Some(Redaction::Mask("****".to_string())),
+        }
+    } else {
+        None
+    };
+    let unique = if matches.is_present("unique-global") {
+        Some(UniqueScope::Global)
+    } else if matches.is_present("unique-lines") {
+        Some(UniqueScope::File)
+    } else {
+        None
+    };
+    let is_explain = matches.is_present("explain");
+    let is_suggest = matches.is_present("suggest");
+    let verbosity = matches.occurrences_of("verbose");
+    let is_no_messages = matches.is_present("no-messages");
+    let debug: Option<usize> = if matches.is_present("debug") {
+        Some(matches.value_of("debug").map_or(Ok(10), |v| parse_number("debug", v))?)
+    } else {
+        None
+    };
+    let summary_json: Option<i32> = if matches.is_present("summary-json") {
+        Some(matches.value_of("summary-json").map_or(Ok(summary::STDERR), |v| {
+            v.parse().ok().filter(|&fd| fd >= 0).ok_or_else(|| {
+                ArgError::InvalidNumber(format!("Expected a file descriptor for --summary-json but got '{}'", v))
+            })
+        })?)
+    } else {
+        None
+    };
+    let is_index_search = command == Command::Index || matches.value_of("index") == Some("search");
+    let serve = matches.value_of("serve").map(|address| address.to_string());
+    // Results are only paged in a terminal, and not while grusp or an editor needs it
+    let is_paged = !matches.is_present("no-pager") &&
+        atty::is(Stream::Stdout) &&
+        (!matches.is_present("output") || matches.is_present("tee")) &&
+        !matches.is_present("interactive") &&
+        !matches.is_present("edit") &&
+        serve.is_none();
+    let cache_dir = matches.value_of("cache").map(PathBuf::from);
+    if !matches.is_present("no-hints") {
+        if let Some(hint) = queries.as_ref().and_then(|queries| swapped_args_hint(&regex, queries)) {
+            eprintln!("{}", hint);
+        }
+    }
+    let is_git_only = matches.is_present("git");
+    let git_rev = matches.value_of("git-rev").map(|rev| rev.to_string());
+    let now = SystemTime::now();
+    let newer_than = match matches.value_of("newer-than") {
+        Some(value) => Some(parse_time(value, now)?),
+        None => None,
+    };
+    let older_than = match matches.value_of("older-than") {
+        Some(value) => Some(parse_time(value, now)?),
+        None => None,
+    };
+    let owner = match matches.value_of("owner") {
+        Some(user) => {
+            let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+            Some(get_uid(user, &passwd)?)
+        }
+        None => None,
+    };
+    let permissions = match matches.value_of("perm") {
+        Some(mode) => Some(Permissions::parse(mode).ok_or_else(|| {
+            ArgError::InvalidPermissions(format!("Expected an octal mode like 644, -220 or /002 but got '{}'", mode))
+        })?),
+        None => None,
+    };
+    let types = match matches.values_of("type") {
+        Some(names) => Some(file_types.matcher(&names.collect::<Vec<_>>()).map_err(ArgError::InvalidFileTypes)?),
+        None => None,
+    };
+    let mime = match matches.values_of("mime") {
+        Some(types) => {
+            let types: Vec<&str> = types.collect();
+            Some(MimeFilter::new(&types).ok_or_else(|| {
+                ArgError::InvalidMime(format!("Expected content types like text/plain or text/* but got '{}'", types.join(", ")))
+            })?)
+        }
+        None => None,
+    };
+    let traversal = match matches.value_of("traversal") {
+        Some("bfs") => Traversal::BreadthFirst,
+        _ => Traversal::DepthFirst,
+    };
+    let priority = match matches.value_of("prioritize") {
+        Some("small-first") => Some(Priority::SmallFirst),
+        Some("recent-first") => Some(Priority::RecentFirst),
+        _ => None,
+    };
+    let exclude = get_exclude_rules(matches.values_of("exclude"))?;
+    let regex = get_regex(&regex, case_insensitive)?;
+    let json_fields = match matches.values_of("json-field") {
+        Some(fields) => Some(get_json_fields(fields, case_insensitive)?),
+        None => None,
+    };
+    let record_separator = match matches.value_of("record-separator") {
+        Some(separator) => Some(get_regex(separator, false)?),
+        None => None,
+    };
+    let split_on = match matches.value_of("split-on") {
+        Some(separator) => Some(get_regex(separator, false)?),
+        None => None,
+    };
+    let aggregator = match matches.value_of("agg") {
+        Some(stat) => Some(get_aggregator(stat, matches.value_of("agg-by"), &regex)?),
+        None => None,
+    };
+    let top = matches.value_of("top").map(|v| parse_positive("top", v)).transpose()?;
+    let sarif = match matches.value_of("format") {
+        Some("sarif") => Some(sarif::Report::new(&regex, &presets, patterns.as_ref())),
+        _ => None,
+    };
+    let html = match matches.value_of("format") {
+        Some("html") => Some(html::Report::new()),
+        _ => None,
+    };
+    let output = match matches.value_of("output") {
+        Some(path) => Output::create(Path::new(path), matches.is_present("tee"))
+            .map_err(|e| ArgError::InvalidOutput(format!("Could not create the output {}: {}", path, e)))?,
+        None => Output::stdout(),
+    };
+    let baseline = matches.value_of("baseline").map(PathBuf::from);
+    let is_check_baseline = matches.is_present("check-baseline");
+    let max_total: Option<u64> = matches.value_of("max-total").map(|v| parse_number("max-total", v)).transpose()?;
+    let max_files: Option<u64> = matches
+        .value_of("max-files-with-matches")
+        .map(|v| parse_positive("max-files-with-matches", v).map(|n| n as u64))
+        .transpose()?;
+    let policy = matches.value_of("fail-on").map(|severity| {
+        Policy::new(Severity::parse(severity).expect("Severity must be one of the possible values"), patterns.clone())
+    });
+    let exit_codes = match matches.value_of("error-exit-codes") {
+        Some(spec) => parse_exit_codes(spec)?,
+        None => ExitCodes::default(),
+    };
+    let diff_trees = matches.values_of("diff-trees").map(|roots| {
+        let roots: Vec<&str> = roots.collect();
+        (roots[0].to_string(), roots[1].to_string())
+    });
+    let grouped = match matches.value_of("group-by") {
+        Some(name) => {
+            let group = AggregateGroup::parse(name);
+            if !group.exists_in(&regex) {
+                return Err(ArgError::InvalidAggregateGroup(format!("The regex has no capture group '{}' to group by", name)));
+            }
+            Some(GroupedMatches::new(&regex, group).trim(is_trimmed))
+        }
+        None => None,
+    };
+    let tree = if matches.is_present("tree") { Some(PathTree::new()) } else { None };
+    // The tree already shows each file once, in order
+    let file_list = if just_files.is_some() && tree.is_none() { Some(FileList::new()) } else { None };
+    let edit = if matches.is_present("edit") { Some(Edit::new()) } else { None };
+    let is_vimgrep = matches.is_present("vimgrep");
+    let replacement = match (template, rules) {
+        (Some(template), _) => Some(Replacement::new(&regex, template)),
+        (None, Some(rules)) => {
+            let rules = rules
+                .into_iter()
+                .map(|(pattern, template)| {
+                    // The rules are matched together by their patterns, so the flag has
+                    // to be part of them
+                    let pattern = if case_insensitive { format!("(?i){}", pattern) } else { pattern };
+                    Ok((get_regex(&pattern, false)?, template))
+                })
+                .collect::<Result<_, ArgError>>()?;
+            Some(Replacement::rules(rules).map_err(|e| ArgError::InvalidRules(e.to_string()))?)
+        }
+        (None, None) => None,
+    };
+    let replacement = replacement.map(|replacement| replacement.preserve_case(matches.is_present("preserve-case")));
+    let rewrite = match replacement {
+        Some(ref replacement) if matches.is_present("in-place") => {
+            let backup = match (matches.is_present("backup"), matches.value_of("backup-dir")) {
+                (false, None) => None,
+                // Backups in a dir of their own keep the names of the files, unless a
+                // suffix is asked for too
+                (is_suffixed, dir) => {
+                    let suffix = matches.value_of("backup").unwrap_or(if is_suffixed { ".bak" } else { "" });
+                    Some(Backup::new(suffix).dir(dir.map(PathBuf::from)))
+                }
+            };
+            Some(Rewrite::new(replacement.clone(), matches.is_present("interactive")).backup(backup))
+        }
+        _ => None,
+    };
+    let timeline = match matches.value_of("timeline") {
+        Some(format) => {
+            let bucket = match matches.value_of("timeline-bucket") {
+                Some("minute") => timeline::Bucket::Minute,
+                Some("day") => timeline::Bucket::Day,
+                _ => timeline::Bucket::Hour,
+            };
+            Some(Timeline::new(format, bucket).map_err(ArgError::InvalidTimeline)?)
+        }
+        None => None,
+    };
     Ok(Opts {
-        regex: get_regex(regex, case_insensitive)?,
+        command,
+        regex,
+        is_case_insensitive: case_insensitive,
+        is_explain,
+        debug,
+        summary_json,
+        default_flags,
+        verbosity,
+        is_no_messages,
+        is_suggest,
+        is_index_search,
+        secrets,
+        patterns,
+        policy,
+        serve,
+        cache_dir,
         queries,
         is_concurrent,
-        is_colored,
+        chunk_size,
+        max_line_length,
+        max_memory,
+        color,
+        is_trimmed,
+        number_width,
+        max_columns,
+        is_vimgrep,
+        is_paged,
+        is_passthru,
+        is_paragraph,
+        record_separator,
+        split_on,
+        json_fields,
         is_count_only,
+        is_count_total,
         max_depth,
+        query_max_depths,
+        line_range,
+        search_head,
+        search_tail,
+        max_count_per_line,
+        normalization,
+        code_context,
+        enclosing,
+        highlights,
+        redaction,
+        replacement,
+        rewrite,
+        unique,
+        aggregator,
+        timeline,
+        grouped,
+        tree,
+        file_list,
+        edit,
+        top,
+        sarif,
+        html,
+        output,
+        diff_trees,
+        baseline,
+        is_check_baseline,
+        max_total,
+        max_files,
+        exit_codes,
         just_files,
+        exclude,
+        is_default_excludes: !matches.is_present("no-default-excludes"),
+        is_directives: matches.is_present("directives"),
+        is_git_only,
+        git_rev,
+        newer_than,
+        older_than,
+        owner,
+        permissions,
+        mime,
+        types,
+        traversal,
+        priority,
         is_inverted,
+        is_crlf,
+        is_null_data,
     })
 }
 
+/// Builds and saves the index of the directory, then exits.
+fn build_index(dir: &str) -> ! {
+    let mut index = Index::new(Path::new(dir));
+    match index.update().and_then(|update| index.save().map(|_| update)) {
+        Ok(update) => {
+            println!("Indexed {} files in {}", index.len(), dir);
+            exit_after_index(&update)
+        }
+        Err(e) => {
+            eprintln!("Could not build the index of {}: {}", dir, e);
+            process::exit(2);
+        }
+    }
+}
+
+/// Updates and saves the index of the directory, then exits.
+fn update_index(dir: &str) -> ! {
+    let updated = Index::open(Path::new(dir)).and_then(|mut index| {
+        let update = index.update()?;
+        index.save().map(|_| update)
+    });
+    match updated {
+        Ok(update) => {
+            println!(
+                "Updated the index of {}: {} added, {} changed, {} removed",
+                dir, update.added, update.changed, update.removed
+            );
+            exit_after_index(&update)
+        }
+        Err(e) => {
+            eprintln!("Could not update the index of {}: {}", dir, e);
+            process::exit(2);
+        }
+    }
+}
+
+/// Reports the files that couldn't be read into the index and exits with 2 if there were
+/// any, like a search with files it couldn't read.
+fn exit_after_index(update: &IndexUpdate) -> ! {
+    for (path, e) in &update.unreadable {
+        eprintln!("Could not read {}: {}", path.display(), e);
+    }
+    process::exit(if update.unreadable.is_empty() { 0 } else { 2 })
+}
+
+fn parse_exit_codes(spec: &str) -> Result<ExitCodes, ArgError> {
+    let mut codes = ExitCodes::default();
+    for setting in spec.split(',') {
+        let invalid = || ArgError::InvalidExitCodes(format!("Expected a setting like 'no-match=0' but got '{}'", setting));
+        let mut parts = setting.splitn(2, '=');
+        let (name, code) = match (parts.next(), parts.next()) {
+            (Some(name), Some(code)) => (name.trim(), code.trim().parse::<u8>().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+        let code = i32::from(code);
+        match name {
+            "no-match" => codes.no_match = code,
+            "unreadable" => codes.unreadable = code,
+            "max-total" => codes.max_total = code,
+            "fail-on" => codes.fail_on = code,
+            _ => {
+                return Err(ArgError::InvalidExitCodes(format!(
+                    "Unknown outcome '{}', expected one of no-match, unreadable, max-total or fail-on",
+                    name
+                )))
+            }
+        }
+    }
+    Ok(codes)
+}
+
+/// Parses a size in bytes, which can end in K, M or G for kibibytes, mebibytes or
+/// gibibytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, unit) = match size.char_indices().find(|&(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => size.split_at(i),
+        None => (size, ""),
+    };
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier).filter(|&size| size > 0)
+}
+
+/// Reads the rules of `--rules`, each a line like `PATTERN -> REPLACEMENT`. Blank lines
+/// and lines starting with `#` are skipped, and a rule ending in ` ->` removes its matches.
+fn get_rules(path: &str) -> Result<Vec<(String, String)>, ArgError> {
+    let contents = fs::read_to_string(path).map_err(|e| ArgError::InvalidRules(format!("Could not read the rules {}: {}", path, e)))?;
+    let mut rules = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let (pattern, replacement) = match line.split_once(" -> ") {
+            Some(rule) => rule,
+            None => match line.strip_suffix(" ->") {
+                Some(pattern) => (pattern, ""),
+                None => {
+                    return Err(ArgError::InvalidRules(format!(
+                        "Line {} of {} should be a rule like 'PATTERN -> REPLACEMENT' but is '{}'",
+                        i + 1,
+                        path,
+                        line
+                    )))
+                }
+            },
+        };
+        rules.push((pattern.to_string(), replacement.to_string()));
+    }
+    if rules.is_empty() {
+        return Err(ArgError::InvalidRules(format!("{} has no rules", path)));
+    }
+    Ok(rules)
+}
+
+fn get_patterns(regexps: Option<Values>, files: Option<Values>) -> Result<Option<Patterns>, ArgError> {
+    if regexps.is_none() && files.is_none() {
+        return Ok(None);
+    }
+    let mut patterns = Patterns::new();
+    for pattern in regexps.into_iter().flatten() {
+        patterns.add(pattern).map_err(ArgError::InvalidPatterns)?;
+    }
+    for path in files.into_iter().flatten() {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| ArgError::InvalidPatterns(format!("Could not read the patterns {}: {}", path, e)))?;
+        for line in contents.lines().filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#')) {
+            patterns.add(line).map_err(|e| ArgError::InvalidPatterns(format!("{}: {}", path, e)))?;
+        }
+    }
+    if patterns.is_empty() {
+        return Err(ArgError::InvalidPatterns("There are no patterns to search for".to_string()));
+    }
+    Ok(Some(patterns))
+}
+
+fn get_json_fields(fields: Values, case_insensitive: bool) -> Result<JsonFields, ArgError> {
+    let fields = fields
+        .map(|field| {
+            let mut parts = field.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(regex)) if !name.is_empty() => Ok(JsonField::new(name, get_regex(regex, case_insensitive)?)),
+                _ => Err(ArgError::InvalidJsonField(format!("Expected a field like 'level=error' but got '{}'", field))),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(JsonFields::new(fields))
+}
+
+fn get_aggregator(stat: &str, group_by: Option<&str>, regex: &Regex) -> Result<Aggregator, ArgError> {
+    let aggregation = match stat {
+        "sum" => Aggregation::Sum,
+        "avg" => Aggregation::Avg,
+        "min" => Aggregation::Min,
+        "max" => Aggregation::Max,
+        _ => Aggregation::Count,
+    };
+    let group_by = match group_by {
+        Some(name) if !AggregateGroup::parse(name).exists_in(regex) => {
+            return Err(ArgError::InvalidAggregateGroup(format!("The regex has no capture group '{}' to aggregate by", name)));
+        }
+        Some(name) => Some(AggregateGroup::parse(name)),
+        None => None,
+    };
+    Ok(Aggregator::new(aggregation, regex).group_by(group_by))
+}
+
+/// The built-in file types, extended by the definitions of the config file and then those
+/// given with `--type-add`.
+fn get_file_types(matches: &ArgMatches) -> Result<FileTypes, ArgError> {
+    let config = if matches.is_present("no-config") {
+        Config::default()
+    } else {
+        Config::load().map_err(ArgError::InvalidFileTypes)?
+    };
+    let mut types = FileTypes::new();
+    let definitions = config.type_adds.iter().map(String::as_str).chain(matches.values_of("type-add").into_iter().flatten());
+    for definition in definitions {
+        types.add(definition).map_err(ArgError::InvalidFileTypes)?;
+    }
+    Ok(types)
+}
+
+fn get_highlight_rules(path: &str) -> Result<HighlightRules, ArgError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        ArgError::InvalidHighlightRules(format!("Could not read highlight rules from '{}': {}", path, e))
+    })?;
+    HighlightRules::parse(&contents).map_err(ArgError::InvalidHighlightRules)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn it_parses_sizes_with_units() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("64k"), Some(64 << 10));
+        assert_eq!(parse_size("64M"), Some(64 << 20));
+        assert_eq!(parse_size("2G"), Some(2 << 30));
+        assert_eq!(parse_size("0"), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("64MB"), None);
+    }
+
     #[test]
     fn it_parses_into_a_regex() {
         let regex = get_regex("test", false).unwrap();
@@ -225,6 +2423,144 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn it_points_at_the_regex_error() {
+        let error = match get_regex("fn(", false) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("Expected an invalid regex"),
+        };
+        assert!(error.starts_with("Invalid regex: Unclosed parenthesis."));
+        assert!(error.contains("\n    fn(\n      ^\n"));
+        assert!(error.contains("Hint: unclosed group - did you mean \\( to match a literal parenthesis?"));
+    }
+
+    #[test]
+    fn it_suggests_fixing_backwards_ranges() {
+        let error = match get_regex("[z-a]", false) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("Expected an invalid regex"),
+        };
+        assert!(error.contains("did you mean [a-z]?"));
+    }
+
+    #[test]
+    fn it_hints_when_the_regex_and_path_look_swapped() {
+        let hint = swapped_args_hint("src", &["fn main".to_string()]).unwrap();
+        assert!(hint.contains("did you mean `grusp fn main src`?"));
+        assert_eq!(swapped_args_hint("src", &["Cargo.toml".to_string()]), None);
+        assert_eq!(swapped_args_hint("src", &["*.rs".to_string()]), None);
+        assert_eq!(swapped_args_hint("fn main", &["nowhere".to_string()]), None);
+        assert_eq!(swapped_args_hint("src", &["a".to_string(), "b".to_string()]), None);
+    }
+
+    #[test]
+    fn it_searches_unless_the_first_word_is_a_command() {
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        assert_eq!(search_unless_command(args(&["grusp", "hello", "."])), args(&["grusp", "search", "hello", "."]));
+        assert_eq!(search_unless_command(args(&["grusp", "index", "build", "."])), args(&["grusp", "index", "build", "."]));
+        assert_eq!(search_unless_command(args(&["grusp", "-i", "fn"])), args(&["grusp", "-i", "fn"]));
+        // Each of them is a command of the app
+        for command in COMMANDS {
+            let help = build_app().get_matches_from_safe(vec!["grusp", command, "--help"]).unwrap_err();
+            assert!(help.message.contains(&format!("grusp {} ", command)), "{}", help.message);
+        }
+    }
+
+    #[test]
+    fn it_only_takes_a_command_before_any_other_argument() {
+        let matches = build_app().get_matches_from(vec!["grusp", "files", "src"]);
+        assert_eq!(matches.subcommand_name(), Some("files"));
+        assert_eq!(matches.subcommand_matches("files").unwrap().value_of("PATTERN"), Some("src"));
+        let matches = build_app().get_matches_from(vec!["grusp", "fn", "search"]);
+        assert_eq!(matches.subcommand_name(), None);
+        assert_eq!(matches.values_of("PATTERN").unwrap().collect::<Vec<_>>(), vec!["search"]);
+        let matches = build_app().get_matches_from(vec!["grusp", "-i", "index", "src"]);
+        assert_eq!(matches.subcommand_name(), None);
+        assert_eq!(matches.value_of("REGEX"), Some("index"));
+        let matches = build_app().get_matches_from(vec!["grusp", "replace", "a", "b", "src"]);
+        let replace = matches.subcommand_matches("replace").unwrap();
+        assert_eq!((replace.value_of("REGEX"), replace.value_of("TEMPLATE")), (Some("a"), Some("b")));
+    }
+
+    #[test]
+    fn it_parses_a_depth_suffix_on_queries() {
+        assert_eq!(parse_query_depth("src/::2"), ("src/", Some(2)));
+        assert_eq!(parse_query_depth("a::b::0"), ("a::b", Some(0)));
+        assert_eq!(parse_query_depth("a::b"), ("a::b", None));
+        assert_eq!(parse_query_depth("src/"), ("src/", None));
+    }
+
+    #[test]
+    fn it_parses_a_line_range() {
+        assert_eq!(parse_line_range("10:20").unwrap(), (10, 20));
+        assert_eq!(parse_line_range(":20").unwrap(), (1, 20));
+        assert_eq!(parse_line_range("10:").unwrap(), (10, usize::MAX));
+    }
+
+    #[test]
+    fn it_errors_on_bad_line_ranges() {
+        assert!(parse_line_range("10").is_err());
+        assert!(parse_line_range("20:10").is_err());
+        assert!(parse_line_range("0:10").is_err());
+        assert!(parse_line_range("a:b").is_err());
+    }
+
+    #[test]
+    fn it_parses_times_as_durations_before_now() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(parse_time("10s", now).unwrap(), now - Duration::from_secs(10));
+        assert_eq!(parse_time("2h", now).unwrap(), now - Duration::from_secs(2 * 60 * 60));
+        assert_eq!(parse_time("1w", now).unwrap(), now - Duration::from_secs(7 * 24 * 60 * 60));
+    }
+
+    #[test]
+    fn it_parses_times_as_dates() {
+        let now = SystemTime::now();
+        assert_eq!(parse_time("1970-01-02", now).unwrap(), UNIX_EPOCH + Duration::from_secs(86400));
+        assert_eq!(
+            parse_time("2017-12-31T01:02:03", now).unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1514682123)
+        );
+        assert_eq!(parse_time("2000-03-01 12:00", now).unwrap(), UNIX_EPOCH + Duration::from_secs(951912000));
+    }
+
+    #[test]
+    fn it_errors_on_bad_times() {
+        let now = SystemTime::now();
+        assert!(parse_time("2x", now).is_err());
+        assert!(parse_time("d", now).is_err());
+        assert!(parse_time("2017-13-01", now).is_err());
+        assert!(parse_time("yesterday", now).is_err());
+    }
+
+    #[test]
+    fn it_resolves_user_ids() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\nwww-data:x:33:33:www-data:/var/www:/bin/sh";
+        assert_eq!(get_uid("www-data", passwd).unwrap(), 33);
+        assert_eq!(get_uid("1000", passwd).unwrap(), 1000);
+        assert!(get_uid("nobody", passwd).is_err());
+    }
+
+    #[test]
+    fn it_parses_exit_codes() {
+        let codes = parse_exit_codes("no-match=0, max-total=1").unwrap();
+        assert_eq!(codes, ExitCodes { no_match: 0, unreadable: 2, max_total: 1, fail_on: 1 });
+        assert!(parse_exit_codes("no-match").is_err());
+        assert!(parse_exit_codes("no-match=256").is_err());
+        assert!(parse_exit_codes("missing=1").is_err());
+    }
+
+    #[test]
+    fn it_exits_with_the_first_failure_that_has_a_code() {
+        let codes = ExitCodes { no_match: 0, ..ExitCodes::default() };
+        assert_eq!(codes.code(false, false, false, true), 0);
+        assert_eq!(codes.code(false, false, false, false), 0);
+        assert_eq!(codes.code(true, true, true, false), 2);
+        assert_eq!(codes.code(false, true, true, true), 3);
+        assert_eq!(codes.code(false, false, true, true), 1);
+        assert_eq!(ExitCodes::default().code(false, false, false, false), 1);
+    }
+
     #[test]
     fn it_can_be_case_insensitive() {
         let regex = get_regex("test", true).unwrap();
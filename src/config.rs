@@ -0,0 +1,73 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The settings read from the config file, which persist across searches. Each line is a
+/// setting and its value, such as `type-add web:*.html,*.css,*.js`. Blank lines and lines
+/// starting with `#` are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The file type definitions, in the order they were given.
+    pub type_adds: Vec<String>,
+}
+
+impl Config {
+    /// Parses the settings of a config file. The error describes the first line that
+    /// couldn't be parsed.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (setting, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match setting {
+                "type-add" => config.type_adds.push(value.trim().to_string()),
+                _ => return Err(format!("Unknown setting '{}' on line {} of the config file", setting, number + 1)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads the config file, from `$GRUSP_CONFIG` or `grusp/config` in the user's config
+    /// directory. A config file that doesn't exist has no settings.
+    pub fn load() -> Result<Self, String> {
+        let path = match path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("Could not read {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// The path of the config file, following the XDG base directory spec.
+fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("GRUSP_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("grusp").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_type_definitions() {
+        let config = Config::parse("# team types\ntype-add web:*.html,*.css\n\ntype-add  proto:*.proto\n").unwrap();
+        assert_eq!(config.type_adds, vec!["web:*.html,*.css".to_string(), "proto:*.proto".to_string()]);
+        assert_eq!(
+            Config::parse("type-add a:*.a\ncolor always").unwrap_err(),
+            "Unknown setting 'color' on line 2 of the config file"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::path::PathBuf;
+use regex_syntax::ExprBuilder;
+use grusp_core::grusp::{self, GitRevision};
+use args::Opts;
+use schedule;
+
+/// The options a search resolved to, the files it would search and the rules that
+/// excluded the others, printed to stderr by `--debug` so they can be attached to a bug
+/// report.
+pub struct Report<'a> {
+    opts: &'a Opts,
+    /// The files that would be searched, or None when they aren't known up front.
+    files: Option<Vec<PathBuf>>,
+    limit: usize,
+}
+
+impl<'a> Report<'a> {
+    /// Resolves the files of the search, of which up to `limit` are listed.
+    pub fn new(opts: &'a Opts, limit: usize) -> Self {
+        let files = match (opts.queries.as_ref(), opts.git_rev.as_ref()) {
+            _ if opts.is_index_search => None,
+            (Some(queries), Some(rev)) => GitRevision::new(rev).files(queries).ok(),
+            (Some(queries), None) => Some(opts.file_collector(queries).collect()),
+            (None, _) => None,
+        };
+        Report { opts, files, limit }
+    }
+}
+
+impl<'a> fmt::Display for Report<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opts = self.opts;
+        writeln!(f, "grusp {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(f, "regex: {}", opts.regex.as_str())?;
+        // The parsed expression shows how escapes, classes and case folding were read
+        let parsed = ExprBuilder::new().case_insensitive(opts.is_case_insensitive).parse(opts.regex.as_str());
+        if let Ok(expr) = parsed {
+            writeln!(f, "parsed regex: {}", expr)?;
+        }
+        writeln!(f, "options:")?;
+        let options: Vec<(&str, String)> = vec![
+            ("command", format!("{:?}", opts.command)),
+            ("default flags", format!("{:?}", opts.default_flags)),
+            ("queries", format!("{:?}", opts.queries)),
+            ("patterns", format!("{:?}", opts.patterns.as_ref().map(|patterns| patterns.iter().collect::<Vec<_>>()))),
+            ("case insensitive", opts.is_case_insensitive.to_string()),
+            ("count only", opts.is_count_only.to_string()),
+            ("count total", opts.is_count_total.to_string()),
+            ("files with matches", (opts.just_files.is_some() && opts.just_files.show_matches()).to_string()),
+            ("files without matches", opts.just_files.without_matches().to_string()),
+            ("inverted", opts.is_inverted.to_string()),
+            ("passthru", opts.is_passthru.to_string()),
+            ("directives", opts.is_directives.to_string()),
+            ("paragraph", opts.is_paragraph.to_string()),
+            ("json fields", format!("{:?}", opts.json_fields)),
+            ("record separator", format!("{:?}", opts.record_separator.as_ref().map(|r| r.as_str()))),
+            ("split on", format!("{:?}", opts.split_on.as_ref().map(|r| r.as_str()))),
+            ("concurrent", match self.files {
+                Some(ref files) if opts.is_concurrent && schedule::is_tiny(files) => {
+                    "true, resolved to false for a few small files".to_string()
+                }
+                _ => opts.is_concurrent.to_string(),
+            }),
+            ("color", format!("{:?}, resolved to {}", opts.color, grusp::is_colored())),
+            ("trimmed", opts.is_trimmed.to_string()),
+            ("number width", format!("{:?}", opts.number_width)),
+            ("vimgrep", opts.is_vimgrep.to_string()),
+            ("format", (if opts.sarif.is_some() { "sarif" } else if opts.html.is_some() { "html" } else { "text" }).to_string()),
+            ("output", format!("{:?}", opts.output.path())),
+            ("tee", (opts.output.path().is_some() && opts.output.is_stdout()).to_string()),
+            ("max columns", format!("{:?}", opts.max_columns)),
+            ("chunk size", format!("{:?}", opts.chunk_size)),
+            ("max line length", format!("{:?}", opts.max_line_length)),
+            ("max memory", format!("{:?}", opts.max_memory)),
+            ("tree", opts.tree.is_some().to_string()),
+            ("edit", opts.edit.is_some().to_string()),
+            ("crlf", opts.is_crlf.to_string()),
+            ("null data", opts.is_null_data.to_string()),
+            ("max depth", format!("{:?}", opts.max_depth)),
+            ("query max depths", format!("{:?}", opts.query_max_depths)),
+            ("line range", format!("{:?}", opts.line_range)),
+            ("search head", format!("{:?}", opts.search_head)),
+            ("search tail", format!("{:?}", opts.search_tail)),
+            ("max count per line", format!("{:?}", opts.max_count_per_line)),
+            ("unicode normalization", format!("{:?}", opts.normalization)),
+            ("code context", format!("{:?}", opts.code_context)),
+            ("show enclosing", opts.enclosing.is_some().to_string()),
+            ("redaction", format!("{:?}", opts.redaction)),
+            ("replacement", format!("{:?}", opts.replacement)),
+            ("paged", opts.is_paged.to_string()),
+            ("rewrite", format!("{:?}", opts.rewrite)),
+            ("unique", format!("{:?}", opts.unique)),
+            ("git only", opts.is_git_only.to_string()),
+            ("git revision", format!("{:?}", opts.git_rev)),
+            ("index search", opts.is_index_search.to_string()),
+            ("cache", format!("{:?}", opts.cache_dir)),
+            ("newer than", format!("{:?}", opts.newer_than)),
+            ("older than", format!("{:?}", opts.older_than)),
+            ("owner", format!("{:?}", opts.owner)),
+            ("permissions", format!("{:?}", opts.permissions)),
+            ("mime", format!("{:?}", opts.mime)),
+            ("types", format!("{:?}", opts.types)),
+            ("traversal", format!("{:?}", opts.traversal)),
+            ("priority", format!("{:?}", opts.priority)),
+            ("verbose", opts.verbosity.to_string()),
+            ("no messages", opts.is_no_messages.to_string()),
+            ("summary json", format!("{:?}", opts.summary_json)),
+            ("max total", format!("{:?}", opts.max_total)),
+            ("max files with matches", format!("{:?}", opts.max_files)),
+            ("fail on", format!("{:?}", opts.policy.as_ref().map(|policy| policy.fail_on()))),
+            ("exit codes", format!("{:?}", opts.exit_codes)),
+        ];
+        for (name, value) in options {
+            writeln!(f, "  {}: {}", name, value)?;
+        }
+        writeln!(f, "ignore rules:")?;
+        writeln!(f, "  hidden files and directories")?;
+        if opts.is_default_excludes {
+            writeln!(f, "  files with binary extensions")?;
+        }
+        for rule in opts.exclude.to_string().lines() {
+            writeln!(f, "  {}", rule)?;
+        }
+        match self.files {
+            Some(ref files) => {
+                writeln!(f, "files ({} of {}):", files.len().min(self.limit), files.len())?;
+                for file in files.iter().take(self.limit) {
+                    writeln!(f, "  {}", file.display())?;
+                }
+            }
+            None if opts.queries.is_none() => writeln!(f, "files: stdin")?,
+            None => writeln!(f, "files: not known until the search runs")?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::{self, BufRead, BufReader, Read};
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::{FrameDecoder, StreamingDecoder};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// A compressed format that's recognized by the magic bytes it starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the format of a stream from the first bytes of it, if it's compressed.
+    pub fn detect(start: &[u8]) -> Option<Self> {
+        if start.starts_with(GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if start.starts_with(ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wraps the reader in a decoder when it starts with a compressed stream, so that
+/// `cat logs.gz | grusp ERROR` searches the lines of the log. Other streams are read as
+/// they are. Only the bytes already buffered are checked, so nothing is consumed.
+pub fn decode<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let compression = Compression::detect(reader.fill_buf()?);
+    Ok(match compression {
+        // Concatenated gzip files, such as rotated logs joined with cat, are all read
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(ZstdFrames::new(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+/// Decodes every zstd frame of a stream, rather than stopping after the first.
+struct ZstdFrames<R: BufRead> {
+    decoder: Option<StreamingDecoder<R, FrameDecoder>>,
+}
+
+impl<R: BufRead> ZstdFrames<R> {
+    fn new(reader: R) -> io::Result<Self> {
+        let decoder = StreamingDecoder::new(reader).map_err(invalid_data)?;
+        Ok(ZstdFrames { decoder: Some(decoder) })
+    }
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let decoder = match self.decoder {
+                Some(ref mut decoder) => decoder,
+                None => return Ok(0),
+            };
+            let read = decoder.read(buf)?;
+            if read > 0 || buf.is_empty() || decoder.get_mut().fill_buf()?.is_empty() {
+                return Ok(read);
+            }
+            // The frame ended with more of the stream left, so the next frame starts there
+            let (reader, frame) = self.decoder.take().expect("Decoder is set").into_parts();
+            self.decoder = Some(StreamingDecoder::new_with_decoder(reader, frame).map_err(invalid_data)?);
+        }
+    }
+}
+
+fn invalid_data<E: ::std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression as Level;
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn read(bytes: &[u8]) -> String {
+        let mut text = String::new();
+        decode(bytes).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn it_detects_compression_from_magic_bytes() {
+        assert_eq!(Compression::detect(&gzip("a")), Some(Compression::Gzip));
+        assert_eq!(Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0]), Some(Compression::Zstd));
+        assert_eq!(Compression::detect(b"plain"), None);
+        assert_eq!(Compression::detect(b""), None);
+    }
+
+    #[test]
+    fn it_reads_every_gzip_member_and_plain_text() {
+        let mut bytes = gzip("first\n");
+        bytes.extend(gzip("second\n"));
+        assert_eq!(read(&bytes), "first\nsecond\n");
+        assert_eq!(read(b"plain\n"), "plain\n");
+    }
+
+    #[test]
+    fn it_reads_every_zstd_frame() {
+        let bytes = include_bytes!("../tests/fixtures/compressed.log.zst");
+        assert_eq!(read(bytes), "INFO started\nERROR disk full\nINFO retry\nERROR disk still full\n");
+    }
+}
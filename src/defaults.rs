@@ -0,0 +1,179 @@
+use std::ffi::OsString;
+use clap::{App, ErrorKind};
+
+/// The environment variable with the flags every search starts with, such as
+/// `--trim --no-pager`, for personal defaults that don't need a config file.
+pub const VAR: &str = "GRUSP_DEFAULT_FLAGS";
+
+/// Adds the default flags to the command line, right after the command that takes up its
+/// first `start` arguments, and returns it along with the defaults that were kept. The
+/// command line takes precedence:
+///
+/// - A default is dropped when the command line gives the same flag, unless it can be
+///   given many times like `--exclude`, in which case both apply.
+/// - A default is dropped when it conflicts with a flag of the command line, so
+///   `--case-sensitive` overrides a default `--ignore-case`.
+/// - With `--no-env` on the command line every default is ignored, even invalid ones.
+///
+/// The defaults are split like a shell would and can only be flags and their values.
+/// Which flags they are is left to clap, by parsing them along with the command line.
+pub fn merge(app: &App<'static, 'static>, args: Vec<OsString>, start: usize, defaults: &str) -> Result<(Vec<OsString>, Vec<String>), String> {
+    let (command, given) = args.split_at(start);
+    // Commands like `index build` take no flags, and the help command takes none either
+    let positionals = positionals(app, command);
+    let takes_flags = check(app, &[command, &[OsString::from("--no-env")], &positionals].concat()) != Some(ErrorKind::UnknownArgument);
+    let is_help = given.first().is_some_and(|arg| arg == "help") &&
+        check(app, &[command, &[OsString::from("help")]].concat()) == Some(ErrorKind::HelpDisplayed);
+    let is_no_env = given.iter().take_while(|arg| *arg != "--").any(|arg| arg == "--no-env");
+    if !takes_flags || is_help || is_no_env {
+        return Ok((args, Vec::new()));
+    }
+    let defaults: Vec<OsString> = split(defaults)?.into_iter().map(OsString::from).collect();
+    let mut kept = Vec::new();
+    let mut i = 0;
+    while i < defaults.len() {
+        let end = flag_end(app, command, &defaults, i, &positionals)?;
+        let flag = &defaults[i..end];
+        match check(app, &[command, flag, given].concat()) {
+            Some(ErrorKind::ArgumentConflict) | Some(ErrorKind::UnexpectedMultipleUsage) => {}
+            _ => kept.extend_from_slice(flag),
+        }
+        i = end;
+    }
+    let flags = kept.iter().map(|flag| flag.to_string_lossy().into_owned()).collect();
+    let mut merged = command.to_vec();
+    merged.extend(kept);
+    merged.extend_from_slice(given);
+    Ok((merged, flags))
+}
+
+/// Returns the positional arguments that complete the command, to parse flags with so
+/// that clap doesn't stop at a missing regex before telling whether an option is
+/// missing its value.
+fn positionals(app: &App<'static, 'static>, command: &[OsString]) -> Vec<OsString> {
+    let mut positionals = vec![OsString::from("--")];
+    while positionals.len() < 3 && check(app, &[command, &positionals].concat()).is_some() {
+        positionals.push(OsString::from("x"));
+    }
+    positionals
+}
+
+/// Parses the arguments with the app, returning the kind of error they're rejected with.
+fn check(app: &App<'static, 'static>, args: &[OsString]) -> Option<ErrorKind> {
+    app.clone().get_matches_from_safe(args).err().map(|e| e.kind)
+}
+
+/// Returns where the default flag at `i` ends, after the values that follow it. Short
+/// flags can be grouped like `-ic`, where an option ends the group and the rest of it
+/// is its value.
+fn flag_end(app: &App<'static, 'static>, command: &[OsString], defaults: &[OsString], i: usize, positionals: &[OsString]) -> Result<usize, String> {
+    let flag = defaults[i].to_string_lossy();
+    if !flag.starts_with('-') || flag == "-" || flag == "--" {
+        return Err(format!("Expected only flags but got '{}'", flag));
+    }
+    let mut end = i + 1;
+    loop {
+        match check(app, &[command, &defaults[i..end], positionals].concat()) {
+            Some(ErrorKind::UnknownArgument) => return Err(format!("Unknown flag '{}'", flag)),
+            // An option takes the next word as its value when it isn't given with `=`
+            Some(ErrorKind::EmptyValue) | Some(ErrorKind::TooFewValues) => match defaults.get(end) {
+                Some(word) if !word.to_string_lossy().starts_with('-') => end += 1,
+                _ => return Err(format!("Expected a value after '{}'", flag)),
+            },
+            _ => return Ok(end),
+        }
+    }
+}
+
+/// Splits the flags into words at whitespace like a shell would, where single or double
+/// quotes keep a value with spaces together and a backslash escapes the next character.
+fn split(flags: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = flags.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    word.get_or_insert_with(String::new).push(escaped);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(quote) = quote {
+        return Err(format!("Expected a closing {} in '{}'", quote, flags));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, SubCommand};
+
+    fn app() -> App<'static, 'static> {
+        let args = || vec![
+            Arg::with_name("trim").long("trim"),
+            Arg::with_name("case-sensitive").long("case-sensitive"),
+            Arg::with_name("ignore-case").short("i").long("ignore-case").conflicts_with("case-sensitive"),
+            Arg::with_name("exclude").long("exclude").takes_value(true).multiple(true).number_of_values(1),
+            Arg::with_name("depth").short("d").long("depth").takes_value(true),
+            Arg::with_name("no-env").long("no-env"),
+            Arg::with_name("REGEX").index(1),
+        ];
+        App::new("grusp")
+            .args(&args())
+            .subcommand(SubCommand::with_name("files").args(&args()))
+            .subcommand(SubCommand::with_name("index").subcommand(SubCommand::with_name("build").arg(Arg::with_name("DIR").index(1))))
+    }
+
+    fn merge_strs(args: &[&str], defaults: &str) -> Result<Vec<String>, String> {
+        let start = 1 + args[1..].iter().take_while(|arg| ["files", "index", "build"].contains(arg)).count();
+        let args = args.iter().map(OsString::from).collect();
+        merge(&app(), args, start, defaults).map(|(merged, _)| merged.iter().map(|arg| arg.to_string_lossy().into_owned()).collect())
+    }
+
+    #[test]
+    fn it_splits_flags_like_a_shell() {
+        assert_eq!(split(" --trim  --exclude 'a b' -d\"2\" x\\ y ").unwrap(), vec!["--trim", "--exclude", "a b", "-d2", "x y"]);
+        assert_eq!(split("--exclude ''").unwrap(), vec!["--exclude", ""]);
+        assert!(split("--exclude 'a").is_err());
+    }
+
+    #[test]
+    fn it_puts_the_defaults_after_the_command() {
+        assert_eq!(merge_strs(&["grusp", "fn", "."], "--trim -d 2").unwrap(), vec!["grusp", "--trim", "-d", "2", "fn", "."]);
+        assert_eq!(merge_strs(&["grusp", "files", "src"], "--trim").unwrap(), vec!["grusp", "files", "--trim", "src"]);
+        assert_eq!(merge_strs(&["grusp", "index", "build", "src"], "--trim").unwrap(), vec!["grusp", "index", "build", "src"]);
+        assert_eq!(merge_strs(&["grusp", "help"], "--trim").unwrap(), vec!["grusp", "help"]);
+    }
+
+    #[test]
+    fn it_lets_the_command_line_take_precedence() {
+        let defaults = "--trim -i --depth 2 --exclude target/";
+        assert_eq!(
+            merge_strs(&["grusp", "--case-sensitive", "--depth=3", "--exclude", "*.md", "fn"], defaults).unwrap(),
+            vec!["grusp", "--trim", "--exclude", "target/", "--case-sensitive", "--depth=3", "--exclude", "*.md", "fn"]
+        );
+        assert_eq!(merge_strs(&["grusp", "-id1", "fn"], "--depth 2 --ignore-case").unwrap(), vec!["grusp", "-id1", "fn"]);
+        assert_eq!(merge_strs(&["grusp", "--no-env", "fn"], "--trim 'unclosed").unwrap(), vec!["grusp", "--no-env", "fn"]);
+    }
+
+    #[test]
+    fn it_only_takes_flags_as_defaults() {
+        assert_eq!(merge_strs(&["grusp", "fn"], "--smart-case").unwrap_err(), "Unknown flag '--smart-case'");
+        assert_eq!(merge_strs(&["grusp", "fn"], "--trim src").unwrap_err(), "Expected only flags but got 'src'");
+        assert_eq!(merge_strs(&["grusp", "fn"], "--trim --depth").unwrap_err(), "Expected a value after '--depth'");
+        assert_eq!(merge_strs(&["grusp", "fn"], "--exclude --trim").unwrap_err(), "Expected a value after '--exclude'");
+    }
+}
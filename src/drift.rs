@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+use rayon::prelude::*;
+use grusp_core::grusp::Matcher;
+use args::Opts;
+
+/// How many lines of a file matched in each tree, for `--diff-trees`.
+#[derive(Debug, PartialEq)]
+pub struct Delta {
+    /// The path of the file relative to the roots of the trees.
+    pub path: PathBuf,
+    pub old: u32,
+    pub new: u32,
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let change = i64::from(self.new) - i64::from(self.old);
+        write!(f, "{} {} -> {} ({:+})", self.path.display(), self.old, self.new, change)
+    }
+}
+
+/// Searches both trees with the same options and returns the files whose number of
+/// matching lines differs, including files only in one of them, sorted by path.
+pub fn compare(old: &str, new: &str, opts: &Opts, matcher: &Matcher) -> Vec<Delta> {
+    let old_counts = counts(old, opts, matcher);
+    let new_counts = counts(new, opts, matcher);
+    let mut paths: Vec<&PathBuf> = old_counts.keys().chain(new_counts.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .map(|path| Delta {
+            path: path.clone(),
+            old: old_counts.get(path).cloned().unwrap_or(0),
+            new: new_counts.get(path).cloned().unwrap_or(0),
+        })
+        .filter(|delta| delta.old != delta.new)
+        .collect()
+}
+
+/// The number of matching lines of each file in the tree that matched, by relative path.
+fn counts(root: &str, opts: &Opts, matcher: &Matcher) -> BTreeMap<PathBuf, u32> {
+    let files = opts.file_collector(&vec![root.to_string()]).collect();
+    // The collected paths don't start with ./ even when the root does
+    let prefix: PathBuf = Path::new(root).components().skip_while(|c| *c == Component::CurDir).collect();
+    let count = |path: &PathBuf| {
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        let count = matcher.collect(&mut reader).ok()?.count();
+        let relative = path.strip_prefix(&prefix).unwrap_or(path).to_path_buf();
+        if count > 0 { Some((relative, count)) } else { None }
+    };
+    if opts.is_concurrent {
+        files.par_iter().filter_map(count).collect()
+    } else {
+        files.iter().filter_map(count).collect()
+    }
+}
+
+/// The totals printed after the deltas.
+pub struct Summary<'a>(pub &'a [Delta]);
+
+impl<'a> fmt::Display for Summary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let added: i64 = self.0.iter().map(|d| (i64::from(d.new) - i64::from(d.old)).max(0)).sum();
+        let removed: i64 = self.0.iter().map(|d| (i64::from(d.old) - i64::from(d.new)).max(0)).sum();
+        let files = if self.0.len() == 1 { "file" } else { "files" };
+        write!(f, "{} {} changed, {} matching lines added, {} removed", self.0.len(), files, added, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_formats_the_change_with_a_sign() {
+        let delta = Delta { path: PathBuf::from("src/lib.rs"), old: 3, new: 1 };
+        assert_eq!(delta.to_string(), "src/lib.rs 3 -> 1 (-2)");
+        let delta = Delta { path: PathBuf::from("new.rs"), old: 0, new: 2 };
+        assert_eq!(delta.to_string(), "new.rs 0 -> 2 (+2)");
+    }
+
+    #[test]
+    fn it_sums_the_lines_added_and_removed() {
+        let deltas = [
+            Delta { path: PathBuf::from("a"), old: 3, new: 1 },
+            Delta { path: PathBuf::from("b"), old: 0, new: 5 },
+        ];
+        assert_eq!(Summary(&deltas).to_string(), "2 files changed, 5 matching lines added, 2 removed");
+    }
+}
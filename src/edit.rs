@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Mutex;
+use grusp_core::grusp::Matches;
+
+/// How an editor is told which line to open a file at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineArg {
+    /// `+LINE FILE`, as vi, emacs, nano and most terminal editors take it.
+    Plus,
+    /// `--goto FILE:LINE`, as VS Code takes it.
+    Goto,
+    /// `FILE:LINE`, as Sublime Text takes it.
+    Suffix,
+}
+
+impl LineArg {
+    fn of(program: &str) -> Option<Self> {
+        let name = Path::new(program).file_name().and_then(|name| name.to_str()).unwrap_or(program);
+        match name {
+            "vi" | "vim" | "nvim" | "gvim" | "mvim" | "nano" | "pico" | "emacs" | "emacsclient" | "kak" | "micro" |
+            "joe" | "mg" | "hx" => Some(LineArg::Plus),
+            "code" | "code-insiders" | "codium" => Some(LineArg::Goto),
+            "subl" | "sublime_text" => Some(LineArg::Suffix),
+            _ => None,
+        }
+    }
+}
+
+/// The files that matched, collected for `--edit` to open in `$EDITOR` once the search
+/// is done. Files are opened in order of their paths, and the first is opened at the
+/// line of its first match when the editor is known to take one.
+#[derive(Debug, Default)]
+pub struct Edit {
+    files: Mutex<BTreeMap<PathBuf, Option<usize>>>,
+}
+
+impl Edit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the file of the matches, if any of them matched.
+    pub fn add(&self, matches: &Matches) {
+        let path = match matches.path() {
+            Some(path) if matches.has_matches() => path.to_path_buf(),
+            _ => return,
+        };
+        // Passthru and enclosing headings keep lines that didn't match ahead of the first match
+        let line = matches
+            .lines()
+            .iter()
+            .find(|line| !line.captures.is_empty())
+            .or_else(|| matches.lines().first())
+            .and_then(|line| line.number);
+        self.files.lock().expect("Edit lock poisoned").insert(path, line);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.lock().expect("Edit lock poisoned").is_empty()
+    }
+
+    /// Returns the program and arguments that open the files in the editor, which can
+    /// be a command with arguments of its own, such as `code -w`.
+    fn command(&self, editor: &str) -> Vec<String> {
+        let mut command: Vec<String> = editor.split_whitespace().map(String::from).collect();
+        let line_arg = command.first().and_then(|program| LineArg::of(program));
+        let files = self.files.lock().expect("Edit lock poisoned");
+        for (i, (path, line)) in files.iter().enumerate() {
+            let path = path.display().to_string();
+            match (line_arg, line) {
+                (Some(LineArg::Plus), &Some(line)) if i == 0 => command.extend(vec![format!("+{}", line), path]),
+                (Some(LineArg::Goto), &Some(line)) if i == 0 => {
+                    command.extend(vec!["--goto".to_string(), format!("{}:{}", path, line)])
+                }
+                (Some(LineArg::Suffix), &Some(line)) if i == 0 => command.push(format!("{}:{}", path, line)),
+                _ => command.push(path),
+            }
+        }
+        command
+    }
+
+    /// Opens the files in `$EDITOR`, or `$VISUAL` or vi when it isn't set, and waits for
+    /// it to close.
+    pub fn open(&self) -> io::Result<ExitStatus> {
+        let editor = env::var("EDITOR")
+            .or_else(|_| env::var("VISUAL"))
+            .ok()
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        let command = self.command(&editor);
+        Command::new(&command[0]).args(&command[1..]).status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grusp_core::grusp::Line;
+
+    fn edit(files: &[(&str, Option<usize>)]) -> Edit {
+        let edit = Edit::new();
+        for &(path, number) in files {
+            let line = Line { number, value: "TODO".to_string(), captures: Vec::new() };
+            let matches = Matches::from_lines(vec![line]).add_path(Path::new(path));
+            edit.add(&matches);
+        }
+        edit
+    }
+
+    #[test]
+    fn it_opens_the_first_file_at_its_first_match() {
+        let edit = edit(&[("b.rs", Some(9)), ("a.rs", Some(3))]);
+        assert_eq!(edit.command("vim"), vec!["vim", "+3", "a.rs", "b.rs"]);
+        assert_eq!(edit.command("/usr/bin/nvim"), vec!["/usr/bin/nvim", "+3", "a.rs", "b.rs"]);
+        assert_eq!(edit.command("code -w"), vec!["code", "-w", "--goto", "a.rs:3", "b.rs"]);
+        assert_eq!(edit.command("subl"), vec!["subl", "a.rs:3", "b.rs"]);
+        assert_eq!(edit.command("ed"), vec!["ed", "a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn it_opens_files_without_line_numbers_as_they_are() {
+        let edit = edit(&[("a.rs", None)]);
+        assert_eq!(edit.command("vim"), vec!["vim", "a.rs"]);
+    }
+}
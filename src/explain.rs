@@ -0,0 +1,262 @@
+use std::fmt;
+use regex_syntax::{Expr, ExprBuilder, Repeater};
+use regex_syntax::Error as SyntaxError;
+
+/// A description of how a pattern is parsed and which optimizations the regex
+/// engine can apply to it. Displaying it renders a report for `--explain`.
+pub struct Explanation {
+    pattern: String,
+    is_case_insensitive: bool,
+    expr: Expr,
+}
+
+/// A rough idea of how expensive a pattern is to run.
+#[derive(Debug, PartialEq, Eq)]
+enum Complexity {
+    Low,
+    Medium,
+    High,
+}
+
+/// Counts of the parts of an expression that drive the cost of matching.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Stats {
+    nodes: usize,
+    repetitions: usize,
+    max_repeat_nesting: usize,
+    largest_counted_repeat: u32,
+    classes: usize,
+    unicode_word_boundaries: usize,
+}
+
+impl Explanation {
+    pub fn new(pattern: &str, is_case_insensitive: bool) -> Result<Self, SyntaxError> {
+        let expr = ExprBuilder::new()
+            .case_insensitive(is_case_insensitive)
+            .parse(pattern)?;
+        Ok(Self { pattern: pattern.to_string(), is_case_insensitive, expr })
+    }
+
+    fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        collect_stats(&self.expr, 0, &mut stats);
+        stats
+    }
+
+    fn complexity(stats: &Stats) -> Complexity {
+        if stats.max_repeat_nesting > 1 || stats.largest_counted_repeat > 100 {
+            Complexity::High
+        } else if stats.repetitions > 3 || stats.classes > 5 || stats.unicode_word_boundaries > 0 {
+            Complexity::Medium
+        } else {
+            Complexity::Low
+        }
+    }
+
+    fn fast_path(&self, stats: &Stats) -> String {
+        let prefixes = self.expr.prefixes();
+        let engine = if stats.unicode_word_boundaries > 0 {
+            "The lazy DFA can't handle Unicode word boundaries (\\b), so the slower NFA is used. Use (?-u:\\b) if ASCII is enough."
+        } else {
+            "The lazy DFA can be used."
+        };
+        if !prefixes.is_empty() && prefixes.all_complete() && !prefixes.contains_empty() {
+            format!("Pure literal search, the regex engine is skipped entirely. {}", engine)
+        } else if !prefixes.is_empty() && !prefixes.contains_empty() {
+            format!("Lines are scanned for the literal prefixes before running the regex engine. {}", engine)
+        } else {
+            format!("No literal prefix, every position is tried with the regex engine. {}", engine)
+        }
+    }
+}
+
+fn collect_stats(expr: &Expr, nesting: usize, stats: &mut Stats) {
+    stats.nodes += 1;
+    match *expr {
+        Expr::Class(_) | Expr::ClassBytes(_) => stats.classes += 1,
+        Expr::WordBoundary | Expr::NotWordBoundary => stats.unicode_word_boundaries += 1,
+        Expr::Group { ref e, .. } => collect_stats(e, nesting, stats),
+        Expr::Repeat { ref e, r, .. } => {
+            stats.repetitions += 1;
+            stats.max_repeat_nesting = stats.max_repeat_nesting.max(nesting + 1);
+            if let Repeater::Range { min, max } = r {
+                stats.largest_counted_repeat = stats.largest_counted_repeat.max(max.unwrap_or(min));
+            }
+            collect_stats(e, nesting + 1, stats);
+        }
+        Expr::Concat(ref es) | Expr::Alternate(ref es) => {
+            for e in es {
+                collect_stats(e, nesting, stats);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Unicode classes can have hundreds of ranges, so they're summarized past this many.
+const MAX_CLASS_RANGES: usize = 8;
+
+fn escape(c: char) -> String {
+    if c.is_ascii_graphic() {
+        c.to_string()
+    } else {
+        c.escape_unicode().to_string()
+    }
+}
+
+fn describe(expr: &Expr) -> String {
+    match *expr {
+        Expr::Empty => "empty".to_string(),
+        Expr::Literal { ref chars, casei } => {
+            format!("literal {:?}{}", chars.iter().collect::<String>(), if casei { " (ignoring case)" } else { "" })
+        }
+        Expr::LiteralBytes { ref bytes, casei } => {
+            format!("literal bytes {:?}{}", bytes, if casei { " (ignoring case)" } else { "" })
+        }
+        Expr::AnyChar => "any character".to_string(),
+        Expr::AnyCharNoNL => "any character except newline".to_string(),
+        Expr::AnyByte => "any byte".to_string(),
+        Expr::AnyByteNoNL => "any byte except newline".to_string(),
+        Expr::Class(ref class) => {
+            if class.len() <= MAX_CLASS_RANGES {
+                let ranges: String = class
+                    .iter()
+                    .map(|r| if r.start == r.end {
+                        escape(r.start)
+                    } else {
+                        format!("{}-{}", escape(r.start), escape(r.end))
+                    })
+                    .collect();
+                format!("class [{}]", ranges)
+            } else {
+                let size: u32 = class.iter().map(|r| r.end as u32 - r.start as u32 + 1).sum();
+                format!("class of {} ranges ({} characters)", class.len(), size)
+            }
+        }
+        Expr::ClassBytes(ref class) => format!("byte class {}", Expr::ClassBytes(class.clone())),
+        Expr::StartLine => "start of line".to_string(),
+        Expr::EndLine => "end of line".to_string(),
+        Expr::StartText => "start of text".to_string(),
+        Expr::EndText => "end of text".to_string(),
+        Expr::WordBoundary => "unicode word boundary".to_string(),
+        Expr::NotWordBoundary => "not a unicode word boundary".to_string(),
+        Expr::WordBoundaryAscii => "ascii word boundary".to_string(),
+        Expr::NotWordBoundaryAscii => "not an ascii word boundary".to_string(),
+        Expr::Group { i: None, .. } => "group".to_string(),
+        Expr::Group { i: Some(i), name: None, .. } => format!("capture group {}", i),
+        Expr::Group { i: Some(i), name: Some(ref name), .. } => format!("capture group {} named {:?}", i, name),
+        Expr::Repeat { r, greedy, .. } => {
+            let times = match r {
+                Repeater::ZeroOrOne => "zero or one time".to_string(),
+                Repeater::ZeroOrMore => "zero or more times".to_string(),
+                Repeater::OneOrMore => "one or more times".to_string(),
+                Repeater::Range { min, max: None } => format!("at least {} times", min),
+                Repeater::Range { min, max: Some(max) } if min == max => format!("exactly {} times", min),
+                Repeater::Range { min, max: Some(max) } => format!("between {} and {} times", min, max),
+            };
+            format!("repeat {}{}", times, if greedy { "" } else { " (lazy)" })
+        }
+        Expr::Concat(_) => "sequence".to_string(),
+        Expr::Alternate(_) => "alternation".to_string(),
+    }
+}
+
+fn write_tree(f: &mut fmt::Formatter, expr: &Expr, depth: usize) -> fmt::Result {
+    writeln!(f, "{:indent$}{}", "", describe(expr), indent = 2 + depth * 2)?;
+    match *expr {
+        Expr::Group { ref e, .. } | Expr::Repeat { ref e, .. } => write_tree(f, e, depth + 1),
+        Expr::Concat(ref es) | Expr::Alternate(ref es) => {
+            for e in es {
+                write_tree(f, e, depth + 1)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn literals(lits: &::regex_syntax::Literals) -> String {
+    if lits.is_empty() || lits.contains_empty() {
+        return "none".to_string();
+    }
+    lits.literals()
+        .iter()
+        .map(|lit| format!("{:?}", String::from_utf8_lossy(lit)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stats = self.stats();
+        writeln!(f, "Pattern: {}", self.pattern)?;
+        writeln!(f, "Case: {}", if self.is_case_insensitive { "insensitive" } else { "sensitive" })?;
+        writeln!(f)?;
+        writeln!(f, "Parsed as:")?;
+        write_tree(f, &self.expr, 0)?;
+        writeln!(f)?;
+        writeln!(f, "Literal prefixes: {}", literals(&self.expr.prefixes()))?;
+        writeln!(f, "Literal suffixes: {}", literals(&self.expr.suffixes()))?;
+        writeln!(f, "Anchored: {}{}",
+                 if self.expr.is_anchored_start() { "start " } else { "" },
+                 if self.expr.is_anchored_end() { "end" } else if self.expr.is_anchored_start() { "" } else { "no" })?;
+        writeln!(f, "Fast path: {}", self.fast_path(&stats))?;
+        writeln!(f, "Complexity: {:?} ({} nodes, {} repetitions nested {} deep, {} classes)",
+                 Self::complexity(&stats),
+                 stats.nodes,
+                 stats.repetitions,
+                 stats.max_repeat_nesting,
+                 stats.classes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_explains_a_literal_pattern() {
+        let explanation = Explanation::new("fn", false).unwrap();
+        let output = explanation.to_string();
+        assert!(output.contains("literal \"fn\""));
+        assert!(output.contains("Literal prefixes: \"fn\""));
+        assert!(output.contains("Pure literal search"));
+        assert!(output.contains("Complexity: Low"));
+    }
+
+    #[test]
+    fn it_explains_the_syntax_tree() {
+        let output = Explanation::new(r"fn.*\(", false).unwrap().to_string();
+        assert!(output.contains("  sequence\n    literal \"fn\"\n    repeat zero or more times\n      any character except newline\n"));
+        assert!(output.contains("Lines are scanned for the literal prefixes"));
+    }
+
+    #[test]
+    fn it_summarizes_large_classes() {
+        let output = Explanation::new(r"[a-c]\w", false).unwrap().to_string();
+        assert!(output.contains("class [a-c]\n"));
+        assert!(output.contains("class of "));
+
+        let output = Explanation::new(r"[\t ]", false).unwrap().to_string();
+        assert!(output.contains("class [\\u{9}\\u{20}]"));
+    }
+
+    #[test]
+    fn it_warns_about_unicode_word_boundaries() {
+        let output = Explanation::new(r"\bfn\b", false).unwrap().to_string();
+        assert!(output.contains("can't handle Unicode word boundaries"));
+    }
+
+    #[test]
+    fn it_rates_nested_repetition_as_complex() {
+        let explanation = Explanation::new(r"(a+)+b", false).unwrap();
+        let stats = explanation.stats();
+        assert_eq!(stats.max_repeat_nesting, 2);
+        assert_eq!(Explanation::complexity(&stats), Complexity::High);
+    }
+
+    #[test]
+    fn it_errors_on_invalid_patterns() {
+        assert!(Explanation::new("fn(", false).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+use regex::Regex;
+use serde_json::Value;
+use grusp_core::grusp::{Line, MatchFilter};
+
+/// A field of a JSON object and the regex its value has to match.
+#[derive(Debug, Clone)]
+pub struct JsonField {
+    /// The JSON pointer to the field, from a name like `user.name`.
+    pointer: String,
+    regex: Regex,
+}
+
+impl JsonField {
+    /// Creates a field from its name, where dots separate the names of nested objects
+    /// and the indexes of arrays.
+    pub fn new(name: &str, regex: Regex) -> Self {
+        let pointer = name
+            .split('.')
+            .map(|part| format!("/{}", part.replace('~', "~0").replace('/', "~1")))
+            .collect();
+        JsonField { pointer, regex }
+    }
+
+    fn is_match(&self, object: &Value) -> bool {
+        match object.pointer(&self.pointer) {
+            Some(Value::String(value)) => self.regex.is_match(value),
+            // Numbers, booleans and null are matched as they're written, and nested
+            // objects and arrays as compact JSON
+            Some(value) => self.regex.is_match(&value.to_string()),
+            None => false,
+        }
+    }
+}
+
+/// Keeps the lines of JSON-lines input whose fields all match their regexes, so a
+/// structured log can be searched without false positives from its other fields. Lines
+/// that aren't JSON, or that don't have every field, are dropped.
+#[derive(Debug, Clone)]
+pub struct JsonFields {
+    fields: Vec<JsonField>,
+}
+
+impl JsonFields {
+    pub fn new(fields: Vec<JsonField>) -> Self {
+        JsonFields { fields }
+    }
+}
+
+impl MatchFilter for JsonFields {
+    fn keep(&self, line: &Line, _: &Path) -> bool {
+        match ::serde_json::from_str::<Value>(&line.value) {
+            Ok(object) => self.fields.iter().all(|field| field.is_match(&object)),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keeps(fields: &[(&str, &str)], value: &str) -> bool {
+        let fields = fields.iter().map(|&(name, regex)| JsonField::new(name, Regex::new(regex).unwrap())).collect();
+        let line = Line { number: Some(1), value: value.to_string(), captures: Vec::new() };
+        JsonFields::new(fields).keep(&line, Path::new(""))
+    }
+
+    #[test]
+    fn it_only_matches_the_named_field() {
+        let line = r#"{"level":"info","msg":"error budget ok"}"#;
+        assert!(!keeps(&[("level", "error")], line));
+        assert!(keeps(&[("msg", "error")], line));
+        assert!(!keeps(&[("msg", "error"), ("level", "^error$")], line));
+    }
+
+    #[test]
+    fn it_matches_nested_and_non_string_fields() {
+        let line = r#"{"user":{"name":"alice","roles":["admin"]},"status":503}"#;
+        assert!(keeps(&[("user.name", "^alice$")], line));
+        assert!(keeps(&[("user.roles.0", "admin")], line));
+        assert!(keeps(&[("status", "^5\\d\\d$")], line));
+        assert!(!keeps(&[("user.email", "")], line));
+        assert!(!keeps(&[("msg", "")], "not json"));
+    }
+}
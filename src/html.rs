@@ -0,0 +1,232 @@
+use std::fmt;
+use std::sync::Mutex;
+use grusp_core::grusp::{Line, Matches};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #24292f; }
+table { border-collapse: collapse; }
+.summary td, .summary th { padding: 0.2em 1em; border-bottom: 1px solid #d0d7de; text-align: left; }
+.summary td.count { text-align: right; }
+details { margin: 1em 0; border: 1px solid #d0d7de; border-radius: 6px; }
+summary { padding: 0.5em 1em; background: #f6f8fa; cursor: pointer; font-family: monospace; }
+.lines td { font-family: monospace; white-space: pre; padding: 0 1em; }
+.lines td.number { color: #8c959f; text-align: right; user-select: none; }
+.rule { color: #8250df; }
+.k { color: #cf222e; }
+.s { color: #0a3069; }
+.c { color: #6e7781; font-style: italic; }
+.n { color: #0550ae; }
+mark { background: #fff8c5; outline: 1px solid #d4a72c; }
+";
+
+/// Words colored as keywords, shared by most languages closely enough for a report.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "default", "defer",
+    "do", "elif", "else", "enum", "except", "export", "extends", "false", "finally", "fn", "for", "from", "func",
+    "function", "go", "if", "impl", "import", "in", "interface", "let", "loop", "match", "mod", "mut", "new",
+    "nil", "None", "null", "package", "pub", "public", "private", "return", "self", "static", "struct", "switch",
+    "this", "throw", "trait", "true", "try", "type", "use", "var", "where", "while", "yield",
+];
+
+/// How a byte of a line is colored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+impl Token {
+    fn class(self) -> Option<&'static str> {
+        match self {
+            Token::Plain => None,
+            Token::Keyword => Some("k"),
+            Token::String => Some("s"),
+            Token::Comment => Some("c"),
+            Token::Number => Some("n"),
+        }
+    }
+}
+
+/// Collects the matches of a search as a standalone HTML page for `--format html`, so
+/// they can be shared with people who don't use a terminal. The page starts with a
+/// table of the files and how many lines of each matched, followed by a section for
+/// each file that can be collapsed, where keywords, strings, comments and numbers are
+/// colored and the matches are marked.
+#[derive(Default)]
+pub struct Report {
+    files: Mutex<Vec<(String, Vec<Line>)>>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the matching lines of a file, if any matched.
+    pub fn add(&self, matches: &Matches) {
+        if !matches.has_matches() {
+            return;
+        }
+        let path = matches.path().map_or("<stdin>".to_string(), |path| path.display().to_string());
+        self.files.lock().expect("HTML lock poisoned").push((path, matches.lines().to_vec()));
+    }
+}
+
+/// Escapes the text to be shown as is in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the token of each byte of the line. Only single lines are looked at, so a
+/// string or comment spanning several lines is only colored on the line it starts.
+fn tokenize(line: &str) -> Vec<Token> {
+    let bytes = line.as_bytes();
+    let mut tokens = vec![Token::Plain; bytes.len()];
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &line[i..];
+        let (token, len) = if rest.starts_with("//") || rest.starts_with('#') {
+            (Token::Comment, rest.len())
+        } else if rest.starts_with('"') || rest.starts_with('\'') || rest.starts_with('`') {
+            let quote = bytes[i];
+            let mut end = 1;
+            while end < rest.len() && bytes[i + end] != quote {
+                end += if bytes[i + end] == b'\\' { 2 } else { 1 };
+            }
+            (Token::String, (end + 1).min(rest.len()))
+        } else if bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let word = &rest[..len];
+            if bytes[i].is_ascii_digit() {
+                (Token::Number, len)
+            } else if KEYWORDS.contains(&word) {
+                (Token::Keyword, len)
+            } else {
+                (Token::Plain, len)
+            }
+        } else {
+            (Token::Plain, rest.chars().next().map_or(1, char::len_utf8))
+        };
+        for t in &mut tokens[i..i + len] {
+            *t = token;
+        }
+        i += len;
+    }
+    tokens
+}
+
+/// Writes the line as HTML, colored by its tokens with its matches marked.
+fn write_line(f: &mut fmt::Formatter, line: &Line) -> fmt::Result {
+    let text = line.value.trim_end();
+    let tokens = tokenize(text);
+    let mut marked = vec![false; text.len()];
+    for range in line.highlight_ranges() {
+        for m in &mut marked[range.start.min(text.len())..range.end.min(text.len())] {
+            *m = true;
+        }
+    }
+    // Tokens and matches start and end on character boundaries, so each run of the same
+    // token and mark can be sliced out of the line
+    let mut start = 0;
+    while start < text.len() {
+        let (token, mark) = (tokens[start], marked[start]);
+        let run = (start..text.len()).take_while(|&i| tokens[i] == token && marked[i] == mark).count();
+        let mut html = escape(&text[start..start + run]);
+        if let Some(class) = token.class() {
+            html = format!("<span class=\"{}\">{}</span>", class, html);
+        }
+        if mark {
+            html = format!("<mark>{}</mark>", html);
+        }
+        f.write_str(&html)?;
+        start += run;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut files = self.files.lock().expect("HTML lock poisoned").clone();
+        // Files are searched concurrently, so sort for a stable report
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let total: usize = files.iter().map(|(_, lines)| lines.iter().filter(|line| !line.captures.is_empty()).count()).sum();
+        writeln!(f, "<!DOCTYPE html>")?;
+        writeln!(f, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>grusp report</title>")?;
+        writeln!(f, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+        writeln!(f, "<h1>grusp report</h1>")?;
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        writeln!(f, "<p>{} matching line{} in {} file{}</p>", total, plural(total), files.len(), plural(files.len()))?;
+        writeln!(f, "<table class=\"summary\">\n<tr><th>File</th><th>Lines</th></tr>")?;
+        for (i, (path, lines)) in files.iter().enumerate() {
+            let count = lines.iter().filter(|line| !line.captures.is_empty()).count();
+            writeln!(f, "<tr><td><a href=\"#file-{}\">{}</a></td><td class=\"count\">{}</td></tr>", i, escape(path), count)?;
+        }
+        writeln!(f, "</table>")?;
+        for (i, (path, lines)) in files.iter().enumerate() {
+            writeln!(f, "<details id=\"file-{}\" open>\n<summary>{}</summary>", i, escape(path))?;
+            writeln!(f, "<table class=\"lines\">")?;
+            for line in lines {
+                let number = line.number.map(|number| number.to_string()).unwrap_or_default();
+                write!(f, "<tr><td class=\"number\">{}</td><td>", number)?;
+                let mut rules: Vec<&str> = Vec::new();
+                for rule in line.captures.iter().filter_map(|capture| capture.rule.as_deref()) {
+                    if !rules.contains(&rule) {
+                        rules.push(rule);
+                    }
+                }
+                if !rules.is_empty() {
+                    write!(f, "<span class=\"rule\">[{}]</span> ", escape(&rules.join(", ")))?;
+                }
+                write_line(f, line)?;
+                writeln!(f, "</td></tr>")?;
+            }
+            writeln!(f, "</table>\n</details>")?;
+        }
+        writeln!(f, "</body>\n</html>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grusp_core::grusp::Matcher;
+    use regex::Regex;
+    use std::path::Path;
+
+    #[test]
+    fn it_colors_tokens_and_marks_matches() {
+        let regex = Regex::new("TODO").unwrap();
+        let text = "let s = \"<a>\"; // TODO: 42\n";
+        let report = Report::new();
+        report.add(&Matcher::new(&regex).collect_path(&mut text.as_bytes(), Path::new("src/a&b.rs")).unwrap());
+        let html = report.to_string();
+        assert!(html.contains("<a href=\"#file-0\">src/a&amp;b.rs</a></td><td class=\"count\">1</td>"));
+        assert!(html.contains(
+            "<span class=\"k\">let</span> s = <span class=\"s\">&quot;&lt;a&gt;&quot;</span>; <span class=\"c\">// </span>\
+             <mark><span class=\"c\">TODO</span></mark><span class=\"c\">: 42</span>"
+        ));
+    }
+
+    #[test]
+    fn it_splits_tokens_on_characters() {
+        let tokens = tokenize("é1 'x\\'y' z");
+        assert_eq!(tokens[0], Token::Plain);
+        assert_eq!(tokens[2], Token::Number);
+        assert_eq!(&tokens[4..10], &[Token::String; 6]);
+        assert_eq!(tokens[11], Token::Plain);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The files listed by `--files-with-matches` or `--files-without-matches`, printed once
+/// the search is done, sorted by path and each only once. Files are searched concurrently
+/// and can be reached by more than one query, so printing them as they're found would
+/// make the list differ between runs, while scripts and diffs need it stable.
+#[derive(Debug, Default)]
+pub struct FileList {
+    files: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl FileList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the file with how it's displayed. A file added again keeps its first display.
+    pub fn add(&self, path: &Path, display: String) {
+        self.files.lock().expect("File list lock poisoned").entry(path.to_path_buf()).or_insert(display);
+    }
+}
+
+impl fmt::Display for FileList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for display in self.files.lock().expect("File list lock poisoned").values() {
+            writeln!(f, "{}", display)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lists_each_file_once_in_order() {
+        let list = FileList::new();
+        for path in &["src/b.rs", "src/a/c.rs", "src/b.rs", "src/a.rs"] {
+            list.add(Path::new(path), path.to_string());
+        }
+        // Paths are compared a component at a time, so a directory sorts before a file
+        // whose name it starts
+        assert_eq!(list.to_string(), "src/a/c.rs\nsrc/a.rs\nsrc/b.rs\n");
+    }
+}
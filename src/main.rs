@@ -2,90 +2,844 @@ extern crate rayon;
 extern crate clap;
 extern crate atty;
 extern crate regex;
+extern crate regex_syntax;
+#[macro_use]
+extern crate serde_json;
+extern crate flate2;
+extern crate ruzstd;
+#[macro_use]
+extern crate log;
+#[cfg(unix)]
+extern crate libc;
 extern crate grusp_core;
 
 pub mod args;
+mod config;
+mod debug;
+mod decompress;
+mod defaults;
+mod drift;
+mod edit;
+mod explain;
+mod fields;
+mod html;
+mod listing;
+mod man;
+mod output;
+mod pager;
+mod policy;
+mod presets;
+mod rewrite;
+mod sarif;
+mod schedule;
+mod secrets;
+mod server;
+mod split;
+mod suggest;
+mod summary;
+mod timeline;
+mod verbose;
 
 use rayon::prelude::*;
-use std::path::PathBuf;
-use std::io::BufReader;
-use std::fs::File;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::io::{self, BufRead, BufReader, Cursor};
+use std::fs::{self, File};
 use std::io::stdin;
+use std::time::Instant;
 use grusp_core::grusp;
 
 fn main() {
     let opts = match args::get_opts() {
         Ok(o) => o,
         Err(e) => {
-            println!("{:?}", e);
+            println!("{}", e);
             std::process::exit(1);
         }
     };
+    verbose::Logger::init(opts.verbosity);
+    if let Some(limit) = opts.debug {
+        eprint!("{}", debug::Report::new(&opts, limit));
+    }
+    // Without a pager the results are still shown, just all at once
+    if opts.is_paged {
+        let _ = pager::start();
+    }
+    if opts.is_explain {
+        match explain::Explanation::new(opts.regex.as_str(), opts.is_case_insensitive) {
+            Ok(explanation) => print!("{}", explanation),
+            Err(e) => println!("{}", args::RegexError::from_syntax(opts.regex.as_str(), &e)),
+        }
+        return;
+    }
+    if let Some(ref address) = opts.serve {
+        if let Err(e) = server::Server::new(&opts).serve(address) {
+            eprintln!("Could not serve on {}: {}", address, e);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if let Some(format) = opts.secrets {
+        let stats = grusp::StatCollector::new();
+        let (findings, is_valid) = scan_secrets(&opts, &stats);
+        writeln!(opts.output, "{}", secrets::Report { findings: &findings, format });
+        report_unreadable(&opts, &stats);
+        if stats.unreadable() > 0 || !is_valid {
+            std::process::exit(2);
+        }
+        if !findings.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if opts.command == args::Command::Files {
+        return list_files(&opts);
+    }
+    let mut filters = grusp::MatchFilters::new();
+    if let Some(context) = opts.code_context {
+        filters = filters.with(grusp::CodeContextFilter::new(context));
+    }
+    if let Some(ref fields) = opts.json_fields {
+        filters = filters.with(fields.clone());
+    }
+    if let Some(scope) = opts.unique {
+        filters = filters.with(grusp::UniqueLinesFilter::new(scope));
+    }
+    // The baseline goes last so it only sees the lines the other filters keep
+    let baseline = opts.baseline.as_ref().map(|path| {
+        if opts.queries.is_none() {
+            eprintln!("A baseline records matches by path, so it needs paths to search");
+            std::process::exit(2);
+        }
+        if !opts.is_check_baseline {
+            return grusp::Baseline::record();
+        }
+        grusp::Baseline::load(path).unwrap_or_else(|e| {
+            eprintln!("Could not read the baseline {}: {}", path.display(), e);
+            std::process::exit(2);
+        })
+    });
+    if let Some(ref baseline) = baseline {
+        filters = filters.with(baseline.clone());
+    }
+    // The severity of a match comes from its captures, so --fail-on needs them kept
+    let keep_lines = opts.policy.is_some() || !(opts.just_files.is_some() || opts.is_count_only || opts.top.is_some());
+    // Filters can keep a line based on any of its captures, so they all have to be found
+    let needs_captures = keep_lines || !filters.is_empty();
+    let options = grusp::SearchOptions::new()
+        .keep_lines(keep_lines)
+        .first_match_only(opts.just_files.is_some())
+        .directives(opts.is_directives)
+        .max_captures_per_line(opts.max_count_per_line.or(if needs_captures { None } else { Some(1) }))
+        .invert_match(opts.is_inverted)
+        .crlf(opts.is_crlf)
+        .max_line_length(opts.max_line_length)
+        .line_terminator(if opts.is_null_data { b'\0' } else { b'\n' })
+        .line_range(opts.line_range)
+        .head(opts.search_head)
+        .tail(opts.search_tail)
+        .passthru(opts.is_passthru)
+        .paragraph(opts.is_paragraph)
+        .normalization(opts.normalization);
     let matcher = grusp::Matcher::new(&opts.regex)
-        .keep_lines(!(opts.just_files.is_some() || opts.is_count_only))
-        .invert_match(opts.is_inverted);
+        .options(options)
+        .patterns(opts.patterns.as_ref())
+        .filters(&filters)
+        .enclosing(opts.enclosing.as_ref())
+        .record_separator(opts.record_separator.as_ref());
+    // The matcher describes every option that changes what matches, except for the case
+    // insensitivity built into the regex. Filters can depend on the path or on the files
+    // searched before, so their results aren't cached.
+    let cache = match opts.cache_dir {
+        Some(ref dir) if filters.is_empty() => {
+            Some(grusp::ResultCache::new(dir, &format!("{:?} {}", matcher, opts.is_case_insensitive)))
+        }
+        _ => None,
+    };
 
-    if let Some(ref queries) = opts.queries {
-        let stats = grusp::StatCollector::new();
-        let files = grusp::FileCollector::new(&queries).max_depth(opts.max_depth).collect();
-        let has_files = !files.is_empty();
-
-        if opts.is_concurrent {
-            files
-                .into_par_iter()
-                .for_each(|p| {
-                    match_file(p, &opts, &matcher, &stats)
-                });
+    if let Some((ref old, ref new)) = opts.diff_trees {
+        let deltas = drift::compare(old, new, &opts, &matcher);
+        for delta in &deltas {
+            writeln!(opts.output, "{}", delta);
+        }
+        writeln!(opts.output, "{}", drift::Summary(&deltas));
+        if deltas.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+    // The documents of stdin are searched like files, with no paths to search
+    let documents = opts.split_on.as_ref().map(|_| Vec::new());
+    if let Some(queries) = opts.queries.as_ref().or(documents.as_ref()) {
+        let start = Instant::now();
+        let stats = grusp::StatCollector::new().max_files(opts.max_files);
+        let mut is_missing = false;
+        let has_files = if let Some(ref separator) = opts.split_on {
+            let stdin = stdin();
+            let splitter = split::Splitter::new(separator, if opts.is_null_data { b'\0' } else { b'\n' });
+            let documents = decompress::decode(stdin.lock()).and_then(|mut reader| {
+                splitter.for_each(&mut reader, |name, contents| {
+                    if !stats.is_full() {
+                        match_reader(Path::new(name), &mut Cursor::new(contents), &opts, &matcher, &stats);
+                    }
+                })
+            });
+            documents.unwrap_or_else(|e| {
+                warn(&opts, format!("Could not read stdin: {}", e));
+                is_missing = true;
+                false
+            })
+        } else if let Some(ref rev) = opts.git_rev {
+            let revision = grusp::GitRevision::new(rev);
+            let files = match revision.files(queries) {
+                Ok(files) => files,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            };
+            let has_files = !files.is_empty();
+            for_each_path(files, opts.is_concurrent, None, |p| {
+                if stats.is_full() {
+                    return;
+                }
+                let path = revision.display_path(&p);
+                match revision.read(&p) {
+                    Ok(contents) => match_reader(&path, &mut Cursor::new(contents), &opts, &matcher, &stats),
+                    Err(e) => unreadable(&path, &e, &stats),
+                }
+            });
+            has_files
+        } else if opts.is_index_search {
+            let mut files = Vec::new();
+            for query in queries {
+                let index = match grusp::Index::open(Path::new(query)) {
+                    Ok(index) => index,
+                    Err(e) => {
+                        eprintln!("Could not open the index of {}, build it with `grusp index build {}`: {}", query, query, e);
+                        std::process::exit(2);
+                    }
+                };
+                // Inverted and normalized searches can match lines without the literals, and
+                // the trigrams of the index don't span lines where matches of records can
+                let is_multiline = opts.is_paragraph || opts.record_separator.is_some() || opts.is_null_data;
+                if opts.is_inverted || opts.normalization.is_some() || is_multiline {
+                    files.extend(index.paths());
+                } else {
+                    files.extend(index.candidates(&opts.regex, opts.is_case_insensitive));
+                }
+            }
+            let has_files = !files.is_empty();
+            for_each_path(files, opts.is_concurrent, opts.priority, |p| {
+                match_file(p, &opts, &matcher, cache.as_ref(), &stats)
+            });
+            has_files
         } else {
-            files
-                .into_iter()
-                .for_each(|p| {
-                    match_file(p, &opts, &matcher, &stats)
+            // A `-` among the paths is stdin, which is searched in its place among them like
+            // grep does, so the paths between each `-` are collected and searched in turn
+            let is_stdin = queries.iter().any(|query| query == "-");
+            let paths: Vec<String> = queries.iter().filter(|query| *query != "-").cloned().collect();
+            is_missing = report_missing(&opts, &paths);
+            let negations: Vec<String> = paths.iter().filter(|query| query.starts_with('!')).cloned().collect();
+            let mut has_files = false;
+            let mut searched = Vec::new();
+            let mut seen = HashSet::new();
+            for (i, segment) in queries.split(|query| query == "-").enumerate() {
+                if i > 0 {
+                    let stdin = stdin();
+                    match decompress::decode(stdin.lock()) {
+                        Ok(mut reader) => match_reader(Path::new("<stdin>"), &mut reader, &opts, &matcher, &stats),
+                        Err(e) => unreadable(Path::new("<stdin>"), &e, &stats),
+                    }
+                    has_files = true;
+                }
+                let mut segment: Vec<String> = segment.iter().filter(|query| !query.starts_with('!')).cloned().collect();
+                // Only negations around a `-` leave out files, they don't search the current directory
+                if segment.is_empty() && is_stdin {
+                    continue;
+                }
+                segment.extend(negations.iter().cloned());
+                let (mut files, is_valid) = collect_files(&opts, &segment, &stats);
+                is_missing |= !is_valid;
+                files.retain(|file| seen.insert(file.clone()));
+                has_files |= !files.is_empty();
+                if opts.is_suggest {
+                    searched.extend(files.iter().cloned());
+                }
+                for_each_path(files, opts.is_concurrent, opts.priority, |p| {
+                    match_file(p, &opts, &matcher, cache.as_ref(), &stats)
                 });
+            }
+            if opts.is_suggest && stats.total() == 0 {
+                write!(
+                    opts.output,
+                    "{}",
+                    suggest::Suggestions::find(&opts.regex, opts.is_case_insensitive, queries, &searched)
+                );
+            }
+            has_files
         };
-        if stats.total() == 0 && !(has_files && opts.just_files.without_matches()) {
-            std::process::exit(1);
+        info!("Found {} matching lines in {} files in {:?}", stats.lines(), stats.total(), start.elapsed());
+        if let Some(fd) = opts.summary_json {
+            write_summary(&opts, fd, &summary::Summary::new(&stats, start.elapsed()));
+        }
+        if let Some(ref aggregator) = opts.aggregator {
+            if !aggregator.is_empty() {
+                writeln!(opts.output, "{}", aggregator);
+            }
+        }
+        if let Some(ref timeline) = opts.timeline {
+            print_timeline(&opts, timeline);
+        }
+        if let Some(ref grouped) = opts.grouped {
+            write!(opts.output, "{}", grouped);
+        }
+        if let Some(ref sarif) = opts.sarif {
+            writeln!(opts.output, "{}", sarif);
+        }
+        if let Some(ref html) = opts.html {
+            writeln!(opts.output, "{}", html);
+        }
+        if let Some(ref tree) = opts.tree {
+            write!(opts.output, "{}", tree);
+        }
+        if let Some(ref list) = opts.file_list {
+            write!(opts.output, "{}", list);
+        }
+        if opts.is_count_total && stats.total() > 1 {
+            writeln!(opts.output, "TOTAL: {}", stats.lines());
+        }
+        if let Some(ref rewrite) = opts.rewrite {
+            let (lines, files) = rewrite.summary();
+            writeln!(opts.output, "Replaced {} lines in {} files", lines, files);
         }
+        report_unreadable(&opts, &stats);
+        report_replaced(&opts, &stats);
+        report_long_lines(&opts, stats.long_lines());
+        if let Some(ref edit) = opts.edit {
+            if !edit.is_empty() {
+                if let Err(e) = edit.open() {
+                    eprintln!("Could not open the editor: {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        if let (Some(path), Some(baseline)) = (opts.baseline.as_ref(), baseline.as_ref()) {
+            if opts.is_check_baseline {
+                if stats.total() > 0 {
+                    eprintln!("Found {} new matching lines that aren't in the baseline", stats.lines());
+                    std::process::exit(1);
+                }
+                return;
+            }
+            if let Err(e) = baseline.save(path) {
+                eprintln!("Could not write the baseline {}: {}", path.display(), e);
+                std::process::exit(2);
+            }
+            eprintln!("Recorded {} matching lines in {}", baseline.len(), path.display());
+            return;
+        }
+        if let Some(n) = opts.top {
+            for (path, count) in stats.top(n) {
+                let matches = grusp::Matches::from_count(count).add_path(&path);
+                writeln!(opts.output, "{}", grusp::Display::new(matches).count_only(true));
+            }
+        }
+        let is_found = stats.total() > 0 || (has_files && opts.just_files.without_matches());
+        let is_failed = opts.rewrite.as_ref().is_some_and(|rewrite| rewrite.is_failed());
+        exit_with_policy(&opts, is_missing || is_failed || stats.unreadable() > 0, stats.lines(), is_found);
     } else {
+        if opts.edit.is_some() {
+            eprintln!("--edit opens the files that matched, so it needs paths to search");
+            std::process::exit(2);
+        }
+        if opts.rewrite.is_some() {
+            eprintln!("--in-place writes the files that matched, so it needs paths to search");
+            std::process::exit(2);
+        }
+        let start = Instant::now();
         let stdin = stdin();
-        let mut reader = stdin.lock();
-        let matches = matcher
-            .with_line_numbers(false)
-            .collect(&mut reader)
-            .expect("Could not parse stdin");
-        if matches.has_matches() {
-            println!(
+        let mut reader = match decompress::decode(stdin.lock()) {
+            Ok(reader) => reader,
+            Err(e) => {
+                warn(&opts, format!("Could not read stdin: {}", e));
+                return exit_with_policy(&opts, true, 0, false);
+            }
+        };
+        if opts.is_passthru {
+            let count = matcher
+                .with_line_numbers(false)
+                .for_each_line(&mut reader, |line| {
+                    if let Some(ref policy) = opts.policy {
+                        policy.add_line(&line);
+                    }
+                    writeln!(
+                        opts.output,
+                        "{}",
+                        grusp::LineDisplay::single(&line)
+                            .trim(opts.is_trimmed)
+                            .highlights(opts.highlights.clone())
+                            .redact(opts.redaction.clone())
+                            .replace(opts.replacement.clone())
+                            .max_columns(opts.max_columns)
+                    );
+                });
+            return match count {
+                Ok(count) => exit_with_policy(&opts, false, u64::from(count), count > 0),
+                Err(e) => {
+                    warn(&opts, format!("Could not read stdin: {}", e));
+                    exit_with_policy(&opts, true, 0, false)
+                }
+            };
+        }
+        // Vim needs a line number to jump to, even for stdin
+        let matches = match matcher.with_line_numbers(opts.is_vimgrep).collect(&mut reader) {
+            Ok(matches) => matches,
+            Err(e) => {
+                warn(&opts, format!("Could not read stdin: {}", e));
+                return exit_with_policy(&opts, true, 0, false);
+            }
+        };
+        if let Some(ref policy) = opts.policy {
+            policy.add(&matches);
+        }
+        report_long_lines(&opts, matches.long_lines() as u64);
+        if let Some(fd) = opts.summary_json {
+            let stats = grusp::StatCollector::new();
+            stats.add(&matches);
+            write_summary(&opts, fd, &summary::Summary::new(&stats, start.elapsed()));
+        }
+        let lines = u64::from(matches.count());
+        let is_found = if let Some(ref aggregator) = opts.aggregator {
+            aggregator.add(&matches);
+            if !aggregator.is_empty() {
+                writeln!(opts.output, "{}", aggregator);
+            }
+            !aggregator.is_empty()
+        } else if let Some(ref timeline) = opts.timeline {
+            timeline.add(&matches);
+            if matches.has_matches() {
+                print_timeline(&opts, timeline);
+            }
+            matches.has_matches()
+        } else if let Some(ref sarif) = opts.sarif {
+            sarif.add(&matches);
+            writeln!(opts.output, "{}", sarif);
+            matches.has_matches()
+        } else if let Some(ref html) = opts.html {
+            html.add(&matches);
+            writeln!(opts.output, "{}", html);
+            matches.has_matches()
+        } else if let Some(ref grouped) = opts.grouped {
+            grouped.add(matches);
+            if !grouped.is_empty() {
+                write!(opts.output, "{}", grouped);
+            }
+            !grouped.is_empty()
+        } else if matches.has_matches() && opts.is_vimgrep {
+            write!(
+                opts.output,
+                "{}",
+                grusp::Display::new(matches)
+                    .trim(opts.is_trimmed)
+                    .redact(opts.redaction.clone())
+                    .replace(opts.replacement.clone())
+                    .vimgrep(true)
+            );
+            true
+        } else if matches.has_matches() {
+            writeln!(
+                opts.output,
                 "{}",
                 grusp::Display::new(matches)
                     .count_only(opts.is_count_only)
-                    .color(opts.is_colored)
+                    .trim(opts.is_trimmed)
+                    .highlights(opts.highlights.clone())
+                    .redact(opts.redaction.clone())
+                    .replace(opts.replacement.clone())
+                    .max_columns(opts.max_columns)
                     .just_file_names(opts.just_files.is_some())
             );
+            true
         } else {
-            std::process::exit(1);
+            false
+        };
+        exit_with_policy(&opts, false, lines, is_found);
+    }
+}
+
+/// Exits with the code that `--error-exit-codes` gives the first way the search failed,
+/// if any. Without a code for any of them this returns and the search succeeds. With
+/// `--fail-on`, only severe enough matches fail the search, and no matches don't.
+fn exit_with_policy(opts: &args::Opts, is_unreadable: bool, lines: u64, is_found: bool) {
+    let is_over_max_total = opts.max_total.is_some_and(|max| lines > max);
+    if is_over_max_total {
+        eprintln!("Found {} matching lines, more than the {} allowed by --max-total", lines, opts.max_total.unwrap_or(0));
+    }
+    let failure = opts.policy.as_ref().and_then(|policy| policy.failure());
+    if let Some(severity) = failure {
+        eprintln!("Found matches with a severity of {}, which --fail-on fails at", severity);
+    }
+    let is_found = is_found || opts.policy.is_some();
+    let code = opts.exit_codes.code(is_unreadable, is_over_max_total, failure.is_some(), is_found);
+    if code != 0 {
+        std::process::exit(code);
+    }
+}
+
+fn print_timeline(opts: &args::Opts, timeline: &timeline::Timeline) {
+    if !timeline.is_empty() {
+        writeln!(opts.output, "{}", timeline);
+    }
+    if timeline.skipped() > 0 {
+        eprintln!("{} matching lines had no timestamp", timeline.skipped());
+    }
+}
+
+/// Scans the queried files, or stdin without any, for secrets in a stable order. Files
+/// that can't be read are recorded in the stats and skipped. Also returns whether every
+/// query was a valid glob.
+fn scan_secrets(opts: &args::Opts, stats: &grusp::StatCollector) -> (Vec<secrets::Finding>, bool) {
+    let scanner = secrets::Scanner::new();
+    let scan_file = |path: &PathBuf| {
+        let scanned = File::open(path).and_then(|file| scanner.scan(&mut BufReader::new(file), path));
+        scanned.unwrap_or_else(|e| {
+            unreadable(path, &e, stats);
+            Vec::new()
+        })
+    };
+    let mut is_valid = true;
+    let mut findings: Vec<secrets::Finding> = match opts.queries {
+        Some(ref queries) => {
+            let (files, is_valid_query) = collect_files(opts, queries, stats);
+            is_valid = is_valid_query;
+            if opts.is_concurrent {
+                files.par_iter().flat_map(scan_file).collect()
+            } else {
+                files.iter().flat_map(scan_file).collect()
+            }
         }
+        None => {
+            let stdin = stdin();
+            scanner.scan(&mut stdin.lock(), Path::new("<stdin>")).unwrap_or_else(|e| {
+                unreadable(Path::new("<stdin>"), &e, stats);
+                Vec::new()
+            })
+        }
+    };
+    findings.sort_by(|a, b| (&a.path, a.line, a.column).cmp(&(&b.path, b.line, b.column)));
+    (findings, is_valid)
+}
+
+/// Prints the files the queries find for `grusp files`, one per line, instead of
+/// searching them.
+fn list_files(opts: &args::Opts) {
+    let queries = opts.queries.as_ref().expect("Files are listed from the current directory without paths");
+    let is_missing = report_missing(opts, queries);
+    let stats = grusp::StatCollector::new();
+    let (files, is_valid) = collect_files(opts, queries, &stats);
+    for file in &files {
+        writeln!(opts.output, "{}", file.display());
+    }
+    report_unreadable(opts, &stats);
+    exit_with_policy(opts, is_missing || !is_valid || stats.unreadable() > 0, 0, !files.is_empty());
+}
+
+/// Reports the queries naming paths that don't exist, since the collector skips them, and
+/// returns whether there were any. Globs and negated queries are expanded by the
+/// collector, which reports the invalid ones.
+fn report_missing(opts: &args::Opts, queries: &[String]) -> bool {
+    let mut is_missing = false;
+    for query in queries.iter().filter(|query| !query.contains(['*', '?', '[', '{']) && !query.starts_with('!')) {
+        if let Err(e) = fs::symlink_metadata(query) {
+            warn(opts, format!("Could not search {}: {}", query, e));
+            is_missing = true;
+        }
+    }
+    is_missing
+}
+
+/// Collects the files the queries find. The queries that aren't valid globs are reported
+/// and the files of the rest are still returned, along with whether there were none. The
+/// directories that can't be read are added to the stats like the files that can't be.
+fn collect_files(opts: &args::Opts, queries: &Vec<String>, stats: &grusp::StatCollector) -> (Vec<PathBuf>, bool) {
+    let start = Instant::now();
+    let (files, is_valid) = match opts.file_collector(queries).try_collect() {
+        Ok(files) => (files, true),
+        Err(e) => {
+            for (path, error) in e.unreadable() {
+                unreadable(path, error, stats);
+            }
+            let is_valid = e.queries().is_empty();
+            if !is_valid {
+                warn(opts, &e);
+            }
+            (e.into_files(), is_valid)
+        }
+    };
+    info!("Found {} files to search in {:?}", files.len(), start.elapsed());
+    (files, is_valid)
+}
+
+fn for_each_path<F>(paths: Vec<PathBuf>, is_concurrent: bool, priority: Option<schedule::Priority>, f: F)
+    where F: Fn(PathBuf) + Send + Sync
+{
+    let paths = match priority {
+        Some(priority) => priority.sort(paths),
+        None => paths,
+    };
+    // Starting the threads would take longer than searching a few small files
+    let is_concurrent = is_concurrent && !schedule::is_tiny(&paths);
+    if !is_concurrent {
+        info!("Searching {} files on one thread", paths.len());
+    }
+    if is_concurrent && priority.is_some() {
+        schedule::for_each_in_order(paths, f);
+    } else if is_concurrent {
+        paths.into_par_iter().for_each(f);
+    } else {
+        paths.into_iter().for_each(f);
     }
 }
 
 fn match_file(path: PathBuf,
               opts: &args::Opts,
               matcher: &grusp::Matcher,
+              cache: Option<&grusp::ResultCache>,
               stats: &grusp::StatCollector) {
-    let handle = File::open(&path).unwrap();
-    let mut reader = BufReader::new(handle);
-    let matches = matcher
-        .collect(&mut reader)
-        .expect("Could not parse file")
-        .add_path(&path);
-    stats.add(&matches);
+    if stats.is_full() {
+        debug!("Skipped {}: enough files have matched", path.display());
+        return;
+    }
+    let start = Instant::now();
+    debug!("Searching {}", path.display());
+    search_file(&path, opts, matcher, cache, stats);
+    debug!("Searched {} in {:?}", path.display(), start.elapsed());
+}
+
+fn search_file(path: &Path,
+               opts: &args::Opts,
+               matcher: &grusp::Matcher,
+               cache: Option<&grusp::ResultCache>,
+               stats: &grusp::StatCollector) {
+    if let Some(ref mime) = opts.mime {
+        match mime.is_wanted(path) {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Skipped {}: it isn't of the wanted MIME types", path.display());
+                return;
+            }
+            Err(e) => return unreadable(path, &e, stats),
+        }
+    }
+    let cache = match (cache, opts.chunk_size) {
+        (Some(cache), _) => cache,
+        (None, Some(chunk_size)) => {
+            return match matcher.collect_chunked(path, chunk_size) {
+                Ok(matches) => show_matches(matches, opts, stats),
+                Err(e) => unreadable(path, &e, stats),
+            };
+        }
+        (None, None) => {
+            return match File::open(path) {
+                Ok(handle) => match_reader(path, &mut BufReader::new(handle), opts, matcher, stats),
+                Err(e) => unreadable(path, &e, stats),
+            };
+        }
+    };
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) => return unreadable(path, &e, stats),
+    };
+    let matches = match cache.get(&contents) {
+        Some(matches) => {
+            debug!("Found the matches of {} in the cache", path.display());
+            matches.add_path(path)
+        }
+        None => {
+            let matches = match matcher.collect_path(&mut Cursor::new(&contents), path) {
+                Ok(matches) => matches,
+                Err(e) => return unreadable(path, &e, stats),
+            };
+            if let Err(e) = cache.put(&contents, &matches) {
+                warn(opts, format!("Could not cache the matches of {}: {}", path.display(), e));
+            }
+            matches
+        }
+    };
+    show_matches(matches, opts, stats);
+}
+
+fn match_reader<R: BufRead>(path: &Path,
+                            reader: &mut R,
+                            opts: &args::Opts,
+                            matcher: &grusp::Matcher,
+                            stats: &grusp::StatCollector) {
+    match matcher.collect_path(reader, path) {
+        Ok(matches) => show_matches(matches, opts, stats),
+        Err(e) => unreadable(path, &e, stats),
+    }
+}
+
+/// Records a file that couldn't be read, such as one without permission, and carries on
+/// with the rest of the search. They're reported together once it's done.
+fn unreadable(path: &Path, e: &io::Error, stats: &grusp::StatCollector) {
+    info!("Could not read {}: {}", path.display(), e);
+    stats.add_unreadable(path, e);
+}
+
+/// Prints a message about a file or stream that couldn't be searched to stderr, unless
+/// `--no-messages` is given. Either way the exit code still reflects it.
+fn warn<M: std::fmt::Display>(opts: &args::Opts, message: M) {
+    if !opts.is_no_messages {
+        eprintln!("{}", message);
+    }
+}
+
+/// Writes the stats for `--summary-json`, warning when the file descriptor can't be
+/// written to.
+fn write_summary(opts: &args::Opts, fd: i32, summary: &summary::Summary) {
+    if let Err(e) = summary.write(fd) {
+        warn(opts, format!("Could not write the summary to file descriptor {}: {}", fd, e));
+    }
+}
+
+/// Prints how many files couldn't be read to stderr, or each of them and why with
+/// `--debug`, unless `--no-messages` is given.
+fn report_unreadable(opts: &args::Opts, stats: &grusp::StatCollector) {
+    let files = stats.unreadable_files();
+    if files.is_empty() || opts.is_no_messages {
+        return;
+    }
+    let summary = match files.len() {
+        1 => "1 file could not be read".to_string(),
+        n => format!("{} files could not be read", n),
+    };
+    if opts.debug.is_none() {
+        eprintln!("{}; rerun with --debug for details", summary);
+        return;
+    }
+    for (path, e) in &files {
+        eprintln!("Could not read {}: {}", path.display(), e);
+    }
+    eprintln!("{}", summary);
+}
+
+/// Prints how many bytes weren't valid UTF-8 and were searched as replacement
+/// characters to stderr with `--debug`, unless `--no-messages` is given.
+fn report_replaced(opts: &args::Opts, stats: &grusp::StatCollector) {
+    let replaced = stats.replaced_bytes();
+    if replaced == 0 || opts.debug.is_none() || opts.is_no_messages {
+        return;
+    }
+    match replaced {
+        1 => eprintln!("1 byte that wasn't valid UTF-8 was replaced"),
+        n => eprintln!("{} bytes that weren't valid UTF-8 were replaced", n),
+    }
+}
+
+/// Prints how many lines were longer than `--max-line-length` to stderr, since they
+/// weren't searched or were searched in pieces, unless `--no-messages` is given.
+fn report_long_lines(opts: &args::Opts, long_lines: u64) {
+    let (max, long) = match opts.max_line_length {
+        Some(max_line_length) if long_lines > 0 && !opts.is_no_messages => max_line_length,
+        _ => return,
+    };
+    let action = match long {
+        grusp::LongLines::Skip => "skipped",
+        grusp::LongLines::Chunk => "searched in pieces",
+    };
+    let hint = if opts.verbosity == 0 { "; rerun with --verbose to see which" } else { "" };
+    match long_lines {
+        1 => eprintln!("1 line longer than {} bytes was {}{}", max, action, hint),
+        n => eprintln!("{} lines longer than {} bytes were {}{}", n, max, action, hint),
+    }
+}
+
+fn show_matches(matches: grusp::Matches, opts: &args::Opts, stats: &grusp::StatCollector) {
+    if matches.has_matches() {
+        debug!("Found {} matching lines in {}", matches.count(), matches.path().unwrap_or(Path::new("<stdin>")).display());
+    }
+    // Matches found after the limit of --max-files-with-matches was reached are dropped
+    if !stats.add(&matches) {
+        return;
+    }
+    if let Some(ref edit) = opts.edit {
+        edit.add(&matches);
+    }
+    if let Some(ref policy) = opts.policy {
+        policy.add(&matches);
+    }
+    if let Some(ref rewrite) = opts.rewrite {
+        if let Err(e) = rewrite.rewrite(&matches) {
+            let path = matches.path().map(|path| path.display().to_string()).unwrap_or_default();
+            warn(opts, format!("Could not replace the matches of {}: {}", path, e));
+        }
+        return;
+    }
+    if opts.top.is_some() {
+        return;
+    }
+    if let Some(ref sarif) = opts.sarif {
+        sarif.add(&matches);
+        return;
+    }
+    if let Some(ref html) = opts.html {
+        html.add(&matches);
+        return;
+    }
+    if let Some(ref aggregator) = opts.aggregator {
+        aggregator.add(&matches);
+        return;
+    }
+    if let Some(ref timeline) = opts.timeline {
+        timeline.add(&matches);
+        return;
+    }
+    if let Some(ref grouped) = opts.grouped {
+        grouped.add(matches);
+        // Past the budget the groups so far are printed and the next block is started
+        if opts.max_memory.is_some_and(|max| grouped.size() > max) {
+            write!(opts.output, "{}", grouped.take());
+        }
+        return;
+    }
     if (matches.has_matches() && opts.just_files.show_matches()) ||
         (!matches.has_matches() && opts.just_files.without_matches()) {
-        println!(
-            "{}",
-            grusp::Display::new(matches)
-                .count_only(opts.is_count_only)
-                .color(opts.is_colored)
-                .just_file_names(opts.just_files.is_some())
-        );
+        let mut matches = matches;
+        let listed = matches.path().map(Path::to_path_buf);
+        // The tree shows the directories, so each file is displayed by its name
+        let path = match opts.tree {
+            Some(_) => listed.clone(),
+            None => None,
+        };
+        if let Some(name) = path.as_ref().and_then(|path| path.file_name()) {
+            matches = matches.add_path(Path::new(name));
+        }
+        let display = grusp::Display::new(matches)
+            .count_only(opts.is_count_only)
+            .trim(opts.is_trimmed)
+            .passthru(opts.is_passthru)
+            .highlights(opts.highlights.clone())
+            .redact(opts.redaction.clone())
+            .replace(opts.replacement.clone())
+            .max_columns(opts.max_columns)
+            .number_width(opts.number_width)
+            .vimgrep(opts.is_vimgrep)
+            .just_file_names(opts.just_files.is_some());
+        if let (Some(list), Some(path)) = (opts.file_list.as_ref(), listed) {
+            return list.add(&path, display.to_string());
+        }
+        match (opts.tree.as_ref(), path) {
+            (Some(tree), Some(path)) => {
+                tree.add(&path, display.to_string());
+                if opts.max_memory.is_some_and(|max| tree.size() > max) {
+                    write!(opts.output, "{}", tree.take());
+                }
+            }
+            // Each match is a line of its own, without a blank line between files
+            _ if opts.is_vimgrep => write!(opts.output, "{}", display),
+            _ => writeln!(opts.output, "{}", display),
+        }
     }
 }
@@ -0,0 +1,169 @@
+use clap::{App, ArgSettings};
+
+/// Renders the clap definition of the app, followed by the examples, as a roff man page.
+///
+/// clap doesn't expose its arguments publicly, so this reads them from the parser the
+/// same way clap's own completion generators do.
+pub fn render(app: &App, examples: &str) -> String {
+    let meta = &app.p.meta;
+    let about = meta.about.unwrap_or("");
+    let mut page = String::new();
+    page.push_str(&format!(".TH GRUSP 1 \"\" \"grusp {}\" \"User Commands\"\n", env!("CARGO_PKG_VERSION")));
+    page.push_str(".SH NAME\n");
+    page.push_str(&format!("grusp \\- {}\n", escape(about)));
+    page.push_str(".SH SYNOPSIS\n");
+    page.push_str("\\fBgrusp\\fR [\\fIFLAGS\\fR] [\\fIOPTIONS\\fR] \\fIREGEX\\fR [\\fIPATTERN\\fR]...\n");
+    if !app.p.subcommands.is_empty() {
+        page.push_str(".br\n\\fBgrusp\\fR \\fICOMMAND\\fR [\\fIFLAGS\\fR] [\\fIOPTIONS\\fR] [\\fIARGS\\fR]...\n");
+    }
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&format!("{}\n", escape(about)));
+
+    let mut flags: Vec<_> = app.p.flags.iter().filter(|f| !f.b.is_set(ArgSettings::Hidden)).collect();
+    flags.sort_by_key(|f| f.s.long.unwrap_or(f.b.name));
+    page.push_str(".SH FLAGS\n");
+    for flag in flags {
+        page.push_str(&format!(".TP\n{}\n{}\n", switches(flag.s.short, flag.s.long), escape(flag.b.help.unwrap_or(""))));
+    }
+
+    let mut opts: Vec<_> = app.p.opts.iter().filter(|o| !o.b.is_set(ArgSettings::Hidden)).collect();
+    opts.sort_by_key(|o| o.s.long.unwrap_or(o.b.name));
+    page.push_str(".SH OPTIONS\n");
+    for opt in opts {
+        let value_name = opt.v.val_names.as_ref().and_then(|names| names.values().next().cloned()).unwrap_or(opt.b.name);
+        page.push_str(&format!(".TP\n{} \\fI{}\\fR\n{}", switches(opt.s.short, opt.s.long), escape(value_name), escape(opt.b.help.unwrap_or(""))));
+        if let Some(ref values) = opt.v.possible_vals {
+            page.push_str(&format!(" [possible values: {}]", escape(&values.join(", "))));
+        }
+        if let Some(default) = opt.v.default_val {
+            page.push_str(&format!(" [default: {}]", escape(&default.to_string_lossy())));
+        }
+        page.push('\n');
+    }
+
+    if !app.p.subcommands.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        page.push_str(&commands(app, "grusp"));
+    }
+
+    page.push_str(".SH ARGS\n");
+    for (_, pos) in app.p.positionals.iter() {
+        page.push_str(&format!(".TP\n\\fI{}\\fR\n{}\n", pos.b.name, escape(pos.b.help.unwrap_or(""))));
+    }
+
+    page.push_str(".SH EXAMPLES\n");
+    page.push_str(&render_examples(examples));
+    if let Some(author) = meta.author {
+        page.push_str(".SH AUTHORS\n");
+        page.push_str(&format!("{}\n", escape(author)));
+    }
+    page
+}
+
+/// Lists each command of the app with what it does, followed by its own commands.
+fn commands(app: &App, prefix: &str) -> String {
+    let mut out = String::new();
+    for command in &app.p.subcommands {
+        let name = format!("{} {}", prefix, command.p.meta.name);
+        out.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", escape(&name), escape(command.p.meta.about.unwrap_or(""))));
+        out.push_str(&commands(command, &name));
+    }
+    out
+}
+
+fn switches(short: Option<char>, long: Option<&str>) -> String {
+    let short = short.map(|s| format!("\\fB\\-{}\\fR", s));
+    let long = long.map(|l| format!("\\fB\\-\\-{}\\fR", escape(l)));
+    short.into_iter().chain(long).collect::<Vec<_>>().join(", ")
+}
+
+/// Converts the plain text examples from the help output, where each example is a
+/// paragraph starting with `- ` followed by an indented command.
+fn render_examples(examples: &str) -> String {
+    let mut out = String::new();
+    let mut is_command = false;
+    for line in examples.lines().skip_while(|line| line.ends_with(':') || line.is_empty()) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(command) = line.strip_prefix("    ") {
+            if !is_command {
+                out.push_str(".IP\n.nf\n");
+                is_command = true;
+            }
+            out.push_str(&format!("{}\n", escape(command)));
+            continue;
+        }
+        if is_command {
+            out.push_str(".fi\n");
+            is_command = false;
+        }
+        match line.strip_prefix("- ") {
+            Some(text) => out.push_str(&format!(".PP\n{}\n", escape(text))),
+            None => out.push_str(&format!("{}\n", escape(line))),
+        }
+    }
+    if is_command {
+        out.push_str(".fi\n");
+    }
+    out
+}
+
+/// Escapes text so roff renders it literally.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    escaped
+        .lines()
+        .map(|line| if line.starts_with('.') || line.starts_with('\'') {
+            format!("\\&{}", line)
+        } else {
+            line.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, SubCommand};
+
+    #[test]
+    fn it_escapes_roff_control_characters() {
+        assert_eq!(escape("a\\b-c"), "a\\eb\\-c");
+        assert_eq!(escape(".hidden\n'quoted"), "\\&.hidden\n\\&'quoted");
+    }
+
+    #[test]
+    fn it_renders_flags_and_options() {
+        let app = App::new("grusp")
+            .about("Searches")
+            .arg(Arg::with_name("count").short("c").long("count").help("Just counts"))
+            .arg(Arg::with_name("depth").long("depth").takes_value(true).value_name("NUM").help("Search deep"));
+        let page = render(&app, "");
+        assert!(page.contains(".SH NAME\ngrusp \\- Searches\n"));
+        assert!(page.contains(".TP\n\\fB\\-c\\fR, \\fB\\-\\-count\\fR\nJust counts\n"));
+        assert!(page.contains(".TP\n\\fB\\-\\-depth\\fR \\fINUM\\fR\nSearch deep\n"));
+        assert!(!page.contains(".SH COMMANDS"));
+    }
+
+    #[test]
+    fn it_renders_nested_commands() {
+        let app = App::new("grusp")
+            .subcommand(SubCommand::with_name("files").about("Lists files"))
+            .subcommand(SubCommand::with_name("index").about("Indexes").subcommand(SubCommand::with_name("build").about("Builds")));
+        let page = render(&app, "");
+        assert!(page.contains(
+            ".SH COMMANDS\n.TP\n\\fBgrusp files\\fR\nLists files\n.TP\n\\fBgrusp index\\fR\nIndexes\n.TP\n\\fBgrusp index build\\fR\nBuilds\n"
+        ));
+    }
+
+    #[test]
+    fn it_renders_examples_as_paragraphs_and_commands() {
+        let examples = "EXAMPLES:\n\n- Find fn\n\n    $ grusp fn .\n\n- Count fn\n\n    $ grusp -c fn .\n";
+        assert_eq!(
+            render_examples(examples),
+            ".PP\nFind fn\n.IP\n.nf\n$ grusp fn .\n.fi\n.PP\nCount fn\n.IP\n.nf\n$ grusp \\-c fn .\n.fi\n"
+        );
+    }
+}
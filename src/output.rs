@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+
+/// Where the results of a search are written: stdout, the file of `--output`, or both
+/// with `--tee`. Results are written through it with `write!` and `writeln!`, so the
+/// displays don't need to know where they go. Colors are only kept on the terminal, and
+/// stripped from what's written to the file.
+#[derive(Debug, Default)]
+pub struct Output {
+    file: Option<(PathBuf, Mutex<File>)>,
+    is_tee: bool,
+}
+
+impl Output {
+    /// Writes the results to stdout.
+    pub fn stdout() -> Self {
+        Self::default()
+    }
+
+    /// Writes the results to a new file at the path, and to stdout as well when teeing.
+    pub fn create(path: &Path, is_tee: bool) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Output { file: Some((path.to_path_buf(), Mutex::new(file))), is_tee })
+    }
+
+    /// Returns the file the results are written to, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Whether the results are printed to stdout, so colors and paging follow it.
+    pub fn is_stdout(&self) -> bool {
+        self.file.is_none() || self.is_tee
+    }
+
+    /// Writes the text to each sink. A file that can't be written to ends the search,
+    /// since its results would be incomplete.
+    pub fn write_fmt(&self, args: fmt::Arguments) {
+        let text = fmt::format(args);
+        if self.is_stdout() {
+            print!("{}", text);
+        }
+        if let Some((ref path, ref file)) = self.file {
+            let mut file = file.lock().expect("Output lock poisoned");
+            if let Err(e) = file.write_all(strip_colors(&text).as_bytes()) {
+                eprintln!("Could not write the results to {}: {}", path.display(), e);
+                process::exit(2);
+            }
+        }
+    }
+}
+
+/// Removes the escape sequences that color text in a terminal, such as `\x1b[33m`.
+fn strip_colors(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        // A control sequence is ESC [, parameters, and a final letter
+        if chars.clone().next() == Some('[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() || c == '~' {
+                    break;
+                }
+            }
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn it_strips_colors() {
+        assert_eq!(strip_colors("\x1b[33m12\x1b[0m:a \x1b[1;30;43mtest\x1b[0m"), "12:a test");
+        assert_eq!(strip_colors("plain"), "plain");
+    }
+
+    #[test]
+    fn it_writes_to_the_file_without_colors() {
+        let path = ::std::env::temp_dir().join("grusp-output-test.txt");
+        let output = Output::create(&path, false).unwrap();
+        assert!(!output.is_stdout());
+        let colored = "\x1b[32ma.rs\x1b[0m";
+        writeln!(output, "{}:{}", colored, 3);
+        write!(output, "done");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a.rs:3\ndone");
+    }
+}
@@ -0,0 +1,79 @@
+use std::env;
+use std::io;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+
+/// The pager that stdout was sent to, waited for when grusp exits.
+static PAGER: Mutex<Option<Child>> = Mutex::new(None);
+
+/// Returns the program and arguments of the pager, from `$PAGER` or less by default.
+/// An empty `$PAGER`, or one that's just cat, turns paging off.
+fn command(pager: Option<String>) -> Option<Vec<String>> {
+    let pager = pager.unwrap_or_else(|| "less".to_string());
+    let command: Vec<String> = pager.split_whitespace().map(String::from).collect();
+    match command.first().map(String::as_str) {
+        None | Some("cat") => None,
+        Some(_) => Some(command),
+    }
+}
+
+/// Sends everything written to stdout from here on through the pager, for results shown
+/// in a terminal. Less is told to keep colors and to quit at once when the output fits
+/// on a screen, unless `$LESS` says otherwise, so short results are printed as usual.
+#[cfg(unix)]
+pub fn start() -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let command = match command(env::var("PAGER").ok()) {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    let mut pager = Command::new(&command[0]);
+    pager.args(&command[1..]).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        pager.env("LESS", "FRX");
+    }
+    let mut child = pager.spawn()?;
+    let input = child.stdin.take().expect("The pager's stdin is piped");
+    if unsafe { libc::dup2(input.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        let _ = child.kill();
+        return Err(io::Error::last_os_error());
+    }
+    drop(input);
+    *PAGER.lock().expect("Pager lock poisoned") = Some(child);
+    // Every way out of grusp, including process::exit, runs the handlers registered here
+    unsafe { libc::atexit(finish) };
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn start() -> io::Result<()> {
+    Ok(())
+}
+
+/// Closes stdout so the pager sees the end of the output, and waits for it to be quit.
+#[cfg(unix)]
+extern "C" fn finish() {
+    use std::io::Write;
+
+    let _ = io::stdout().flush();
+    unsafe { libc::close(libc::STDOUT_FILENO) };
+    if let Ok(mut pager) = PAGER.lock() {
+        if let Some(mut child) = pager.take() {
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_pages_with_less_unless_told_otherwise() {
+        assert_eq!(command(None), Some(vec!["less".to_string()]));
+        assert_eq!(command(Some("most -s".to_string())), Some(vec!["most".to_string(), "-s".to_string()]));
+        assert_eq!(command(Some("".to_string())), None);
+        assert_eq!(command(Some("cat".to_string())), None);
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Mutex;
+use grusp_core::grusp::{Line, Matches, Patterns, Severity};
+
+/// The severity `--fail-on` fails a search at, and the most severe match found so far.
+/// Every match is still reported, so a policy scan can show warnings without breaking
+/// the build over them.
+#[derive(Debug)]
+pub struct Policy {
+    fail_on: Severity,
+    patterns: Patterns,
+    worst: Mutex<Option<Severity>>,
+}
+
+impl Policy {
+    pub fn new(fail_on: Severity, patterns: Option<Patterns>) -> Self {
+        Policy { fail_on, patterns: patterns.unwrap_or_default(), worst: Mutex::new(None) }
+    }
+
+    pub fn fail_on(&self) -> Severity {
+        self.fail_on
+    }
+
+    /// Records the severity of each match of the line. Lines kept for context have none.
+    pub fn add_line(&self, line: &Line) {
+        let severity = line.captures.iter().map(|capture| self.patterns.severity(capture.rule.as_deref())).max();
+        let mut worst = self.worst.lock().expect("Policy lock poisoned");
+        *worst = (*worst).max(severity);
+    }
+
+    pub fn add(&self, matches: &Matches) {
+        for line in matches.lines() {
+            self.add_line(line);
+        }
+    }
+
+    /// Returns the severity of the most severe match, if it's one the search fails at.
+    pub fn failure(&self) -> Option<Severity> {
+        let worst = *self.worst.lock().expect("Policy lock poisoned");
+        worst.filter(|&worst| worst >= self.fail_on)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grusp_core::grusp::Matcher;
+    use regex::Regex;
+
+    #[test]
+    fn it_only_fails_at_the_threshold() {
+        let mut patterns = Patterns::new();
+        patterns.add("todo:info=TODO").unwrap();
+        patterns.add("no-unwrap:warn=unwrap").unwrap();
+        let regex = Regex::new(&patterns.pattern()).unwrap();
+        let matcher = Matcher::new(&regex).patterns(Some(&patterns));
+        let policy = Policy::new(Severity::Error, Some(patterns.clone()));
+        policy.add(&matcher.collect(&mut "TODO\nx.unwrap()\n".as_bytes()).unwrap());
+        assert_eq!(policy.failure(), None);
+        let policy = Policy::new(Severity::Warn, Some(patterns.clone()));
+        policy.add(&matcher.collect(&mut "TODO\n".as_bytes()).unwrap());
+        assert_eq!(policy.failure(), None);
+        policy.add(&matcher.collect(&mut "x.unwrap()\n".as_bytes()).unwrap());
+        assert_eq!(policy.failure(), Some(Severity::Warn));
+    }
+}
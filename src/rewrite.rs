@@ -0,0 +1,327 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use grusp_core::grusp::{safe_write, Matches, Replacement};
+
+/// How many lines around a change are shown when asking to confirm it.
+const CONTEXT_LINES: usize = 2;
+
+/// A line of a file and what it's replaced with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    /// The index of the line in the file, counting from zero.
+    index: usize,
+    before: String,
+    after: String,
+}
+
+/// An answer to whether a change should be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    /// Write this change.
+    Yes,
+    /// Skip this change.
+    No,
+    /// Write this change and all of the ones after it.
+    All,
+    /// Skip this change and all of the ones after it.
+    Quit,
+}
+
+impl Answer {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim() {
+            "y" => Some(Answer::Yes),
+            "n" => Some(Answer::No),
+            "a" => Some(Answer::All),
+            "q" => Some(Answer::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Where the original of each file is copied before it's replaced, so that the files of
+/// a bulk edit can be recovered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    suffix: String,
+    dir: Option<PathBuf>,
+}
+
+impl Backup {
+    /// Creates a backup of each file next to it, with the suffix added to its name.
+    pub fn new(suffix: &str) -> Self {
+        Backup { suffix: suffix.to_string(), dir: None }
+    }
+
+    /// Consumes the backup and sets the directory it's written to instead, where each
+    /// file is kept at the path it was searched at.
+    pub fn dir(self, dir: Option<PathBuf>) -> Self {
+        Self { dir, ..self }
+    }
+
+    fn path(&self, path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(&self.suffix);
+        let dir = match self.dir {
+            // Roots and parent directories are dropped so every backup ends up in the dir
+            Some(ref dir) => path
+                .parent()
+                .map(|parent| {
+                    parent
+                        .components()
+                        .filter(|component| matches!(component, Component::Normal(_)))
+                        .fold(dir.clone(), |dir, component| dir.join(component))
+                })
+                .unwrap_or_else(|| dir.clone()),
+            None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        dir.join(name)
+    }
+
+    /// Copies the file to its backup, creating the directories of it.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let backup = self.path(path);
+        if let Some(parent) = backup.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::copy(path, backup).map(|_| ())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    /// Set once every change after it is to be written without asking.
+    is_all: bool,
+    /// Set once no more changes are to be written.
+    is_quit: bool,
+    replaced: usize,
+    files: usize,
+    is_failed: bool,
+}
+
+/// Writes the replacements of `--replace` back into the files that matched, for
+/// `--in-place`. With `--interactive` each change is shown with the lines around it and
+/// only the ones confirmed are written.
+#[derive(Debug)]
+pub struct Rewrite {
+    replacement: Replacement,
+    is_interactive: bool,
+    backup: Option<Backup>,
+    progress: Mutex<Progress>,
+}
+
+impl Rewrite {
+    pub fn new(replacement: Replacement, is_interactive: bool) -> Self {
+        Rewrite { replacement, is_interactive, backup: None, progress: Mutex::new(Progress::default()) }
+    }
+
+    /// Consumes the rewrite and sets where the originals of the files are backed up.
+    pub fn backup(self, backup: Option<Backup>) -> Self {
+        Self { backup, ..self }
+    }
+
+    /// Replaces the matched lines of the file, asking on stdin which to write when
+    /// interactive. Returns how many lines were changed.
+    pub fn rewrite(&self, matches: &Matches) -> io::Result<usize> {
+        let path = match matches.path() {
+            Some(path) if matches.has_matches() => path,
+            _ => return Ok(0),
+        };
+        self.write(path, matches).inspect_err(|_| {
+            self.progress.lock().expect("Rewrite lock poisoned").is_failed = true;
+        })
+    }
+
+    fn write(&self, path: &Path, matches: &Matches) -> io::Result<usize> {
+        let contents = fs::read_to_string(path)?;
+        let mut lines: Vec<String> = contents.split_inclusive('\n').map(String::from).collect();
+        let changes = self.changes(matches, &lines);
+        let changes = if self.is_interactive {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            self.confirm(path, &lines, changes, &mut stdin.lock(), &mut stdout.lock())?
+        } else {
+            changes
+        };
+        if changes.is_empty() {
+            return Ok(0);
+        }
+        for change in &changes {
+            lines[change.index] = change.after.clone();
+        }
+        if let Some(ref backup) = self.backup {
+            backup.save(path)?;
+        }
+        safe_write(path, lines.concat().as_bytes())?;
+        let mut progress = self.progress.lock().expect("Rewrite lock poisoned");
+        progress.replaced += changes.len();
+        progress.files += 1;
+        Ok(changes.len())
+    }
+
+    /// Returns the change to each line of the file that the matches captured in. Lines
+    /// that no longer read as they did when they were matched are left alone.
+    fn changes(&self, matches: &Matches, lines: &[String]) -> Vec<Change> {
+        matches
+            .lines()
+            .iter()
+            .filter(|line| !line.captures.is_empty())
+            .filter_map(|line| {
+                let index = line.number?.checked_sub(1)?;
+                let before = lines.get(index)?;
+                if !before.starts_with(line.value.as_str()) ||
+                    !matches!(&before[line.value.len()..], "" | "\n" | "\r\n") {
+                    return None;
+                }
+                let after = format!("{}{}", self.replacement.line(line).value, &before[line.value.len()..]);
+                if after == *before {
+                    return None;
+                }
+                Some(Change { index, before: before.clone(), after })
+            })
+            .collect()
+    }
+
+    /// Shows each change and asks whether to write it, returning the ones that should be.
+    fn confirm<R: BufRead, W: Write>(&self,
+                                     path: &Path,
+                                     lines: &[String],
+                                     changes: Vec<Change>,
+                                     input: &mut R,
+                                     output: &mut W)
+                                     -> io::Result<Vec<Change>> {
+        let mut progress = self.progress.lock().expect("Rewrite lock poisoned");
+        let mut confirmed = Vec::new();
+        for change in changes {
+            if progress.is_quit {
+                break;
+            }
+            if progress.is_all {
+                confirmed.push(change);
+                continue;
+            }
+            write_hunk(output, path, lines, &change)?;
+            match ask(input, output)? {
+                Answer::Yes => confirmed.push(change),
+                Answer::No => {}
+                Answer::All => {
+                    progress.is_all = true;
+                    confirmed.push(change);
+                }
+                Answer::Quit => progress.is_quit = true,
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// Returns how many lines were changed and in how many files.
+    pub fn summary(&self) -> (usize, usize) {
+        let progress = self.progress.lock().expect("Rewrite lock poisoned");
+        (progress.replaced, progress.files)
+    }
+
+    /// Returns whether any of the files couldn't be read or written.
+    pub fn is_failed(&self) -> bool {
+        self.progress.lock().expect("Rewrite lock poisoned").is_failed
+    }
+}
+
+/// Writes the change as a hunk of a diff, between the lines around it.
+fn write_hunk<W: Write>(output: &mut W, path: &Path, lines: &[String], change: &Change) -> io::Result<()> {
+    writeln!(output, "{}:{}", path.display(), change.index + 1)?;
+    let start = change.index.saturating_sub(CONTEXT_LINES);
+    let end = (change.index + CONTEXT_LINES + 1).min(lines.len());
+    for line in &lines[start..change.index] {
+        writeln!(output, "  {}", line.trim_end())?;
+    }
+    writeln!(output, "- {}", change.before.trim_end())?;
+    writeln!(output, "+ {}", change.after.trim_end())?;
+    for line in &lines[change.index + 1..end] {
+        writeln!(output, "  {}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Asks until one of the answers is given. The end of the input is taken as quitting.
+fn ask<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> io::Result<Answer> {
+    loop {
+        write!(output, "Replace this line [y,n,a,q,?]? ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(Answer::Quit);
+        }
+        match Answer::parse(&answer) {
+            Some(answer) => return Ok(answer),
+            None => {
+                writeln!(output, "y - replace this line")?;
+                writeln!(output, "n - leave this line as it is")?;
+                writeln!(output, "a - replace this line and every line after it")?;
+                writeln!(output, "q - leave this line and every line after it as they are")?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use regex::Regex;
+    use grusp_core::grusp::Matcher;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_inclusive('\n').map(String::from).collect()
+    }
+
+    fn changes(rewrite: &Rewrite, regex: &Regex, text: &str) -> Vec<Change> {
+        let matches = Matcher::new(regex).collect(&mut Cursor::new(text)).unwrap().add_path(Path::new("a.txt"));
+        rewrite.changes(&matches, &lines(text))
+    }
+
+    #[test]
+    fn it_changes_the_matched_lines_keeping_their_terminators() {
+        let regex = Regex::new("foo").unwrap();
+        let rewrite = Rewrite::new(Replacement::new(&regex, "bar"), false);
+        let changes = changes(&rewrite, &regex, "foo\r\nkeep\nfoo foo");
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[0].index, changes[0].after.as_str()), (0, "bar\r\n"));
+        assert_eq!((changes[1].index, changes[1].after.as_str()), (2, "bar bar"));
+    }
+
+    #[test]
+    fn it_backs_up_next_to_the_file_or_under_the_dir() {
+        let backup = Backup::new(".bak");
+        assert_eq!(backup.path(Path::new("src/main.rs")), PathBuf::from("src/main.rs.bak"));
+        assert_eq!(backup.path(Path::new("main.rs")), PathBuf::from("main.rs.bak"));
+        let backup = Backup::new("").dir(Some(PathBuf::from("/tmp/backups")));
+        assert_eq!(backup.path(Path::new("src/main.rs")), PathBuf::from("/tmp/backups/src/main.rs"));
+        assert_eq!(backup.path(Path::new("/home/a/main.rs")), PathBuf::from("/tmp/backups/home/a/main.rs"));
+        assert_eq!(backup.path(Path::new("../main.rs")), PathBuf::from("/tmp/backups/main.rs"));
+    }
+
+    #[test]
+    fn it_only_keeps_the_confirmed_changes() {
+        let regex = Regex::new("foo").unwrap();
+        let rewrite = Rewrite::new(Replacement::new(&regex, "bar"), true);
+        let text = "foo 1\nfoo 2\nfoo 3\nfoo 4\n";
+        let all = changes(&rewrite, &regex, text);
+        let mut output = Vec::new();
+        let mut input = Cursor::new("n\nwhat\ny\nq\n");
+        let confirmed = rewrite.confirm(Path::new("a.txt"), &lines(text), all, &mut input, &mut output).unwrap();
+        assert_eq!(confirmed.iter().map(|change| change.index).collect::<Vec<_>>(), vec![1]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("a.txt:1\n- foo 1\n+ bar 1\n  foo 2\n  foo 3\n"));
+        assert!(output.contains("y - replace this line"));
+        // Quitting skips the changes of the files after it too
+        let more = changes(&rewrite, &regex, text);
+        let confirmed = rewrite.confirm(Path::new("b.txt"), &lines(text), more, &mut Cursor::new("y\n"), &mut Vec::new());
+        assert!(confirmed.unwrap().is_empty());
+    }
+}
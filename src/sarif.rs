@@ -0,0 +1,208 @@
+use std::fmt;
+use std::sync::Mutex;
+use regex::Regex;
+use serde_json::Value;
+use grusp_core::grusp::{Matches, Patterns, Severity};
+use presets::Preset;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+struct Rule {
+    id: String,
+    description: String,
+    regex: Regex,
+}
+
+/// Collects the matches of a search as SARIF 2.1 results for `--format sarif`, so they
+/// can be uploaded to code scanning dashboards. Each preset searched for is a rule of its
+/// own, and a match is reported under the first preset that matches it. Named patterns
+/// are rules too, and report the matches tagged with their names. Otherwise the regex is
+/// the only rule. The level of a match of a named pattern follows its severity.
+pub struct Report {
+    rules: Vec<Rule>,
+    patterns: Option<Patterns>,
+    results: Mutex<Vec<Value>>,
+}
+
+impl Report {
+    pub fn new(regex: &Regex, presets: &[&Preset], patterns: Option<&Patterns>) -> Self {
+        let whole = || Rule { id: "pattern".to_string(), description: format!("Matches {}", regex.as_str()), regex: regex.clone() };
+        let rules = if presets.is_empty() {
+            let mut rules: Vec<Rule> = patterns
+                .into_iter()
+                .flat_map(Patterns::iter)
+                .filter_map(|(name, pattern)| Some(Rule {
+                    id: name?.to_string(),
+                    description: format!("Matches {}", pattern),
+                    regex: Regex::new(pattern).ok()?,
+                }))
+                .collect();
+            // Matches of the unnamed patterns are reported under the whole regex
+            if rules.is_empty() || patterns.is_some_and(|patterns| patterns.iter().any(|(name, _)| name.is_none())) {
+                rules.push(whole());
+            }
+            rules
+        } else {
+            presets
+                .iter()
+                .map(|preset| Rule {
+                    id: preset.name.to_string(),
+                    description: preset.description.to_string(),
+                    regex: Regex::new(preset.pattern).expect("Presets must compile"),
+                })
+                .collect()
+        };
+        Report { rules, patterns: patterns.cloned(), results: Mutex::new(Vec::new()) }
+    }
+
+    /// Adds a result for every match of every line.
+    pub fn add(&self, matches: &Matches) {
+        let uri = match matches.path() {
+            Some(path) => uri(&path.to_string_lossy()),
+            None => "stdin".to_string(),
+        };
+        let mut results = self.results.lock().expect("SARIF lock poisoned");
+        for line in matches.lines() {
+            for capture in &line.captures {
+                let index = match capture.rule {
+                    Some(ref name) => self.rules.iter().position(|rule| rule.id == *name),
+                    None => self.rules.iter().position(|rule| rule.regex.is_match(&capture.value)),
+                };
+                let index = index.unwrap_or(0);
+                let level = match self.patterns.as_ref().map(|patterns| patterns.severity(capture.rule.as_deref())) {
+                    Some(Severity::Info) => "note",
+                    Some(Severity::Error) => "error",
+                    Some(Severity::Warn) | None => "warning",
+                };
+                // SARIF counts columns in UTF-16 code units from one
+                let column = |offset: usize| line.value[..offset].encode_utf16().count() + 1;
+                let mut region = json!({
+                    "startColumn": column(capture.start),
+                    "endColumn": column(capture.end),
+                    "snippet": { "text": line.value },
+                });
+                if let Some(number) = line.number {
+                    region["startLine"] = json!(number);
+                }
+                results.push(json!({
+                    "ruleId": self.rules[index].id,
+                    "ruleIndex": index,
+                    "level": level,
+                    "message": { "text": format!("{}: {}", self.rules[index].description, capture.value) },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": region,
+                        },
+                    }],
+                }));
+            }
+        }
+    }
+}
+
+/// A relative URI reference for a path, which always uses forward slashes.
+fn uri(path: &str) -> String {
+    let mut uri = String::new();
+    for c in path.trim_start_matches("./").chars() {
+        match c {
+            '\\' => uri.push('/'),
+            ' ' | '%' | '#' | '?' => uri.push_str(&format!("%{:02X}", c as u32)),
+            c => uri.push(c),
+        }
+    }
+    uri
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut results = self.results.lock().expect("SARIF lock poisoned").clone();
+        // Files are searched concurrently, so sort for a stable report
+        results.sort_by_key(|result| {
+            let region = &result["locations"][0]["physicalLocation"];
+            (
+                region["artifactLocation"]["uri"].as_str().unwrap_or("").to_string(),
+                region["region"]["startLine"].as_u64(),
+                region["region"]["startColumn"].as_u64(),
+            )
+        });
+        let rules: Vec<Value> = self.rules
+            .iter()
+            .map(|rule| json!({
+                "id": rule.id,
+                "shortDescription": { "text": rule.description },
+                "properties": { "pattern": rule.regex.as_str() },
+            }))
+            .collect();
+        let report = json!({
+            "$schema": SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "grusp",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": "https://github.com/choubacha/grusp",
+                        "rules": rules,
+                    },
+                },
+                "columnKind": "utf16CodeUnits",
+                "results": results,
+            }],
+        });
+        write!(f, "{}", report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grusp_core::grusp::Matcher;
+    use presets;
+    use std::path::Path;
+
+    #[test]
+    fn it_makes_relative_uris() {
+        assert_eq!(uri("./src/main.rs"), "src/main.rs");
+        assert_eq!(uri("src\\my file#1.rs"), "src/my%20file%231.rs");
+    }
+
+    #[test]
+    fn it_reports_matches_under_their_preset() {
+        let presets = [presets::find("todos").unwrap(), presets::find("email").unwrap()];
+        let regex = Regex::new(&presets::pattern(&presets)).unwrap();
+        let report = Report::new(&regex, &presets, None);
+        let text = "a@b.io\n// é TODO\n";
+        report.add(&Matcher::new(&regex).collect_path(&mut text.as_bytes(), Path::new("./lib.rs")).unwrap());
+        let report: Value = ::serde_json::from_str(&report.to_string()).unwrap();
+        let run = &report["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "email");
+        assert_eq!(run["results"][0]["ruleId"], "email");
+        assert_eq!(run["results"][1]["ruleId"], "todos");
+        assert_eq!(run["results"][1]["ruleIndex"], 0);
+        let region = &run["results"][1]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startLine"], 2);
+        assert_eq!(region["startColumn"], 6);
+        assert_eq!(region["endColumn"], 10);
+        assert_eq!(run["results"][1]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "lib.rs");
+    }
+
+    #[test]
+    fn it_reports_matches_under_their_named_pattern() {
+        let mut patterns = Patterns::new();
+        patterns.add("no-unwrap=unwrap").unwrap();
+        patterns.add("no-expect:info=expect").unwrap();
+        let regex = Regex::new(&patterns.pattern()).unwrap();
+        let report = Report::new(&regex, &[], Some(&patterns));
+        let matcher = Matcher::new(&regex).patterns(Some(&patterns));
+        report.add(&matcher.collect(&mut "a.expect(b.unwrap())".as_bytes()).unwrap());
+        let report: Value = ::serde_json::from_str(&report.to_string()).unwrap();
+        let run = &report["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 2);
+        assert_eq!(run["results"][0]["ruleId"], "no-expect");
+        assert_eq!(run["results"][0]["ruleIndex"], 1);
+        assert_eq!(run["results"][0]["level"], "note");
+        assert_eq!(run["results"][1]["level"], "error");
+        assert_eq!(run["results"][1]["ruleId"], "no-unwrap");
+    }
+}
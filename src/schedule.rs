@@ -0,0 +1,115 @@
+use rayon;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::fs::{self, Metadata};
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicUsize};
+
+/// Searches of fewer files than this are run on one thread when the files are small.
+const TINY_FILES: usize = 16;
+/// The most bytes all the files of a search run on one thread can add up to.
+const TINY_BYTES: u64 = 1024 * 1024;
+
+/// Which files `--prioritize` searches first, so the matches most likely to be useful
+/// show up sooner in an interactive search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// The smallest files first, as they're the quickest to search.
+    SmallFirst,
+    /// The most recently modified files first, as they're likely what's being worked on.
+    RecentFirst,
+}
+
+impl Priority {
+    /// Sorts the paths by their metadata, keeping the order they were found in for ties.
+    /// Files whose metadata can't be read go last, where they're reported like any other
+    /// file that can't be read.
+    pub fn sort(self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut files: Vec<(Option<Metadata>, PathBuf)> = paths
+            .into_par_iter()
+            .map(|path| (fs::metadata(&path).ok(), path))
+            .collect();
+        files.sort_by(|a, b| match (&a.0, &b.0) {
+            (Some(a), Some(b)) => self.compare(a, b),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        });
+        files.into_iter().map(|(_, path)| path).collect()
+    }
+
+    fn compare(self, a: &Metadata, b: &Metadata) -> Ordering {
+        match self {
+            Priority::SmallFirst => a.len().cmp(&b.len()),
+            // Files without a modification time sort as the oldest
+            Priority::RecentFirst => b.modified().ok().cmp(&a.modified().ok()),
+        }
+    }
+}
+
+/// Returns whether the files are few and small enough that searching them on one thread
+/// is quicker than starting the thread pool, as for the searches scripts run on a file
+/// or two. Files whose metadata can't be read count as empty.
+pub fn is_tiny(paths: &[PathBuf]) -> bool {
+    paths.len() < TINY_FILES &&
+        paths.iter().map(|path| fs::metadata(path).map_or(0, |metadata| metadata.len())).sum::<u64>() <= TINY_BYTES
+}
+
+/// Calls `f` with each path on every thread of the pool, where each thread takes the
+/// next path left as soon as it's done with one, so the paths are started in order.
+/// Splitting them between the threads up front would have each thread start on a part
+/// of its own, far from the front.
+pub fn for_each_in_order<F>(paths: Vec<PathBuf>, f: F)
+    where F: Fn(PathBuf) + Send + Sync
+{
+    let next = AtomicUsize::new(0);
+    rayon::scope(|scope| {
+        for _ in 0..rayon::current_num_threads() {
+            scope.spawn(|_| {
+                while let Some(path) = paths.get(next.fetch_add(1, atomic::Ordering::Relaxed)) {
+                    f(path.clone());
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn it_puts_the_smallest_files_first() {
+        let dir = ::std::env::temp_dir().join("grusp-schedule-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, size) in &[("a", 30), ("b", 10), ("c", 20), ("d", 10)] {
+            fs::write(dir.join(name), vec![b'x'; *size]).unwrap();
+        }
+        let paths = ["a", "missing", "b", "c", "d"].iter().map(|name| dir.join(name)).collect();
+        let sorted = Priority::SmallFirst.sort(paths);
+        let names: Vec<_> = sorted.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["b", "d", "c", "a", "missing"]);
+    }
+
+    #[test]
+    fn it_runs_a_few_small_files_on_one_thread() {
+        let dir = ::std::env::temp_dir().join("grusp-tiny-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("small"), "x").unwrap();
+        fs::write(dir.join("large"), vec![b'x'; TINY_BYTES as usize + 1]).unwrap();
+        assert!(is_tiny(&[dir.join("small"), dir.join("missing")]));
+        assert!(!is_tiny(&[dir.join("small"), dir.join("large")]));
+        assert!(!is_tiny(&vec![dir.join("small"); TINY_FILES]));
+    }
+
+    #[test]
+    fn it_calls_each_path_once() {
+        let paths: Vec<PathBuf> = (0..100).map(|i| PathBuf::from(i.to_string())).collect();
+        let seen = Mutex::new(Vec::new());
+        for_each_in_order(paths.clone(), |path| seen.lock().unwrap().push(path));
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort_by_key(|path| path.to_str().unwrap().parse::<u32>().unwrap());
+        assert_eq!(seen, paths);
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use rayon::prelude::*;
+use regex::RegexBuilder;
+use serde_json::{self, Value};
+use grusp_core::grusp::{self, CancellationToken};
+use args::Opts;
+
+/// The largest body of an HTTP request, far more than any search needs.
+const MAX_BODY: u64 = 1024 * 1024;
+
+/// Answers JSON search requests for `--serve`. The files found for each set of paths are
+/// kept between requests, so repeated searches don't walk the directories again, and
+/// the thread pool stays warm.
+///
+/// A request is an object with the `regex` to search for and optionally the `paths` to
+/// search (defaults to the current directory), `ignore_case` and `refresh`, which walks
+/// the paths again to pick up new files. The response lists the matching lines of each
+/// file and the files that couldn't be read with why, or has an `error`. Over HTTP, a
+/// body larger than 1 MiB is refused with a 413.
+///
+/// Over a unix socket or stdio, requests with a `jsonrpc` field are JSON-RPC 2.0 calls
+/// instead. The `search` method takes the same parameters, streams each matching file as
+/// a `search/partial` notification carrying the request id, then responds with the
+/// number of files, the files that couldn't be read and whether it was cancelled. Sending `$/cancelRequest` with the id,
+/// like in LSP, stops the search.
+pub struct Server<'a> {
+    opts: &'a Opts,
+    files: Mutex<HashMap<Vec<String>, Vec<PathBuf>>>,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(opts: &'a Opts) -> Self {
+        Self { opts, files: Mutex::new(HashMap::new()) }
+    }
+
+    /// Serves HTTP on localhost when the address is a port, where each request is
+    /// POSTed as the body. Otherwise requests and responses are sent one per line, over
+    /// stdin and stdout when the address is `stdio` or else a unix socket at the path.
+    pub fn serve(&self, address: &str) -> io::Result<()> {
+        if address == "stdio" {
+            let stdin = io::stdin();
+            self.respond_lines(stdin.lock(), io::stdout())
+        } else if let Ok(port) = address.parse::<u16>() {
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("Serving searches on http://127.0.0.1:{}", port);
+            thread::scope(|scope| {
+                for stream in listener.incoming() {
+                    let stream = stream?;
+                    scope.spawn(move || report(self.respond_http(stream)));
+                }
+                Ok(())
+            })
+        } else {
+            self.serve_socket(address)
+        }
+    }
+
+    #[cfg(unix)]
+    fn serve_socket(&self, path: &str) -> io::Result<()> {
+        let listener = UnixListener::bind(path)?;
+        eprintln!("Serving searches on {}", path);
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = stream?;
+                scope.spawn(move || report(stream.try_clone().and_then(|writer| {
+                    self.respond_lines(BufReader::new(stream), writer)
+                })));
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn serve_socket(&self, _path: &str) -> io::Result<()> {
+        Err(io::Error::other("Unix sockets aren't supported on this platform, serve on a port instead"))
+    }
+
+    fn respond_http(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        let mut length = 0;
+        reader.read_line(&mut line)?;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            let header = line.to_lowercase();
+            if let Some(value) = header.trim().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+        let (status, response) = if length > MAX_BODY {
+            ("413 Payload Too Large", json!({ "error": format!("The request is larger than {} bytes", MAX_BODY) }))
+        } else {
+            let mut body = Vec::new();
+            reader.by_ref().take(length).read_to_end(&mut body)?;
+            let request = serde_json::from_slice(&body).map_err(|e| format!("Invalid request: {}", e));
+            match request.and_then(|request| self.handle(&request)) {
+                Ok(response) => ("200 OK", response),
+                Err(error) => ("400 Bad Request", json!({ "error": error })),
+            }
+        };
+        let response = response.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            response.len(),
+            response
+        )
+    }
+
+    /// Answers each line as soon as it's read, running JSON-RPC searches in the background
+    /// so they can stream results and be cancelled while further requests are read.
+    fn respond_lines<R: BufRead, W: Write + Send>(&self, reader: R, writer: W) -> io::Result<()> {
+        let writer = &Mutex::new(writer);
+        let running = &Mutex::new(HashMap::new());
+        thread::scope(|scope| {
+            for line in reader.lines() {
+                let request: Value = match serde_json::from_str(&line?) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        send(writer, &json!({ "error": format!("Invalid request: {}", e) }))?;
+                        continue;
+                    }
+                };
+                if request["jsonrpc"].is_null() {
+                    let response = self.handle(&request).unwrap_or_else(|error| json!({ "error": error }));
+                    send(writer, &response)?;
+                    continue;
+                }
+                let id = request["id"].clone();
+                match request["method"].as_str() {
+                    Some("search") => {
+                        let token = CancellationToken::new();
+                        running.lock().expect("Running searches lock poisoned").insert(id.to_string(), token.clone());
+                        scope.spawn(move || {
+                            let partial = |file| {
+                                let params = json!({ "id": id, "file": file });
+                                report(send(writer, &json!({ "jsonrpc": "2.0", "method": "search/partial", "params": params })));
+                            };
+                            let stats = grusp::StatCollector::new();
+                            let result = self.search(&request["params"], &token, &partial, &stats);
+                            running.lock().expect("Running searches lock poisoned").remove(&id.to_string());
+                            let response = match result {
+                                Ok(files) => json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "result": { "files": files, "unreadable": unreadable(&stats), "cancelled": token.is_cancelled() },
+                                }),
+                                Err(message) => rpc_error(&id, -32602, &message),
+                            };
+                            report(send(writer, &response));
+                        });
+                    }
+                    Some("$/cancelRequest") => {
+                        let running = running.lock().expect("Running searches lock poisoned");
+                        if let Some(token) = running.get(&request["params"]["id"].to_string()) {
+                            token.cancel();
+                        }
+                    }
+                    Some(method) => send(writer, &rpc_error(&id, -32601, &format!("Unknown method {}", method)))?,
+                    None => send(writer, &rpc_error(&id, -32600, "The request is missing a method"))?,
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Runs the search described by a JSON request and describes all of the matches in JSON.
+    pub fn handle(&self, request: &Value) -> Result<Value, String> {
+        let results = Mutex::new(Vec::new());
+        let stats = grusp::StatCollector::new();
+        self.search(request, &CancellationToken::new(), &|file| {
+            results.lock().expect("Results lock poisoned").push(file)
+        }, &stats)?;
+        Ok(json!({ "files": results.into_inner().expect("Results lock poisoned"), "unreadable": unreadable(&stats) }))
+    }
+
+    /// Searches the files the request asks for, passing the matches of each file to
+    /// `on_file` as soon as it's searched, and returns the number of files that matched.
+    /// Files are skipped once the token is cancelled, and the files that can't be read
+    /// are added to the stats like in a search from the command line.
+    fn search(&self,
+              request: &Value,
+              token: &CancellationToken,
+              on_file: &(dyn Fn(Value) + Sync),
+              stats: &grusp::StatCollector)
+              -> Result<usize, String> {
+        let pattern = request["regex"].as_str().ok_or("The request is missing a regex")?;
+        let is_case_insensitive = request["ignore_case"].as_bool().unwrap_or(self.opts.is_case_insensitive);
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(is_case_insensitive)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let paths = match request["paths"].as_array() {
+            Some(paths) => paths.iter().filter_map(|path| path.as_str().map(String::from)).collect(),
+            None => vec![".".to_string()],
+        };
+        let files = self.files(paths, request["refresh"].as_bool().unwrap_or(false));
+        let cache = self.opts.cache_dir.as_ref().map(|dir| {
+            grusp::ResultCache::new(dir, &format!("{:?} {}", grusp::Matcher::new(&regex), is_case_insensitive))
+        });
+        let matcher = grusp::Matcher::new(&regex).cancellation(token);
+        let files = files
+            .par_iter()
+            .filter_map(|path| {
+                if token.is_cancelled() {
+                    return None;
+                }
+                let unreadable = |e: io::Error| stats.add_unreadable(path, &e);
+                let matches = match cache {
+                    Some(ref cache) => {
+                        let contents = fs::read(path).map_err(unreadable).ok()?;
+                        match cache.get(&contents) {
+                            Some(matches) => matches,
+                            None => {
+                                let matches = matcher.collect_path(&mut Cursor::new(&contents), path).map_err(unreadable).ok()?;
+                                // A cancelled search only has some of the matches
+                                if !token.is_cancelled() {
+                                    if let Err(e) = cache.put(&contents, &matches) {
+                                        eprintln!("Could not cache the matches of {}: {}", path.display(), e);
+                                    }
+                                }
+                                matches
+                            }
+                        }
+                    }
+                    None => File::open(path)
+                        .and_then(|file| matcher.collect_path(&mut BufReader::new(file), path))
+                        .map_err(unreadable)
+                        .ok()?,
+                };
+                if !matches.has_matches() {
+                    return None;
+                }
+                let lines: Vec<Value> = matches
+                    .lines()
+                    .iter()
+                    .map(|line| {
+                        let captures: Vec<Value> = line.captures
+                            .iter()
+                            .map(|capture| json!({ "start": capture.start, "end": capture.end }))
+                            .collect();
+                        json!({ "number": line.number, "text": line.value, "matches": captures })
+                    })
+                    .collect();
+                on_file(json!({ "path": path.to_string_lossy(), "lines": lines }));
+                Some(path)
+            })
+            .count();
+        Ok(files)
+    }
+
+    /// The files the paths find, walking them only if they haven't been yet.
+    fn files(&self, paths: Vec<String>, is_refresh: bool) -> Vec<PathBuf> {
+        let mut files = self.files.lock().expect("File cache lock poisoned");
+        if is_refresh {
+            files.remove(&paths);
+        }
+        let collector = self.opts.file_collector(&paths);
+        let found = files.entry(paths.clone()).or_insert_with(|| collector.collect());
+        found.clone()
+    }
+}
+
+fn send<W: Write>(writer: &Mutex<W>, message: &Value) -> io::Result<()> {
+    let mut writer = writer.lock().expect("Writer lock poisoned");
+    writeln!(writer, "{}", message)?;
+    writer.flush()
+}
+
+/// The files of a search that couldn't be read, with why.
+fn unreadable(stats: &grusp::StatCollector) -> Vec<Value> {
+    stats
+        .unreadable_files()
+        .iter()
+        .map(|(path, error)| json!({ "path": path.to_string_lossy(), "error": error }))
+        .collect()
+}
+
+fn rpc_error(id: &Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn report(result: io::Result<()>) {
+    if let Err(e) = result {
+        eprintln!("Could not respond to a request: {}", e);
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{self, BufRead};
+use regex::Regex;
+
+/// Splits a stream into the documents it's made of for `--split-on`, such as the diff of
+/// each file in the output of `git show`, so each is searched and reported like a file.
+/// A line matching the separator starts a document and names it, and isn't part of it,
+/// so the lines of a document are numbered from the one after it. Lines before the first
+/// separator are a document named `<stdin>`.
+#[derive(Debug)]
+pub struct Splitter<'a> {
+    separator: &'a Regex,
+    terminator: u8,
+}
+
+impl<'a> Splitter<'a> {
+    pub fn new(separator: &'a Regex, terminator: u8) -> Self {
+        Splitter { separator, terminator }
+    }
+
+    /// Calls `f` with the name and contents of each document as soon as it ends, so only
+    /// one is kept in memory at a time. Returns whether there were any documents.
+    pub fn for_each<R, F>(&self, reader: &mut R, mut f: F) -> io::Result<bool>
+        where R: BufRead, F: FnMut(&str, &[u8])
+    {
+        let mut name: Option<String> = None;
+        let mut contents = Vec::new();
+        let mut line = Vec::new();
+        let mut has_documents = false;
+        while reader.read_until(self.terminator, &mut line)? > 0 {
+            let text = String::from_utf8_lossy(&line);
+            let header = text.trim_end_matches([self.terminator as char, '\r']);
+            if self.separator.is_match(header) {
+                if name.is_some() || !contents.is_empty() {
+                    f(name.as_deref().unwrap_or("<stdin>"), &contents);
+                    has_documents = true;
+                }
+                name = Some(header.trim().to_string());
+                contents.clear();
+            } else {
+                contents.extend_from_slice(&line);
+            }
+            line.clear();
+        }
+        if name.is_some() || !contents.is_empty() {
+            f(name.as_deref().unwrap_or("<stdin>"), &contents);
+            has_documents = true;
+        }
+        Ok(has_documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_names_each_document_by_its_header() {
+        let separator = Regex::new("^diff --git ").unwrap();
+        let stream = "commit abc\ndiff --git a/x.rs b/x.rs\n+one\ndiff --git a/y.rs b/y.rs\r\n+two\n+three";
+        let mut documents = Vec::new();
+        let has_documents = Splitter::new(&separator, b'\n')
+            .for_each(&mut stream.as_bytes(), |name, contents| {
+                documents.push((name.to_string(), String::from_utf8(contents.to_vec()).unwrap()))
+            })
+            .unwrap();
+        assert!(has_documents);
+        assert_eq!(documents, vec![
+            ("<stdin>".to_string(), "commit abc\n".to_string()),
+            ("diff --git a/x.rs b/x.rs".to_string(), "+one\n".to_string()),
+            ("diff --git a/y.rs b/y.rs".to_string(), "+two\n+three".to_string()),
+        ]);
+        assert!(!Splitter::new(&separator, b'\n').for_each(&mut "".as_bytes(), |_, _| {}).unwrap());
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use regex::{Regex, RegexBuilder};
+use grusp_core::grusp;
+
+/// The most skipped files listed by name before the rest are summarized.
+const MAX_LISTED_FILES: usize = 5;
+
+/// Near misses found after a search of files matched nothing, so users can tell whether
+/// case sensitivity or the file filters hid their results. Displaying it renders the
+/// report for `--suggest`.
+pub struct Suggestions {
+    case_insensitive_files: usize,
+    skipped_files: Vec<PathBuf>,
+}
+
+impl Suggestions {
+    /// Searches the queries again without the options that could have hidden a match:
+    /// once ignoring case if the search was case sensitive, and once over every file
+    /// the queries expand to that wasn't searched because of --exclude, --depth or
+    /// the metadata filters.
+    pub fn find(regex: &Regex, is_case_insensitive: bool, queries: &Vec<String>, searched: &[PathBuf]) -> Self {
+        let case_insensitive_files = if is_case_insensitive {
+            0
+        } else {
+            let regex = RegexBuilder::new(regex.as_str())
+                .case_insensitive(true)
+                .build()
+                .expect("A valid regex is still valid when ignoring case");
+            searched.iter().filter(|path| has_match(&regex, path)).count()
+        };
+        let skipped_files = grusp::FileCollector::new(queries)
+            .collect()
+            .into_iter()
+            .filter(|path| !searched.contains(path) && has_match(regex, path))
+            .collect();
+        Self { case_insensitive_files, skipped_files }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.case_insensitive_files == 0 && self.skipped_files.is_empty()
+    }
+}
+
+fn has_match(regex: &Regex, path: &Path) -> bool {
+    let mut reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(_) => return false,
+    };
+    grusp::Matcher::new(regex)
+        .keep_lines(false)
+        .first_match_only(true)
+        .max_captures_per_line(Some(1))
+        .collect(&mut reader)
+        .map(|matches| matches.has_matches())
+        .unwrap_or(false)
+}
+
+fn files(count: usize) -> String {
+    format!("{} file{}", count, if count == 1 { "" } else { "s" })
+}
+
+impl fmt::Display for Suggestions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No near misses found");
+        }
+        writeln!(f, "No matches found, but there are near misses:")?;
+        if self.case_insensitive_files > 0 {
+            writeln!(
+                f,
+                "    {} match when ignoring case, try --ignore-case",
+                files(self.case_insensitive_files)
+            )?;
+        }
+        if !self.skipped_files.is_empty() {
+            writeln!(
+                f,
+                "    {} skipped by --exclude, --depth or the file filters match:",
+                files(self.skipped_files.len())
+            )?;
+            for path in self.skipped_files.iter().take(MAX_LISTED_FILES) {
+                writeln!(f, "        {}", path.display())?;
+            }
+            if self.skipped_files.len() > MAX_LISTED_FILES {
+                writeln!(f, "        and {} more", self.skipped_files.len() - MAX_LISTED_FILES)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_both_kinds_of_near_misses() {
+        let suggestions = Suggestions {
+            case_insensitive_files: 1,
+            skipped_files: (0..7).map(|i| PathBuf::from(format!("vendor/{}.rs", i))).collect(),
+        };
+        let report = suggestions.to_string();
+        assert!(report.contains("    1 file match when ignoring case, try --ignore-case\n"));
+        assert!(report.contains("    7 files skipped by --exclude, --depth or the file filters match:\n"));
+        assert!(report.contains("        vendor/4.rs\n        and 2 more\n"));
+        assert!(!report.contains("vendor/5.rs"));
+    }
+
+    #[test]
+    fn it_says_when_there_are_no_near_misses() {
+        let suggestions = Suggestions { case_insensitive_files: 0, skipped_files: Vec::new() };
+        assert!(suggestions.is_empty());
+        assert_eq!(suggestions.to_string(), "No near misses found\n");
+    }
+}
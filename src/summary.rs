@@ -0,0 +1,90 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+use grusp_core::grusp::StatCollector;
+
+/// The file descriptor of stderr, where `--summary-json` writes by default.
+pub const STDERR: i32 = 2;
+
+/// The stats of a finished search for `--summary-json`, written as a single line of JSON
+/// apart from the matches on stdout, so scripts wrapping grusp can read the numbers
+/// without parsing text meant for people.
+#[derive(Debug)]
+pub struct Summary<'a> {
+    stats: &'a StatCollector,
+    elapsed: Duration,
+}
+
+impl<'a> Summary<'a> {
+    pub fn new(stats: &'a StatCollector, elapsed: Duration) -> Self {
+        Summary { stats, elapsed }
+    }
+
+    /// Writes the summary to the file descriptor, which the caller is expected to have
+    /// opened, like with `3>summary.json`. It's left open for anything written after.
+    pub fn write(&self, fd: i32) -> io::Result<()> {
+        if fd == STDERR {
+            return writeln!(io::stderr(), "{}", self);
+        }
+        write_fd(fd, &format!("{}\n", self))
+    }
+}
+
+#[cfg(unix)]
+fn write_fd(fd: i32, text: &str) -> io::Result<()> {
+    use std::fs::File;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::FromRawFd;
+
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The file isn't dropped, so the descriptor isn't closed under whoever opened it
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    file.write_all(text.as_bytes())?;
+    file.flush()
+}
+
+#[cfg(not(unix))]
+fn write_fd(_fd: i32, _text: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "only stderr is supported on this platform"))
+}
+
+impl<'a> fmt::Display for Summary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = json!({
+            "matched_files": self.stats.total(),
+            "matched_lines": self.stats.lines(),
+            "captures": self.stats.captures(),
+            "unreadable_files": self.stats.unreadable(),
+            "replaced_bytes": self.stats.replaced_bytes(),
+            "long_lines": self.stats.long_lines(),
+            "elapsed_ms": self.elapsed.as_millis() as u64,
+        });
+        write!(f, "{}", summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grusp_core::grusp::Matcher;
+    use regex::Regex;
+    use serde_json::Value;
+    use std::path::Path;
+
+    #[test]
+    fn it_summarizes_the_stats_as_json() {
+        let regex = Regex::new("old_api").unwrap();
+        let stats = StatCollector::new();
+        for &(path, text) in &[("a.rs", "old_api()\nold_api()"), ("b.rs", "new_api()"), ("c.rs", "old_api()")] {
+            stats.add(&Matcher::new(&regex).collect_path(&mut text.as_bytes(), Path::new(path)).unwrap());
+        }
+        let summary: Value = ::serde_json::from_str(&Summary::new(&stats, Duration::from_millis(42)).to_string()).unwrap();
+        assert_eq!(summary["matched_files"], 2);
+        assert_eq!(summary["matched_lines"], 3);
+        assert_eq!(summary["captures"], 3);
+        assert_eq!(summary["unreadable_files"], 0);
+        assert_eq!(summary["elapsed_ms"], 42);
+    }
+}
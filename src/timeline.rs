@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use regex::{Captures, Regex};
+use grusp_core::grusp::Matches;
+use args::days_from_civil;
+
+/// A timestamp format that can be given to `--timeline` by name.
+pub struct Format {
+    pub name: &'static str,
+    pub pattern: &'static str,
+}
+
+pub const FORMATS: &[Format] = &[
+    Format {
+        name: "iso8601",
+        pattern: r"(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})[T ](?P<hour>\d{2}):(?P<minute>\d{2})",
+    },
+    Format {
+        name: "clf",
+        pattern: r"(?P<day>\d{2})/(?P<month>[A-Z][a-z]{2})/(?P<year>\d{4}):(?P<hour>\d{2}):(?P<minute>\d{2})",
+    },
+    Format {
+        name: "syslog",
+        pattern: r"^(?P<month>[A-Z][a-z]{2}) +(?P<day>\d{1,2}) (?P<hour>\d{2}):(?P<minute>\d{2})",
+    },
+    Format {
+        name: "epoch",
+        pattern: r"\b(?P<epoch>1\d{9})\b",
+    },
+];
+
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// Gaps are only filled in when it takes fewer empty buckets than this.
+const MAX_FILLED_BUCKETS: i64 = 500;
+
+/// The width of the longest bar.
+const BAR_WIDTH: u64 = 40;
+
+/// How much time each bar of the histogram covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    fn minutes(self) -> i64 {
+        match self {
+            Bucket::Minute => 1,
+            Bucket::Hour => 60,
+            Bucket::Day => 60 * 24,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    /// The number of lines in each bucket, keyed by the minutes since the epoch it starts at.
+    buckets: BTreeMap<i64, u64>,
+    /// Whether a timestamp without a year was found, which are all placed in 2000.
+    is_yearless: bool,
+    skipped: u64,
+}
+
+/// A histogram of how many lines matched over time, from a timestamp found on each line.
+/// The regex of the timestamp names its parts with the groups `year`, `month`, `day`,
+/// `hour` and `minute`, any of which can be left out, or captures seconds since the epoch
+/// in a group named `epoch`. Months can be numbers or names like `Dec`.
+pub struct Timeline {
+    regex: Regex,
+    bucket: Bucket,
+    counts: Mutex<Counts>,
+}
+
+impl Timeline {
+    /// Creates a timeline of the timestamps found by a named format or a regex.
+    pub fn new(format: &str, bucket: Bucket) -> Result<Self, String> {
+        let pattern = FORMATS.iter().find(|f| f.name == format).map_or(format, |f| f.pattern);
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid timestamp regex: {}", e))?;
+        if !regex.capture_names().any(|name| name.is_some()) {
+            let names: Vec<&str> = FORMATS.iter().map(|f| f.name).collect();
+            return Err(format!(
+                "The timestamp regex needs named groups like (?P<hour>..), or use one of {}",
+                names.join(", ")
+            ));
+        }
+        Ok(Timeline { regex, bucket, counts: Mutex::new(Counts::default()) })
+    }
+
+    /// Adds each matching line to the bucket of its timestamp.
+    pub fn add(&self, matches: &Matches) {
+        let mut counts = self.counts.lock().expect("Timeline lock poisoned");
+        for line in matches.lines() {
+            let minutes = self.regex.captures(&line.value).and_then(|captures| {
+                counts.is_yearless |= captures.name("year").is_none() && captures.name("epoch").is_none();
+                minutes_since_epoch(&captures)
+            });
+            match minutes {
+                Some(minutes) => {
+                    let start = minutes.div_euclid(self.bucket.minutes()) * self.bucket.minutes();
+                    *counts.buckets.entry(start).or_insert(0) += 1;
+                }
+                None => counts.skipped += 1,
+            }
+        }
+    }
+
+    /// Returns true if no timestamps have been found.
+    pub fn is_empty(&self) -> bool {
+        self.counts.lock().expect("Timeline lock poisoned").buckets.is_empty()
+    }
+
+    /// The number of matching lines without a timestamp.
+    pub fn skipped(&self) -> u64 {
+        self.counts.lock().expect("Timeline lock poisoned").skipped
+    }
+
+    fn label(&self, minutes: i64, is_yearless: bool) -> String {
+        let (year, month, day) = civil_from_days(minutes.div_euclid(60 * 24));
+        let date = if is_yearless {
+            format!("{:02}-{:02}", month, day)
+        } else {
+            format!("{:04}-{:02}-{:02}", year, month, day)
+        };
+        match self.bucket {
+            Bucket::Day => date,
+            _ => format!("{} {:02}:{:02}", date, minutes.rem_euclid(60 * 24) / 60, minutes.rem_euclid(60)),
+        }
+    }
+}
+
+fn minutes_since_epoch(captures: &Captures) -> Option<i64> {
+    if let Some(epoch) = captures.name("epoch") {
+        return epoch.as_str().parse::<i64>().ok().map(|seconds| seconds.div_euclid(60));
+    }
+    let number = |name: &str, default: i64| match captures.name(name) {
+        Some(value) => value.as_str().parse::<i64>().ok(),
+        None => Some(default),
+    };
+    let month = match captures.name("month") {
+        Some(month) => match month.as_str().parse::<i64>() {
+            Ok(month) => month,
+            Err(_) => {
+                let name = month.as_str().to_lowercase();
+                MONTHS.iter().position(|m| name.starts_with(m))? as i64 + 1
+            }
+        },
+        None => 1,
+    };
+    let (year, day, hour, minute) = (number("year", 2000)?, number("day", 1)?, number("hour", 0)?, number("minute", 0)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    Some((days_from_civil(year, month, day) * 24 + hour) * 60 + minute)
+}
+
+/// The date of a number of days since 1970-01-01, the inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl fmt::Display for Timeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = self.counts.lock().expect("Timeline lock poisoned");
+        let (first, last) = match (counts.buckets.keys().next(), counts.buckets.keys().next_back()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return Ok(()),
+        };
+        // Quiet periods are as telling as busy ones, so show the empty buckets in between
+        let rows: Vec<(i64, u64)> = if (last - first) / self.bucket.minutes() < MAX_FILLED_BUCKETS {
+            (0..=(last - first) / self.bucket.minutes())
+                .map(|i| first + i * self.bucket.minutes())
+                .map(|start| (start, counts.buckets.get(&start).cloned().unwrap_or(0)))
+                .collect()
+        } else {
+            counts.buckets.iter().map(|(&start, &count)| (start, count)).collect()
+        };
+        let max = rows.iter().map(|&(_, count)| count).max().unwrap_or(0);
+        let count_width = max.to_string().len();
+        let rows: Vec<String> = rows
+            .iter()
+            .map(|&(start, count)| {
+                let bar = "#".repeat(((count * BAR_WIDTH).div_ceil(max.max(1))) as usize);
+                format!(
+                    "{}  {:>width$}  {}",
+                    self.label(start, counts.is_yearless),
+                    count,
+                    bar,
+                    width = count_width
+                )
+                .trim_end()
+                .to_string()
+            })
+            .collect();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grusp_core::grusp::Line;
+
+    fn collect(format: &str, bucket: Bucket, lines: &[&str]) -> Timeline {
+        let matches = Matches::from_lines(
+            lines
+                .iter()
+                .map(|line| Line { number: None, value: line.to_string(), captures: Vec::new() })
+                .collect(),
+        );
+        let timeline = Timeline::new(format, bucket).unwrap();
+        timeline.add(&matches);
+        timeline
+    }
+
+    #[test]
+    fn it_converts_days_back_to_dates() {
+        for &(year, month, day) in &[(1970, 1, 1), (2000, 2, 29), (2017, 12, 31), (1969, 7, 20)] {
+            assert_eq!(civil_from_days(days_from_civil(year, month, day)), (year, month, day));
+        }
+    }
+
+    #[test]
+    fn it_buckets_and_fills_in_quiet_hours() {
+        let timeline = collect(
+            "iso8601",
+            Bucket::Hour,
+            &["2017-12-31T10:15:00 ERROR", "2017-12-31 10:59:59 ERROR", "2017-12-31T12:00:00 ERROR", "no time"],
+        );
+        assert_eq!(
+            timeline.to_string(),
+            format!("2017-12-31 10:00  2  {}\n2017-12-31 11:00  0\n2017-12-31 12:00  1  {}", "#".repeat(40), "#".repeat(20))
+        );
+        assert_eq!(timeline.skipped(), 1);
+    }
+
+    #[test]
+    fn it_reads_month_names_and_epochs() {
+        let timeline = collect("clf", Bucket::Day, &["[31/Dec/2017:23:59:01 +0000] GET"]);
+        assert_eq!(timeline.to_string(), format!("2017-12-31  1  {}", "#".repeat(40)));
+        let timeline = collect("epoch", Bucket::Minute, &["ts=1514764799"]);
+        assert!(timeline.to_string().starts_with("2017-12-31 23:59  1"));
+        let timeline = collect("syslog", Bucket::Day, &["Dec  3 08:00:00 host sshd"]);
+        assert!(timeline.to_string().starts_with("12-03  1"));
+    }
+
+    #[test]
+    fn it_requires_named_groups() {
+        assert!(Timeline::new(r"\d+:\d+", Bucket::Hour).is_err());
+        assert!(Timeline::new(r"(?P<hour>\d+):(?P<minute>\d+)", Bucket::Hour).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+use log::{self, LevelFilter, Log, Metadata, Record};
+
+/// Prints what grusp does while it searches to stderr with `--verbose`, to find out why
+/// a file wasn't searched. Once, it prints each file that was skipped and why, and how
+/// long finding the files and searching them took. Twice, it also prints each directory
+/// walked and each file searched, with what matched in it and how long it took. Each
+/// message starts with the time since the search started.
+#[derive(Debug)]
+pub struct Logger {
+    start: Instant,
+    level: LevelFilter,
+}
+
+impl Logger {
+    /// Prints the messages of the level `--verbose` sets when given that many times, from
+    /// then on. Without it nothing is printed.
+    pub fn init(verbosity: u64) {
+        let level = level(verbosity);
+        if level == LevelFilter::Off {
+            return;
+        }
+        let logger = Box::leak(Box::new(Logger { start: Instant::now(), level }));
+        if log::set_logger(logger).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+}
+
+impl Log for Logger {
+    /// Only grusp's own messages are printed, not those of its dependencies.
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with("grusp")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", format(self.start.elapsed(), record));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The level of the messages printed when `--verbose` is given that many times.
+fn level(verbosity: u64) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+fn format(elapsed: Duration, record: &Record) -> String {
+    format!(
+        "[{:>8.3}s {:<5}] {}",
+        elapsed.as_secs_f64(),
+        record.level().as_str().to_lowercase(),
+        record.args()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn it_prints_more_each_time_verbose_is_given() {
+        assert_eq!(level(0), LevelFilter::Off);
+        assert_eq!(level(1), LevelFilter::Info);
+        assert_eq!(level(2), LevelFilter::Debug);
+        assert_eq!(level(5), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn it_starts_messages_with_the_time_and_level() {
+        let message = format(Duration::from_millis(1250), &Record::builder()
+            .level(Level::Info)
+            .args(format_args!("Skipped {}: it's hidden", ".git"))
+            .build());
+        assert_eq!(message, "[   1.250s info ] Skipped .git: it's hidden");
+    }
+}
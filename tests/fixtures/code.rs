@@ -0,0 +1,3 @@
+// TODO: a comment
+let message = "TODO: a string";
+let TODO = 1; /* done */
@@ -0,0 +1 @@
+old_api();
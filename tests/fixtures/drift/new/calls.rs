@@ -0,0 +1,2 @@
+new_api();
+old_api();
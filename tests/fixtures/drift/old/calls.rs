@@ -0,0 +1,2 @@
+old_api();
+old_api();
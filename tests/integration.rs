@@ -4,6 +4,11 @@ extern crate assert_cli;
 mod integration {
     use assert_cli;
 
+    /// Colors piped output, as the tests aren't run in a terminal.
+    fn forced_color() -> assert_cli::Environment {
+        assert_cli::Environment::inherit().insert("CLICOLOR_FORCE", "1").insert("NO_COLOR", "")
+    }
+
     #[test]
     fn it_can_find_fixture() {
         assert_cli::Assert::main_binary()
@@ -146,4 +151,1659 @@ mod integration {
             .contains("example-1.txt")
             .unwrap();
     }
+
+    #[test]
+    fn it_only_searches_within_the_line_range() {
+        assert_cli::Assert::main_binary()
+            .with_args(
+                &[
+                    "--nocolor",
+                    "--line-range",
+                    "3:4",
+                    "line",
+                    "./tests/fixtures/numbered.txt",
+                ],
+            )
+            .succeeds()
+            .stdout()
+            .contains("matched 2 times\n3:line 3\n4:line 4\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_only_searches_the_tail_of_files() {
+        assert_cli::Assert::main_binary()
+            .with_args(
+                &[
+                    "--nocolor",
+                    "--search-tail",
+                    "2",
+                    "line",
+                    "./tests/fixtures/numbered.txt",
+                ],
+            )
+            .succeeds()
+            .stdout()
+            .contains("matched 2 times\n9:line 9\n10:line 10\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_skips_excluded_files() {
+        assert_cli::Assert::main_binary()
+            .with_args(
+                &[
+                    "--nocolor",
+                    "--ignore-case",
+                    "--exclude",
+                    "*.txt",
+                    "--exclude",
+                    "!example-2.txt",
+                    "find",
+                    "./tests/fixtures",
+                ],
+            )
+            .succeeds()
+            .stdout()
+            .contains("example-2.txt")
+            .stdout()
+            .not()
+            .contains("example-1.txt")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_can_search_only_git_tracked_files() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--git", "FIND THIS", "./tests/fixtures"])
+            .succeeds()
+            .stdout()
+            .contains("example-1.txt")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_can_search_a_git_revision() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--git-rev", "HEAD", "FIND THIS", "./tests/fixtures"])
+            .succeeds()
+            .stdout()
+            .contains("HEAD:tests/fixtures/example-1.txt matched 1 time")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_explains_a_pattern() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--explain", "FIND THIS"])
+            .succeeds()
+            .stdout()
+            .contains("Literal prefixes: \"FIND THIS\"")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_shows_where_a_regex_is_invalid() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND(THIS", "tests/fixtures/"])
+            .fails()
+            .stdout()
+            .contains("    FIND(THIS\n        ^")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_completions_without_a_regex() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--completions", "bash"])
+            .succeeds()
+            .stdout()
+            .contains("--line-range")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_generates_a_man_page() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--generate-man"])
+            .succeeds()
+            .stdout()
+            .contains(".SH EXAMPLES")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_searches_nul_separated_records() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["-z", "--nocolor", "^b"])
+            .stdin("a\nb\0b\0c")
+            .succeeds()
+            .stdout()
+            .contains("matched 1 time\nb\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_trims_leading_whitespace() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--trim", "--nocolor", "FIND"])
+            .stdin("        FIND THIS")
+            .succeeds()
+            .stdout()
+            .contains("\nFIND THIS\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_passes_through_every_line() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--passthru", "--nocolor", "FIND"])
+            .stdin("before\nFIND THIS\nafter\n")
+            .succeeds()
+            .stdout()
+            .is("before\nFIND THIS\nafter")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_fails_a_passthru_without_matches() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--passthru", "--nocolor", "FIND"])
+            .stdin("before\nafter\n")
+            .fails()
+            .unwrap();
+    }
+
+    #[test]
+    fn it_limits_the_matches_per_line() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--max-count-per-line", "1", "FIND"])
+            .with_env(forced_color())
+            .stdin("FIND FIND FIND\n")
+            .succeeds()
+            .stdout()
+            .contains("FIND\u{1b}[0m FIND FIND\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_matches_decomposed_accents_when_normalizing() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--unicode-normalize", "nfc", "--nocolor", "caf\u{e9}"])
+            .stdin("cafe\u{301}\n")
+            .succeeds()
+            .stdout()
+            .contains("matched 1 time")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_only_keeps_matches_in_comments() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--code-context", "comments", "TODO", "./tests/fixtures"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/code.rs matched 1 time\n1:// TODO: a comment")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_colors_lines_with_highlight_rules() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--passthru", "--highlight-rules", "./tests/fixtures/severities.rules", "user"])
+            .with_env(forced_color())
+            .stdin("ERROR for user\n")
+            .succeeds()
+            .stdout()
+            .contains("\u{1b}[31mERROR\u{1b}[0m for ")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_rejects_invalid_highlight_rules() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--highlight-rules", "./tests/fixtures/numbered.txt", "user"])
+            .fails()
+            .stdout()
+            .contains("Invalid highlight rule on line 1")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_skips_lines_already_printed() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--unique-lines", "--nocolor", "ERROR"])
+            .stdin("ERROR disk\n  ERROR disk\nERROR net\nERROR disk\n")
+            .succeeds()
+            .stdout()
+            .is("matched 2 times\nERROR disk\nERROR net")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_limits_the_depth_of_a_single_path() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--files-with-matches", "--depth", "0", "FIND THIS", "./tests::1"])
+            .succeeds()
+            .stdout()
+            .contains("tests/fixtures/example-1.txt")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_suggests_ignoring_case() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--suggest", "find this", "./tests/fixtures"])
+            .fails_with(1)
+            .stdout()
+            .contains("1 file match when ignoring case, try --ignore-case")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_suggests_files_skipped_by_filters() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--suggest", "--exclude", "*.txt", "FIND THIS", "./tests/fixtures"])
+            .fails_with(1)
+            .stdout()
+            .contains("1 file skipped by --exclude, --depth or the file filters match:\n        tests/fixtures/example-1.txt")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_hints_when_the_regex_and_path_are_swapped() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["./tests/fixtures", "FIND THIS"])
+            .fails_with(2)
+            .stderr()
+            .contains("did you mean `grusp FIND THIS ./tests/fixtures`?")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_can_turn_off_hints() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--no-hints", "./tests/fixtures", "FIND THIS"])
+            .fails_with(2)
+            .stderr()
+            .not()
+            .contains("Hint")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_searches_with_an_index() {
+        use std::fs;
+
+        let path = ::std::env::temp_dir().join("grusp-index-integration-test");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("a.txt"), "fn needle_handler() {}\n").unwrap();
+        fs::write(path.join("b.txt"), "fn other_handler() {}\n").unwrap();
+        let dir = path.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--index", "build", dir])
+            .succeeds()
+            .stdout()
+            .contains("Indexed 2 files")
+            .unwrap();
+        fs::write(path.join("c.txt"), "fn needle_handler() {}\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--index", "update", dir])
+            .succeeds()
+            .stdout()
+            .contains("1 added, 0 changed, 0 removed")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--files-with-matches", "--index", "search", "needle_[a-z]+", dir])
+            .succeeds()
+            .stdout()
+            .contains("a.txt")
+            .stdout()
+            .not()
+            .contains("b.txt")
+            .unwrap();
+        // Paragraphs can match across the lines the trigrams are split at
+        fs::write(path.join("d.txt"), "fn needle\nhandler() {}\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--index", "update", dir])
+            .succeeds()
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--files-with-matches", "--paragraph", "--index", "search", "needle\\s+handler", dir])
+            .succeeds()
+            .stdout()
+            .contains("d.txt")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_fails_to_search_without_an_index() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--index", "search", "FIND THIS", "./tests/fixtures"])
+            .fails_with(2)
+            .stderr()
+            .contains("build it with `grusp index build ./tests/fixtures`")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_serves_searches_over_http() {
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+        use std::process::Command;
+        use std::thread;
+        use std::time::Duration;
+
+        let mut server = Command::new(env!("CARGO_BIN_EXE_grusp"))
+            .args(["--serve", "47863"])
+            .spawn()
+            .unwrap();
+        let mut stream = (0..50)
+            .filter_map(|_| {
+                thread::sleep(Duration::from_millis(100));
+                TcpStream::connect("127.0.0.1:47863").ok()
+            })
+            .next()
+            .expect("The server never started");
+        let body = r#"{"regex": "FIND THIS", "paths": ["./tests/fixtures"]}"#;
+        write!(stream, "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        // A body too large to be a search is refused without being read
+        let mut stream = TcpStream::connect("127.0.0.1:47863").unwrap();
+        write!(stream, "POST / HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n").unwrap();
+        let mut too_large = String::new();
+        stream.read_to_string(&mut too_large).unwrap();
+        server.kill().unwrap();
+        server.wait().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#""path":"tests/fixtures/example-1.txt""#));
+        assert!(response.contains(r#""number":1,"text":"FIND THIS""#));
+        assert!(response.contains(r#""unreadable":[]"#));
+        assert!(too_large.starts_with("HTTP/1.1 413 Payload Too Large"));
+    }
+
+    #[test]
+    fn it_streams_json_rpc_searches_over_stdio() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--serve", "stdio"])
+            .stdin(r#"{"jsonrpc": "2.0", "id": 7, "method": "search", "params": {"regex": "FIND THIS", "paths": ["./tests/fixtures"]}}"#)
+            .succeeds()
+            .stdout()
+            .contains(r#""method":"search/partial","params":{"file":{"lines":[{"matches":[{"end":9,"start":0}],"number":1,"text":"FIND THIS"}]"#)
+            .stdout()
+            .contains(r#"{"id":7,"jsonrpc":"2.0","result":{"cancelled":false,"files":1,"unreadable":[]}}"#)
+            .unwrap();
+        let dir = ::std::env::temp_dir().join("grusp-serve-unreadable-test");
+        let _ = ::std::fs::remove_dir_all(&dir);
+        ::std::fs::create_dir_all(&dir).unwrap();
+        ::std::os::unix::fs::symlink(dir.join("missing.txt"), dir.join("dangling.txt")).unwrap();
+        let request = format!(r#"{{"regex": "FIND THIS", "paths": ["{}"]}}"#, dir.display());
+        assert_cli::Assert::main_binary()
+            .with_args(&["--serve", "stdio"])
+            .stdin(request.as_str())
+            .succeeds()
+            .stdout()
+            .contains(r#""unreadable":[{"error":"No such file or directory (os error 2)","path":"#)
+            .unwrap();
+    }
+
+    #[test]
+    fn it_reuses_cached_matches() {
+        let dir = ::std::env::temp_dir().join("grusp-cache-integration-test");
+        let _ = ::std::fs::remove_dir_all(&dir);
+        for _ in 0..2 {
+            assert_cli::Assert::main_binary()
+                .with_args(&["--nocolor", "--cache", dir.to_str().unwrap(), "FIND THIS", "./tests/fixtures"])
+                .succeeds()
+                .stdout()
+                .is("tests/fixtures/example-1.txt matched 1 time\n1:FIND THIS")
+                .unwrap();
+        }
+        assert!(::std::fs::read_dir(&dir).unwrap().count() > 0);
+    }
+
+    #[test]
+    fn it_searches_for_a_preset() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--preset", "todos", "./tests/fixtures/code.rs"])
+            .succeeds()
+            .stdout()
+            .contains("1:// TODO: a comment")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_lists_the_presets() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--preset-list"])
+            .succeeds()
+            .stdout()
+            .contains("aws-keys")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_reports_secrets_and_fails() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--secrets", "./tests/fixtures/credentials.env"])
+            .fails_with(1)
+            .stdout()
+            .is("tests/fixtures/credentials.env:2:21: high aws-access-key AKIA****************
+tests/fixtures/credentials.env:4:11: medium secret-assignment q8Zr****************
+2 secrets found (1 high, 1 medium)")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_reports_secrets_as_json() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--secrets", "--secrets-format", "json", "./tests/fixtures/credentials.env"])
+            .fails_with(1)
+            .stdout()
+            .contains(r#""rule":"aws-access-key","secret":"AKIA****************","severity":"high""#)
+            .unwrap();
+    }
+
+    #[test]
+    fn it_redacts_the_matches() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--redact", "FIND", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/example-1.txt matched 1 time\n1:**** THIS")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--redact=[hidden]", "FIND", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .contains("1:[hidden] THIS")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_aggregates_the_first_capture() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--agg", "count", "(FIND) THIS", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .is("1")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--agg", "count", "--agg-by", "word", "(?P<word>FIND|THIS)", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .is("FIND  1\nTHIS  1")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_rejects_aggregating_by_a_missing_group() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--agg", "sum", "--agg-by", "2", "(FIND)", "./tests/fixtures/example-1.txt"])
+            .fails_with(1)
+            .stdout()
+            .is("The regex has no capture group '2' to aggregate by")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_a_timeline_of_the_matches() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--timeline", "iso8601", "--timeline-bucket", "day", "ERROR"])
+            .stdin("2017-12-30 10:00:00 ERROR\n2017-12-30 11:00:00 INFO\n2017-12-31T09:00:00 ERROR\n2017-12-31T09:30:00 ERROR\n")
+            .succeeds()
+            .stdout()
+            .is("2017-12-30  1  ####################\n2017-12-31  2  ########################################")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_groups_lines_by_a_capture() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--group-by", "status", r"(?P<status>\d{3})$"])
+            .stdin("GET / 200\nGET /a 404\nGET /b 200\n")
+            .succeeds()
+            .stdout()
+            .is("200 matched 2 times\nGET / 200\nGET /b 200\n404 matched 1 time\nGET /a 404")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_the_files_with_the_most_matching_lines() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--top", "2", "e", "./tests/fixtures/code.rs", "./tests/fixtures/numbered.txt", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/numbered.txt matched 10 times\ntests/fixtures/code.rs matched 3 times")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_counts_every_matching_line() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--count", "e", "./tests/fixtures/numbered.txt"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/numbered.txt matched 10 times")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_the_drift_between_two_trees() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--diff-trees", "./tests/fixtures/drift/old", "./tests/fixtures/drift/new", "old_api"])
+            .succeeds()
+            .stdout()
+            .is("added.rs 0 -> 1 (+1)\ncalls.rs 2 -> 1 (-1)\n2 files changed, 1 matching lines added, 1 removed")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_fails_only_on_matches_missing_from_the_baseline() {
+        use std::fs;
+
+        let path = ::std::env::temp_dir().join("grusp-baseline-integration-test");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let (source, baseline) = (path.join("lib.rs"), path.join("todos.baseline"));
+        let (source, baseline) = (source.to_str().unwrap(), baseline.to_str().unwrap());
+        fs::write(source, "// TODO: old\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--baseline", baseline, "TODO", source])
+            .succeeds()
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--baseline", baseline, "--check-baseline", "TODO", source])
+            .succeeds()
+            .stdout()
+            .is("")
+            .unwrap();
+        fs::write(source, "// TODO: new\n    // TODO: old\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--baseline", baseline, "--check-baseline", "TODO", source])
+            .fails_with(1)
+            .stdout()
+            .contains("1:// TODO: new")
+            .stderr()
+            .contains("Found 1 new matching lines that aren't in the baseline")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_a_sarif_report() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--format", "sarif", "--preset", "todos", "./tests/fixtures/code.rs"])
+            .succeeds()
+            .stdout()
+            .contains(r#""version":"2.1.0""#)
+            .stdout()
+            .contains(r#""artifactLocation":{"uri":"tests/fixtures/code.rs"}"#)
+            .stdout()
+            .contains(r#""ruleId":"todos""#)
+            .unwrap();
+    }
+
+    #[test]
+    fn it_fails_with_its_own_code_over_the_max_total() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["-c", "--max-total", "2", r"\d", "./tests/fixtures/numbered.txt"])
+            .fails_with(3)
+            .stderr()
+            .contains("Found 10 matching lines, more than the 2 allowed by --max-total")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_can_succeed_without_matches() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--error-exit-codes", "no-match=0", "zzzqqq", "./tests/fixtures/numbered.txt"])
+            .succeeds()
+            .stdout()
+            .is("")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_the_resolved_search_to_stderr() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--debug=1", "--exclude", "*.txt", "-i", "todo", "./tests/fixtures/code.rs"])
+            .succeeds()
+            .stderr()
+            .contains("parsed regex: (?iu:todo)")
+            .stderr()
+            .contains("ignore rules:\n  hidden files and directories\n  files with binary extensions\n  *.txt\n")
+            .stderr()
+            .contains("files (1 of 1):\n  tests/fixtures/code.rs\n")
+            .stderr()
+            .contains("  concurrent: true, resolved to false for a few small files\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_colors_piped_output_only_when_forced() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND"])
+            .stdin("FIND\n")
+            .succeeds()
+            .stdout()
+            .doesnt_contain("\u{1b}[")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND"])
+            .with_env(forced_color())
+            .stdin("FIND\n")
+            .succeeds()
+            .stdout()
+            .contains("\u{1b}[")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND"])
+            .with_env(forced_color().insert("NO_COLOR", "1"))
+            .stdin("FIND\n")
+            .succeeds()
+            .stdout()
+            .doesnt_contain("\u{1b}[")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_right_aligns_line_numbers() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--number-width", "auto", "line (9|10)", "./tests/fixtures/numbered.txt"])
+            .succeeds()
+            .stdout()
+            .contains(" 9:line 9\n10:line 10")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_files_beneath_their_directories() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--tree", "-c", "old_api", "./tests/fixtures/drift"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/drift/\n  new/\n    added.rs matched 1 time\n    calls.rs matched 1 time\n  old/\n    calls.rs matched 2 times\nTOTAL: 4")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_shows_the_function_enclosing_the_matches() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--show-enclosing", "FIXME", "./tests/fixtures/enclosing.py"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/enclosing.py matched 2 times\n2:    def total(self):\n3:        # FIXME: taxes\n6:    def empty(self):\n7:        # FIXME: confirm")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_whole_paragraphs() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["-p", "parse_config", "tests/fixtures/paragraphs.log"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/paragraphs.log matched 1 time\n3:ERROR request failed\n  at parse_config\n  at main")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_whole_records_between_separators() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--record-separator", r"^\d{4}-", "alice", "tests/fixtures/records.log"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/records.log matched 1 time\n2:2024-05-01 10:01 ERROR request failed\n  user=alice\n  at parse_config")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_matches_only_the_named_json_fields() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--json-field", "level=error", "--json-field", "user.name=alice", "tests/fixtures/log.jsonl"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/log.jsonl matched 1 time\n2:{\"level\":\"error\",\"msg\":\"request failed\",\"user\":{\"name\":\"alice\"}}")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_decompresses_stdin() {
+        use std::fs::File;
+        use std::process::{Command, Stdio};
+
+        for fixture in &["tests/fixtures/compressed.log.gz", "tests/fixtures/compressed.log.zst"] {
+            let output = Command::new(env!("CARGO_BIN_EXE_grusp"))
+                .args(["ERROR disk full"])
+                .stdin(Stdio::from(File::open(fixture).unwrap()))
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8_lossy(&output.stdout), "matched 1 time\nERROR disk full\n\n");
+        }
+    }
+
+    #[test]
+    fn it_stops_after_the_max_files_with_matches() {
+        use std::process::Command;
+
+        for args in &[&["--unthreaded"][..], &[]] {
+            let output = Command::new(env!("CARGO_BIN_EXE_grusp"))
+                .args(*args)
+                .args(["--files-with-matches", "--max-files-with-matches", "2", "a", "tests/fixtures"])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8_lossy(&output.stdout).lines().filter(|l| !l.is_empty()).count(), 2);
+        }
+    }
+
+    #[test]
+    fn it_opens_the_matching_files_in_the_editor() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--edit", "FIND THIS", "tests/fixtures"])
+            .with_env(assert_cli::Environment::inherit().insert("EDITOR", "echo opened"))
+            .succeeds()
+            .stdout()
+            .contains("1:FIND THIS\n\nopened tests/fixtures/example-1.txt")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--edit", "FIND"])
+            .stdin("FIND\n")
+            .fails_with(2)
+            .stderr()
+            .contains("--edit opens the files that matched, so it needs paths to search")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_each_match_for_vim() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--vimgrep", "TODO", "tests/fixtures/code.rs"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/code.rs:1:4:// TODO: a comment\ntests/fixtures/code.rs:2:16:let message = \"TODO: a string\";\ntests/fixtures/code.rs:3:5:let TODO = 1; /* done */")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--vimgrep", "TODO"])
+            .stdin("a TODO TODO\n")
+            .succeeds()
+            .stdout()
+            .is("<stdin>:1:3:a TODO TODO\n<stdin>:1:8:a TODO TODO")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_summarizes_the_files_that_could_not_be_read() {
+        let dir = ::std::env::temp_dir().join("grusp-unreadable-integration-test");
+        let _ = ::std::fs::remove_dir_all(&dir);
+        ::std::fs::create_dir_all(&dir).unwrap();
+        ::std::fs::write(dir.join("readable.txt"), "FIND THIS\n").unwrap();
+        ::std::os::unix::fs::symlink(dir.join("missing.txt"), dir.join("dangling.txt")).unwrap();
+        let dir = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND THIS", dir])
+            .fails_with(2)
+            .stderr()
+            .contains("1 file could not be read; rerun with --debug for details")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--debug", "FIND THIS", dir])
+            .fails_with(2)
+            .stderr()
+            .contains("dangling.txt: No such file or directory")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--no-messages", "FIND THIS", dir])
+            .fails_with(2)
+            .stderr()
+            .doesnt_contain("could not be read")
+            .unwrap();
+        // A read failing partway through a file is counted like a file that can't be opened
+        if ::std::path::Path::new("/proc/self/mem").exists() {
+            assert_cli::Assert::main_binary()
+                .with_args(&["FIND THIS", "/proc/self/mem"])
+                .fails_with(2)
+                .stderr()
+                .contains("1 file could not be read; rerun with --debug for details")
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn it_summarizes_the_directories_that_could_not_be_read() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = ::std::env::temp_dir().join("grusp-unreadable-dir-summary-test");
+        let locked = dir.join("locked");
+        let _ = ::std::fs::set_permissions(&locked, ::std::fs::Permissions::from_mode(0o755));
+        let _ = ::std::fs::remove_dir_all(&dir);
+        ::std::fs::create_dir_all(&locked).unwrap();
+        ::std::fs::write(dir.join("readable.txt"), "FIND THIS\n").unwrap();
+        ::std::fs::set_permissions(&locked, ::std::fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions aren't enforced for root, who can read the directory anyway
+        if ::std::fs::read_dir(&locked).is_err() {
+            let path = dir.to_str().unwrap();
+            assert_cli::Assert::main_binary()
+                .with_args(&["FIND THIS", path])
+                .fails_with(2)
+                .stderr()
+                .contains("1 file could not be read; rerun with --debug for details")
+                .unwrap();
+            assert_cli::Assert::main_binary()
+                .with_args(&["--debug", "FIND THIS", path])
+                .fails_with(2)
+                .stderr()
+                .contains(format!("Could not read {}: Permission denied", locked.display()).as_str())
+                .unwrap();
+        }
+        ::std::fs::set_permissions(&locked, ::std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn it_reports_missing_paths_unless_told_not_to() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND THIS", "tests/fixtures/missing.txt", "tests/fixtures/example-1.txt"])
+            .fails_with(2)
+            .stdout()
+            .contains("1:FIND THIS")
+            .stderr()
+            .contains("Could not search tests/fixtures/missing.txt: No such file or directory")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--no-messages", "FIND THIS", "tests/fixtures/missing.txt"])
+            .fails_with(2)
+            .stderr()
+            .doesnt_contain("Could not search")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_matches_replaced_in_the_case_they_were_in() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["-r", "bar", "--preserve-case", "-i", "foo"])
+            .stdin("Foo, FOO and foo\n")
+            .stdout()
+            .contains("Bar, BAR and bar\n")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["-r", "$2=$1", "(\\w+)=(\\w+)"])
+            .stdin("a=1, b=2\n")
+            .stdout()
+            .contains("1=a, 2=b\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_writes_only_the_confirmed_replacements_in_place() {
+        use std::fs;
+        let path = ::std::env::temp_dir().join("grusp-in-place-integration-test");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let (all, some) = (path.join("all.txt"), path.join("some.txt"));
+        fs::write(&all, "old_name()\nkeep\nOld_Name()\n").unwrap();
+        fs::write(&some, "old_name(1)\nold_name(2)\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["-i", "--preserve-case", "-r", "new_name", "--in-place", "old_name", all.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("Replaced 2 lines in 1 files")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&all).unwrap(), "new_name()\nkeep\nNew_name()\n");
+        assert_cli::Assert::main_binary()
+            .with_args(&["-r", "new_name", "--in-place", "--interactive", "old_name", some.to_str().unwrap()])
+            .stdin("n\ny\n")
+            .succeeds()
+            .stdout()
+            .contains("- old_name(2)\n+ new_name(2)\n")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&some).unwrap(), "old_name(1)\nnew_name(2)\n");
+    }
+
+    #[test]
+    fn it_backs_up_the_files_it_replaces_in() {
+        use std::fs;
+        let path = ::std::env::temp_dir().join("grusp-backup-integration-test");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let (file, backups) = (path.join("hosts.conf"), path.join("backups"));
+        fs::write(&file, "db.local\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["-r", "db.internal", "--in-place", "--backup", "db[.]local", file.to_str().unwrap()])
+            .succeeds()
+            .unwrap();
+        assert_eq!(fs::read_to_string(path.join("hosts.conf.bak")).unwrap(), "db.local\n");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "db.internal\n");
+        assert_cli::Assert::main_binary()
+            .with_args(&["-r", "db.example", "--in-place", "--backup-dir", backups.to_str().unwrap(), "db[.]internal", file.to_str().unwrap()])
+            .succeeds()
+            .unwrap();
+        let backup = file.strip_prefix("/").unwrap();
+        assert_eq!(fs::read_to_string(backups.join(backup)).unwrap(), "db.internal\n");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "db.example\n");
+    }
+
+    #[test]
+    fn it_replaces_with_every_rule_in_a_file() {
+        use std::fs;
+        let path = ::std::env::temp_dir().join("grusp-rules-integration-test");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let rules = path.join("codemod.rules");
+        fs::write(&rules, "# Spelling\ncolou?r -> hue\n([0-9]+) -> <$1>\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "-i", "--preserve-case", "--rules", rules.to_str().unwrap()])
+            .stdin("Colour 7, color 8\nnothing\n")
+            .succeeds()
+            .stdout()
+            .contains("Hue <7>, hue <8>\n")
+            .unwrap();
+        fs::write(&rules, "colour\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--rules", rules.to_str().unwrap()])
+            .stdin("colour\n")
+            .fails()
+            .stdout()
+            .contains("Line 1 of")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_shows_long_lines_around_their_first_match() {
+        let line = format!("{}fetch(url){}\n", "x=1;".repeat(40), "y=2;".repeat(40));
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--max-columns", "18", "fetch"])
+            .stdin(line.as_str())
+            .succeeds()
+            .stdout()
+            .contains("…=1;x=1;fetch(url)y…\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_skips_files_with_binary_extensions_unless_told_not_to() {
+        let dir = std::env::temp_dir().join("grusp-default-excludes-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "FIND\n").unwrap();
+        std::fs::write(dir.join("logo.png"), "FIND\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "FIND", dir.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("notes.txt")
+            .stdout()
+            .doesnt_contain("logo.png")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "--no-default-excludes", "FIND", dir.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("logo.png")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_only_searches_files_of_the_content_type() {
+        let dir = std::env::temp_dir().join("grusp-mime-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes"), "FIND\n").unwrap();
+        std::fs::write(dir.join("data"), b"\x89PNG\r\n\x1a\nFIND\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "--mime", "text/*", "FIND", dir.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("notes")
+            .stdout()
+            .doesnt_contain("data")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--mime", "text", "FIND", dir.to_str().unwrap()])
+            .fails()
+            .stdout()
+            .contains("Expected content types like text/plain or text/* but got 'text'")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_prints_groups_in_blocks_past_the_memory_budget() {
+        let dir = std::env::temp_dir().join("grusp-max-memory-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.log"), "x 1\n").unwrap();
+        std::fs::write(dir.join("b.log"), "x 1\nx 2\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--group-by", "1", "(x [0-9])", dir.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("x 1 matched 2 times")
+            .unwrap();
+        // Each file takes up more than a byte, so it's printed as a block of its own
+        assert_cli::Assert::main_binary()
+            .with_args(&["--max-memory", "1", "--group-by", "1", "(x [0-9])", dir.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("x 1 matched 1 time")
+            .stdout()
+            .doesnt_contain("x 1 matched 2 times")
+            .stdout()
+            .contains("x 2 matched 1 time")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_reports_invalid_globs_and_searches_the_other_queries() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["FIND", "./tests/fixtures/[", "./tests/fixtures/*.txt"])
+            .fails_with(2)
+            .stderr()
+            .contains("Invalid glob ./tests/fixtures/[: invalid range pattern at position 17")
+            .stderr()
+            .doesnt_contain("No such file")
+            .stdout()
+            .contains("tests/fixtures/")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_only_searches_files_of_the_types_defined() {
+        let dir = std::env::temp_dir().join("grusp-types-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "FIND\n").unwrap();
+        std::fs::write(dir.join("main.rs"), "FIND\n").unwrap();
+        std::fs::write(dir.join("config"), "type-add web:*.html\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "--type", "rust", "FIND", dir.to_str().unwrap()])
+            .with_env(assert_cli::Environment::inherit().insert("GRUSP_CONFIG", dir.join("config")))
+            .succeeds()
+            .stdout()
+            .contains("main.rs")
+            .stdout()
+            .doesnt_contain("index.html")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "--type-add", "web:*.css", "-t", "web", "FIND", dir.to_str().unwrap()])
+            .with_env(assert_cli::Environment::inherit().insert("GRUSP_CONFIG", dir.join("config")))
+            .succeeds()
+            .stdout()
+            .contains("index.html")
+            .stdout()
+            .doesnt_contain("main.rs")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--type-list", "--type-add", "web:*.css"])
+            .with_env(assert_cli::Environment::inherit().insert("GRUSP_CONFIG", dir.join("config")))
+            .succeeds()
+            .stdout()
+            .contains("web:*.html,*.css\n")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_skips_matches_ignored_by_directives() {
+        let dir = std::env::temp_dir().join("grusp-directives-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "a.unwrap();\nb.unwrap(); // grusp:ignore-line\n").unwrap();
+        std::fs::write(dir.join("generated.rs"), "// grusp:ignore-file\nc.unwrap();\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--directives", "unwrap", dir.to_str().unwrap()])
+            .succeeds()
+            .stdout()
+            .contains("1:a.unwrap();")
+            .stdout()
+            .doesnt_contain("b.unwrap")
+            .stdout()
+            .doesnt_contain("generated.rs")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--directives", "unwrap", dir.join("generated.rs").to_str().unwrap()])
+            .fails_with(1)
+            .unwrap();
+    }
+
+    #[test]
+    fn it_shows_the_name_of_the_pattern_that_matched() {
+        let dir = std::env::temp_dir().join("grusp-named-patterns-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "a.unwrap();\nprintln!(\"hi\");\nok();\n").unwrap();
+        std::fs::write(dir.join("lint.rules"), "# lint rules\nno-println=println!\n").unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&[
+                "-e",
+                r"no-unwrap=\.unwrap\(\)",
+                "-f",
+                dir.join("lint.rules").to_str().unwrap(),
+                dir.join("main.rs").to_str().unwrap(),
+            ])
+            .succeeds()
+            .stdout()
+            .contains("matched 2 times")
+            .stdout()
+            .contains("1:[no-unwrap] a.unwrap();")
+            .stdout()
+            .contains("2:[no-println] println!(\"hi\");")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_only_fails_on_matches_as_severe_as_fail_on() {
+        let dir = std::env::temp_dir().join("grusp-fail-on-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs");
+        std::fs::write(&file, "// TODO: tidy\ndbg!(x);\n").unwrap();
+        let file = file.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--fail-on", "error", "-e", "todo:info=TODO", "-e", "no-dbg:warn=dbg!", file])
+            .succeeds()
+            .stdout()
+            .contains("2:[no-dbg] dbg!(x);")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--fail-on", "warn", "--count", "-e", "todo:info=TODO", "-e", "no-dbg:warn=dbg!", file])
+            .fails_with(1)
+            .stderr()
+            .contains("Found matches with a severity of warn")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--fail-on", "warn", "-e", "missing:error=nothing", file])
+            .succeeds()
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["-e", "todo:fatal=TODO", file])
+            .fails_with(1)
+            .stdout()
+            .contains("Unknown severity 'fatal' of todo")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_writes_an_html_report_to_the_output() {
+        let dir = std::env::temp_dir().join("grusp-html-report-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.rs"), "fn main() {}\nlet password = \"<hunter2>\";\n").unwrap();
+        let output = dir.join("report.html");
+        assert_cli::Assert::main_binary()
+            .with_args(&[
+                "--format",
+                "html",
+                "--output",
+                output.to_str().unwrap(),
+                "password",
+                dir.join("a.rs").to_str().unwrap(),
+            ])
+            .succeeds()
+            .stdout()
+            .is("")
+            .unwrap();
+        let html = std::fs::read_to_string(&output).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("1 matching line in 1 file"));
+        assert!(html.contains("<mark>password</mark> = <span class=\"s\">&quot;&lt;hunter2&gt;&quot;</span>;"));
+    }
+
+    #[test]
+    fn it_writes_the_results_to_the_output_and_tees_them() {
+        let dir = std::env::temp_dir().join("grusp-output-tee-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "one\nTODO two\n").unwrap();
+        let output = dir.join("results.txt");
+        let args = ["--output", output.to_str().unwrap(), "TODO", file.to_str().unwrap()];
+        assert_cli::Assert::main_binary()
+            .with_args(&args)
+            .succeeds()
+            .stdout()
+            .is("")
+            .unwrap();
+        let results = std::fs::read_to_string(&output).unwrap();
+        assert!(results.contains("matched 1 time\n2:TODO two\n"));
+        assert!(!results.contains('\x1b'));
+        assert_cli::Assert::main_binary()
+            .with_args(&args)
+            .with_args(&["--tee"])
+            .succeeds()
+            .stdout()
+            .contains("2:TODO two")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), results);
+    }
+
+    #[test]
+    fn it_explains_why_files_were_skipped_when_verbose() {
+        let dir = std::env::temp_dir().join("grusp-verbose-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "TODO one\n").unwrap();
+        std::fs::write(dir.join("b.png"), "TODO two\n").unwrap();
+        std::fs::write(dir.join(".c.txt"), "TODO three\n").unwrap();
+        let path = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--verbose", "TODO", path])
+            .succeeds()
+            .stderr()
+            .contains("b.png: it has the extension of a binary format")
+            .stderr()
+            .contains(".c.txt: it's hidden")
+            .stderr()
+            .contains("Found 1 files to search in")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--verbose", "--verbose", "TODO", path])
+            .succeeds()
+            .stderr()
+            .contains("Found 1 matching lines in")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["TODO", path])
+            .succeeds()
+            .stderr()
+            .doesnt_contain("Skipped")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_skips_or_chunks_lines_longer_than_the_max() {
+        let dir = std::env::temp_dir().join("grusp-long-lines-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("dump.json");
+        std::fs::write(&file, format!("[{}\"TODO\"]\nTODO short\n", "0, ".repeat(100))).unwrap();
+        let path = file.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--max-line-length", "64", "TODO", path])
+            .succeeds()
+            .stdout()
+            .contains("matched 1 time\n2:TODO short")
+            .stderr()
+            .contains("1 line longer than 64 bytes was skipped")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--max-line-length", "64", "--long-lines", "chunk", "TODO", path])
+            .succeeds()
+            .stdout()
+            .contains("matched 2 times\n1:")
+            .stderr()
+            .contains("1 line longer than 64 bytes was searched in pieces")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--max-line-length", "big", "TODO", path])
+            .fails()
+            .stdout()
+            .contains("Expected a line length like 1M but got 'big'")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_reports_the_matches_of_each_document_of_stdin() {
+        let diff = "commit abc\ndiff --git a/x.rs b/x.rs\n+dbg!(1)\n ok\ndiff --git a/y.rs b/y.rs\n ok\n+dbg!(2)\n";
+        assert_cli::Assert::main_binary()
+            .with_args(&["--split-on", "^diff --git ", "--files-with-matches", "dbg!"])
+            .stdin(diff)
+            .succeeds()
+            .stdout()
+            .is("diff --git a/x.rs b/x.rs\ndiff --git a/y.rs b/y.rs")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--split-on", "^diff --git ", "dbg!"])
+            .stdin(diff)
+            .succeeds()
+            .stdout()
+            .contains("diff --git a/y.rs b/y.rs matched 1 time\n2:+dbg!(2)")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--split-on", "^diff --git ", "missing"])
+            .stdin(diff)
+            .fails()
+            .unwrap();
+    }
+
+    #[test]
+    fn it_expands_braces_and_negated_globs_in_paths() {
+        let dir = std::env::temp_dir().join("grusp-brace-glob-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/tests")).unwrap();
+        for file in &["src/a.rs", "src/b.toml", "src/c.md", "src/tests/d.rs"] {
+            std::fs::write(dir.join(file), "TODO\n").unwrap();
+        }
+        let glob = format!("{}/**/*.{{rs,toml}}", dir.display());
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "--unthreaded", "TODO", &glob, "!**/tests/**"])
+            .succeeds()
+            .stdout()
+            .is(format!("{0}/src/a.rs\n{0}/src/b.toml", dir.display()).as_str())
+            .unwrap();
+    }
+
+    #[test]
+    fn it_runs_the_commands() {
+        let dir = std::env::temp_dir().join("grusp-commands-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.rs"), "fn old_name() {}\n").unwrap();
+        std::fs::write(dir.join("b.md"), "files\n").unwrap();
+        let path = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["files", "--type", "rust", path])
+            .succeeds()
+            .stdout()
+            .is(format!("{}/a.rs", path).as_str())
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["replace", "--nocolor", "old_(name)", "new_$1", path])
+            .succeeds()
+            .stdout()
+            .contains("fn new_name() {}")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["search", "--nocolor", "--files-with-matches", "files", path])
+            .succeeds()
+            .stdout()
+            .is(format!("{}/b.md", path).as_str())
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["index", "build", path])
+            .succeeds()
+            .stdout()
+            .contains("Indexed 2 files")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["index", "search", "--nocolor", "--files-with-matches", "old_name", path])
+            .succeeds()
+            .stdout()
+            .is(format!("{}/a.rs", path).as_str())
+            .unwrap();
+    }
+
+    #[test]
+    fn it_searches_for_a_first_word_close_to_a_command() {
+        let dir = std::env::temp_dir().join("grusp-near-command-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "hello\nfile\n").unwrap();
+        let path = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["hello", path, "--nocolor", "--files-with-matches"])
+            .succeeds()
+            .stdout()
+            .is(format!("{}/a.txt", path).as_str())
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["file", path, "--nocolor", "--count"])
+            .succeeds()
+            .stdout()
+            .contains("1")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_starts_with_the_default_flags() {
+        let defaults = || assert_cli::Environment::inherit().insert("GRUSP_DEFAULT_FLAGS", "--count --ignore-case");
+        assert_cli::Assert::main_binary()
+            .with_env(defaults())
+            .with_args(&["--nocolor", "FIND THIS", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .contains("matched 1 time")
+            .stdout()
+            .not()
+            .contains("FIND THIS")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_env(defaults())
+            .with_args(&["--nocolor", "--case-sensitive", "find this", "./tests/fixtures/example-1.txt"])
+            .fails()
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_env(defaults())
+            .with_args(&["--nocolor", "--no-env", "FIND THIS", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .contains("FIND THIS")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_env(assert_cli::Environment::inherit().insert("GRUSP_DEFAULT_FLAGS", "--smart-case"))
+            .with_args(&["FIND THIS", "./tests/fixtures/example-1.txt"])
+            .fails_with(1)
+            .stdout()
+            .contains("GRUSP_DEFAULT_FLAGS: Unknown flag '--smart-case'")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_takes_a_pattern_starting_with_a_dash_after_the_paths() {
+        let dir = std::env::temp_dir().join("grusp-dash-pattern-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("-v.txt"), "run --verbose\n").unwrap();
+        std::fs::write(dir.join("a.txt"), "run quietly\n").unwrap();
+        let (verbose, quiet) = (dir.join("-v.txt"), dir.join("a.txt"));
+        let (verbose, quiet) = (verbose.to_str().unwrap(), quiet.to_str().unwrap());
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--unthreaded", quiet, "--regexp", "--verbose", "--", verbose])
+            .succeeds()
+            .stdout()
+            .is(format!("{} matched 1 time\n1:run --verbose", verbose).as_str())
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--", "-{2}verbose", verbose])
+            .succeeds()
+            .stdout()
+            .contains("1:run --verbose")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_lists_files_sorted_and_once() {
+        let dir = std::env::temp_dir().join("grusp-sorted-list-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        for file in &["d.txt", "b/c.txt", "a.txt", "b.txt", "e.txt"] {
+            let contents = if *file == "e.txt" { "nothing\n" } else { "needle\n" };
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        let path = dir.to_str().unwrap();
+        let repeated = format!("{}/d.txt", path);
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--files-with-matches", "needle", &repeated, path])
+            .succeeds()
+            .stdout()
+            .is(format!("{0}/a.txt\n{0}/b/c.txt\n{0}/b.txt\n{0}/d.txt", path).as_str())
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--files-without-matches", "needle", path])
+            .succeeds()
+            .stdout()
+            .is(format!("{}/e.txt", path).as_str())
+            .unwrap();
+    }
+
+    #[test]
+    fn it_ends_counts_with_the_total() {
+        let dir = std::env::temp_dir().join("grusp-count-total-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "needle\nneedle\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "needle\n").unwrap();
+        std::fs::write(dir.join("c.txt"), "nothing\n").unwrap();
+        let path = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--count", "needle", path])
+            .succeeds()
+            .stdout()
+            .contains(format!("{}/a.txt matched 2 times", path).as_str())
+            .stdout()
+            .contains("\nTOTAL: 3")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--count", "--no-total", "needle", path])
+            .succeeds()
+            .stdout()
+            .doesnt_contain("TOTAL")
+            .unwrap();
+        // A single file has nothing to sum up
+        let file = format!("{}/a.txt", path);
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--count", "needle", &file])
+            .succeeds()
+            .stdout()
+            .doesnt_contain("TOTAL")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_writes_a_summary_as_json() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--summary-json", "old_api", "./tests/fixtures/drift"])
+            .succeeds()
+            .stdout()
+            .doesnt_contain("matched_files")
+            .stderr()
+            .contains("\"matched_files\":3,\"matched_lines\":4,")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--summary-json=9", "old_api", "./tests/fixtures/drift"])
+            .succeeds()
+            .stderr()
+            .contains("Could not write the summary to file descriptor 9")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_searches_the_smallest_files_first() {
+        let dir = std::env::temp_dir().join("grusp-prioritize-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "needle\nhay\nhay\n").unwrap();
+        std::fs::write(dir.join("z.txt"), "needle\n").unwrap();
+        let path = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--unthreaded", "--prioritize", "small-first", "needle", path])
+            .succeeds()
+            .stdout()
+            .is(format!("{0}/z.txt matched 1 time\n1:needle\n\n{0}/a.txt matched 1 time\n1:needle", path).as_str())
+            .unwrap();
+    }
+
+    #[test]
+    fn it_searches_stdin_among_the_files() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "--unthreaded", "FIND", "-", "tests/fixtures/example-1.txt", "-"])
+            .stdin("FIND stdin\n")
+            .succeeds()
+            .stdout()
+            .is("<stdin> matched 1 time\n1:FIND stdin\n\ntests/fixtures/example-1.txt matched 1 time\n1:FIND THIS")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "FIND", "tests/fixtures/example-1.txt", "-", "tests/fixtures/example-1.txt"])
+            .stdin("FIND stdin\n")
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/example-1.txt matched 1 time\n1:FIND THIS\n\n<stdin> matched 1 time\n1:FIND stdin")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_numbers_counts_and_encloses_the_lines_of_an_inverted_search() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "-v", "--show-enclosing", "FIXME", "./tests/fixtures/enclosing.py"])
+            .succeeds()
+            .stdout()
+            .is("tests/fixtures/enclosing.py matched 6 times\n1:class Cart:\n2:    def total(self):\n4:        return 0\n5:\n6:    def empty(self):\n8:        pass")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--nocolor", "-v", "-c", "--summary-json", "FIXME", "./tests/fixtures/enclosing.py", "./tests/fixtures/example-1.txt"])
+            .succeeds()
+            .stdout()
+            .contains("TOTAL: 7")
+            .stderr()
+            .contains("\"matched_files\":2,\"matched_lines\":7,")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_anchors_excludes_to_the_searched_directory() {
+        let dir = std::env::temp_dir().join("grusp-anchored-exclude-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("build")).unwrap();
+        std::fs::create_dir_all(dir.join("src/build")).unwrap();
+        std::fs::write(dir.join("build/out.txt"), "TODO\n").unwrap();
+        std::fs::write(dir.join("src/build/gen.txt"), "TODO\n").unwrap();
+        let path = dir.to_str().unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--files-with-matches", "--exclude", "build/*", "TODO", path])
+            .succeeds()
+            .stdout()
+            .is(format!("{}/src/build/gen.txt", path).as_str())
+            .unwrap();
+    }
+
+    #[test]
+    fn it_rejects_flags_with_invalid_numbers() {
+        for flag in &["--search-head", "--search-tail", "--max-columns", "--max-total"] {
+            assert_cli::Assert::main_binary()
+                .with_args(&[flag, "abc", "TODO", "./tests/fixtures"])
+                .fails()
+                .stdout()
+                .contains(format!("Expected a number for {} but got 'abc'", flag).as_str())
+                .unwrap();
+        }
+        assert_cli::Assert::main_binary()
+            .with_args(&["--number-width", "wide", "TODO", "./tests/fixtures"])
+            .fails()
+            .stdout()
+            .contains("Expected a number or auto for --number-width but got 'wide'")
+            .unwrap();
+        for flag in &["--max-count-per-line", "--top", "--max-files-with-matches"] {
+            assert_cli::Assert::main_binary()
+                .with_args(&[flag, "0", "TODO", "./tests/fixtures"])
+                .fails()
+                .stdout()
+                .contains(format!("Expected a positive number for {} but got '0'", flag).as_str())
+                .unwrap();
+        }
+        assert_cli::Assert::main_binary()
+            .with_args(&["--debug=all", "TODO", "./tests/fixtures"])
+            .fails()
+            .stdout()
+            .contains("Expected a number for --debug but got 'all'")
+            .unwrap();
+        assert_cli::Assert::main_binary()
+            .with_args(&["--summary-json=-1", "TODO", "./tests/fixtures"])
+            .fails()
+            .stdout()
+            .contains("Expected a file descriptor for --summary-json but got '-1'")
+            .unwrap();
+    }
+
+    #[test]
+    fn it_exits_with_2_for_a_directory_it_cannot_read() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join("grusp-unreadable-dir-test");
+        let locked = dir.join("locked");
+        let _ = std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(dir.join("a.txt"), "needle\n").unwrap();
+        std::fs::write(locked.join("b.txt"), "needle\n").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions aren't enforced for root, who can read the directory anyway
+        if std::fs::read_dir(&locked).is_err() {
+            let path = dir.to_str().unwrap();
+            assert_cli::Assert::main_binary()
+                .with_args(&["--nocolor", "needle", path])
+                .fails_with(2)
+                .stdout()
+                .contains(format!("{}/a.txt matched 1 time", path).as_str())
+                .unwrap();
+        }
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
 }